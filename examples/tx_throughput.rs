@@ -0,0 +1,78 @@
+//! Measure the cost of the `AT+TX` send path without a radio.
+//!
+//! `cargo run --release --example tx_throughput [frames]` sends 250 byte frames
+//! to a transport confirming every line at once, so only encoding and writing
+//! are timed. Run it on the target, e.g. a Raspberry Pi Zero, to compare builds.
+
+use lora_modem_hal::{LoraModemDevice, Rf95Modem, Transport};
+use std::io::{self, Read, Write};
+use std::time::Instant;
+
+// Confirms every complete line with `+SENT`, like the firmware would.
+#[derive(Default)]
+struct Confirming {
+    answer: Vec<u8>,
+}
+
+impl Read for Confirming {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.answer.is_empty() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        let n = buf.len().min(self.answer.len());
+        buf[..n].copy_from_slice(&self.answer[..n]);
+        self.answer.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for Confirming {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for _ in buf.iter().filter(|&&b| b == b'\n') {
+            self.answer.extend_from_slice(b"+SENT 250 bytes\n");
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for Confirming {
+    fn open(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+    fn is_open(&self) -> bool {
+        true
+    }
+}
+
+fn main() {
+    let frames: usize = std::env::args()
+        .nth(1)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(100_000);
+    let frame = vec![0xa5u8; 250];
+    let mut modem = Rf95Modem::from_transport(Confirming::default());
+
+    let started = Instant::now();
+    for _ in 0..frames {
+        modem.send_data(frame.clone()).unwrap();
+    }
+    let owned = started.elapsed();
+
+    let started = Instant::now();
+    for _ in 0..frames {
+        modem.send_slice(&frame).unwrap();
+    }
+    let borrowed = started.elapsed();
+
+    for (name, elapsed) in [("send_data", owned), ("send_slice", borrowed)] {
+        println!(
+            "{:>10}: {:>8.2} µs/frame, {:>8.0} frames/s",
+            name,
+            elapsed.as_secs_f64() * 1e6 / frames as f64,
+            frames as f64 / elapsed.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,34 @@
+#![no_main]
+
+// Feeds arbitrary modem output to every parser, run with `cargo fuzz run parsers`.
+
+use libfuzzer_sys::fuzz_target;
+use lora_modem_hal::{GpsFix, LoraModemDevice, MockModem, RxPacket, Status};
+use std::convert::TryFrom;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+
+    let _ = RxPacket::try_from(text.as_ref());
+    let _ = Status::parse(&lines);
+    let _ = GpsFix::parse(&text);
+    let _ = GpsFix::decode(data);
+
+    // the line routing of a device, up to the end of the script
+    let mut modem = MockModem::new();
+    for line in &lines {
+        modem.push_line(line);
+    }
+    let _ = modem.open();
+    while modem.remaining() > 0 {
+        let _ = modem.read_packet();
+    }
+
+    // received payloads survive the hex encoding of the firmware
+    let mut modem = MockModem::new();
+    let _ = modem.open();
+    let payload = &data[..data.len().min(255)];
+    modem.push_rx(payload, -80, 7.0);
+    assert_eq!(modem.read_packet().unwrap().data, payload);
+});
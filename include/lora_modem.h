@@ -0,0 +1,167 @@
+#ifndef LORA_MODEM_H
+#define LORA_MODEM_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit by hand. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Success.
+ */
+#define LORA_OK 0
+
+/**
+ * Any failure without a code of its own.
+ */
+#define LORA_ERR -1
+
+/**
+ * No packet arrived or the modem did not answer in time.
+ */
+#define LORA_ERR_TIMEOUT -2
+
+/**
+ * Null pointer or invalid argument.
+ */
+#define LORA_ERR_INVALID -3
+
+/**
+ * Buffer too small for the received packet, which is dropped.
+ */
+#define LORA_ERR_BUFFER -4
+
+/**
+ * Packets are delivered to the RX callback instead.
+ */
+#define LORA_ERR_BUSY -5
+
+/**
+ * Modem opened through the C interface, opaque to C
+ */
+typedef struct LoraModemHandle LoraModemHandle;
+
+/**
+ * Packet handed to the RX callback or filled in by `lora_modem_read_packet`
+ */
+typedef struct LoraRxPacket {
+  /**
+   * Payload, for the callback only valid until it returns
+   */
+  const uint8_t *data;
+  size_t len;
+  /**
+   * Signal strength in dBm
+   */
+  int16_t rssi;
+  /**
+   * Signal-to-Noise ratio in dB
+   */
+  float snr;
+} LoraRxPacket;
+
+/**
+ * Called on a background thread for every received packet.
+ */
+typedef void (*LoraRxCallback)(const struct LoraRxPacket *packet, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Open the rf95modem at the serial device `path`, null on failure.
+ *
+ * # Safety
+ *
+ * `path` is a null-terminated string.
+ */
+LoraModemHandle *lora_modem_open(const char *path, uint32_t baud);
+
+/**
+ * Open the rf95modem behind the serial bridge at `addr` (`host:port`), null on failure.
+ *
+ * # Safety
+ *
+ * `addr` is a null-terminated string.
+ */
+LoraModemHandle *lora_modem_open_tcp(const char *addr);
+
+/**
+ * Stop the RX callback and close the modem, null is ignored.
+ *
+ * # Safety
+ *
+ * `modem` was returned by this library, no other call on it is in progress
+ * and it is not used afterwards.
+ */
+void lora_modem_close(LoraModemHandle *modem);
+
+/**
+ * Message of the last failure on this thread, valid until the next call failing.
+ */
+const char *lora_modem_last_error(void);
+
+/**
+ * Send `len` bytes at `data`.
+ *
+ * # Safety
+ *
+ * `modem` is a valid handle, `data` points to `len` readable bytes.
+ */
+int lora_modem_send(LoraModemHandle *modem, const uint8_t *data, size_t len);
+
+/**
+ * Tune to `hz`.
+ *
+ * # Safety
+ *
+ * `modem` is a valid handle.
+ */
+int lora_modem_set_frequency(LoraModemHandle *modem, uint32_t hz);
+
+/**
+ * Switch to predefined modem config `mode`, numbered as by `AT+MODE`.
+ *
+ * # Safety
+ *
+ * `modem` is a valid handle.
+ */
+int lora_modem_set_mode(LoraModemHandle *modem, uint32_t mode);
+
+/**
+ * Wait up to `timeout_ms` for a packet and copy its payload into `buf`.
+ *
+ * `packet` is filled in with `data` pointing into `buf`. Fails with
+ * `LORA_ERR_BUSY` while an RX callback is set. Once the callback stopped
+ * because the modem went away, the next call reports why.
+ *
+ * # Safety
+ *
+ * `modem` is a valid handle, `buf` points to `capacity` writable bytes and
+ * `packet` to a writable `LoraRxPacket`.
+ */
+int lora_modem_read_packet(LoraModemHandle *modem,
+                           uint8_t *buf,
+                           size_t capacity,
+                           struct LoraRxPacket *packet,
+                           uint32_t timeout_ms);
+
+/**
+ * Deliver received packets to `callback` on a background thread, null stops it.
+ *
+ * Lines failing to parse are skipped. Delivery stops for good when the modem
+ * is disconnected, `lora_modem_read_packet` then fails with the reason.
+ *
+ * # Safety
+ *
+ * `modem` is a valid handle, `callback` may be called with `user_data` from
+ * another thread until replaced or the modem is closed.
+ */
+int lora_modem_set_rx_callback(LoraModemHandle *modem, LoraRxCallback callback, void *user_data);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* LORA_MODEM_H */
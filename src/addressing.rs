@@ -0,0 +1,350 @@
+use crate::radio::RadioParams;
+#[cfg(feature = "std")]
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    round, send_framed, Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError,
+    ModemEvent, Result, RxPacket, Status, TxReport,
+};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// Destination address received by all nodes.
+pub const BROADCAST: u8 = 0xff;
+/// Size of the addressing header prepended to every payload.
+pub const HEADER_LEN: usize = 3;
+/// Header flag marking an echo request or reply, see `AddressedModem::ping`.
+pub const FLAG_ECHO: u8 = 0x10;
+
+const ECHO_REQUEST: u8 = 0x01;
+const ECHO_REPLY: u8 = 0x02;
+
+/// Addressing header: destination, source and flags, one byte each
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Destination node id
+    pub dst: u8,
+    /// Source node id
+    pub src: u8,
+    /// Flags for use by higher layers
+    pub flags: u8,
+}
+
+impl Header {
+    /// Prepend the header to `payload`.
+    pub fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+        frame.extend_from_slice(&[self.dst, self.src, self.flags]);
+        frame.extend_from_slice(payload);
+        frame
+    }
+    /// Split a frame into header and payload.
+    pub fn decode(frame: &[u8]) -> Result<(Header, &[u8])> {
+        if frame.len() < HEADER_LEN {
+            return Err(ModemError::Parse(
+                "frame shorter than addressing header!".into(),
+            ));
+        }
+        let header = Header {
+            dst: frame[0],
+            src: frame[1],
+            flags: frame[2],
+        };
+        Ok((header, &frame[HEADER_LEN..]))
+    }
+}
+
+/// A received packet together with its addressing header
+#[derive(Debug, Clone)]
+pub struct AddressedPacket {
+    /// Addressing header of the frame
+    pub header: Header,
+    /// The packet, `data` holds the payload without header
+    pub packet: RxPacket,
+}
+
+/// Round trip to a peer measured by `AddressedModem::ping`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PingReport {
+    /// Time from sending the request to receiving the reply
+    pub rtt: Duration,
+    /// RSSI in dBm the peer received the request with
+    pub rssi_fwd: i16,
+    /// RSSI in dBm the reply was received with
+    pub rssi_back: i16,
+    /// SNR in dB the peer received the request with, in quarter dB steps
+    pub snr_fwd: f32,
+    /// SNR in dB the reply was received with
+    pub snr_back: f32,
+}
+
+/// Adds node addressing on top of a device.
+///
+/// Used as a `LoraModemDevice`, `send_data` broadcasts and `read_packet` returns
+/// payloads addressed to this node, stripped of their header. Echo requests
+/// for this node are answered while reading, unless disabled with `set_echo`.
+pub struct AddressedModem<T: LoraModemDevice> {
+    inner: T,
+    node_id: u8,
+    promiscuous: bool,
+    echo: bool,
+    #[cfg(feature = "std")]
+    ping_seq: u8,
+    #[cfg(feature = "std")]
+    ping_timeout: Duration,
+}
+
+impl<T: LoraModemDevice> AddressedModem<T> {
+    pub fn new(inner: T, node_id: u8) -> Self {
+        AddressedModem {
+            inner,
+            node_id,
+            promiscuous: false,
+            echo: true,
+            #[cfg(feature = "std")]
+            ping_seq: 0,
+            #[cfg(feature = "std")]
+            ping_timeout: Duration::from_secs(3),
+        }
+    }
+    /// Id of this node.
+    pub fn node_id(&self) -> u8 {
+        self.node_id
+    }
+    /// In promiscuous mode frames for all destinations are received.
+    pub fn set_promiscuous(&mut self, promiscuous: bool) {
+        self.promiscuous = promiscuous;
+    }
+    /// Whether echo requests are answered, enabled by default.
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+    /// Time `ping` waits for the reply, 3 s by default.
+    #[cfg(feature = "std")]
+    pub fn set_ping_timeout(&mut self, timeout: Duration) {
+        self.ping_timeout = timeout;
+    }
+    /// Access the wrapped device.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+    /// Send `data` to node `dst` with the given header flags, reporting payload bytes sent.
+    pub fn send_with_flags(&mut self, dst: u8, flags: u8, data: &[u8]) -> Result<TxReport> {
+        let header = Header {
+            dst,
+            src: self.node_id,
+            flags,
+        };
+        let report = self.inner.send_data(header.encode(data))?;
+        Ok(TxReport {
+            bytes: report.bytes.saturating_sub(HEADER_LEN),
+            ..report
+        })
+    }
+    /// Send `data` to node `dst`.
+    pub fn send_to(&mut self, dst: u8, data: &[u8]) -> Result<TxReport> {
+        self.send_with_flags(dst, 0, data)
+    }
+    /// Send `data` to all nodes.
+    pub fn broadcast(&mut self, data: &[u8]) -> Result<TxReport> {
+        self.send_with_flags(BROADCAST, 0, data)
+    }
+    /// Whether a frame with this header is delivered to this node.
+    pub fn accepts(&self, header: &Header) -> bool {
+        self.promiscuous || header.dst == self.node_id || header.dst == BROADCAST
+    }
+    /// Read the next frame, regardless of its destination.
+    ///
+    /// Frames too short to carry a header are reported as parse errors.
+    pub fn read_frame(&mut self) -> Result<AddressedPacket> {
+        let mut packet = self.inner.read_packet()?;
+        let (header, payload) = Header::decode(&packet.data)?;
+        packet.data = payload.to_vec();
+        Ok(AddressedPacket { header, packet })
+    }
+    /// Read the next frame addressed to this node or broadcast, dropping all others.
+    ///
+    /// Echo requests for this node are answered and not returned. In promiscuous
+    /// mode every frame is returned, echo requests and replies included.
+    pub fn read_packet_for_me(&mut self) -> Result<AddressedPacket> {
+        loop {
+            match self.read_frame() {
+                Ok(frame) if frame.header.flags & FLAG_ECHO != 0 => {
+                    self.answer_echo(&frame)?;
+                    if self.promiscuous {
+                        return Ok(frame);
+                    }
+                }
+                Ok(frame) if self.accepts(&frame.header) => return Ok(frame),
+                Ok(_) | Err(ModemError::Parse(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Reply to an echo request for this node with the signal it was received with.
+    fn answer_echo(&mut self, frame: &AddressedPacket) -> Result<()> {
+        let data = &frame.packet.data;
+        if !self.echo
+            || frame.header.dst != self.node_id
+            || data.len() != 2
+            || data[0] != ECHO_REQUEST
+        {
+            return Ok(());
+        }
+        let rssi = frame.packet.rssi.to_be_bytes();
+        let snr = round(f64::from(frame.packet.snr) * 4.0).clamp(-128.0, 127.0) as i8;
+        let reply = [ECHO_REPLY, data[1], rssi[0], rssi[1], snr as u8];
+        self.send_with_flags(frame.header.src, FLAG_ECHO, &reply)?;
+        Ok(())
+    }
+
+    /// Send an echo request to `peer` and wait for its reply.
+    ///
+    /// Frames other than the reply arriving meanwhile are dropped. Fails with
+    /// `ModemError::Timeout` if no reply arrived within the ping timeout.
+    #[cfg(feature = "std")]
+    pub fn ping(&mut self, peer: u8) -> Result<PingReport> {
+        self.ping_seq = self.ping_seq.wrapping_add(1);
+        let seq = self.ping_seq;
+        let started = Instant::now();
+        self.send_with_flags(peer, FLAG_ECHO, &[ECHO_REQUEST, seq])?;
+        while started.elapsed() < self.ping_timeout {
+            let frame = match self.read_frame() {
+                Ok(frame) => frame,
+                Err(ModemError::Timeout) | Err(ModemError::Parse(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            let data = &frame.packet.data;
+            let reply = frame.header.flags & FLAG_ECHO != 0
+                && frame.header.src == peer
+                && frame.header.dst == self.node_id
+                && data.len() == 5
+                && data[..2] == [ECHO_REPLY, seq];
+            if reply {
+                return Ok(PingReport {
+                    rtt: started.elapsed(),
+                    rssi_fwd: i16::from_be_bytes([data[2], data[3]]),
+                    rssi_back: frame.packet.rssi,
+                    snr_fwd: f32::from(data[4] as i8) / 4.0,
+                    snr_back: frame.packet.snr,
+                });
+            }
+            if frame.header.flags & FLAG_ECHO != 0 {
+                self.answer_echo(&frame)?;
+            }
+        }
+        Err(ModemError::Timeout)
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for AddressedModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        Ok(self.inner.max_payload()?.saturating_sub(HEADER_LEN))
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    #[cfg(feature = "std")]
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    #[cfg(feature = "std")]
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.broadcast(&data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        self.broadcast(data)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        let header = Header {
+            dst: BROADCAST,
+            src: self.node_id,
+            flags: 0,
+        };
+        let framed: Vec<(Vec<u8>, usize)> = frames
+            .iter()
+            .map(|data| (header.encode(data), HEADER_LEN))
+            .collect();
+        send_framed(&mut self.inner, &framed)
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        self.read_packet_for_me().map(|frame| frame.packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
@@ -0,0 +1,352 @@
+use crate::addressing::AddressedPacket;
+use crate::quality::link_margin;
+use crate::radio::{Bandwidth, RadioParams};
+use crate::reliable::{Delivery, ReliableModem};
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError, ModemEvent, Result,
+    RxPacket, Status, TxReport,
+};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::time::{Duration, Instant};
+
+/// Header flag marking a data rate change announced by an `AdrModem`.
+pub const FLAG_ADR: u8 = 0x04;
+
+/// Settings of the data rate adaptation of an `AdrModem`
+#[derive(Debug, Clone)]
+pub struct AdrConfig {
+    /// Spreading factor and bandwidth pairs from the most robust to the fastest
+    pub rates: Vec<(u8, Bandwidth)>,
+    /// Link margin in dB a rate has to leave
+    pub margin_db: f32,
+    /// Extra margin in dB required before stepping to a faster rate
+    pub hysteresis_db: f32,
+    /// Consecutive deliveries leaving enough margin before stepping to a faster rate
+    pub step_up_after: usize,
+    /// Time without traffic after which the most robust rate is resumed
+    pub fallback_after: Duration,
+}
+
+impl Default for AdrConfig {
+    fn default() -> Self {
+        AdrConfig {
+            rates: vec![
+                (12, Bandwidth::Bw125kHz),
+                (11, Bandwidth::Bw125kHz),
+                (10, Bandwidth::Bw125kHz),
+                (9, Bandwidth::Bw125kHz),
+                (8, Bandwidth::Bw125kHz),
+                (7, Bandwidth::Bw125kHz),
+                (7, Bandwidth::Bw250kHz),
+                (7, Bandwidth::Bw500kHz),
+            ],
+            margin_db: 5.0,
+            hysteresis_db: 3.0,
+            step_up_after: 3,
+            fallback_after: Duration::from_secs(60),
+        }
+    }
+}
+
+// Margin expected at rate `to` for a frame received with `snr` at rate `from`,
+// wider bandwidths let in more noise.
+fn expected_margin(snr: f32, from: (u8, Bandwidth), to: (u8, Bandwidth)) -> f32 {
+    let noise = 10.0 * (to.1.hz() as f32 / from.1.hz() as f32).log10();
+    link_margin(snr - noise, to.0)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PeerRate {
+    // index into `AdrConfig::rates`
+    rate: usize,
+    // deliveries in a row that would have made it at the next faster rate
+    good: usize,
+}
+
+/// Adaptive data rate for point-to-point links on top of the ARQ layer.
+///
+/// Every peer starts at the most robust rate. Acknowledgements report the SNR
+/// the peer received a frame with, which gives the link margin left at the
+/// current rate; after `step_up_after` deliveries with enough margin for the
+/// next faster rate, retransmissions resetting the count, the link steps up.
+/// It steps down as soon as a delivery leaves less than `margin_db`. Changes
+/// are announced to the peer with a `FLAG_ADR` frame and only take effect
+/// once it has been acknowledged.
+///
+/// A failed delivery returns the link to the most robust rate, as does the
+/// receiving side after `fallback_after` without traffic, so both ends find
+/// each other again after a lost announcement. Both ends have to use the same
+/// `rates`. With several peers the radio is switched to the rate of a peer
+/// before sending to it.
+pub struct AdrModem<T: LoraModemDevice> {
+    modem: ReliableModem<T>,
+    config: AdrConfig,
+    peers: HashMap<u8, PeerRate>,
+    // rate the radio is set to, `None` until first applied
+    current: Option<usize>,
+    last_traffic: Instant,
+}
+
+impl<T: LoraModemDevice> AdrModem<T> {
+    pub fn new(modem: ReliableModem<T>, config: AdrConfig) -> Result<Self> {
+        if config.rates.is_empty() {
+            return Err(ModemError::InvalidArgument(
+                "no data rates to adapt between".into(),
+            ));
+        }
+        Ok(AdrModem {
+            modem,
+            config,
+            peers: HashMap::new(),
+            current: None,
+            last_traffic: Instant::now(),
+        })
+    }
+    /// The ARQ layer below.
+    pub fn reliable(&mut self) -> &mut ReliableModem<T> {
+        &mut self.modem
+    }
+    /// Unwrap the ARQ layer.
+    pub fn into_inner(self) -> ReliableModem<T> {
+        self.modem
+    }
+    /// Spreading factor and bandwidth used for `peer`.
+    pub fn rate(&self, peer: u8) -> (u8, Bandwidth) {
+        self.config.rates[self.peers.get(&peer).map_or(0, |p| p.rate)]
+    }
+
+    // Switch the radio to `rate`, keeping coding rate, preamble and CRC.
+    fn apply(&mut self, rate: usize) -> Result<()> {
+        if self.current == Some(rate) {
+            return Ok(());
+        }
+        let (spreading_factor, bandwidth) = self.config.rates[rate];
+        let params = RadioParams {
+            spreading_factor,
+            bandwidth,
+            ..self.modem.get_radio_params()?
+        };
+        self.current = None;
+        self.modem.set_radio_params(params)?;
+        self.current = Some(rate);
+        Ok(())
+    }
+
+    // Resume the most robust rate with all peers.
+    fn fall_back(&mut self) -> Result<()> {
+        self.peers.clear();
+        self.apply(0)
+    }
+
+    /// Send `data` to `dst` and wait until it has been acknowledged, adapting
+    /// the rate to the link margin reported by the peer.
+    pub fn send(&mut self, dst: u8, data: &[u8]) -> Result<usize> {
+        let peer = self.peers.get(&dst).copied().unwrap_or_default();
+        self.apply(peer.rate)?;
+        let delivery = match self.modem.deliver(dst, 0, data) {
+            Ok(delivery) => delivery,
+            Err(e) => {
+                if let ModemError::NotAcknowledged { .. } = e {
+                    self.fall_back()?;
+                }
+                return Err(e);
+            }
+        };
+        self.last_traffic = Instant::now();
+        if let Some(rate) = self.adapt(dst, delivery) {
+            self.announce(dst, rate)?;
+        }
+        Ok(data.len())
+    }
+
+    // Rate `peer` should move to after `delivery`, if any.
+    fn adapt(&mut self, peer: u8, delivery: Delivery) -> Option<usize> {
+        let rates = &self.config.rates;
+        let state = self.peers.entry(peer).or_default();
+        // without SNR reports from the peer there is nothing to adapt to
+        let snr = delivery.peer_snr?;
+        let current = rates[state.rate];
+        if state.rate > 0 && link_margin(snr, current.0) < self.config.margin_db {
+            state.good = 0;
+            return Some(state.rate - 1);
+        }
+        let faster = *rates.get(state.rate + 1)?;
+        let required = self.config.margin_db + self.config.hysteresis_db;
+        if delivery.attempts == 1 && expected_margin(snr, current, faster) >= required {
+            state.good += 1;
+        } else {
+            state.good = 0;
+        }
+        if state.good >= self.config.step_up_after {
+            state.good = 0;
+            return Some(state.rate + 1);
+        }
+        None
+    }
+
+    // Tell `peer` to switch to `rate`, then switch ourselves.
+    fn announce(&mut self, peer: u8, rate: usize) -> Result<()> {
+        let (spreading_factor, bandwidth) = self.config.rates[rate];
+        let mut frame = vec![spreading_factor];
+        frame.extend_from_slice(&bandwidth.hz().to_be_bytes());
+        match self.modem.deliver(peer, FLAG_ADR, &frame) {
+            Ok(_) => {
+                self.peers.entry(peer).or_default().rate = rate;
+                self.apply(rate)
+            }
+            // the peer may have switched with only the acknowledgement lost
+            Err(ModemError::NotAcknowledged { .. }) => self.fall_back(),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Follow a rate change announced by the peer, returns false if it is not one of our rates.
+    fn follow(&mut self, frame: &AddressedPacket) -> Result<bool> {
+        let data = &frame.packet.data;
+        if data.len() != 5 {
+            return Ok(false);
+        }
+        let hz = u32::from_be_bytes(data[1..].try_into().unwrap_or_default());
+        let rate = self
+            .config
+            .rates
+            .iter()
+            .position(|&(sf, bw)| sf == data[0] && bw.hz() == hz);
+        match rate {
+            Some(rate) => {
+                let state = self.peers.entry(frame.header.src).or_default();
+                state.rate = rate;
+                state.good = 0;
+                self.apply(rate)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Receive the next frame for this node, following rate changes of the peer.
+    pub fn receive(&mut self) -> Result<AddressedPacket> {
+        if self.current.is_none() {
+            self.apply(0)?;
+        }
+        loop {
+            let frame = match self.modem.receive() {
+                Ok(frame) => frame,
+                Err(ModemError::Timeout) => {
+                    if self.current != Some(0)
+                        && self.last_traffic.elapsed() >= self.config.fallback_after
+                    {
+                        self.fall_back()?;
+                    }
+                    return Err(ModemError::Timeout);
+                }
+                Err(e) => return Err(e),
+            };
+            self.last_traffic = Instant::now();
+            if frame.header.flags & FLAG_ADR != 0 {
+                self.follow(&frame)?;
+                continue;
+            }
+            return Ok(frame);
+        }
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for AdrModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.modem.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.modem.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.modem.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.modem.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.current = None;
+        self.modem.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        // spreading factor and bandwidth are replaced by the rate of the next peer
+        self.current = None;
+        self.modem.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.modem.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.modem.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.modem.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.modem.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.modem.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.modem.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.modem.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.modem.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.modem.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        self.modem.max_payload()
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.modem.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.modem.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.modem.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.modem.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.modem.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.modem.wake()
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.modem.stats()
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.modem.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.modem.send_data(data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        self.modem.send_slice(data)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        self.modem.send_batch(frames)
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        self.receive().map(|frame| frame.packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.modem.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.modem.next_event()
+    }
+}
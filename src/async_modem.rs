@@ -0,0 +1,280 @@
+//! Asynchronous interface to any modem device.
+//!
+//! Instead of a tokio-serial backend, which cannot be fetched in the offline
+//! build environment, `AsyncModem` drives any blocking `LoraModemDevice`,
+//! serial ports included, on its own thread. Its futures need no particular
+//! executor, so they run under tokio as well as any other runtime.
+
+use crate::queue::{Completions, Priority, QueueLimits, TxHandle, TxQueue};
+use crate::{
+    Frequency, LoRaChannels, LoraModemDevice, ModemConfig, ModemError, Result, RxPacket, Status,
+    TxReport,
+};
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// Asynchronous counterpart of `LoraModemDevice`.
+pub trait AsyncLoraModemDevice {
+    /// Explicitly open the device.
+    fn open(&mut self) -> impl Future<Output = Result<()>> + Send;
+    /// Set channel on the modem.
+    fn set_channel(&mut self, channel: LoRaChannels) -> impl Future<Output = Result<()>> + Send {
+        self.set_frequency(channel.frequency())
+    }
+    /// Set frequency on the modem.
+    fn set_frequency(&mut self, freq: Frequency) -> impl Future<Output = Result<()>> + Send;
+    /// Get current configuration of modem firmware.
+    fn config(&mut self) -> impl Future<Output = Result<Status>> + Send;
+    /// Set config mode on the modem.
+    fn set_mode(&mut self, mode: ModemConfig) -> impl Future<Output = Result<()>> + Send;
+    /// Send data via the modem.
+    fn send_data(&mut self, data: Vec<u8>) -> impl Future<Output = Result<TxReport>> + Send;
+    /// Read a packet from the modem.
+    fn read_packet(&mut self) -> impl Future<Output = Result<RxPacket>> + Send;
+}
+
+type Job = Box<dyn FnOnce(&mut dyn LoraModemDevice) + Send>;
+
+struct Slot<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+// Sending half of a single-use channel completing a `Reply`.
+struct Responder<T>(Arc<Mutex<Slot<T>>>);
+
+impl<T> Responder<T> {
+    fn send(self, value: T) {
+        self.0.lock().unwrap().value = Some(value);
+    }
+}
+
+impl<T> Drop for Responder<T> {
+    fn drop(&mut self) {
+        let mut slot = self.0.lock().unwrap();
+        slot.closed = true;
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+// Future resolving once the modem thread answered, `None` if it went away.
+struct Reply<T>(Arc<Mutex<Slot<T>>>);
+
+impl<T> Future for Reply<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.0.lock().unwrap();
+        if let Some(value) = slot.value.take() {
+            Poll::Ready(Some(value))
+        } else if slot.closed {
+            Poll::Ready(None)
+        } else {
+            slot.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+// Fails all outstanding transmissions once the device thread ends, even by a panic.
+struct CloseOnDrop(Arc<Completions>);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+fn oneshot<T>() -> (Responder<T>, Reply<T>) {
+    let slot = Arc::new(Mutex::new(Slot {
+        value: None,
+        waker: None,
+        closed: false,
+    }));
+    (Responder(slot.clone()), Reply(slot))
+}
+
+/// Runs any blocking `LoraModemDevice` on a dedicated thread and exposes it asynchronously.
+///
+/// Operations are executed one after another in the order they were issued, the
+/// returned futures are executor agnostic and can be used from tokio or any other runtime.
+/// Only transmissions are reordered: they wait in a bounded queue and the highest
+/// priority frame is sent whenever the device thread gets to a transmission.
+/// `submit` queues a frame without waiting for it, so transmissions can be
+/// pipelined while their outcomes are still collected one by one.
+pub struct AsyncModem {
+    jobs: mpsc::Sender<Job>,
+    queue: Arc<Mutex<TxQueue<TxHandle>>>,
+    completions: Arc<Completions>,
+}
+
+impl AsyncModem {
+    /// Move `device` onto its own thread.
+    pub fn spawn<D: LoraModemDevice + Send + 'static>(device: D) -> Self {
+        Self::spawn_with_limits(device, QueueLimits::default())
+    }
+    /// Like `spawn`, bounding the transmit queue by `limits`.
+    pub fn spawn_with_limits<D: LoraModemDevice + Send + 'static>(
+        mut device: D,
+        limits: QueueLimits,
+    ) -> Self {
+        let (jobs, rx) = mpsc::channel::<Job>();
+        let completions = Arc::new(Completions::default());
+        let closed = CloseOnDrop(completions.clone());
+        thread::spawn(move || {
+            let _closed = closed;
+            for job in rx {
+                job(&mut device);
+            }
+        });
+        AsyncModem {
+            jobs,
+            queue: Arc::new(Mutex::new(TxQueue::new(limits))),
+            completions,
+        }
+    }
+
+    /// Queue `data` for transmission with `priority` without waiting for it.
+    ///
+    /// Fails with `ModemError::QueueFull` if the queue is at its limits.
+    pub fn submit(&self, data: Vec<u8>, priority: Priority) -> Result<TxHandle> {
+        let handle = self.completions.handle();
+        self.queue.lock().unwrap().push(priority, data, handle)?;
+        // every queued frame gets a job, which sends whatever frame is most urgent by then
+        let queue = self.queue.clone();
+        let completions = self.completions.clone();
+        let _ = self.jobs.send(Box::new(move |device| {
+            let next = queue.lock().unwrap().pop();
+            if let Some((frame, handle)) = next {
+                completions.complete(handle, device.send_data(frame));
+            }
+        }));
+        Ok(handle)
+    }
+    /// Outcome of a submitted frame, `None` while it waits or is being transmitted.
+    ///
+    /// The outcome is handed out once, to either this or `completion`.
+    pub fn poll_tx(&self, handle: TxHandle) -> Option<Result<TxReport>> {
+        self.completions.take(handle)
+    }
+    /// Wait for the outcome of a submitted frame.
+    pub fn completion(&self, handle: TxHandle) -> impl Future<Output = Result<TxReport>> + Send {
+        let completions = self.completions.clone();
+        poll_fn(move |cx| completions.poll(handle, cx))
+    }
+
+    /// Queue `data` for transmission with `priority`.
+    ///
+    /// Fails with `ModemError::QueueFull` right away if the queue is at its limits.
+    pub fn send_with_priority(
+        &self,
+        data: Vec<u8>,
+        priority: Priority,
+    ) -> impl Future<Output = Result<TxReport>> + Send {
+        let completion = self.submit(data, priority).map(|h| self.completion(h));
+        async move { completion?.await }
+    }
+
+    fn call<R, F>(&self, f: F) -> impl Future<Output = Result<R>> + Send
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut dyn LoraModemDevice) -> Result<R> + Send + 'static,
+    {
+        let (responder, reply) = oneshot();
+        // if the thread is gone the job, and with it the responder, is dropped right away
+        let _ = self
+            .jobs
+            .send(Box::new(move |device| responder.send(f(device))));
+        async move { reply.await.unwrap_or(Err(ModemError::Disconnected)) }
+    }
+
+    /// Stream of received packets.
+    ///
+    /// Timeouts and unparsable packets are skipped, the stream ends at the first
+    /// other error reading from the device.
+    pub fn packets(&self) -> PacketStream {
+        PacketStream {
+            jobs: self.jobs.clone(),
+            inflight: None,
+            done: false,
+        }
+    }
+}
+
+impl AsyncLoraModemDevice for AsyncModem {
+    fn open(&mut self) -> impl Future<Output = Result<()>> + Send {
+        self.call(|device| device.open())
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> impl Future<Output = Result<()>> + Send {
+        self.call(move |device| device.set_frequency(freq))
+    }
+    fn config(&mut self) -> impl Future<Output = Result<Status>> + Send {
+        self.call(|device| device.config())
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> impl Future<Output = Result<()>> + Send {
+        self.call(move |device| device.set_mode(mode))
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> impl Future<Output = Result<TxReport>> + Send {
+        self.send_with_priority(data, Priority::Data)
+    }
+    fn read_packet(&mut self) -> impl Future<Output = Result<RxPacket>> + Send {
+        self.call(|device| device.read_packet())
+    }
+}
+
+/// Stream of packets received by an `AsyncModem`.
+///
+/// Implements the `poll_next` shape of `futures::Stream`, so it can be wrapped
+/// trivially, and offers `next()` for use in `while let Some(pkt) = rx.next().await`.
+pub struct PacketStream {
+    jobs: mpsc::Sender<Job>,
+    inflight: Option<Reply<Result<RxPacket>>>,
+    done: bool,
+}
+
+impl PacketStream {
+    /// Attempt to pull out the next packet.
+    pub fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<RxPacket>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        let this = &mut *self;
+        let jobs = &this.jobs;
+        let reply = this.inflight.get_or_insert_with(|| {
+            let (responder, reply) = oneshot();
+            let _ = jobs.send(Box::new(move |device: &mut dyn LoraModemDevice| {
+                responder.send(device.read_packet())
+            }));
+            reply
+        });
+        match Pin::new(reply).poll(cx) {
+            Poll::Ready(result) => {
+                this.inflight = None;
+                match result {
+                    Some(Ok(packet)) => Poll::Ready(Some(packet)),
+                    Some(Err(ModemError::Timeout)) | Some(Err(ModemError::Parse(_))) => {
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    }
+                    _ => {
+                        this.done = true;
+                        Poll::Ready(None)
+                    }
+                }
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Wait for the next packet, `None` once the stream ended.
+    pub async fn next(&mut self) -> Option<RxPacket> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
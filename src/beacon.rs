@@ -0,0 +1,221 @@
+use crate::duty_cycle::DutyCycleTracker;
+use crate::gps::{GpsFix, ENCODED_LEN};
+use crate::radio::airtime;
+use crate::rng::Rng;
+use crate::telemetry::ModemTelemetry;
+use crate::{LoraModemDevice, ModemError, Result, RxPacket};
+use std::time::{Duration, Instant};
+
+/// First byte of every beacon frame, distinguishes beacons from other traffic.
+pub const BEACON_MAGIC: u8 = 0xbe;
+// magic, flags, node id, uptime
+const FIXED_LEN: usize = 7;
+const FLAG_BATTERY: u8 = 0x01;
+const FLAG_GPS: u8 = 0x02;
+const FLAG_TEMPERATURE: u8 = 0x04;
+
+/// Periodically broadcasts a small frame announcing this node.
+///
+/// The frame carries the node id, the uptime and, if available, battery voltage,
+/// chip temperature and GPS fix. Each interval is stretched by up to a tenth at random so nodes
+/// started together do not keep colliding. With a duty-cycle tracker a beacon
+/// exceeding the budget is postponed instead of sent.
+pub struct Beacon {
+    node_id: u8,
+    interval: Duration,
+    started: Instant,
+    next: Instant,
+    rng: Rng,
+    battery: Option<Box<dyn FnMut() -> Option<f32> + Send>>,
+    gps: bool,
+    telemetry: bool,
+    tracker: Option<DutyCycleTracker>,
+}
+
+impl Beacon {
+    /// Beacon for `node_id`, the first one is due right away.
+    pub fn new(node_id: u8, interval: Duration) -> Self {
+        let now = Instant::now();
+        Beacon {
+            node_id,
+            interval,
+            started: now,
+            next: now,
+            rng: Rng::from_time(),
+            battery: None,
+            gps: true,
+            telemetry: false,
+            tracker: None,
+        }
+    }
+    /// Report the battery voltage in volts returned by `battery`, `None` if unknown.
+    pub fn set_battery<F: FnMut() -> Option<f32> + Send + 'static>(&mut self, battery: F) {
+        self.battery = Some(Box::new(battery));
+    }
+    /// Include the GPS fix of the device, enabled by default.
+    pub fn set_gps(&mut self, enabled: bool) {
+        self.gps = enabled;
+    }
+    /// Include the readings of the device `telemetry()`, disabled by default.
+    ///
+    /// The supply voltage is reported as battery voltage unless `set_battery` is used.
+    pub fn set_telemetry(&mut self, enabled: bool) {
+        self.telemetry = enabled;
+    }
+    /// Only send beacons the duty-cycle budget of `tracker` allows.
+    pub fn set_duty_cycle(&mut self, tracker: DutyCycleTracker) {
+        self.tracker = Some(tracker);
+    }
+    /// The duty-cycle tracker in use, if any.
+    pub fn tracker(&mut self) -> Option<&mut DutyCycleTracker> {
+        self.tracker.as_mut()
+    }
+    /// Whether the next beacon is due.
+    pub fn due(&self) -> bool {
+        Instant::now() >= self.next
+    }
+
+    /// Send a beacon on `device` if due, returns whether one was sent.
+    pub fn poll<D: LoraModemDevice + ?Sized>(&mut self, device: &mut D) -> Result<bool> {
+        if !self.due() {
+            return Ok(false);
+        }
+        let frame = self.frame(device)?;
+        if let Some(tracker) = self.tracker.as_mut() {
+            let freq = device.config()?.frequency;
+            let toa = airtime(frame.len(), &device.get_radio_params()?)?;
+            if tracker.wait_time(freq, toa) > Duration::from_secs(0) {
+                return Ok(false);
+            }
+            device.send_data(frame)?;
+            tracker.record(freq, toa);
+        } else {
+            device.send_data(frame)?;
+        }
+        let jitter = self.rng.below(self.interval.as_millis() as u64 / 10 + 1);
+        self.next = Instant::now() + self.interval + Duration::from_millis(jitter);
+        Ok(true)
+    }
+
+    fn frame<D: LoraModemDevice + ?Sized>(&mut self, device: &mut D) -> Result<Vec<u8>> {
+        let telemetry = if self.telemetry {
+            match device.telemetry() {
+                Ok(telemetry) => telemetry,
+                Err(ModemError::Unsupported(_)) | Err(ModemError::UnsupportedCommand(_)) => {
+                    ModemTelemetry::default()
+                }
+                Err(e) => return Err(e),
+            }
+        } else {
+            ModemTelemetry::default()
+        };
+        let battery = match self.battery.as_mut() {
+            Some(battery) => battery(),
+            None => telemetry.vcc_mv.map(|mv| mv as f32 / 1000.0),
+        };
+        let fix = if self.gps {
+            match device.gps_fix() {
+                Ok(fix) => fix,
+                Err(ModemError::Unsupported(_)) | Err(ModemError::UnsupportedCommand(_)) => None,
+                Err(e) => return Err(e),
+            }
+        } else {
+            None
+        };
+        let uptime = self.started.elapsed().as_secs().min(u32::MAX as u64) as u32;
+        let mut frame = Vec::with_capacity(FIXED_LEN + 3 + ENCODED_LEN);
+        let mut flags = 0;
+        if battery.is_some() {
+            flags |= FLAG_BATTERY;
+        }
+        if fix.is_some() {
+            flags |= FLAG_GPS;
+        }
+        if telemetry.temp_c.is_some() {
+            flags |= FLAG_TEMPERATURE;
+        }
+        frame.extend_from_slice(&[BEACON_MAGIC, flags, self.node_id]);
+        frame.extend_from_slice(&uptime.to_be_bytes());
+        if let Some(volts) = battery {
+            let millivolts = (volts * 1000.0).clamp(0.0, u16::MAX as f32) as u16;
+            frame.extend_from_slice(&millivolts.to_be_bytes());
+        }
+        if let Some(celsius) = telemetry.temp_c {
+            frame.push(celsius as u8);
+        }
+        if let Some(fix) = fix {
+            frame.extend_from_slice(&fix.encode());
+        }
+        Ok(frame)
+    }
+}
+
+/// A beacon received from another node
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeaconReport {
+    /// Node id of the sender
+    pub node_id: u8,
+    /// Time since the sender started beaconing, in whole seconds
+    pub uptime: Duration,
+    /// Battery voltage of the sender in volts
+    pub battery: Option<f32>,
+    /// Chip temperature of the sender in degrees Celsius
+    pub temperature: Option<i8>,
+    /// Position of the sender
+    pub fix: Option<GpsFix>,
+    /// Signal strength the beacon was received with
+    pub rssi: i16,
+    /// Signal-to-Noise ratio the beacon was received with
+    pub snr: f32,
+}
+
+impl BeaconReport {
+    /// Whether `frame` looks like a beacon.
+    pub fn is_beacon(frame: &[u8]) -> bool {
+        frame.len() >= FIXED_LEN && frame[0] == BEACON_MAGIC
+    }
+
+    /// Parse a received packet produced by `Beacon`.
+    pub fn from_packet(packet: &RxPacket) -> Result<BeaconReport> {
+        let frame = &packet.data[..];
+        if !BeaconReport::is_beacon(frame) {
+            return Err(ModemError::Parse("not a beacon frame!".into()));
+        }
+        let flags = frame[1];
+        let uptime = u32::from_be_bytes([frame[3], frame[4], frame[5], frame[6]]);
+        let mut rest = &frame[FIXED_LEN..];
+        let battery = if flags & FLAG_BATTERY != 0 {
+            if rest.len() < 2 {
+                return Err(ModemError::Parse("beacon battery field truncated!".into()));
+            }
+            let millivolts = u16::from_be_bytes([rest[0], rest[1]]);
+            rest = &rest[2..];
+            Some(millivolts as f32 / 1000.0)
+        } else {
+            None
+        };
+        let temperature = if flags & FLAG_TEMPERATURE != 0 {
+            let (&celsius, tail) = rest
+                .split_first()
+                .ok_or_else(|| ModemError::Parse("beacon temperature field truncated!".into()))?;
+            rest = tail;
+            Some(celsius as i8)
+        } else {
+            None
+        };
+        let fix = if flags & FLAG_GPS != 0 {
+            Some(GpsFix::decode(rest)?)
+        } else {
+            None
+        };
+        Ok(BeaconReport {
+            node_id: frame[2],
+            uptime: Duration::from_secs(uptime as u64),
+            battery,
+            temperature,
+            fix,
+            rssi: packet.rssi,
+            snr: packet.snr,
+        })
+    }
+}
@@ -0,0 +1,568 @@
+//! Command line access to a LoRa modem for bring-up and debugging.
+
+use lora_modem_hal::decode::meshtastic;
+use lora_modem_hal::fragment::fragment;
+use lora_modem_hal::serial::DEFAULT_BAUD;
+use lora_modem_hal::transfer::{receive_file, send_file};
+use lora_modem_hal::transport::Transport;
+use lora_modem_hal::{
+    enumerate_modems, probe, AddressedModem, ArqConfig, LoraModem, LoraModemDevice, ModemConfig,
+    ModemError, ModemProfile, Reassembler, ReliableModem, Result, Rf95Modem, RxPacket, SiteSurvey,
+    TcpModem, Timeouts,
+};
+use std::convert::TryFrom;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// How long a read waits for a packet before input is checked again.
+const RX_POLL: Duration = Duration::from_millis(200);
+
+const USAGE: &str = "usage: lora-modem [options] <command> [args]
+
+options:
+  -d, --device <path>     serial device of the modem, e.g. COM5 on Windows
+                          (default /dev/ttyUSB0)
+  -b, --baud <rate>       baud rate of the serial device (default 115200)
+  -t, --tcp <host:port>   connect to a modem exposed over TCP instead
+  -p, --profile <path>    apply the settings of a TOML profile after connecting,
+                          its port and baud rate apply unless given explicitly
+  -h, --help              show this help
+
+commands:
+  detect                  list serial ports with an rf95modem attached
+  probe                   identify the firmware and baud rate of the modem on
+                          the serial device
+  sniff                   print every received packet with RSSI, SNR and a hexdump,
+                          tagging Meshtastic packets
+  send <hex>              transmit a payload given as hex
+  send -s <text>          transmit a payload given as text
+  info                    show the modem status and capabilities
+  set-freq <MHz>          set the frequency
+  set-mode <0-3>          set one of the predefined modem configs
+  send-file <id> <peer> <path>
+                          send a file to node <peer> as node <id>
+  receive-file <id> [dir] receive a file as node <id> into <dir>, interrupted
+                          transfers resume when the file is sent again
+  chat <id> <peer>        chat with node <peer> as node <id>, messages are
+                          fragmented and acknowledged, latency assumes
+                          synchronized clocks
+  ping <id> <peer> [count]
+                          ping node <peer> as node <id> once a second, 10
+                          times by default, showing the signal both ways
+  echo <id>               answer pings as node <id>
+  survey <id> <peer> <path>
+                          ping node <peer> as node <id> every 5 seconds and
+                          log the position and signal to <path>, written as
+                          GPX for a .gpx extension and as CSV otherwise";
+
+struct Options {
+    device: String,
+    baud: u32,
+    tcp: Option<String>,
+    profile: Option<ModemProfile>,
+    command: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> std::result::Result<Options, String> {
+    let mut options = Options {
+        device: "/dev/ttyUSB0".to_string(),
+        baud: DEFAULT_BAUD,
+        tcp: None,
+        profile: None,
+        command: Vec::new(),
+    };
+    let (mut device, mut baud) = (None, None);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "-d" | "--device" => device = Some(value(&arg)?),
+            "-b" | "--baud" => {
+                baud = Some(
+                    value(&arg)?
+                        .parse()
+                        .map_err(|_| "invalid baud rate".to_string())?,
+                )
+            }
+            "-t" | "--tcp" => options.tcp = Some(value(&arg)?),
+            "-p" | "--profile" => {
+                let path = value(&arg)?;
+                let profile =
+                    ModemProfile::load_toml(&path).map_err(|e| format!("{}: {}", path, e))?;
+                options.profile = Some(profile);
+            }
+            "-h" | "--help" => return Err(String::new()),
+            _ => {
+                options.command.push(arg);
+                options.command.extend(args);
+                break;
+            }
+        }
+    }
+    if options.command.is_empty() {
+        return Err("no command given".to_string());
+    }
+    let profile = options.profile.as_ref();
+    if let Some(device) = device.or_else(|| profile.and_then(|p| p.port.clone())) {
+        options.device = device;
+    }
+    if let Some(baud) = baud.or_else(|| profile.and_then(|p| p.baud)) {
+        options.baud = baud;
+    }
+    Ok(options)
+}
+
+// Print a hexdump of `data`, 16 bytes per line with their ASCII characters.
+fn hexdump(data: &[u8]) {
+    for (i, line) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = line
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        println!("  {:04x}  {:<47}  |{}|", i * 16, hex.join(" "), ascii);
+    }
+}
+
+fn print_packet(packet: &RxPacket) {
+    let time = packet
+        .received_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    print!(
+        "[{}.{:03}] {} bytes  RSSI {} dBm  SNR {} dB ({})",
+        time.as_secs(),
+        time.subsec_millis(),
+        packet.data.len(),
+        packet.rssi,
+        packet.snr,
+        packet.quality()
+    );
+    if let Some(hz) = packet.freq_error {
+        print!("  freq error {} Hz", hz);
+    }
+    println!();
+    hexdump(&packet.data);
+}
+
+fn sniff<D: LoraModemDevice>(device: &mut D) -> Result<()> {
+    device.enable_rx()?;
+    loop {
+        match device.read_packet() {
+            Ok(packet) => {
+                print_packet(&packet);
+                // foreign traffic on the channel
+                if let Some(mesh) = meshtastic::decode(&packet.data) {
+                    println!("  {}", mesh);
+                }
+            }
+            Err(ModemError::Timeout) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn reliable<D: LoraModemDevice>(device: D, node_id: u8) -> Result<ReliableModem<D>> {
+    let mut modem = ReliableModem::new(AddressedModem::new(device, node_id), ArqConfig::default());
+    modem.enable_rx()?;
+    Ok(modem)
+}
+
+// Send `text` to `peer` behind the time it was sent, in fragments fitting `mtu`.
+fn send_message<D: LoraModemDevice>(
+    modem: &mut ReliableModem<D>,
+    peer: u8,
+    id: u16,
+    text: &str,
+    mtu: usize,
+) -> Result<()> {
+    let mut message = now_ms().to_be_bytes().to_vec();
+    message.extend_from_slice(text.as_bytes());
+    let started = Instant::now();
+    for part in fragment(id, &message, mtu)? {
+        match modem.send_reliable(peer, &part) {
+            Ok(_) => {}
+            Err(ModemError::NotAcknowledged { attempts }) => {
+                println!("-- not delivered after {} attempts", attempts);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    println!("-- delivered in {} ms", started.elapsed().as_millis());
+    Ok(())
+}
+
+fn chat<D: LoraModemDevice>(device: D, node_id: u8, peer: u8) -> Result<()> {
+    let mut modem = reliable(device, node_id)?;
+    // room for the sequence number of the ARQ layer
+    let mtu = modem.max_payload()?.saturating_sub(1);
+    let (lines, input) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let sent = match line {
+                Ok(line) => lines.send(line).is_ok(),
+                Err(_) => false,
+            };
+            if !sent {
+                break;
+            }
+        }
+    });
+    let mut reassembler = Reassembler::new(Duration::from_secs(60), 8);
+    let mut next_id = now_ms() as u16;
+    println!(
+        "chatting as node {} with node {}, end with Ctrl-D",
+        node_id, peer
+    );
+    loop {
+        loop {
+            match input.try_recv() {
+                Ok(line) if line.is_empty() => {}
+                Ok(line) => {
+                    send_message(&mut modem, peer, next_id, &line, mtu)?;
+                    next_id = next_id.wrapping_add(1);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+        let frame = match modem.receive() {
+            Ok(frame) => frame,
+            Err(ModemError::Timeout) => continue,
+            Err(e) => return Err(e),
+        };
+        let message = match reassembler.push_from(frame.header.src, &frame.packet.data) {
+            Ok(Some(message)) if message.len() >= 8 => message,
+            Ok(_) | Err(ModemError::Parse(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        let mut sent_at = [0u8; 8];
+        sent_at.copy_from_slice(&message[..8]);
+        println!(
+            "<{}> {}  (RSSI {} dBm, SNR {} dB, latency {} ms)",
+            frame.header.src,
+            String::from_utf8_lossy(&message[8..]),
+            frame.packet.rssi,
+            frame.packet.snr,
+            now_ms().saturating_sub(u64::from_be_bytes(sent_at))
+        );
+    }
+}
+
+fn ping<D: LoraModemDevice>(device: D, node_id: u8, peer: u8, count: usize) -> Result<()> {
+    let mut modem = AddressedModem::new(device, node_id);
+    modem.enable_rx()?;
+    println!("pinging node {} as node {}", peer, node_id);
+    let mut rtts = Vec::new();
+    for i in 0..count {
+        let started = Instant::now();
+        match modem.ping(peer) {
+            Ok(report) => {
+                println!(
+                    "reply from {}: time {} ms  there RSSI {} dBm SNR {} dB  back RSSI {} dBm SNR {} dB",
+                    peer,
+                    report.rtt.as_millis(),
+                    report.rssi_fwd,
+                    report.snr_fwd,
+                    report.rssi_back,
+                    report.snr_back
+                );
+                rtts.push(report.rtt);
+            }
+            Err(ModemError::Timeout) => println!("no reply from {}", peer),
+            Err(e) => return Err(e),
+        }
+        if i + 1 < count {
+            thread::sleep(Duration::from_secs(1).saturating_sub(started.elapsed()));
+        }
+    }
+    println!(
+        "-- {} sent, {} received, {}% loss",
+        count,
+        rtts.len(),
+        (count - rtts.len()) * 100 / count
+    );
+    if let (Some(min), Some(max)) = (rtts.iter().min(), rtts.iter().max()) {
+        let avg = rtts.iter().sum::<Duration>() / rtts.len() as u32;
+        println!(
+            "-- rtt min/avg/max {}/{}/{} ms",
+            min.as_millis(),
+            avg.as_millis(),
+            max.as_millis()
+        );
+    }
+    Ok(())
+}
+
+fn echo<D: LoraModemDevice>(device: D, node_id: u8) -> Result<()> {
+    let mut modem = AddressedModem::new(device, node_id);
+    modem.enable_rx()?;
+    println!("answering pings as node {}, stop with Ctrl-C", node_id);
+    loop {
+        match modem.read_packet_for_me() {
+            Ok(_) | Err(ModemError::Timeout) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Ping `peer` from wherever the modem is, rewriting `path` after every sample.
+fn survey<D: LoraModemDevice>(device: D, node_id: u8, peer: u8, path: &Path) -> Result<()> {
+    let mut modem = AddressedModem::new(device, node_id);
+    modem.enable_rx()?;
+    let gpx = path.extension().is_some_and(|ext| ext == "gpx");
+    let mut survey = SiteSurvey::new();
+    println!(
+        "surveying node {} into {}, stop with Ctrl-C",
+        peer,
+        path.display()
+    );
+    loop {
+        let started = Instant::now();
+        match survey.ping(&mut modem, peer)? {
+            Some(sample) => {
+                match (sample.rssi, sample.snr) {
+                    (Some(rssi), Some(snr)) => println!(
+                        "{:.6},{:.6}: RSSI {} dBm  SNR {} dB",
+                        sample.fix.lat, sample.fix.lon, rssi, snr
+                    ),
+                    _ => println!("{:.6},{:.6}: no reply", sample.fix.lat, sample.fix.lon),
+                }
+                let file = io::BufWriter::new(File::create(path)?);
+                if gpx {
+                    survey.write_gpx(file)?;
+                } else {
+                    survey.write_csv(file)?;
+                }
+            }
+            None => println!("waiting for a GPS fix"),
+        }
+        thread::sleep(Duration::from_secs(5).saturating_sub(started.elapsed()));
+    }
+}
+
+// Overwrite the current line with the share of `total` bytes done.
+fn print_progress(done: u64, total: u64) {
+    let percent = (done * 100).checked_div(total).unwrap_or(100);
+    print!("\r{:3}% {}/{} bytes", percent, done, total);
+    let _ = io::stdout().flush();
+}
+
+fn run<D: LoraModemDevice>(
+    mut device: D,
+    profile: Option<&ModemProfile>,
+    command: &[String],
+) -> Result<()> {
+    if let Some(profile) = profile {
+        profile.apply(&mut device)?;
+    }
+    let arg = |i: usize| {
+        command
+            .get(i)
+            .map(String::as_str)
+            .ok_or_else(|| ModemError::InvalidArgument(format!("{} needs an argument", command[0])))
+    };
+    let id = |i: usize| {
+        arg(i)?
+            .parse::<u8>()
+            .map_err(|_| ModemError::InvalidArgument("invalid node id".into()))
+    };
+    match command[0].as_str() {
+        "sniff" => sniff(&mut device),
+        "send" => {
+            let data = if arg(1)? == "-s" {
+                arg(2)?.as_bytes().to_vec()
+            } else {
+                lora_modem_hal::hex::decode(arg(1)?)?
+            };
+            let report = device.send_data(data)?;
+            match report.airtime_estimate {
+                Some(toa) => println!(
+                    "sent {} bytes, about {} ms on air",
+                    report.bytes,
+                    toa.as_millis()
+                ),
+                None => println!("sent {} bytes", report.bytes),
+            }
+            Ok(())
+        }
+        "info" => {
+            let status = device.config()?;
+            println!("firmware:    {}", status.version);
+            println!("mode:        {:?}", status.config);
+            println!("frequency:   {} MHz", status.frequency);
+            if let Some(dbm) = status.tx_power {
+                println!("tx power:    {} dBm", dbm);
+            }
+            if let Some(word) = status.sync_word {
+                println!("sync word:   {:#04x}", word);
+            }
+            println!("max packet:  {} bytes", status.max_pkt_size);
+            println!("rx enabled:  {}", status.rx_listener);
+            if let Some(state) = status.radio_state {
+                println!("radio:       {}", state);
+            }
+            println!(
+                "counters:    rx good {}, rx bad {}, tx good {}",
+                status.rx_good, status.rx_bad, status.tx_good
+            );
+            match device.capabilities() {
+                Ok(caps) => println!("features:    {}", caps),
+                Err(ModemError::UnsupportedCommand(_)) => {}
+                Err(e) => return Err(e),
+            }
+            match device.telemetry() {
+                Ok(telemetry) => {
+                    if let Some(mv) = telemetry.vcc_mv {
+                        println!("supply:      {} mV", mv);
+                    }
+                    if let Some(celsius) = telemetry.temp_c {
+                        println!("temperature: {} C", celsius);
+                    }
+                }
+                Err(ModemError::Unsupported(_)) | Err(ModemError::UnsupportedCommand(_)) => {}
+                Err(e) => return Err(e),
+            }
+            Ok(())
+        }
+        "set-freq" => {
+            let freq = arg(1)?
+                .parse()
+                .map_err(|_| ModemError::InvalidArgument("invalid frequency".into()))?;
+            device.set_frequency(freq)
+        }
+        "set-mode" => {
+            let mode = arg(1)?
+                .parse::<usize>()
+                .map_err(|_| ModemError::InvalidArgument("invalid mode".into()))?;
+            device.set_mode(ModemConfig::try_from(mode)?)
+        }
+        "send-file" => {
+            let mut modem = reliable(device, id(1)?)?;
+            send_file(&mut modem, id(2)?, Path::new(arg(3)?), print_progress)?;
+            println!();
+            Ok(())
+        }
+        "receive-file" => {
+            let dir = Path::new(command.get(2).map_or(".", String::as_str));
+            let mut modem = reliable(device, id(1)?)?;
+            let path = receive_file(&mut modem, dir, print_progress)?;
+            println!("\nreceived {}", path.display());
+            Ok(())
+        }
+        "chat" => chat(device, id(1)?, id(2)?),
+        "ping" => {
+            let count = match command.get(3) {
+                Some(count) => count
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&count| count > 0)
+                    .ok_or_else(|| ModemError::InvalidArgument("invalid count".into()))?,
+                None => 10,
+            };
+            ping(device, id(1)?, id(2)?, count)
+        }
+        "echo" => echo(device, id(1)?),
+        "survey" => survey(device, id(1)?, id(2)?, Path::new(arg(3)?)),
+        other => Err(ModemError::InvalidArgument(format!(
+            "unknown command {}",
+            other
+        ))),
+    }
+}
+
+// Shorten the read timeout so that reading packets alternates with user input.
+fn polling<T: Transport>(mut modem: Rf95Modem<T>) -> Rf95Modem<T> {
+    modem.set_timeouts(Timeouts {
+        rx: Some(RX_POLL),
+        ..modem.timeouts()
+    });
+    modem
+}
+
+// List the modems answering on candidate serial ports.
+fn detect() {
+    let modems = enumerate_modems();
+    if modems.is_empty() {
+        println!("no modem found");
+    }
+    for modem in modems {
+        let usb = modem.port.usb_id.map_or(String::new(), |(vid, pid)| {
+            format!(" [{:04x}:{:04x}]", vid, pid)
+        });
+        let product = modem
+            .port
+            .product
+            .map_or(String::new(), |product| format!(" {}", product));
+        println!(
+            "{}{}{}: {}, {} MHz",
+            modem.port.path, usb, product, modem.status.version, modem.status.frequency
+        );
+    }
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            if !e.is_empty() {
+                eprintln!("lora-modem: {}", e);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    match options.command[0].as_str() {
+        "detect" => return detect(),
+        "probe" => match probe(&options.device) {
+            Ok(found) => {
+                println!(
+                    "{}: {} {} at {} baud",
+                    found.path, found.firmware, found.version, found.baud
+                );
+                return;
+            }
+            Err(e) => {
+                eprintln!("lora-modem: {}: {}", options.device, e);
+                process::exit(1);
+            }
+        },
+        _ => {}
+    }
+    let result = match &options.tcp {
+        Some(addr) => {
+            let mut modem = TcpModem::new(addr);
+            modem
+                .open()
+                .and_then(|_| run(polling(modem), options.profile.as_ref(), &options.command))
+        }
+        None => LoraModem::builder()
+            .path(&options.device)
+            .baud(options.baud)
+            .open()
+            .and_then(|modem| run(polling(modem), options.profile.as_ref(), &options.command)),
+    };
+    if let Err(e) = result {
+        eprintln!("lora-modem: {}", e);
+        process::exit(1);
+    }
+}
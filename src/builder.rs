@@ -0,0 +1,168 @@
+use crate::radio::validate_tx_power;
+use crate::region::Region;
+use crate::rf95::{LineProtocol, WritePacing};
+use crate::serial::{FlowControl, SerialModem, DEFAULT_BAUD};
+use crate::{Frequency, LoraModemDevice, ModemConfig, ModemError, Result};
+use std::time::Duration;
+
+/// Entry point for configuring a modem with `LoraModem::builder()`.
+pub enum LoraModem {}
+
+impl LoraModem {
+    /// Start configuring a serial modem.
+    pub fn builder() -> ModemBuilder {
+        ModemBuilder::default()
+    }
+}
+
+/// Settings applied to a modem right after opening it.
+///
+/// `open()` only hands out the modem once every setting has been acknowledged
+/// and reads back from `AT+INFO` as requested, otherwise the modem is dropped
+/// and the error returned, so callers never get a half-configured device.
+#[derive(Debug, Clone)]
+pub struct ModemBuilder {
+    path: Option<String>,
+    baud: u32,
+    timeout: Option<Duration>,
+    frequency: Option<Frequency>,
+    mode: Option<ModemConfig>,
+    tx_power: Option<i8>,
+    region: Option<Region>,
+    line_protocol: LineProtocol,
+    write_pacing: WritePacing,
+    flow_control: FlowControl,
+}
+
+impl Default for ModemBuilder {
+    fn default() -> Self {
+        ModemBuilder {
+            path: None,
+            baud: DEFAULT_BAUD,
+            timeout: Some(Duration::from_secs(5)),
+            frequency: None,
+            mode: None,
+            tx_power: None,
+            region: None,
+            line_protocol: LineProtocol::default(),
+            write_pacing: WritePacing::default(),
+            flow_control: FlowControl::None,
+        }
+    }
+}
+
+impl ModemBuilder {
+    /// Serial device the modem is attached to.
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+    pub fn baud(mut self, baud: u32) -> Self {
+        self.baud = baud;
+        self
+    }
+    /// Timeout of commands and reads, `None` blocks forever.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    pub fn frequency(mut self, freq: Frequency) -> Self {
+        self.frequency = Some(freq);
+        self
+    }
+    pub fn mode(mut self, mode: ModemConfig) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+    /// Transmit power in dBm.
+    pub fn tx_power(mut self, dbm: i8) -> Self {
+        self.tx_power = Some(dbm);
+        self
+    }
+    /// Reject frequency and transmit power not permitted in `region`.
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Line framing of the firmware, see `LineProtocol`.
+    pub fn line_protocol(mut self, protocol: LineProtocol) -> Self {
+        self.line_protocol = protocol;
+        self
+    }
+    /// Pacing of written lines, see `WritePacing`.
+    pub fn write_pacing(mut self, pacing: WritePacing) -> Self {
+        self.write_pacing = pacing;
+        self
+    }
+    pub fn flow_control(mut self, flow_control: FlowControl) -> Self {
+        self.flow_control = flow_control;
+        self
+    }
+
+    /// Open the modem and apply and verify all settings.
+    pub fn open(self) -> Result<SerialModem> {
+        let path = self
+            .path
+            .as_deref()
+            .ok_or_else(|| ModemError::InvalidArgument("no device path given".into()))?;
+        // reject bad arguments before touching the device
+        if let Some(dbm) = self.tx_power {
+            validate_tx_power(dbm)?;
+        }
+        if let (Some(region), Some(freq)) = (self.region, self.frequency) {
+            region.check_frequency(freq)?;
+            if let Some(dbm) = self.tx_power {
+                region.check_tx_power(freq, dbm)?;
+            }
+        }
+        let mut modem = SerialModem::new(path, self.baud);
+        modem.set_timeout(self.timeout);
+        modem.set_line_protocol(self.line_protocol.clone());
+        modem.set_write_pacing(self.write_pacing);
+        modem.transport_mut().set_flow_control(self.flow_control);
+        modem.open()?;
+        if let Some(mode) = self.mode {
+            modem.set_mode(mode)?;
+        }
+        if let Some(freq) = self.frequency {
+            modem.set_frequency(freq)?;
+        }
+        if let Some(dbm) = self.tx_power {
+            modem.set_tx_power(dbm)?;
+        }
+        self.verify(&mut modem)?;
+        Ok(modem)
+    }
+
+    // Compare the settings reported by the modem with the requested ones.
+    fn verify(&self, modem: &mut SerialModem) -> Result<()> {
+        let status = modem.config()?;
+        if let Some(mode) = self.mode {
+            if status.config != mode {
+                return Err(ModemError::ModemReported(format!(
+                    "mode reads back as {:?} instead of {:?}",
+                    status.config, mode
+                )));
+            }
+        }
+        if let Some(freq) = self.frequency {
+            // the firmware reports the frequency with four decimals, i.e. in steps of 100 Hz
+            if status.frequency.hz().abs_diff(freq.hz()) > 100 {
+                return Err(ModemError::ModemReported(format!(
+                    "frequency reads back as {} MHz instead of {} MHz",
+                    status.frequency, freq
+                )));
+            }
+        }
+        if let (Some(dbm), Some(reported)) = (self.tx_power, status.tx_power) {
+            if reported != dbm {
+                return Err(ModemError::ModemReported(format!(
+                    "tx power reads back as {} dBm instead of {} dBm",
+                    reported, dbm
+                )));
+            }
+        }
+        Ok(())
+    }
+}
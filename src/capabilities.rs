@@ -0,0 +1,179 @@
+use crate::{info_fields, Result, Status};
+use alloc::string::String;
+use core::fmt;
+use core::ops::{BitOr, BitOrAssign};
+
+/// Optional firmware features, combined as bit flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// GPS receiver (`AT+GPS`)
+    pub const GPS: Capabilities = Capabilities(1 << 0);
+    /// BLE bridge (`AT+BLE`)
+    pub const BLE: Capabilities = Capabilities(1 << 1);
+    /// Individual radio settings (`AT+BW`, `AT+SF`, ...)
+    pub const RADIO_PARAMS: Capabilities = Capabilities(1 << 2);
+    /// Transmit power control (`AT+TXPOWER`)
+    pub const TX_POWER: Capabilities = Capabilities(1 << 3);
+    /// Channel activity detection (`AT+CAD`)
+    pub const CAD: Capabilities = Capabilities(1 << 4);
+    /// Sleep and standby power modes (`AT+SLEEP`, `AT+STANDBY`)
+    pub const POWER_MODES: Capabilities = Capabilities(1 << 5);
+    /// Supply voltage and temperature readings (`AT+TELEMETRY`)
+    pub const TELEMETRY: Capabilities = Capabilities(1 << 6);
+    /// Implicit header mode with a fixed payload length (`AT+IMPLICIT`)
+    pub const IMPLICIT_HEADER: Capabilities = Capabilities(1 << 7);
+    /// Configurable sync word (`AT+SYNCWORD`)
+    pub const SYNC_WORD: Capabilities = Capabilities(1 << 8);
+
+    const NAMES: [(Capabilities, &'static str); 9] = [
+        (Capabilities::GPS, "GPS"),
+        (Capabilities::BLE, "BLE"),
+        (Capabilities::RADIO_PARAMS, "RADIO_PARAMS"),
+        (Capabilities::TX_POWER, "TX_POWER"),
+        (Capabilities::CAD, "CAD"),
+        (Capabilities::POWER_MODES, "POWER_MODES"),
+        (Capabilities::TELEMETRY, "TELEMETRY"),
+        (Capabilities::IMPLICIT_HEADER, "IMPLICIT_HEADER"),
+        (Capabilities::SYNC_WORD, "SYNC_WORD"),
+    ];
+
+    /// No optional features.
+    pub const fn empty() -> Self {
+        Capabilities(0)
+    }
+    /// All known features.
+    pub const fn all() -> Self {
+        Capabilities(0b1_1111_1111)
+    }
+    /// Raw flag bits.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+    /// Features from raw flag bits, unknown bits are dropped.
+    pub const fn from_bits_truncate(bits: u32) -> Self {
+        Capabilities(bits & Capabilities::all().0)
+    }
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    /// Whether all features of `other` are present.
+    pub const fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+    pub fn insert(&mut self, other: Capabilities) {
+        self.0 |= other.0;
+    }
+
+    /// Features advertised by the commands listed in `AT+HELP` output.
+    pub fn from_help(lines: &[String]) -> Self {
+        let mut caps = Capabilities::empty();
+        for line in lines {
+            let line = line.to_uppercase();
+            if line.contains("AT+GPS") {
+                caps.insert(Capabilities::GPS);
+            }
+            if line.contains("AT+BLE") {
+                caps.insert(Capabilities::BLE);
+            }
+            if line.contains("AT+BW") || line.contains("AT+SF") {
+                caps.insert(Capabilities::RADIO_PARAMS);
+            }
+            if line.contains("AT+TXPOWER") {
+                caps.insert(Capabilities::TX_POWER);
+            }
+            if line.contains("AT+CAD") {
+                caps.insert(Capabilities::CAD);
+            }
+            if line.contains("AT+SLEEP") {
+                caps.insert(Capabilities::POWER_MODES);
+            }
+            if line.contains("AT+TELEMETRY") {
+                caps.insert(Capabilities::TELEMETRY);
+            }
+            if line.contains("AT+IMPLICIT") {
+                caps.insert(Capabilities::IMPLICIT_HEADER);
+            }
+            if line.contains("AT+SYNCWORD") {
+                caps.insert(Capabilities::SYNC_WORD);
+            }
+        }
+        caps
+    }
+
+    /// Features to expect from a firmware version string, for firmware without `AT+HELP`.
+    ///
+    /// GPS and BLE are build options and cannot be derived from the version.
+    pub fn from_version(version: &str) -> Self {
+        let mut parts = version
+            .trim()
+            .trim_start_matches('v')
+            .split(|c: char| !c.is_ascii_digit())
+            .map(|p| p.parse::<u32>().unwrap_or(0));
+        let major = parts.next().unwrap_or(0);
+        let minor = parts.next().unwrap_or(0);
+        if (major, minor) >= (0, 7) {
+            Capabilities::RADIO_PARAMS | Capabilities::TX_POWER
+        } else {
+            Capabilities::empty()
+        }
+    }
+
+    /// Features listed in `AT+INFO` output, for firmware without a usable `AT+HELP`.
+    pub(crate) fn from_info(lines: &[String]) -> Result<Self> {
+        let mut caps = Capabilities::from_version(&Status::parse(lines)?.version);
+        for (key, value) in info_fields(lines) {
+            match key.as_str() {
+                "features" => {
+                    for feature in value.split_whitespace() {
+                        match feature.to_uppercase().as_str() {
+                            "GPS" => caps |= Capabilities::GPS,
+                            "BLE" => caps |= Capabilities::BLE,
+                            _ => {}
+                        }
+                    }
+                }
+                "tx power" => caps |= Capabilities::TX_POWER,
+                "bandwidth" | "spreading factor" => caps |= Capabilities::RADIO_PARAMS,
+                "implicit header" => caps |= Capabilities::IMPLICIT_HEADER,
+                "sync word" => caps |= Capabilities::SYNC_WORD,
+                _ => {}
+            }
+        }
+        Ok(caps)
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Capabilities(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Capabilities {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (cap, name) in Capabilities::NAMES.iter() {
+            if self.contains(*cap) {
+                if !first {
+                    f.write_str(" | ")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        if first {
+            f.write_str("(none)")?;
+        }
+        Ok(())
+    }
+}
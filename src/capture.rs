@@ -0,0 +1,345 @@
+use crate::radio::RadioParams;
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemEvent, Result, RxPacket,
+    Status, TxReport,
+};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// pcap link type of LoRaTap encapsulated frames
+pub const LINKTYPE_LORATAP: u16 = 270;
+const LORATAP_HEADER_LEN: usize = 15;
+// Sync word of rf95modem, the RadioHead default.
+const SYNC_WORD: u8 = 0x12;
+
+/// Direction of a captured frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Received from the air
+    Inbound,
+    /// Transmitted by this device
+    Outbound,
+}
+
+/// Radio metadata stored with a captured frame
+#[derive(Debug, Clone)]
+pub struct FrameInfo {
+    /// Received or transmitted
+    pub direction: Direction,
+    /// Capture time
+    pub time: SystemTime,
+    /// Frequency, zero if unknown
+    pub frequency: Frequency,
+    /// Radio settings, if known
+    pub params: Option<RadioParams>,
+    /// Signal strength of received frames
+    pub rssi: Option<i16>,
+    /// Signal-to-Noise ratio of received frames
+    pub snr: Option<f32>,
+}
+
+/// Writes frames into a pcapng file using the LoRaTap link type, readable by Wireshark.
+pub struct CaptureWriter<W: Write> {
+    out: W,
+}
+
+impl CaptureWriter<BufWriter<File>> {
+    /// Create or truncate the capture file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        CaptureWriter::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Start a capture on `out` by writing the section and interface headers.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        // section header block: byte-order magic, version 1.0, unknown section length
+        let mut shb = Vec::with_capacity(16);
+        shb.extend_from_slice(&0x1a2b_3c4du32.to_le_bytes());
+        shb.extend_from_slice(&1u16.to_le_bytes());
+        shb.extend_from_slice(&0u16.to_le_bytes());
+        shb.extend_from_slice(&(-1i64).to_le_bytes());
+        write_block(&mut out, 0x0a0d_0d0a, &shb)?;
+        // interface description block, no snap length limit
+        let mut idb = Vec::with_capacity(8);
+        idb.extend_from_slice(&LINKTYPE_LORATAP.to_le_bytes());
+        idb.extend_from_slice(&0u16.to_le_bytes());
+        idb.extend_from_slice(&0u32.to_le_bytes());
+        write_block(&mut out, 1, &idb)?;
+        Ok(CaptureWriter { out })
+    }
+
+    /// Append one frame.
+    pub fn write_frame(&mut self, data: &[u8], info: &FrameInfo) -> io::Result<()> {
+        let mut frame = loratap_header(info).to_vec();
+        frame.extend_from_slice(data);
+
+        let micros = info
+            .time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+        let mut epb = Vec::with_capacity(frame.len() + 36);
+        epb.extend_from_slice(&0u32.to_le_bytes());
+        epb.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+        epb.extend_from_slice(&(micros as u32).to_le_bytes());
+        epb.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&frame);
+        epb.resize(epb.len().div_ceil(4) * 4, 0);
+        // epb_flags option with the direction bits, then end of options
+        let flags: u32 = match info.direction {
+            Direction::Inbound => 1,
+            Direction::Outbound => 2,
+        };
+        epb.extend_from_slice(&2u16.to_le_bytes());
+        epb.extend_from_slice(&4u16.to_le_bytes());
+        epb.extend_from_slice(&flags.to_le_bytes());
+        epb.extend_from_slice(&[0; 4]);
+        write_block(&mut self.out, 6, &epb)
+    }
+
+    /// Flush buffered frames to the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+    /// Unwrap the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+// Write a pcapng block, `body` has to be padded to 32 bit already.
+fn write_block<W: Write>(out: &mut W, block_type: u32, body: &[u8]) -> io::Result<()> {
+    let total = (body.len() + 12) as u32;
+    out.write_all(&block_type.to_le_bytes())?;
+    out.write_all(&total.to_le_bytes())?;
+    out.write_all(body)?;
+    out.write_all(&total.to_le_bytes())
+}
+
+// LoRaTap version 0 header, all multi-byte fields in network byte order.
+fn loratap_header(info: &FrameInfo) -> [u8; LORATAP_HEADER_LEN] {
+    let mut header = [0u8; LORATAP_HEADER_LEN];
+    header[2..4].copy_from_slice(&(LORATAP_HEADER_LEN as u16).to_be_bytes());
+    let hz = info.frequency.hz();
+    header[4..8].copy_from_slice(&hz.to_be_bytes());
+    if let Some(params) = info.params {
+        // bandwidth in 125kHz steps, 0 for bandwidths not representable
+        let bw = params.bandwidth.hz();
+        header[8] = if bw % 125_000 == 0 {
+            (bw / 125_000) as u8
+        } else {
+            0
+        };
+        header[9] = params.spreading_factor;
+    }
+    // rssi fields are offset by 139 dB, snr is in quarter dB
+    let rssi = info
+        .rssi
+        .map(|r| r.saturating_add(139).clamp(0, 255) as u8)
+        .unwrap_or(0);
+    header[10] = rssi;
+    header[11] = rssi;
+    header[12] = 0;
+    header[13] = info
+        .snr
+        .map(|s| (s * 4.0).clamp(-128.0, 127.0) as i8 as u8)
+        .unwrap_or(0);
+    header[14] = SYNC_WORD;
+    header
+}
+
+/// Writes all frames passing through a device into a capture.
+///
+/// Received frames are always captured, transmitted ones if enabled with `capture_tx`.
+pub struct CaptureModem<T: LoraModemDevice, W: Write> {
+    inner: T,
+    writer: CaptureWriter<W>,
+    capture_tx: bool,
+    frequency: Option<Frequency>,
+    params: Option<RadioParams>,
+}
+
+impl<T: LoraModemDevice, W: Write> CaptureModem<T, W> {
+    pub fn new(inner: T, writer: CaptureWriter<W>) -> Self {
+        CaptureModem {
+            inner,
+            writer,
+            capture_tx: false,
+            frequency: None,
+            params: None,
+        }
+    }
+    /// Also capture transmitted frames.
+    pub fn capture_tx(&mut self, enabled: bool) {
+        self.capture_tx = enabled;
+    }
+    /// The capture being written.
+    pub fn writer(&mut self) -> &mut CaptureWriter<W> {
+        &mut self.writer
+    }
+    /// Unwrap device and capture.
+    pub fn into_inner(self) -> (T, CaptureWriter<W>) {
+        (self.inner, self.writer)
+    }
+
+    // Frequency and radio settings for captured frames, queried once from the device.
+    fn radio(&mut self) -> (Frequency, Option<RadioParams>) {
+        if self.frequency.is_none() {
+            self.frequency = self.inner.config().ok().map(|status| status.frequency);
+        }
+        if self.params.is_none() {
+            self.params = self.inner.get_radio_params().ok();
+        }
+        (self.frequency.unwrap_or_default(), self.params)
+    }
+    // Capture a frame just sent.
+    fn write_outbound(&mut self, frame: &[u8]) -> Result<()> {
+        let (frequency, params) = self.radio();
+        let info = FrameInfo {
+            direction: Direction::Outbound,
+            time: SystemTime::now(),
+            frequency,
+            params,
+            rssi: None,
+            snr: None,
+        };
+        Ok(self.writer.write_frame(frame, &info)?)
+    }
+}
+
+impl<T: LoraModemDevice, W: Write> LoraModemDevice for CaptureModem<T, W> {
+    fn open(&mut self) -> Result<()> {
+        self.frequency = None;
+        self.params = None;
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)?;
+        self.frequency = Some(freq);
+        Ok(())
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)?;
+        self.params = Some(mode.into());
+        Ok(())
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)?;
+        self.params = Some(params);
+        Ok(())
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        self.inner.max_payload()
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        if !self.capture_tx {
+            return self.inner.send_data(data);
+        }
+        let report = self.inner.send_slice(&data)?;
+        self.write_outbound(&data)?;
+        Ok(report)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        let report = self.inner.send_slice(data)?;
+        if self.capture_tx {
+            self.write_outbound(data)?;
+        }
+        Ok(report)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        let mut results = self.inner.send_batch(frames);
+        if self.capture_tx {
+            for (frame, result) in frames.iter().zip(results.iter_mut()) {
+                if result.is_ok() {
+                    if let Err(e) = self.write_outbound(frame) {
+                        *result = Err(e);
+                    }
+                }
+            }
+        }
+        results
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        let packet = self.inner.read_packet()?;
+        let (frequency, params) = self.radio();
+        let info = FrameInfo {
+            direction: Direction::Inbound,
+            time: packet.received_at,
+            frequency,
+            params,
+            rssi: Some(packet.rssi),
+            snr: Some(packet.snr),
+        };
+        self.writer.write_frame(&packet.data, &info)?;
+        Ok(packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
@@ -0,0 +1,216 @@
+use crate::radio::RadioParams;
+#[cfg(feature = "std")]
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    send_framed, Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError,
+    ModemEvent, Result, RxPacket, Status, TxReport,
+};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Checksum appended to every payload by `ChecksumModem`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// CRC-16/CCITT-FALSE, two bytes
+    Crc16,
+    /// CRC-32 as used by Ethernet and zip, four bytes
+    Crc32,
+}
+
+impl Checksum {
+    /// Number of bytes the checksum adds to a payload.
+    pub fn size(self) -> usize {
+        match self {
+            Checksum::Crc16 => 2,
+            Checksum::Crc32 => 4,
+        }
+    }
+    /// Checksum of `data`, big endian.
+    pub fn compute(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Checksum::Crc16 => crc16(data).to_be_bytes().to_vec(),
+            Checksum::Crc32 => crc32(data).to_be_bytes().to_vec(),
+        }
+    }
+}
+
+/// CRC-16/CCITT-FALSE (polynomial 0x1021, initial value 0xffff).
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC-32 (reflected polynomial 0xedb88320).
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Appends a checksum to outgoing payloads and verifies it on incoming ones.
+///
+/// Protects payloads on firmwares running with the radio CRC disabled, corrupted
+/// packets are rejected with `ModemError::ChecksumMismatch` instead of being handed out.
+pub struct ChecksumModem<T: LoraModemDevice> {
+    inner: T,
+    checksum: Checksum,
+    mismatches: usize,
+}
+
+impl<T: LoraModemDevice> ChecksumModem<T> {
+    pub fn new(inner: T, checksum: Checksum) -> Self {
+        ChecksumModem {
+            inner,
+            checksum,
+            mismatches: 0,
+        }
+    }
+    /// Number of received packets rejected so far.
+    pub fn mismatches(&self) -> usize {
+        self.mismatches
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for ChecksumModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        let frame = self.inner.max_payload()?;
+        Ok(frame.saturating_sub(self.checksum.size()))
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    #[cfg(feature = "std")]
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    #[cfg(feature = "std")]
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, mut data: Vec<u8>) -> Result<TxReport> {
+        let checksum = self.checksum.compute(&data);
+        data.extend_from_slice(&checksum);
+        let report = self.inner.send_data(data)?;
+        Ok(TxReport {
+            bytes: report.bytes.saturating_sub(checksum.len()),
+            ..report
+        })
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        let framed: Vec<(Vec<u8>, usize)> = frames
+            .iter()
+            .map(|data| {
+                let checksum = self.checksum.compute(data);
+                let mut frame = Vec::with_capacity(data.len() + checksum.len());
+                frame.extend_from_slice(data);
+                frame.extend_from_slice(&checksum);
+                (frame, checksum.len())
+            })
+            .collect();
+        send_framed(&mut self.inner, &framed)
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        let mut packet = self.inner.read_packet()?;
+        let len = self.checksum.size();
+        let valid = packet.data.len() >= len && {
+            let (payload, received) = packet.data.split_at(packet.data.len() - len);
+            self.checksum.compute(payload) == received
+        };
+        if !valid {
+            self.mismatches += 1;
+            return Err(ModemError::ChecksumMismatch);
+        }
+        packet.data.truncate(packet.data.len() - len);
+        Ok(packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
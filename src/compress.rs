@@ -0,0 +1,270 @@
+use crate::radio::RadioParams;
+#[cfg(feature = "std")]
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError, ModemEvent, Result,
+    RxPacket, Status, TxReport,
+};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// First byte of every compressed payload.
+///
+/// Text payloads never start with it, so packets from peers without compression
+/// are passed through unchanged.
+pub const MARKER: u8 = 0xfc;
+
+const WINDOW: usize = 4096;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = MIN_MATCH + 15;
+
+/// Compress `data` with a small LZSS variant suited for short payloads.
+///
+/// Every group of up to eight tokens is preceded by a flag byte, set bits mark
+/// back references of two bytes (12 bit distance, 4 bit length), clear bits literals.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 8 + 1);
+    let mut pos = 0;
+    while pos < data.len() {
+        let flags_at = out.len();
+        out.push(0);
+        for bit in 0..8 {
+            if pos >= data.len() {
+                break;
+            }
+            let (dist, len) = longest_match(data, pos);
+            if len >= MIN_MATCH {
+                out[flags_at] |= 1 << bit;
+                let token = ((dist - 1) << 4) | (len - MIN_MATCH);
+                out.extend_from_slice(&(token as u16).to_be_bytes());
+                pos += len;
+            } else {
+                out.push(data[pos]);
+                pos += 1;
+            }
+        }
+    }
+    out
+}
+
+// Distance and length of the longest earlier occurrence of the bytes at `pos`.
+fn longest_match(data: &[u8], pos: usize) -> (usize, usize) {
+    let max = (data.len() - pos).min(MAX_MATCH);
+    let mut best = (0, 0);
+    for start in pos.saturating_sub(WINDOW)..pos {
+        let len = (0..max)
+            .take_while(|&i| data[start + i] == data[pos + i])
+            .count();
+        if len > best.1 {
+            best = (pos - start, len);
+        }
+    }
+    best
+}
+
+/// Reverse `compress`.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    let mut pos = 0;
+    while pos < data.len() {
+        let flags = data[pos];
+        pos += 1;
+        for bit in 0..8 {
+            if pos >= data.len() {
+                break;
+            }
+            if flags & (1 << bit) == 0 {
+                out.push(data[pos]);
+                pos += 1;
+                continue;
+            }
+            if pos + 2 > data.len() {
+                return Err(ModemError::Parse("truncated compressed payload!".into()));
+            }
+            let token = u16::from_be_bytes([data[pos], data[pos + 1]]) as usize;
+            pos += 2;
+            let dist = (token >> 4) + 1;
+            let len = (token & 0xf) + MIN_MATCH;
+            if dist > out.len() {
+                return Err(ModemError::Parse(
+                    "invalid back reference in compressed payload!".into(),
+                ));
+            }
+            let start = out.len() - dist;
+            for i in 0..len {
+                out.push(out[start + i]);
+            }
+        }
+    }
+    Ok(out)
+}
+
+// The frame to send for `data`, none if it goes out uncompressed.
+fn pack(data: &[u8]) -> Option<Vec<u8>> {
+    let compressed = compress(data);
+    // raw payloads starting with the marker are always compressed to stay unambiguous
+    if compressed.len() + 1 < data.len() || data.first() == Some(&MARKER) {
+        let mut frame = Vec::with_capacity(compressed.len() + 1);
+        frame.push(MARKER);
+        frame.extend_from_slice(&compressed);
+        Some(frame)
+    } else {
+        None
+    }
+}
+
+// Report `len` payload bytes for a compressed frame of `frame_len` bytes, if sent whole.
+fn unpacked(report: TxReport, frame_len: usize, len: usize) -> TxReport {
+    let bytes = if report.bytes >= frame_len { len } else { 0 };
+    TxReport { bytes, ..report }
+}
+
+/// Compresses outgoing payloads when that makes them smaller and decompresses incoming ones.
+///
+/// Compressed payloads start with `MARKER`, all others are sent as is, so peers
+/// without compression can still read uncompressed packets and are understood by this one.
+pub struct CompressedModem<T: LoraModemDevice> {
+    inner: T,
+}
+
+impl<T: LoraModemDevice> CompressedModem<T> {
+    pub fn new(inner: T) -> Self {
+        CompressedModem { inner }
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for CompressedModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        // room for the marker and payloads growing by a flag byte per eight literals
+        let frame = self.inner.max_payload()?;
+        Ok(frame.saturating_sub(1) * 8 / 9)
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    #[cfg(feature = "std")]
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    #[cfg(feature = "std")]
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        match pack(&data) {
+            Some(frame) => {
+                let frame_len = frame.len();
+                let report = self.inner.send_data(frame)?;
+                Ok(unpacked(report, frame_len, data.len()))
+            }
+            None => self.inner.send_data(data),
+        }
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        match pack(data) {
+            Some(frame) => {
+                let report = self.inner.send_slice(&frame)?;
+                Ok(unpacked(report, frame.len(), data.len()))
+            }
+            None => self.inner.send_slice(data),
+        }
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        let packed: Vec<Option<Vec<u8>>> = frames.iter().map(|data| pack(data)).collect();
+        let sent: Vec<&[u8]> = frames
+            .iter()
+            .zip(&packed)
+            .map(|(data, frame)| frame.as_deref().unwrap_or(data))
+            .collect();
+        self.inner
+            .send_batch(&sent)
+            .into_iter()
+            .zip(frames.iter().zip(&packed))
+            .map(|(result, (data, frame))| match frame {
+                Some(frame) => result.map(|report| unpacked(report, frame.len(), data.len())),
+                None => result,
+            })
+            .collect()
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        let mut packet = self.inner.read_packet()?;
+        if packet.data.first() == Some(&MARKER) {
+            // a peer without compression may have sent it, keep it as is then
+            if let Ok(data) = decompress(&packet.data[1..]) {
+                packet.data = data;
+            }
+        }
+        Ok(packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
@@ -0,0 +1,388 @@
+use crate::radio::RadioParams;
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    send_framed, Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError,
+    ModemEvent, Result, RxPacket, Status, TxReport,
+};
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::io::Read;
+
+/// Size of the pre-shared key.
+pub const KEY_LEN: usize = 32;
+/// Size of the random nonce prepended to every encrypted payload.
+pub const NONCE_LEN: usize = 12;
+/// Size of the authentication tag appended to every encrypted payload.
+pub const TAG_LEN: usize = 16;
+/// Bytes added to every payload by encryption.
+pub const OVERHEAD: usize = NONCE_LEN + TAG_LEN;
+
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+fn le32(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+// ChaCha20 block function of RFC 8439, section 2.3.
+fn chacha20_block(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; 64] {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]);
+    for i in 0..8 {
+        state[4 + i] = le32(&key[4 * i..]);
+    }
+    state[12] = counter;
+    for i in 0..3 {
+        state[13 + i] = le32(&nonce[4 * i..]);
+    }
+    let mut working = state;
+    for _ in 0..10 {
+        quarter_round(&mut working, 0, 4, 8, 12);
+        quarter_round(&mut working, 1, 5, 9, 13);
+        quarter_round(&mut working, 2, 6, 10, 14);
+        quarter_round(&mut working, 3, 7, 11, 15);
+        quarter_round(&mut working, 0, 5, 10, 15);
+        quarter_round(&mut working, 1, 6, 11, 12);
+        quarter_round(&mut working, 2, 7, 8, 13);
+        quarter_round(&mut working, 3, 4, 9, 14);
+    }
+    let mut out = [0u8; 64];
+    for i in 0..16 {
+        let word = working[i].wrapping_add(state[i]);
+        out[4 * i..4 * i + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    out
+}
+
+fn chacha20_xor(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        let stream = chacha20_block(key, 1 + i as u32, nonce);
+        for (b, k) in chunk.iter_mut().zip(stream.iter()) {
+            *b ^= k;
+        }
+    }
+}
+
+// Poly1305 of RFC 8439, section 2.5, over a message made of whole 16 byte blocks.
+fn poly1305(key: &[u8; 32], msg: &[u8]) -> [u8; TAG_LEN] {
+    const MASK: u32 = 0x3ff_ffff;
+    let r0 = le32(&key[0..]) & 0x3ff_ffff;
+    let r1 = (le32(&key[3..]) >> 2) & 0x3ff_ff03;
+    let r2 = (le32(&key[6..]) >> 4) & 0x3ff_c0ff;
+    let r3 = (le32(&key[9..]) >> 6) & 0x3f0_3fff;
+    let r4 = (le32(&key[12..]) >> 8) & 0x00f_ffff;
+    let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+    let (mut h0, mut h1, mut h2, mut h3, mut h4) = (0u32, 0u32, 0u32, 0u32, 0u32);
+
+    for block in msg.chunks(16) {
+        h0 += le32(&block[0..]) & MASK;
+        h1 += (le32(&block[3..]) >> 2) & MASK;
+        h2 += (le32(&block[6..]) >> 4) & MASK;
+        h3 += (le32(&block[9..]) >> 6) & MASK;
+        h4 += (le32(&block[12..]) >> 8) | (1 << 24);
+
+        let m = |a: u32, b: u32| a as u64 * b as u64;
+        let d0 = m(h0, r0) + m(h1, s4) + m(h2, s3) + m(h3, s2) + m(h4, s1);
+        let mut d1 = m(h0, r1) + m(h1, r0) + m(h2, s4) + m(h3, s3) + m(h4, s2);
+        let mut d2 = m(h0, r2) + m(h1, r1) + m(h2, r0) + m(h3, s4) + m(h4, s3);
+        let mut d3 = m(h0, r3) + m(h1, r2) + m(h2, r1) + m(h3, r0) + m(h4, s4);
+        let mut d4 = m(h0, r4) + m(h1, r3) + m(h2, r2) + m(h3, r1) + m(h4, r0);
+
+        h0 = d0 as u32 & MASK;
+        d1 += d0 >> 26;
+        h1 = d1 as u32 & MASK;
+        d2 += d1 >> 26;
+        h2 = d2 as u32 & MASK;
+        d3 += d2 >> 26;
+        h3 = d3 as u32 & MASK;
+        d4 += d3 >> 26;
+        h4 = d4 as u32 & MASK;
+        h0 += (d4 >> 26) as u32 * 5;
+        h1 += h0 >> 26;
+        h0 &= MASK;
+    }
+
+    // fully carry h
+    let mut c;
+    c = h1 >> 26;
+    h1 &= MASK;
+    h2 += c;
+    c = h2 >> 26;
+    h2 &= MASK;
+    h3 += c;
+    c = h3 >> 26;
+    h3 &= MASK;
+    h4 += c;
+    c = h4 >> 26;
+    h4 &= MASK;
+    h0 += c * 5;
+    c = h0 >> 26;
+    h0 &= MASK;
+    h1 += c;
+
+    // compute h - p and select it if h >= p
+    let mut g0 = h0.wrapping_add(5);
+    c = g0 >> 26;
+    g0 &= MASK;
+    let mut g1 = h1.wrapping_add(c);
+    c = g1 >> 26;
+    g1 &= MASK;
+    let mut g2 = h2.wrapping_add(c);
+    c = g2 >> 26;
+    g2 &= MASK;
+    let mut g3 = h3.wrapping_add(c);
+    c = g3 >> 26;
+    g3 &= MASK;
+    let g4 = h4.wrapping_add(c).wrapping_sub(1 << 26);
+    let select = (g4 >> 31).wrapping_sub(1);
+    h0 = (h0 & !select) | (g0 & select);
+    h1 = (h1 & !select) | (g1 & select);
+    h2 = (h2 & !select) | (g2 & select);
+    h3 = (h3 & !select) | (g3 & select);
+    h4 = (h4 & !select) | (g4 & select);
+
+    // h = h % 2^128 + s
+    let w0 = h0 | (h1 << 26);
+    let w1 = (h1 >> 6) | (h2 << 20);
+    let w2 = (h2 >> 12) | (h3 << 14);
+    let w3 = (h3 >> 18) | (h4 << 8);
+    let mut tag = [0u8; TAG_LEN];
+    let mut f = 0u64;
+    for (i, w) in [w0, w1, w2, w3].iter().enumerate() {
+        f = *w as u64 + le32(&key[16 + 4 * i..]) as u64 + (f >> 32);
+        tag[4 * i..4 * i + 4].copy_from_slice(&(f as u32).to_le_bytes());
+    }
+    tag
+}
+
+fn compute_tag(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> [u8; TAG_LEN] {
+    let mut otk = [0u8; 32];
+    otk.copy_from_slice(&chacha20_block(key, 0, nonce)[..32]);
+    let mut mac_data = Vec::with_capacity(aad.len() + ciphertext.len() + 48);
+    for part in &[aad, ciphertext] {
+        mac_data.extend_from_slice(part);
+        mac_data.resize(mac_data.len().div_ceil(16) * 16, 0);
+    }
+    mac_data.extend_from_slice(&(aad.len() as u64).to_le_bytes());
+    mac_data.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    poly1305(&otk, &mac_data)
+}
+
+/// Encrypt `plaintext` with ChaCha20-Poly1305 (RFC 8439), returning ciphertext and tag.
+pub fn seal(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut out = plaintext.to_vec();
+    chacha20_xor(key, nonce, &mut out);
+    let tag = compute_tag(key, nonce, aad, &out);
+    out.extend_from_slice(&tag);
+    out
+}
+
+/// Authenticate and decrypt ciphertext and tag produced by `seal`.
+pub fn open(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    sealed: &[u8],
+) -> Result<Vec<u8>> {
+    if sealed.len() < TAG_LEN {
+        return Err(ModemError::AuthenticationFailed);
+    }
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+    let expected = compute_tag(key, nonce, aad, ciphertext);
+    // compare in constant time
+    let diff = expected
+        .iter()
+        .zip(tag.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if diff != 0 {
+        return Err(ModemError::AuthenticationFailed);
+    }
+    let mut out = ciphertext.to_vec();
+    chacha20_xor(key, nonce, &mut out);
+    Ok(out)
+}
+
+#[cfg(unix)]
+fn random_nonce() -> Result<[u8; NONCE_LEN]> {
+    let mut nonce = [0u8; NONCE_LEN];
+    File::open("/dev/urandom")?.read_exact(&mut nonce)?;
+    Ok(nonce)
+}
+
+#[cfg(windows)]
+fn random_nonce() -> Result<[u8; NONCE_LEN]> {
+    #[link(name = "advapi32")]
+    extern "system" {
+        #[link_name = "SystemFunction036"]
+        fn RtlGenRandom(buffer: *mut u8, len: u32) -> u8;
+    }
+    let mut nonce = [0u8; NONCE_LEN];
+    // SAFETY: `nonce` is valid for writes of `NONCE_LEN` bytes
+    if unsafe { RtlGenRandom(nonce.as_mut_ptr(), NONCE_LEN as u32) } == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(nonce)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn random_nonce() -> Result<[u8; NONCE_LEN]> {
+    Err(ModemError::UnsupportedCommand(
+        "encryption without an OS random source".into(),
+    ))
+}
+
+/// Transparently encrypts and authenticates payloads with a pre-shared key.
+///
+/// Every payload is sent as nonce, ciphertext and tag, adding `OVERHEAD` bytes.
+/// Nonces come from the random source of the OS, on targets without one
+/// sending fails with `ModemError::UnsupportedCommand`.
+/// Packets failing authentication are rejected with `ModemError::AuthenticationFailed`.
+pub struct SecureModem<T: LoraModemDevice> {
+    inner: T,
+    key: [u8; KEY_LEN],
+}
+
+impl<T: LoraModemDevice> SecureModem<T> {
+    pub fn new(inner: T, key: [u8; KEY_LEN]) -> Self {
+        SecureModem { inner, key }
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    // Nonce, ciphertext and tag for `data`.
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let nonce = random_nonce()?;
+        let mut frame = nonce.to_vec();
+        frame.extend_from_slice(&seal(&self.key, &nonce, &[], data));
+        Ok(frame)
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for SecureModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        Ok(self.inner.max_payload()?.saturating_sub(OVERHEAD))
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.send_slice(&data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        let frame = self.encrypt(data)?;
+        let report = self.inner.send_data(frame)?;
+        Ok(TxReport {
+            bytes: report.bytes.saturating_sub(OVERHEAD),
+            ..report
+        })
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        let mut sealed = Vec::with_capacity(frames.len());
+        for data in frames {
+            match self.encrypt(data) {
+                Ok(frame) => sealed.push((frame, OVERHEAD)),
+                Err(e) => return vec![Err(e)],
+            }
+        }
+        send_framed(&mut self.inner, &sealed)
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        let mut packet = self.inner.read_packet()?;
+        if packet.data.len() < OVERHEAD {
+            return Err(ModemError::AuthenticationFailed);
+        }
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce.copy_from_slice(&packet.data[..NONCE_LEN]);
+        packet.data = open(&self.key, &nonce, &[], &packet.data[NONCE_LEN..])?;
+        Ok(packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
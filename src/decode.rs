@@ -0,0 +1,6 @@
+//! Recognition of foreign traffic overheard on shared frequencies.
+//!
+//! Decoders work on raw frames as received by a radio listening with the
+//! settings and sync word of the foreign network, e.g. through `Sx127xModem`.
+
+pub mod meshtastic;
@@ -0,0 +1,309 @@
+//! Meshtastic packets, decoded as far as needed to tell whose traffic it is.
+//!
+//! Every packet starts with a 16 byte header in the clear, the payload is a
+//! `Data` protobuf encrypted with AES-CTR under the channel key. With the key,
+//! by default the well-known key of the public channels, the port number of
+//! the payload is recovered as well; message contents are not decoded.
+
+use core::fmt;
+
+/// Size of the unencrypted header in front of every packet.
+pub const HEADER_LEN: usize = 16;
+/// Node id addressing every node.
+pub const BROADCAST: u32 = 0xffff_ffff;
+/// Key of the channels using the default pre-shared key `AQ==`, e.g. `LongFast`.
+pub const DEFAULT_KEY: [u8; 16] = [
+    0xd4, 0xf1, 0xbb, 0x3a, 0x20, 0x29, 0x07, 0x59, 0xf0, 0xbc, 0xff, 0xab, 0xcf, 0x4e, 0x69, 0x01,
+];
+
+/// Unencrypted header of a Meshtastic packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Destination node, `BROADCAST` for everyone
+    pub to: u32,
+    /// Originating node
+    pub from: u32,
+    /// Packet id chosen by the originator
+    pub id: u32,
+    /// Hops the packet may still be relayed
+    pub hop_limit: u8,
+    pub want_ack: bool,
+    /// Injected into the mesh by an MQTT gateway
+    pub via_mqtt: bool,
+    /// Hop limit the packet started with, 0 from older firmware
+    pub hop_start: u8,
+    /// Hash of the channel name and key, see `channel_hash`
+    pub channel: u8,
+    /// Last byte of the node expected to relay the packet next, 0 if any
+    pub next_hop: u8,
+    /// Last byte of the node that relayed the packet, 0 if unknown
+    pub relay_node: u8,
+}
+
+impl Header {
+    /// Parse the header of `frame`, `None` if it cannot be a Meshtastic packet.
+    ///
+    /// The header alone is a weak indication, `decode_with_key` confirms a
+    /// packet by its decrypted payload.
+    pub fn parse(frame: &[u8]) -> Option<Header> {
+        if frame.len() <= HEADER_LEN {
+            return None;
+        }
+        let word = |at: usize| {
+            u32::from_le_bytes([frame[at], frame[at + 1], frame[at + 2], frame[at + 3]])
+        };
+        let flags = frame[12];
+        let header = Header {
+            to: word(0),
+            from: word(4),
+            id: word(8),
+            hop_limit: flags & 0x07,
+            want_ack: flags & 0x08 != 0,
+            via_mqtt: flags & 0x10 != 0,
+            hop_start: flags >> 5,
+            channel: frame[13],
+            next_hop: frame[14],
+            relay_node: frame[15],
+        };
+        let plausible = header.from != 0
+            && header.from != BROADCAST
+            && header.to != 0
+            && header.id != 0
+            && (header.hop_start == 0 || header.hop_limit <= header.hop_start);
+        plausible.then_some(header)
+    }
+    pub fn is_broadcast(&self) -> bool {
+        self.to == BROADCAST
+    }
+    /// Hops the packet took so far, if the firmware reported its starting hop limit.
+    pub fn hops_taken(&self) -> Option<u8> {
+        (self.hop_start != 0).then(|| self.hop_start - self.hop_limit)
+    }
+}
+
+/// Meshtastic packet recognized by `decode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub header: Header,
+    /// Port number of the payload, `None` if it could not be decrypted
+    pub portnum: Option<u32>,
+    /// Size of the encrypted payload
+    pub payload_len: usize,
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "meshtastic !{:08x} -> ", self.header.from)?;
+        if self.header.is_broadcast() {
+            f.write_str("^all")?;
+        } else {
+            write!(f, "!{:08x}", self.header.to)?;
+        }
+        write!(
+            f,
+            " id {:08x} channel {:02x}",
+            self.header.id, self.header.channel
+        )?;
+        match self.portnum {
+            Some(port) => match port_name(port) {
+                Some(name) => write!(f, " {}", name),
+                None => write!(f, " port {}", port),
+            },
+            None => f.write_str(" encrypted"),
+        }
+    }
+}
+
+/// Recognize a Meshtastic packet, decrypting its port number with `DEFAULT_KEY`.
+pub fn decode(frame: &[u8]) -> Option<Packet> {
+    let header = Header::parse(frame)?;
+    Some(Packet {
+        header,
+        portnum: portnum(frame, &DEFAULT_KEY),
+        payload_len: frame.len() - HEADER_LEN,
+    })
+}
+
+/// Recognize a Meshtastic packet of the channel with `key`, `None` unless it decrypts.
+pub fn decode_with_key(frame: &[u8], key: &[u8; 16]) -> Option<Packet> {
+    let header = Header::parse(frame)?;
+    Some(Packet {
+        header,
+        portnum: Some(portnum(frame, key)?),
+        payload_len: frame.len() - HEADER_LEN,
+    })
+}
+
+/// Apply the AES-CTR cipher of the channel with `key` to the payload of `frame`.
+///
+/// In counter mode encrypting and decrypting is the same operation.
+pub fn crypt(frame: &mut [u8], key: &[u8; 16]) {
+    if frame.len() <= HEADER_LEN {
+        return;
+    }
+    // packet id as 64 bit and sender, little endian, then the block counter
+    let mut counter = [0u8; 16];
+    counter[..4].copy_from_slice(&frame[8..12]);
+    counter[8..12].copy_from_slice(&frame[4..8]);
+    let round_keys = aes::expand_key(key);
+    for block in frame[HEADER_LEN..].chunks_mut(16) {
+        let stream = aes::encrypt(&round_keys, &counter);
+        for (byte, key) in block.iter_mut().zip(stream.iter()) {
+            *byte ^= key;
+        }
+        // big endian increment of the whole block
+        for byte in counter.iter_mut().rev() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                break;
+            }
+        }
+    }
+}
+
+/// Channel hash sent in the header, the XOR of all bytes of name and key.
+pub fn channel_hash(name: &str, key: &[u8]) -> u8 {
+    name.bytes()
+        .chain(key.iter().copied())
+        .fold(0, |hash, b| hash ^ b)
+}
+
+/// Name of a well-known port number, as in the Meshtastic protobufs.
+pub fn port_name(portnum: u32) -> Option<&'static str> {
+    Some(match portnum {
+        1 => "TEXT_MESSAGE_APP",
+        2 => "REMOTE_HARDWARE_APP",
+        3 => "POSITION_APP",
+        4 => "NODEINFO_APP",
+        5 => "ROUTING_APP",
+        6 => "ADMIN_APP",
+        7 => "TEXT_MESSAGE_COMPRESSED_APP",
+        8 => "WAYPOINT_APP",
+        9 => "AUDIO_APP",
+        10 => "DETECTION_SENSOR_APP",
+        32 => "REPLY_APP",
+        33 => "IP_TUNNEL_APP",
+        34 => "PAXCOUNTER_APP",
+        64 => "SERIAL_APP",
+        65 => "STORE_FORWARD_APP",
+        66 => "RANGE_TEST_APP",
+        67 => "TELEMETRY_APP",
+        68 => "ZPS_APP",
+        69 => "SIMULATOR_APP",
+        70 => "TRACEROUTE_APP",
+        71 => "NEIGHBORINFO_APP",
+        72 => "ATAK_PLUGIN",
+        73 => "MAP_REPORT_APP",
+        256 => "PRIVATE_APP",
+        257 => "ATAK_FORWARDER",
+        _ => return None,
+    })
+}
+
+// Port number of the `Data` message in the payload of `frame`, if it decrypts under `key`.
+fn portnum(frame: &[u8], key: &[u8; 16]) -> Option<u32> {
+    // the port number is the first field, no more than a few bytes in
+    let mut start = [0u8; HEADER_LEN + 8];
+    let len = frame.len().min(start.len());
+    start[..len].copy_from_slice(&frame[..len]);
+    crypt(&mut start[..len], key);
+    let data = &start[HEADER_LEN..len];
+    // field 1, varint
+    if data.first() != Some(&0x08) {
+        return None;
+    }
+    let mut value: u32 = 0;
+    for (i, &b) in data[1..].iter().take(5).enumerate() {
+        value |= u32::from(b & 0x7f) << (7 * i);
+        if b & 0x80 == 0 {
+            // a wrong key rarely yields a known port or the payload field 2 next
+            let payload_follows = data.get(i + 2) == Some(&0x12);
+            return (value != 0 && (payload_follows || port_name(value).is_some()))
+                .then_some(value);
+        }
+    }
+    None
+}
+
+/// Encryption direction of AES-128, all counter mode needs.
+mod aes {
+    const SBOX: [u8; 256] = sbox();
+
+    // S-box from the multiplicative inverse in GF(2^8) and the affine transform.
+    const fn sbox() -> [u8; 256] {
+        let mut sbox = [0u8; 256];
+        let (mut p, mut q) = (1u8, 1u8);
+        loop {
+            // p times 3, q divided by 3
+            p = p ^ (p << 1) ^ if p & 0x80 != 0 { 0x1b } else { 0 };
+            q ^= q << 1;
+            q ^= q << 2;
+            q ^= q << 4;
+            if q & 0x80 != 0 {
+                q ^= 0x09;
+            }
+            let x = q ^ q.rotate_left(1) ^ q.rotate_left(2) ^ q.rotate_left(3) ^ q.rotate_left(4);
+            sbox[p as usize] = x ^ 0x63;
+            if p == 1 {
+                break;
+            }
+        }
+        sbox[0] = 0x63;
+        sbox
+    }
+
+    fn xtime(b: u8) -> u8 {
+        (b << 1) ^ if b & 0x80 != 0 { 0x1b } else { 0 }
+    }
+
+    pub(super) fn expand_key(key: &[u8; 16]) -> [[u8; 16]; 11] {
+        let mut keys = [[0u8; 16]; 11];
+        keys[0] = *key;
+        let mut rcon = 1u8;
+        for round in 1..11 {
+            let prev = keys[round - 1];
+            let mut word = [prev[13], prev[14], prev[15], prev[12]];
+            for b in word.iter_mut() {
+                *b = SBOX[*b as usize];
+            }
+            word[0] ^= rcon;
+            rcon = xtime(rcon);
+            for i in 0..16 {
+                let feed = if i < 4 { word[i] } else { keys[round][i - 4] };
+                keys[round][i] = prev[i] ^ feed;
+            }
+        }
+        keys
+    }
+
+    pub(super) fn encrypt(keys: &[[u8; 16]; 11], block: &[u8; 16]) -> [u8; 16] {
+        let mut state = *block;
+        add(&mut state, &keys[0]);
+        for (round, key) in keys.iter().enumerate().skip(1) {
+            let mut shifted = [0u8; 16];
+            // sub bytes and shift row r left by r, bytes are stored column by column
+            for (i, byte) in shifted.iter_mut().enumerate() {
+                let (row, col) = (i % 4, i / 4);
+                *byte = SBOX[state[row + 4 * ((col + row) % 4)] as usize];
+            }
+            state = shifted;
+            if round < 10 {
+                for col in state.chunks_exact_mut(4) {
+                    let a = [col[0], col[1], col[2], col[3]];
+                    let all = a[0] ^ a[1] ^ a[2] ^ a[3];
+                    for i in 0..4 {
+                        col[i] = a[i] ^ all ^ xtime(a[i] ^ a[(i + 1) % 4]);
+                    }
+                }
+            }
+            add(&mut state, key);
+        }
+        state
+    }
+
+    fn add(state: &mut [u8; 16], key: &[u8; 16]) {
+        for (byte, key) in state.iter_mut().zip(key.iter()) {
+            *byte ^= key;
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use crate::addressing::{AddressedPacket, HEADER_LEN};
+use crate::queue::QueueLimits;
+use crate::radio::airtime;
+use crate::timesync::{micros, SyncedModem};
+use crate::{LoraModemDevice, ModemError, Result};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, SystemTime};
+
+/// Recurring windows a peer listens in, on the reference clock of the time sync
+///
+/// Windows start every `period`, the first one `offset` after the UNIX epoch, and
+/// last `window`. A battery-powered peer synchronized to the same reference can
+/// sleep outside of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListenSchedule {
+    /// Time between the starts of two windows
+    pub period: Duration,
+    /// Start of the windows within the period
+    pub offset: Duration,
+    /// Time the peer listens per window
+    pub window: Duration,
+}
+
+impl ListenSchedule {
+    fn validate(&self) -> Result<()> {
+        if self.period.is_zero() || self.window.is_zero() || self.window > self.period {
+            return Err(ModemError::InvalidArgument(format!(
+                "listen window of {:?} every {:?}",
+                self.window, self.period
+            )));
+        }
+        Ok(())
+    }
+    // Time since the start of the window at or before `now`.
+    fn phase(&self, now: SystemTime) -> Duration {
+        let period = self.period.as_micros() as i128;
+        let since = i128::from(micros(now)) - self.offset.as_micros() as i128;
+        Duration::from_micros(since.rem_euclid(period.max(1)) as u64)
+    }
+    /// End of the window open at `now`, `None` between windows.
+    pub fn open_until(&self, now: SystemTime) -> Option<SystemTime> {
+        let phase = self.phase(now);
+        if phase < self.window {
+            Some(now + (self.window - phase))
+        } else {
+            None
+        }
+    }
+    /// Start of the next window, `now` while one is open.
+    pub fn next_window(&self, now: SystemTime) -> SystemTime {
+        let phase = self.phase(now);
+        if phase < self.window {
+            now
+        } else {
+            now + (self.period - phase)
+        }
+    }
+}
+
+/// Frame waiting for the listen window of its peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedFrame {
+    /// Payload without addressing header
+    pub data: Vec<u8>,
+    /// Reference time the frame was queued at
+    pub queued_at: SystemTime,
+}
+
+/// Holds frames for peers that listen only during agreed windows.
+///
+/// Frames for peers with a `ListenSchedule` are queued and released by `poll`
+/// once a window is open and the frame fits into what is left of it, with a
+/// guard time at both ends for the error of the time sync. Frames for other
+/// peers are sent right away. Windows are taken on the reference clock of the
+/// `SyncedModem`, so it should be synchronized with the same node as the peers.
+pub struct DownlinkScheduler<T: LoraModemDevice> {
+    modem: SyncedModem<T>,
+    schedules: HashMap<u8, ListenSchedule>,
+    queues: HashMap<u8, VecDeque<QueuedFrame>>,
+    limits: QueueLimits,
+    guard: Duration,
+    frames: usize,
+    bytes: usize,
+}
+
+impl<T: LoraModemDevice> DownlinkScheduler<T> {
+    /// Queue frames for scheduled peers within `limits` over all peers.
+    pub fn new(modem: SyncedModem<T>, limits: QueueLimits) -> Self {
+        DownlinkScheduler {
+            modem,
+            schedules: HashMap::new(),
+            queues: HashMap::new(),
+            limits,
+            guard: Duration::from_millis(50),
+            frames: 0,
+            bytes: 0,
+        }
+    }
+    /// The time sync layer below.
+    pub fn modem(&mut self) -> &mut SyncedModem<T> {
+        &mut self.modem
+    }
+    /// Unwrap the time sync layer, dropping queued frames.
+    pub fn into_inner(self) -> SyncedModem<T> {
+        self.modem
+    }
+    /// Margin kept at the start and end of each window, 50 ms by default.
+    pub fn set_guard(&mut self, guard: Duration) {
+        self.guard = guard;
+    }
+    /// Windows `peer` listens in, `None` for a peer that is always listening.
+    ///
+    /// Frames queued for a peer losing its schedule go out with the next `poll`.
+    pub fn set_schedule(&mut self, peer: u8, schedule: Option<ListenSchedule>) -> Result<()> {
+        match schedule {
+            Some(schedule) => {
+                schedule.validate()?;
+                self.schedules.insert(peer, schedule);
+            }
+            None => {
+                self.schedules.remove(&peer);
+            }
+        }
+        Ok(())
+    }
+    /// Windows `peer` listens in, if scheduled.
+    pub fn schedule(&self, peer: u8) -> Option<&ListenSchedule> {
+        self.schedules.get(&peer)
+    }
+
+    /// Frames waiting for `peer`, oldest first.
+    pub fn queue(&self, peer: u8) -> impl Iterator<Item = &QueuedFrame> {
+        self.queues.get(&peer).into_iter().flatten()
+    }
+    /// Frames waiting over all peers.
+    pub fn queued(&self) -> usize {
+        self.frames
+    }
+    /// Reference time the next window of `peer` opens, if frames are waiting for it.
+    pub fn next_release(&self, peer: u8) -> Option<SystemTime> {
+        self.queues.get(&peer).filter(|queue| !queue.is_empty())?;
+        let now = self.modem.now();
+        Some(match self.schedules.get(&peer) {
+            Some(schedule) => schedule.next_window(now) + self.guard,
+            None => now,
+        })
+    }
+    /// Drop the frames waiting for `peer`, returning them.
+    pub fn cancel(&mut self, peer: u8) -> Vec<QueuedFrame> {
+        let frames: Vec<QueuedFrame> = self.queues.remove(&peer).unwrap_or_default().into();
+        self.frames -= frames.len();
+        self.bytes -= frames.iter().map(|frame| frame.data.len()).sum::<usize>();
+        frames
+    }
+
+    /// Send `data` to `peer` now if it listens, otherwise queue it for its next window.
+    ///
+    /// Returns whether the frame was sent right away.
+    pub fn send(&mut self, peer: u8, data: &[u8]) -> Result<bool> {
+        self.poll()?;
+        let waiting = self
+            .queues
+            .get(&peer)
+            .is_some_and(|queue| !queue.is_empty());
+        if !waiting && self.releasable(peer, data.len(), self.modem.now()) {
+            self.modem.link().send_to(peer, data)?;
+            return Ok(true);
+        }
+        if self.frames >= self.limits.max_frames || self.bytes + data.len() > self.limits.max_bytes
+        {
+            return Err(ModemError::QueueFull);
+        }
+        self.frames += 1;
+        self.bytes += data.len();
+        let frame = QueuedFrame {
+            data: data.to_vec(),
+            queued_at: self.modem.now(),
+        };
+        self.queues.entry(peer).or_default().push_back(frame);
+        Ok(false)
+    }
+
+    // Whether a frame of `len` bytes sent to `peer` at `now` arrives within its window.
+    fn releasable(&mut self, peer: u8, len: usize, now: SystemTime) -> bool {
+        let schedule = match self.schedules.get(&peer) {
+            Some(schedule) => *schedule,
+            None => return true,
+        };
+        let toa = match self.modem.get_radio_params() {
+            Ok(params) => airtime(HEADER_LEN + len, &params).unwrap_or_default(),
+            Err(_) => Duration::ZERO,
+        };
+        let opened = schedule.phase(now) >= self.guard;
+        match schedule.open_until(now) {
+            Some(end) => opened && now + toa + self.guard <= end,
+            None => false,
+        }
+    }
+
+    /// Send the frames whose peers are listening now, returns how many were sent.
+    pub fn poll(&mut self) -> Result<usize> {
+        let mut sent = 0;
+        let peers: Vec<u8> = self.queues.keys().copied().collect();
+        for peer in peers {
+            while let Some(frame) = self.queues.get(&peer).and_then(VecDeque::front) {
+                let len = frame.data.len();
+                if !self.releasable(peer, len, self.modem.now()) {
+                    break;
+                }
+                let frame = match self.queues.get_mut(&peer).and_then(VecDeque::pop_front) {
+                    Some(frame) => frame,
+                    None => break,
+                };
+                if let Err(e) = self.modem.link().send_to(peer, &frame.data) {
+                    // keep the frame for the next attempt
+                    self.queues.entry(peer).or_default().push_front(frame);
+                    return Err(e);
+                }
+                self.frames -= 1;
+                self.bytes -= frame.data.len();
+                sent += 1;
+            }
+        }
+        self.queues.retain(|_, queue| !queue.is_empty());
+        Ok(sent)
+    }
+
+    /// Release due frames, then receive the next frame for this node.
+    pub fn receive(&mut self) -> Result<AddressedPacket> {
+        self.poll()?;
+        self.modem.receive()
+    }
+}
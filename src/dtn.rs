@@ -0,0 +1,99 @@
+//! Bundle transport for delay tolerant networking, e.g. as a convergence layer of dtn7-rs.
+//!
+//! Bundles are split into LoRa frames, each starting with the tag byte `0xd7`
+//! followed by a `fragment` header and the bundle data. Frames without the tag
+//! are ignored, so the radio can be shared with other traffic.
+
+use crate::fragment::{fragment, Reassembler, FRAGMENT_HEADER_LEN};
+use crate::rng::Rng;
+use crate::{LoraModemDevice, ModemError, Result};
+use std::time::Duration;
+
+/// First byte of every bundle frame.
+pub const BUNDLE_TAG: u8 = 0xd7;
+
+/// Moves serialized bundles between DTN nodes
+pub trait BundleTransport {
+    /// Send a serialized bundle to all nodes in range.
+    fn send_bundle(&mut self, bundle: &[u8]) -> Result<()>;
+    /// Wait for the next complete bundle, fails with `ModemError::Timeout` like the device does.
+    fn recv_bundle(&mut self) -> Result<Vec<u8>>;
+    /// Largest bundle that can be sent.
+    fn max_bundle_size(&mut self) -> Result<usize>;
+}
+
+/// `BundleTransport` over any LoRa device
+pub struct LoraBundleTransport<T: LoraModemDevice> {
+    inner: T,
+    reassembler: Reassembler,
+    next_id: u16,
+    mtu: Option<usize>,
+}
+
+impl<T: LoraModemDevice> LoraBundleTransport<T> {
+    /// Bundles in progress are dropped after two minutes without completing.
+    pub fn new(inner: T) -> Self {
+        LoraBundleTransport {
+            inner,
+            reassembler: Reassembler::new(Duration::from_secs(120), 8),
+            next_id: Rng::from_time().next_u64() as u16,
+            mtu: None,
+        }
+    }
+    /// Replace the reassembly settings.
+    pub fn set_reassembly(&mut self, timeout: Duration, max_pending: usize) {
+        self.reassembler = Reassembler::new(timeout, max_pending);
+    }
+    /// Access the wrapped device.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    // Frame size of the device, queried once.
+    fn mtu(&mut self) -> Result<usize> {
+        match self.mtu {
+            Some(mtu) => Ok(mtu),
+            None => {
+                let mtu = self.inner.max_payload()?;
+                self.mtu = Some(mtu);
+                Ok(mtu)
+            }
+        }
+    }
+}
+
+impl<T: LoraModemDevice> BundleTransport for LoraBundleTransport<T> {
+    fn send_bundle(&mut self, bundle: &[u8]) -> Result<()> {
+        let mtu = self.mtu()?;
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        for part in fragment(id, bundle, mtu.saturating_sub(1))? {
+            let mut frame = Vec::with_capacity(1 + part.len());
+            frame.push(BUNDLE_TAG);
+            frame.extend_from_slice(&part);
+            self.inner.send_data(frame)?;
+        }
+        Ok(())
+    }
+    fn recv_bundle(&mut self) -> Result<Vec<u8>> {
+        loop {
+            let packet = self.inner.read_packet()?;
+            if packet.data.first() != Some(&BUNDLE_TAG) {
+                continue;
+            }
+            match self.reassembler.push(&packet.data[1..]) {
+                Ok(Some(bundle)) => return Ok(bundle),
+                Ok(None) | Err(ModemError::Parse(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    fn max_bundle_size(&mut self) -> Result<usize> {
+        let chunk = self.mtu()?.saturating_sub(1 + FRAGMENT_HEADER_LEN);
+        Ok(chunk * crate::fragment::MAX_FRAGMENTS)
+    }
+}
@@ -0,0 +1,430 @@
+use crate::radio::{airtime, RadioParams};
+use crate::region::Region;
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    send_each, Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError,
+    ModemEvent, Result, RxPacket, Status, TxReport,
+};
+use std::collections::VecDeque;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What happens to a transmission exceeding the duty-cycle budget of its band
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DutyCyclePolicy {
+    /// Wait until enough budget is available, then transmit
+    Block,
+    /// Keep the frame and transmit it once enough budget is available,
+    /// `send_data` reports 0 bytes sent for queued frames
+    Queue,
+    /// Fail with `ModemError::DutyCycleExceeded`
+    Reject,
+}
+
+/// Frequency range sharing one duty-cycle budget
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubBand {
+    /// Lower edge
+    pub min_freq: Frequency,
+    /// Upper edge
+    pub max_freq: Frequency,
+    /// Allowed fraction of time on air, e.g. 0.01 for 1%
+    pub duty_cycle: f32,
+    /// Behavior once the budget is exhausted
+    pub policy: DutyCyclePolicy,
+}
+
+impl SubBand {
+    pub fn new(
+        min_freq: Frequency,
+        max_freq: Frequency,
+        duty_cycle: f32,
+        policy: DutyCyclePolicy,
+    ) -> Self {
+        SubBand {
+            min_freq,
+            max_freq,
+            duty_cycle,
+            policy,
+        }
+    }
+    fn contains(&self, freq: Frequency) -> bool {
+        freq >= self.min_freq && freq <= self.max_freq
+    }
+}
+
+/// Records time on air per sub-band within a sliding window.
+#[derive(Debug, Clone)]
+pub struct DutyCycleTracker {
+    bands: Vec<SubBand>,
+    window: Duration,
+    history: VecDeque<(Instant, usize, Duration)>,
+}
+
+impl DutyCycleTracker {
+    /// Tracker without any restricted bands, using a one hour window.
+    pub fn new() -> Self {
+        DutyCycleTracker {
+            bands: Vec::new(),
+            window: Duration::from_secs(3600),
+            history: VecDeque::new(),
+        }
+    }
+    /// Sub-bands of the ETSI EN 300 220 rules for EU868.
+    pub fn eu868(policy: DutyCyclePolicy) -> Self {
+        Region::EU868.duty_cycle_tracker(policy)
+    }
+    /// Add a restricted band, earlier bands take precedence on overlap.
+    pub fn add_band(&mut self, band: SubBand) {
+        self.bands.push(band);
+    }
+    /// Set the window over which the duty cycle is calculated.
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+    /// Window over which the duty cycle is calculated.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+    /// Band restricting transmissions on `freq`, if any.
+    pub fn band(&self, freq: Frequency) -> Option<&SubBand> {
+        self.bands.iter().find(|b| b.contains(freq))
+    }
+    fn band_index(&self, freq: Frequency) -> Option<usize> {
+        self.bands.iter().position(|b| b.contains(freq))
+    }
+    fn expire(&mut self) {
+        let window = self.window;
+        while let Some(&(at, _, _)) = self.history.front() {
+            if at.elapsed() < window {
+                break;
+            }
+            self.history.pop_front();
+        }
+    }
+    /// Time on air used within the current window on the band of `freq`.
+    pub fn used(&mut self, freq: Frequency) -> Duration {
+        self.expire();
+        match self.band_index(freq) {
+            Some(band) => self
+                .history
+                .iter()
+                .filter(|(_, b, _)| *b == band)
+                .map(|(_, _, t)| *t)
+                .sum(),
+            None => Duration::from_secs(0),
+        }
+    }
+    /// Time on air allowed per window on the band of `freq`, `None` if unrestricted.
+    pub fn budget(&self, freq: Frequency) -> Option<Duration> {
+        self.band(freq)
+            .map(|band| self.window.mul_f32(band.duty_cycle))
+    }
+    /// How long to wait before `toa` may be spent on `freq`, zero if it can be sent right away.
+    ///
+    /// A `toa` exceeding the whole `budget` never fits, a full window is reported for it.
+    pub fn wait_time(&mut self, freq: Frequency, toa: Duration) -> Duration {
+        self.expire();
+        let band = match self.band_index(freq) {
+            Some(band) => band,
+            None => return Duration::from_secs(0),
+        };
+        let budget = self.window.mul_f32(self.bands[band].duty_cycle);
+        if toa > budget {
+            // never fits, report a full window
+            return self.window;
+        }
+        let mut used: Duration = self.used(freq);
+        let mut wait = Duration::from_secs(0);
+        for &(at, b, t) in &self.history {
+            if used + toa <= budget {
+                break;
+            }
+            if b == band {
+                used -= t;
+                wait = self.window.saturating_sub(at.elapsed());
+            }
+        }
+        wait
+    }
+    /// Record a transmission of `toa` on `freq`.
+    pub fn record(&mut self, freq: Frequency, toa: Duration) {
+        if let Some(band) = self.band_index(freq) {
+            self.history.push_back((Instant::now(), band, toa));
+        }
+    }
+}
+
+impl Default for DutyCycleTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wraps a device and enforces duty-cycle limits on its transmissions.
+///
+/// Frames taking more time on air than their band allows in a whole window
+/// are refused with `ModemError::PayloadTooLarge` and never queued.
+pub struct DutyCycleModem<T: LoraModemDevice> {
+    inner: T,
+    tracker: DutyCycleTracker,
+    frequency: Option<Frequency>,
+    params: Option<RadioParams>,
+    queue: VecDeque<Vec<u8>>,
+}
+
+impl<T: LoraModemDevice> DutyCycleModem<T> {
+    pub fn new(inner: T, tracker: DutyCycleTracker) -> Self {
+        DutyCycleModem {
+            inner,
+            tracker,
+            frequency: None,
+            params: None,
+            queue: VecDeque::new(),
+        }
+    }
+    /// The duty-cycle tracker in use.
+    pub fn tracker(&mut self) -> &mut DutyCycleTracker {
+        &mut self.tracker
+    }
+    /// Number of frames waiting for budget.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+    /// Transmit queued frames as far as the budget allows, returns number of frames sent.
+    ///
+    /// A frame failing to send stays first in the queue.
+    pub fn flush_queue(&mut self) -> Result<usize> {
+        let mut sent = 0;
+        while let Some(len) = self.queue.front().map(Vec::len) {
+            let (freq, toa) = self.estimate(len)?;
+            if self.tracker.wait_time(freq, toa) > Duration::from_secs(0) {
+                break;
+            }
+            let frame = self.queue.front().expect("frame queued");
+            self.inner.send_slice(frame)?;
+            self.queue.pop_front();
+            self.tracker.record(freq, toa);
+            sent += 1;
+        }
+        Ok(sent)
+    }
+    /// Access the wrapped device.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    // Fail with `ModemError::PayloadTooLarge` for `len` bytes taking more than the
+    // whole budget of their band, they could never be sent.
+    fn check_budget(&self, freq: Frequency, len: usize, toa: Duration) -> Result<()> {
+        let budget = match self.tracker.budget(freq) {
+            Some(budget) if toa > budget => budget,
+            _ => return Ok(()),
+        };
+        let params = self.params.unwrap_or_default();
+        let max = (0..len)
+            .rev()
+            .find(|&len| airtime(len, &params).is_ok_and(|toa| toa <= budget))
+            .unwrap_or(0);
+        Err(ModemError::PayloadTooLarge { max })
+    }
+
+    // Frequency and time on air for a payload of `len` bytes with current settings.
+    fn estimate(&mut self, len: usize) -> Result<(Frequency, Duration)> {
+        let freq = match self.frequency {
+            Some(freq) => freq,
+            None => {
+                let freq = self.inner.config()?.frequency;
+                self.frequency = Some(freq);
+                freq
+            }
+        };
+        let params = match self.params {
+            Some(params) => params,
+            None => {
+                let params = self.inner.get_radio_params()?;
+                self.params = Some(params);
+                params
+            }
+        };
+        Ok((freq, airtime(len, &params)?))
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for DutyCycleModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.frequency = None;
+        self.params = None;
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)?;
+        self.frequency = Some(freq);
+        Ok(())
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)?;
+        self.params = Some(mode.into());
+        Ok(())
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)?;
+        self.params = Some(params);
+        Ok(())
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        self.inner.max_payload()
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.flush_queue()?;
+        let (freq, toa) = self.estimate(data.len())?;
+        self.check_budget(freq, data.len(), toa)?;
+        let wait = self.tracker.wait_time(freq, toa);
+        let policy = self.tracker.band(freq).map(|b| b.policy);
+        if policy == Some(DutyCyclePolicy::Queue)
+            && (wait > Duration::from_secs(0) || !self.queue.is_empty())
+        {
+            self.queue.push_back(data);
+            return Ok(TxReport::new(0, None));
+        }
+        if wait > Duration::from_secs(0) {
+            if policy == Some(DutyCyclePolicy::Reject) {
+                return Err(ModemError::DutyCycleExceeded { wait });
+            }
+            thread::sleep(wait);
+        }
+        let report = self.inner.send_data(data)?;
+        self.tracker.record(freq, toa);
+        Ok(report)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        if let Err(e) = self.flush_queue() {
+            return vec![Err(e)];
+        }
+        let mut freq = None;
+        let mut toas = Vec::with_capacity(frames.len());
+        for frame in frames {
+            match self.estimate(frame.len()) {
+                Ok((f, toa)) => {
+                    freq = Some(f);
+                    toas.push(toa);
+                }
+                Err(e) => return vec![Err(e)],
+            }
+        }
+        let freq = match freq {
+            Some(freq) => freq,
+            None => return Vec::new(),
+        };
+        let total: Duration = toas.iter().sum();
+        if self
+            .tracker
+            .budget(freq)
+            .is_some_and(|budget| total > budget)
+        {
+            // the batch never fits as a whole, its frames get a reservation each
+            return send_each(self, frames);
+        }
+        // the batch is admitted or held back as a whole
+        let wait = self.tracker.wait_time(freq, total);
+        let policy = self.tracker.band(freq).map(|b| b.policy);
+        if policy == Some(DutyCyclePolicy::Queue)
+            && (wait > Duration::from_secs(0) || !self.queue.is_empty())
+        {
+            self.queue.extend(frames.iter().map(|frame| frame.to_vec()));
+            return frames.iter().map(|_| Ok(TxReport::new(0, None))).collect();
+        }
+        if wait > Duration::from_secs(0) {
+            if policy == Some(DutyCyclePolicy::Reject) {
+                return frames
+                    .iter()
+                    .map(|_| Err(ModemError::DutyCycleExceeded { wait }))
+                    .collect();
+            }
+            thread::sleep(wait);
+        }
+        let results = self.inner.send_batch(frames);
+        let used = results
+            .iter()
+            .zip(&toas)
+            .filter(|(result, _)| result.is_ok())
+            .map(|(_, toa)| *toa)
+            .sum::<Duration>();
+        if !used.is_zero() {
+            self.tracker.record(freq, used);
+        }
+        results
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        self.flush_queue()?;
+        self.inner.read_packet()
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
@@ -0,0 +1,358 @@
+use crate::radio::RadioParams;
+use crate::trace;
+use crate::transport::Transport;
+use crate::{
+    check_payload, Capabilities, Frequency, LoraModemDevice, ModemConfig, ModemError, Result,
+    RxPacket, Status, TxReport,
+};
+use std::io::ErrorKind;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+// Settling time after switching the M0/M1 pins, replacing a wait on AUX.
+const MODE_SWITCH_DELAY: Duration = Duration::from_millis(50);
+// Largest payload the modules send as a single packet.
+const MAX_PAYLOAD: usize = 58;
+
+/// Drives the M0 and M1 pins selecting the operating mode of an EBYTE module.
+///
+/// Implemented for closures `FnMut(m0, m1) -> Result<()>`, e.g. wrapping GPIO writes.
+pub trait ModePins {
+    fn set(&mut self, m0: bool, m1: bool) -> Result<()>;
+}
+
+impl<F: FnMut(bool, bool) -> Result<()>> ModePins for F {
+    fn set(&mut self, m0: bool, m1: bool) -> Result<()> {
+        self(m0, m1)
+    }
+}
+
+/// Module family, the two use different configuration frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EbyteVariant {
+    /// E32 series, six byte `0xC0` parameter frames
+    E32,
+    /// E22 series, register based `0xC0` frames
+    E22,
+}
+
+impl EbyteVariant {
+    // Air data rates in bit/s selected by the three rate bits.
+    fn air_rates(self) -> [u32; 8] {
+        match self {
+            EbyteVariant::E32 => [300, 1200, 2400, 4800, 9600, 19200, 19200, 19200],
+            EbyteVariant::E22 => [2400, 2400, 2400, 4800, 9600, 19200, 38400, 62500],
+        }
+    }
+    // Output power below the maximum selected by the two power bits.
+    fn power_steps(self) -> [i8; 4] {
+        match self {
+            EbyteVariant::E32 => [0, 3, 6, 10],
+            EbyteVariant::E22 => [0, 5, 9, 12],
+        }
+    }
+    fn max_channel(self) -> u8 {
+        match self {
+            EbyteVariant::E32 => 31,
+            EbyteVariant::E22 => 83,
+        }
+    }
+    fn channel_mask(self) -> u8 {
+        match self {
+            EbyteVariant::E32 => 0x1f,
+            EbyteVariant::E22 => 0xff,
+        }
+    }
+    // Pins (m0, m1) of the configuration mode.
+    fn config_pins(self) -> (bool, bool) {
+        match self {
+            EbyteVariant::E32 => (true, true),
+            EbyteVariant::E22 => (false, true),
+        }
+    }
+    // Parameter bytes read and written, without frame header.
+    fn param_len(self) -> usize {
+        match self {
+            EbyteVariant::E32 => 5,
+            // the crypt registers behind are write-only and left alone
+            EbyteVariant::E22 => 7,
+        }
+    }
+    // Index of the byte holding (air rate, channel, power) bits.
+    fn layout(self) -> (usize, usize, usize) {
+        match self {
+            EbyteVariant::E32 => (2, 3, 4),
+            EbyteVariant::E22 => (3, 5, 4),
+        }
+    }
+}
+
+/// EBYTE E32/E22 UART LoRa module in transparent mode.
+///
+/// The modules do not expose LoRa settings directly, modem configs and radio
+/// settings are mapped to the fastest air data rate not exceeding their bit rate.
+/// Frames carry no delimiters, a packet ends once the line stays silent for a
+/// read poll interval. E22 modules are configured to append the RSSI to every packet.
+pub struct EbyteModem<T: Transport, P: ModePins> {
+    transport: T,
+    pins: P,
+    variant: EbyteVariant,
+    base: Frequency,
+    max_power: i8,
+    timeout: Option<Duration>,
+    params: Vec<u8>,
+}
+
+impl<T: Transport, P: ModePins> EbyteModem<T, P> {
+    /// Create a modem for a module whose channel 0 is at `base`, e.g. 410 MHz for E32-433,
+    /// with a maximum output power of `max_power` dBm.
+    pub fn new(
+        transport: T,
+        pins: P,
+        variant: EbyteVariant,
+        base: Frequency,
+        max_power: i8,
+    ) -> Self {
+        EbyteModem {
+            transport,
+            pins,
+            variant,
+            base,
+            max_power,
+            timeout: Some(Duration::from_secs(2)),
+            params: Vec::new(),
+        }
+    }
+    /// Bound configuration responses and reads by `timeout`, `None` blocks forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+    /// Consume the modem and return transport and pins.
+    pub fn into_inner(self) -> (T, P) {
+        (self.transport, self.pins)
+    }
+
+    // Read exactly `buf.len()` bytes, failing with a timeout once `deadline` passed.
+    fn read_exact(&mut self, buf: &mut [u8], deadline: Option<Instant>) -> Result<()> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.transport.read(&mut buf[filled..]) {
+                Ok(0) => return Err(ModemError::Disconnected),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock => {
+                    if deadline.is_some_and(|d| Instant::now() >= d) {
+                        return Err(ModemError::Timeout);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    // Run `f` in configuration mode, returning to transparent mode afterwards.
+    fn in_config_mode<R>(&mut self, f: impl FnOnce(&mut Self) -> Result<R>) -> Result<R> {
+        let (m0, m1) = self.variant.config_pins();
+        self.pins.set(m0, m1)?;
+        thread::sleep(MODE_SWITCH_DELAY);
+        let result = f(self);
+        self.pins.set(false, false)?;
+        thread::sleep(MODE_SWITCH_DELAY);
+        result
+    }
+
+    fn read_params(&mut self) -> Result<Vec<u8>> {
+        let len = self.variant.param_len();
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        self.in_config_mode(|modem| {
+            let (request, header): (Vec<u8>, usize) = match modem.variant {
+                EbyteVariant::E32 => (vec![0xc1, 0xc1, 0xc1], 1),
+                EbyteVariant::E22 => (vec![0xc1, 0x00, len as u8], 3),
+            };
+            let span = trace::command("read parameters");
+            let _entered = span.enter();
+            trace::bytes_out(&request);
+            modem.transport.write_all(&request)?;
+            modem.transport.flush()?;
+            let mut response = vec![0u8; header + len];
+            modem.read_exact(&mut response, deadline)?;
+            trace::bytes_in(&response);
+            if response[0] != 0xc0 && response[0] != 0xc1 {
+                return Err(ModemError::Parse(
+                    "unexpected EBYTE parameter response!".into(),
+                ));
+            }
+            Ok(response[header..].to_vec())
+        })
+    }
+
+    // Store `params` permanently, the module answers with the stored parameters.
+    fn write_params(&mut self, params: Vec<u8>) -> Result<()> {
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let mut frame = match self.variant {
+            EbyteVariant::E32 => vec![0xc0],
+            EbyteVariant::E22 => vec![0xc0, 0x00, params.len() as u8],
+        };
+        frame.extend_from_slice(&params);
+        self.in_config_mode(|modem| {
+            let span = trace::command("write parameters");
+            let _entered = span.enter();
+            trace::bytes_out(&frame);
+            modem.transport.write_all(&frame)?;
+            modem.transport.flush()?;
+            let mut response = vec![0u8; frame.len()];
+            modem.read_exact(&mut response, deadline)?;
+            trace::bytes_in(&response);
+            if response[frame.len() - params.len()..] != params[..] {
+                return Err(ModemError::ModemReported(
+                    "EBYTE module did not confirm parameters".into(),
+                ));
+            }
+            Ok(())
+        })?;
+        self.params = params;
+        Ok(())
+    }
+
+    fn params(&mut self) -> Result<Vec<u8>> {
+        if self.params.is_empty() {
+            self.params = self.read_params()?;
+        }
+        Ok(self.params.clone())
+    }
+
+    // Update the bits selected by `mask` in parameter byte `index`.
+    fn update(&mut self, index: usize, mask: u8, value: u8) -> Result<()> {
+        let mut params = self.params()?;
+        params[index] = (params[index] & !mask) | (value & mask);
+        self.write_params(params)
+    }
+
+    fn set_air_rate(&mut self, bitrate: f64) -> Result<()> {
+        let rates = self.variant.air_rates();
+        let bits = (0..rates.len())
+            .filter(|&i| rates[i] as f64 <= bitrate)
+            .max_by_key(|&i| rates[i])
+            .unwrap_or(0);
+        let (rate_at, _, _) = self.variant.layout();
+        self.update(rate_at, 0x07, bits as u8)
+    }
+}
+
+// Raw LoRa bit rate of `params` in bit/s.
+fn bitrate(params: &RadioParams) -> f64 {
+    let sf = params.spreading_factor as f64;
+    let cr = 4.0 / params.coding_rate.denominator() as f64;
+    sf * params.bandwidth.hz() as f64 / (1u64 << params.spreading_factor) as f64 * cr
+}
+
+impl<T: Transport, P: ModePins> LoraModemDevice for EbyteModem<T, P> {
+    fn open(&mut self) -> Result<()> {
+        self.transport.open()?;
+        self.params.clear();
+        if self.variant == EbyteVariant::E22 {
+            // append the RSSI byte to received packets
+            self.update(6, 0x80, 0x80)?;
+        }
+        self.pins.set(false, false)
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        // channels are spaced 1 MHz apart, round to the nearest one
+        let offset = freq.hz() as i64 - self.base.hz() as i64;
+        let channel = (offset + 500_000).div_euclid(1_000_000);
+        if channel < 0 || channel > self.variant.max_channel() as i64 {
+            return Err(ModemError::InvalidArgument(format!(
+                "frequency {} MHz outside the channels of this module",
+                freq
+            )));
+        }
+        let (_, channel_at, _) = self.variant.layout();
+        let mask = self.variant.channel_mask();
+        self.update(channel_at, mask, channel as u8)
+    }
+    fn config(&mut self) -> Result<Status> {
+        let params = self.params()?;
+        let (_, channel_at, power_at) = self.variant.layout();
+        let channel = params[channel_at] & self.variant.channel_mask();
+        let step = self.variant.power_steps()[(params[power_at] & 0x03) as usize];
+        Ok(Status {
+            version: format!("{:?}", self.variant),
+            max_pkt_size: MAX_PAYLOAD,
+            frequency: self.base.offset(channel as i32 * 1_000_000),
+            rx_listener: true,
+            tx_power: Some(self.max_power - step),
+            ..Status::new()
+        })
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        Ok(MAX_PAYLOAD)
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.set_air_rate(bitrate(&mode.into()))
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        params.validate()?;
+        self.set_air_rate(bitrate(&params))
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        let steps = self.variant.power_steps();
+        let bits = (0..steps.len())
+            .min_by_key(|&i| ((self.max_power - steps[i]) as i16 - dbm as i16).abs())
+            .unwrap_or(0);
+        let (_, _, power_at) = self.variant.layout();
+        self.update(power_at, 0x03, bits as u8)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        Ok(self.config()?.tx_power.unwrap_or(self.max_power))
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        Ok(Capabilities::TX_POWER)
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        check_payload(data.len(), MAX_PAYLOAD)?;
+        trace::bytes_out(&data);
+        self.transport.write_all(&data)?;
+        self.transport.flush()?;
+        Ok(TxReport::new(data.len(), None))
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let mut data = Vec::new();
+        let mut chunk = [0u8; 64];
+        loop {
+            match self.transport.read(&mut chunk) {
+                Ok(0) => return Err(ModemError::Disconnected),
+                Ok(n) => data.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock => {
+                    if !data.is_empty() {
+                        break;
+                    }
+                    if deadline.is_some_and(|d| Instant::now() >= d) {
+                        return Err(ModemError::Timeout);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        trace::bytes_in(&data);
+        let rssi = match self.variant {
+            EbyteVariant::E22 => -(256 - data.pop().unwrap_or(0) as i16),
+            EbyteVariant::E32 => 0,
+        };
+        Ok(RxPacket {
+            rssi,
+            snr: 0.0,
+            data,
+            received_at: SystemTime::now(),
+            freq_error: None,
+            modem_timestamp: None,
+        })
+    }
+    fn read_line(&mut self) -> Result<String> {
+        // transparent mode has no lines, hand out packets as text
+        let packet = self.read_packet()?;
+        Ok(String::from_utf8_lossy(&packet.data).into_owned())
+    }
+}
@@ -0,0 +1,369 @@
+use crate::error::tx_rejected;
+use crate::hex;
+use crate::line::{parse_cad, parse_sent, LineKind};
+use crate::radio::{
+    parse_radio_params, validate_tx_power, HeaderMode, RadioParams, SYNC_WORD_PRIVATE,
+};
+use crate::trace;
+use crate::{
+    check_payload, Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError,
+    ModemTelemetry, RadioState, Result, RxPacket, RxPacketRef, Status, TxReport,
+};
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+/// Longest line accepted from the modem, a `+RX` line of a full packet fits easily.
+pub const MAX_LINE_LEN: usize = 1024;
+
+/// Error type of an I/O interface, mirroring `embedded_io::ErrorType`.
+pub trait ErrorType {
+    type Error: Debug;
+}
+
+/// Blocking byte source, mirroring `embedded_io::Read`.
+pub trait Read: ErrorType {
+    /// Read at least one byte into `buf`, blocking until data is available.
+    fn read(&mut self, buf: &mut [u8]) -> core::result::Result<usize, Self::Error>;
+}
+
+/// Blocking byte sink, mirroring `embedded_io::Write`.
+pub trait Write: ErrorType {
+    /// Write some bytes of `buf`, returning how many were taken.
+    fn write(&mut self, buf: &[u8]) -> core::result::Result<usize, Self::Error>;
+    /// Wait until all written bytes have been sent.
+    fn flush(&mut self) -> core::result::Result<(), Self::Error>;
+}
+
+/// Whether a `Read` would return without blocking, mirroring `embedded_io::ReadReady`.
+pub trait ReadReady: ErrorType {
+    fn read_ready(&mut self) -> core::result::Result<bool, Self::Error>;
+}
+
+fn transport_error<E: Debug>(e: E) -> ModemError {
+    ModemError::Transport(format!("{:?}", e))
+}
+
+/// Modem running the rf95modem firmware on a UART of an embedded target.
+///
+/// There is no clock to bound waits, so commands block until the modem answers,
+/// while `read_line` and `read_packet` only return buffered output and fail with
+/// `ModemError::Timeout` if there is none, to be called again from a polling loop.
+pub struct EmbeddedModem<T> {
+    io: T,
+    buf: Vec<u8>,
+    pending: VecDeque<String>,
+    capabilities: Option<Capabilities>,
+    // largest payload reported by the latest `config()`
+    max_payload: Option<usize>,
+    // header mode set by the latest `set_radio_params`, `+RX` lines are parsed for it
+    header: HeaderMode,
+    // sync word set by the latest `set_radio_params`
+    sync_word: u8,
+    rx_enabled: bool,
+    radio_state: RadioState,
+}
+
+impl<T: Read + Write + ReadReady> EmbeddedModem<T> {
+    pub fn new(io: T) -> Self {
+        EmbeddedModem {
+            io,
+            buf: Vec::new(),
+            pending: VecDeque::new(),
+            capabilities: None,
+            max_payload: None,
+            header: HeaderMode::Explicit,
+            sync_word: SYNC_WORD_PRIVATE,
+            rx_enabled: true,
+            radio_state: RadioState::Active,
+        }
+    }
+    /// Access the underlying interface.
+    pub fn io(&self) -> &T {
+        &self.io
+    }
+    /// Mutably access the underlying interface.
+    pub fn io_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+    /// Consume the modem and return the underlying interface.
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        trace::line_out(line);
+        let mut data = line.as_bytes().to_vec();
+        data.push(b'\n');
+        let mut written = 0;
+        while written < data.len() {
+            match self.io.write(&data[written..]).map_err(transport_error)? {
+                0 => return Err(ModemError::Disconnected),
+                n => written += n,
+            }
+        }
+        self.io.flush().map_err(transport_error)
+    }
+
+    // Next complete line, `None` if `block` is unset and no more data is waiting.
+    fn poll_line(&mut self, block: bool) -> Result<Option<String>> {
+        let mut chunk = [0u8; 64];
+        loop {
+            if let Some(end) = self.buf.iter().position(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&self.buf[..end])
+                    .trim_end_matches('\r')
+                    .to_string();
+                self.buf.drain(..=end);
+                trace::line_in(&line);
+                return Ok(Some(line));
+            }
+            if self.buf.len() > MAX_LINE_LEN {
+                self.buf.clear();
+                return Err(ModemError::BufferOverflow);
+            }
+            if !block && !self.io.read_ready().map_err(transport_error)? {
+                return Ok(None);
+            }
+            match self.io.read(&mut chunk).map_err(transport_error)? {
+                0 => return Err(ModemError::Disconnected),
+                n => self.buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+    }
+
+    // Send a command and collect all response lines up to and including the final one.
+    fn command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        let span = trace::command(cmd);
+        let _entered = span.enter();
+        self.write_line(cmd)?;
+        let mut lines = Vec::new();
+        loop {
+            let line = match self.poll_line(true)? {
+                Some(line) => line,
+                None => return Err(ModemError::Timeout),
+            };
+            match LineKind::of(&line) {
+                LineKind::Rx => self.pending.push_back(line),
+                LineKind::Error => return Err(ModemError::ModemReported(line)),
+                LineKind::Ok | LineKind::Sent => {
+                    lines.push(line);
+                    return Ok(lines);
+                }
+                LineKind::Other => lines.push(line),
+            }
+        }
+    }
+
+    // Fail with `ModemError::Unsupported` unless the firmware has `cap`.
+    fn require(&mut self, cap: Capabilities) -> Result<()> {
+        if self.capabilities()?.contains(cap) {
+            Ok(())
+        } else {
+            Err(ModemError::Unsupported(cap))
+        }
+    }
+}
+
+impl<T: Read + Write + ReadReady> LoraModemDevice for EmbeddedModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.buf.clear();
+        self.pending.clear();
+        self.capabilities = None;
+        self.max_payload = None;
+        self.rx_enabled = true;
+        self.radio_state = RadioState::Active;
+        Ok(())
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.command(&format!("AT+FREQ={:.6}", freq))?;
+        Ok(())
+    }
+    fn config(&mut self) -> Result<Status> {
+        let lines = self.command("AT+INFO")?;
+        let mut status = trace::parsed_lines(&lines, Status::parse(&lines))?;
+        status.radio_state = Some(self.radio_state);
+        if status.max_pkt_size > 0 {
+            self.max_payload = Some(status.max_pkt_size);
+        }
+        Ok(status)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        match self.max_payload {
+            Some(max) => Ok(max),
+            None => Ok(self.config()?.max_pkt_size),
+        }
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.command(&format!("AT+MODE={}", mode as usize))?;
+        Ok(())
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        params.validate()?;
+        if !self.capabilities()?.contains(Capabilities::RADIO_PARAMS) {
+            // older firmware only knows the predefined configs
+            return match params.preset() {
+                Some(mode) => self.set_mode(mode),
+                None => Err(ModemError::Unsupported(Capabilities::RADIO_PARAMS)),
+            };
+        }
+        // optional settings are only sent when used, so firmware without them
+        // keeps working with the defaults
+        let implicit = params.header != HeaderMode::Explicit || self.header != HeaderMode::Explicit;
+        if implicit {
+            self.require(Capabilities::IMPLICIT_HEADER)?;
+        }
+        let sync_word =
+            params.sync_word != SYNC_WORD_PRIVATE || self.sync_word != SYNC_WORD_PRIVATE;
+        if sync_word {
+            self.require(Capabilities::SYNC_WORD)?;
+        }
+        self.command(&format!("AT+BW={}", params.bandwidth.hz()))?;
+        self.command(&format!("AT+SF={}", params.spreading_factor))?;
+        self.command(&format!("AT+CR={}", params.coding_rate.denominator()))?;
+        self.command(&format!("AT+PREAMBLE={}", params.preamble_len))?;
+        self.command(&format!("AT+CRC={}", params.crc as u8))?;
+        if implicit {
+            let len = params.header.implicit_len().unwrap_or(0);
+            self.command(&format!("AT+IMPLICIT={}", len))?;
+            self.header = params.header;
+        }
+        if sync_word {
+            self.command(&format!("AT+SYNCWORD={:#04x}", params.sync_word))?;
+            self.sync_word = params.sync_word;
+        }
+        Ok(())
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        let lines = self.command("AT+INFO")?;
+        trace::parsed_lines(&lines, parse_radio_params(&lines))
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        validate_tx_power(dbm)?;
+        self.require(Capabilities::TX_POWER)?;
+        self.command(&format!("AT+TXPOWER={}", dbm))?;
+        Ok(())
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        if let Some(caps) = self.capabilities {
+            return Ok(caps);
+        }
+        let caps = match self.command("AT+HELP") {
+            Ok(lines) => Capabilities::from_help(&lines),
+            Err(ModemError::ModemReported(_)) => Capabilities::empty(),
+            Err(e) => return Err(e),
+        };
+        let caps = if caps.is_empty() {
+            Capabilities::from_info(&self.command("AT+INFO")?)?
+        } else {
+            caps
+        };
+        self.capabilities = Some(caps);
+        Ok(caps)
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.require(Capabilities::BLE)?;
+        self.command(&format!("AT+BLE={}", enabled as u8))?;
+        Ok(())
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.require(Capabilities::GPS)?;
+        let lines = self.command("AT+GPS")?;
+        match lines.iter().find(|line| line.starts_with("+GPS")) {
+            Some(line) => trace::parsed(line, GpsFix::parse(line)),
+            None => Err(ModemError::Parse(
+                "modem did not report a GPS position!".into(),
+            )),
+        }
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.require(Capabilities::TELEMETRY)?;
+        let lines = self.command("AT+TELEMETRY")?;
+        trace::parsed_lines(&lines, ModemTelemetry::parse(&lines))
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.require(Capabilities::CAD)?;
+        let lines = self.command("AT+CAD")?;
+        trace::parsed_lines(&lines, parse_cad(&lines))
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        if cmd.contains(['\r', '\n']) {
+            return Err(ModemError::InvalidArgument(
+                "AT command must be a single line".into(),
+            ));
+        }
+        self.command(cmd)
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.command("AT+RX=1")?;
+        self.rx_enabled = true;
+        Ok(())
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.command("AT+RX=0")?;
+        self.rx_enabled = false;
+        Ok(())
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.require(Capabilities::POWER_MODES)?;
+        self.command("AT+SLEEP")?;
+        self.radio_state = RadioState::Sleep;
+        Ok(())
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.require(Capabilities::POWER_MODES)?;
+        self.command("AT+STANDBY")?;
+        self.radio_state = RadioState::Standby;
+        Ok(())
+    }
+    fn wake(&mut self) -> Result<()> {
+        if self.radio_state == RadioState::Active {
+            return Ok(());
+        }
+        // restarting reception brings the radio out of sleep or standby
+        self.command(if self.rx_enabled {
+            "AT+RX=1"
+        } else {
+            "AT+STANDBY"
+        })?;
+        self.radio_state = RadioState::Active;
+        Ok(())
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        if let Some(max) = self.max_payload {
+            check_payload(data.len(), max)?;
+        }
+        self.wake()?;
+        let lines = self
+            .command(&format!("AT+TX={}", hex::encode(&data)))
+            .map_err(tx_rejected)?;
+        match lines.last() {
+            Some(line) if LineKind::of(line) == LineKind::Sent => {
+                let sent = trace::parsed(line, parse_sent(line))?;
+                Ok(TxReport::new(sent, None))
+            }
+            _ => Err(ModemError::Parse(
+                "modem did not confirm transmission!".into(),
+            )),
+        }
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        if !self.rx_enabled || self.radio_state != RadioState::Active {
+            return Err(ModemError::RxDisabled);
+        }
+        loop {
+            let line = self.read_line()?;
+            if LineKind::of(&line) == LineKind::Rx {
+                let parsed = RxPacketRef::parse(&line, self.header).map(|p| p.to_packet());
+                return trace::parsed(&line, parsed);
+            }
+        }
+    }
+    fn read_line(&mut self) -> Result<String> {
+        if let Some(line) = self.pending.pop_front() {
+            return Ok(line);
+        }
+        self.poll_line(false)?.ok_or(ModemError::Timeout)
+    }
+}
@@ -0,0 +1,201 @@
+use crate::capabilities::Capabilities;
+use crate::region::RegulatoryViolation;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use core::error::Error;
+use core::fmt;
+use core::num::{ParseFloatError, ParseIntError};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::io;
+
+/// Result type used throughout this crate.
+pub type Result<T, E = ModemError> = core::result::Result<T, E>;
+
+/// Errors that can occur while talking to a LoRa modem.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ModemError {
+    /// Reading from or writing to the device failed
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// The serial interface failed, e.g. of an embedded target or for lack of `stty`
+    Transport(String),
+    /// Output from the modem could not be parsed
+    Parse(String),
+    /// The modem did not answer in time
+    Timeout,
+    /// The modem rejected a command with the given message
+    ModemReported(String),
+    /// A setting or argument is out of range
+    InvalidArgument(String),
+    /// The command is not supported by this device
+    UnsupportedCommand(String),
+    /// The firmware lacks the given feature
+    Unsupported(Capabilities),
+    /// Data did not fit into a buffer of the device
+    BufferOverflow,
+    /// The payload is longer than the modem can transmit in one frame
+    PayloadTooLarge { max: usize },
+    /// Transmitting now would exceed the duty-cycle budget, retry after `wait`
+    DutyCycleExceeded { wait: Duration },
+    /// The peer did not acknowledge a frame
+    NotAcknowledged { attempts: usize },
+    /// A received payload failed authentication
+    AuthenticationFailed,
+    /// A received payload does not match its checksum
+    ChecksumMismatch,
+    /// A received payload has more errors than forward error correction can repair
+    Uncorrectable,
+    /// The transmit queue is at its size limit
+    QueueFull,
+    /// The channel stayed busy, transmission was not attempted
+    ChannelBusy,
+    /// The modem refused to transmit
+    TxRejected(TxRejection),
+    /// Receiving is disabled, enable it with `enable_rx` or `wake` the radio
+    RxDisabled,
+    /// A setting or transmission is not permitted in the configured region
+    RegulatoryViolation(RegulatoryViolation),
+    /// The device has not been opened yet
+    NotOpen,
+    /// The connection to the device or its worker thread went away
+    Disconnected,
+    /// Error raised by user supplied code
+    Other(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for ModemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            ModemError::Io(e) => write!(f, "IoError: {}", e),
+            ModemError::Transport(msg) => write!(f, "transport error: {}", msg),
+            ModemError::Parse(msg) => write!(f, "could not parse modem output: {}", msg),
+            ModemError::Timeout => write!(f, "timeout while waiting for modem"),
+            ModemError::ModemReported(msg) => write!(f, "modem reported error: {}", msg),
+            ModemError::InvalidArgument(msg) => write!(f, "invalid argument: {}", msg),
+            ModemError::UnsupportedCommand(cmd) => write!(f, "unsupported command: {}", cmd),
+            ModemError::Unsupported(cap) => write!(f, "not supported by firmware: {}", cap),
+            ModemError::BufferOverflow => write!(f, "buffer overflow"),
+            ModemError::PayloadTooLarge { max } => {
+                write!(f, "payload larger than the maximum of {} bytes", max)
+            }
+            ModemError::DutyCycleExceeded { wait } => {
+                write!(f, "duty cycle exceeded, retry in {:?}", wait)
+            }
+            ModemError::NotAcknowledged { attempts } => {
+                write!(f, "no acknowledgement after {} attempts", attempts)
+            }
+            ModemError::AuthenticationFailed => write!(f, "payload authentication failed"),
+            ModemError::ChecksumMismatch => write!(f, "payload checksum mismatch"),
+            ModemError::Uncorrectable => write!(f, "payload has uncorrectable errors"),
+            ModemError::QueueFull => write!(f, "transmit queue full"),
+            ModemError::ChannelBusy => write!(f, "channel busy"),
+            ModemError::TxRejected(reason) => write!(f, "transmission rejected: {}", reason),
+            ModemError::RxDisabled => write!(f, "receiving is disabled"),
+            ModemError::RegulatoryViolation(v) => write!(f, "regulatory violation: {}", v),
+            ModemError::NotOpen => write!(f, "modem device not open"),
+            ModemError::Disconnected => write!(f, "modem disconnected"),
+            ModemError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Reason a modem gave for refusing a transmission
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TxRejection {
+    /// The payload exceeds the maximum packet size
+    TooLong,
+    /// The radio is still transmitting or otherwise occupied
+    Busy,
+    /// The command overran the receive buffer of the firmware
+    BufferFull,
+    /// Any other reason, with the message of the modem
+    Other(String),
+}
+
+impl TxRejection {
+    /// Classify the error message a modem answered a transmission with.
+    pub fn from_message(message: &str) -> Self {
+        let lower = message.to_ascii_lowercase();
+        if lower.contains("full") || lower.contains("overrun") {
+            TxRejection::BufferFull
+        } else if ["too long", "too large", "size", "length", "overflow"]
+            .iter()
+            .any(|hint| lower.contains(hint))
+        {
+            TxRejection::TooLong
+        } else if lower.contains("busy") || lower.contains("in progress") {
+            TxRejection::Busy
+        } else {
+            TxRejection::Other(message.to_string())
+        }
+    }
+}
+
+impl fmt::Display for TxRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxRejection::TooLong => write!(f, "payload too long"),
+            TxRejection::Busy => write!(f, "radio busy"),
+            TxRejection::BufferFull => write!(f, "modem buffer full"),
+            TxRejection::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+// Turn an error reported by the modem in answer to a transmission into `ModemError::TxRejected`.
+pub(crate) fn tx_rejected(e: ModemError) -> ModemError {
+    match e {
+        ModemError::ModemReported(msg) => ModemError::TxRejected(TxRejection::from_message(&msg)),
+        e => e,
+    }
+}
+
+impl Error for ModemError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            ModemError::Io(e) => Some(e),
+            ModemError::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for ModemError {
+    fn from(e: io::Error) -> Self {
+        // transports can only fail with I/O errors, some carry a more specific one
+        let e = match e.downcast::<ModemError>() {
+            Ok(inner) => return inner,
+            Err(e) => e,
+        };
+        match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => ModemError::Timeout,
+            io::ErrorKind::NotConnected => ModemError::NotOpen,
+            _ => ModemError::Io(e),
+        }
+    }
+}
+
+impl From<ParseIntError> for ModemError {
+    fn from(e: ParseIntError) -> Self {
+        ModemError::Parse(e.to_string())
+    }
+}
+
+impl From<ParseFloatError> for ModemError {
+    fn from(e: ParseFloatError) -> Self {
+        ModemError::Parse(e.to_string())
+    }
+}
+
+#[cfg(feature = "anyhow")]
+impl From<anyhow::Error> for ModemError {
+    fn from(e: anyhow::Error) -> Self {
+        ModemError::Other(e.into())
+    }
+}
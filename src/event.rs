@@ -0,0 +1,58 @@
+use crate::gps::GpsFix;
+use crate::line::{parse_sent, LineKind};
+use crate::radio::HeaderMode;
+use crate::{RxPacket, RxPacketRef, StatusChange};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Anything observed on a modem connection
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ModemEvent {
+    /// A packet was received
+    PacketReceived(RxPacket),
+    /// A transmission completed, number of bytes sent
+    TxDone(usize),
+    /// The modem reported an error
+    Error(String),
+    /// Status notification or stray command response of the firmware
+    Info(String),
+    /// Position reported by the GPS receiver
+    GpsFix(GpsFix),
+    /// The transport reconnected and the last known configuration was restored
+    Reconnected,
+    /// A status poll found the modem reconfigured or its counters jumping
+    StatusChanged(Vec<StatusChange>),
+    /// Any other output, e.g. boot banners
+    Unknown(String),
+}
+
+impl ModemEvent {
+    /// Classify a line of modem output.
+    ///
+    /// Lines that look like packets or confirmations but fail to parse become `Unknown`.
+    pub fn from_line(line: String) -> ModemEvent {
+        ModemEvent::from_line_with(line, HeaderMode::Explicit)
+    }
+    /// Classify a line of modem output, parsing packets for `header` mode.
+    pub fn from_line_with(line: String, header: HeaderMode) -> ModemEvent {
+        match LineKind::of(&line) {
+            LineKind::Rx => match RxPacketRef::parse(&line, header) {
+                Ok(packet) => ModemEvent::PacketReceived(packet.to_packet()),
+                Err(_) => ModemEvent::Unknown(line),
+            },
+            LineKind::Sent => match parse_sent(&line) {
+                Ok(n) => ModemEvent::TxDone(n),
+                Err(_) => ModemEvent::Unknown(line),
+            },
+            LineKind::Error => ModemEvent::Error(line),
+            LineKind::Ok => ModemEvent::Info(line),
+            LineKind::Other if line.starts_with("+GPS") => match GpsFix::parse(&line) {
+                Ok(Some(fix)) => ModemEvent::GpsFix(fix),
+                _ => ModemEvent::Info(line),
+            },
+            LineKind::Other if line.starts_with('+') => ModemEvent::Info(line),
+            LineKind::Other => ModemEvent::Unknown(line),
+        }
+    }
+}
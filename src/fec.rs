@@ -0,0 +1,413 @@
+use crate::radio::RadioParams;
+#[cfg(feature = "std")]
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    send_framed, Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError,
+    ModemEvent, Result, RxPacket, Status, TxReport,
+};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Length of a full Reed-Solomon codeword over GF(256).
+pub const CODEWORD_LEN: usize = 255;
+/// Parity bytes per codeword used by `FecModem::new`, giving RS(255,223).
+pub const DEFAULT_PARITY: usize = 32;
+
+const fn gf_tables() -> ([u8; 512], [u8; 256]) {
+    let mut exp = [0u8; 512];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11d;
+        }
+        i += 1;
+    }
+    while i < 512 {
+        exp[i] = exp[i - 255];
+        i += 1;
+    }
+    (exp, log)
+}
+
+const EXP: [u8; 512] = gf_tables().0;
+const LOG: [u8; 256] = gf_tables().1;
+
+fn gf_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        0
+    } else {
+        EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+    }
+}
+
+fn gf_inverse(a: u8) -> u8 {
+    EXP[255 - LOG[a as usize] as usize]
+}
+
+fn gf_pow2(power: usize) -> u8 {
+    EXP[power % 255]
+}
+
+// Polynomials are stored with the highest degree coefficient first.
+fn poly_eval(poly: &[u8], x: u8) -> u8 {
+    poly.iter().fold(0, |y, &c| gf_mul(y, x) ^ c)
+}
+
+fn poly_scale(poly: &[u8], factor: u8) -> Vec<u8> {
+    poly.iter().map(|&c| gf_mul(c, factor)).collect()
+}
+
+fn poly_add(p: &[u8], q: &[u8]) -> Vec<u8> {
+    let len = p.len().max(q.len());
+    let mut sum = vec![0; len];
+    for (i, &c) in p.iter().enumerate() {
+        sum[i + len - p.len()] = c;
+    }
+    for (i, &c) in q.iter().enumerate() {
+        sum[i + len - q.len()] ^= c;
+    }
+    sum
+}
+
+fn poly_mul(p: &[u8], q: &[u8]) -> Vec<u8> {
+    let mut product = vec![0; p.len() + q.len() - 1];
+    for (i, &a) in p.iter().enumerate() {
+        for (j, &b) in q.iter().enumerate() {
+            product[i + j] ^= gf_mul(a, b);
+        }
+    }
+    product
+}
+
+/// Reed-Solomon code over GF(256) with `parity` check symbols per codeword.
+///
+/// Codewords are systematic, the data is followed by its parity bytes, and may
+/// be shortened below `CODEWORD_LEN`. Up to `parity / 2` corrupted bytes per
+/// codeword are corrected.
+#[derive(Debug, Clone)]
+pub struct ReedSolomon {
+    generator: Vec<u8>,
+}
+
+impl ReedSolomon {
+    /// Fails if `parity` leaves no room for data in a codeword.
+    pub fn new(parity: usize) -> Result<Self> {
+        if parity == 0 || parity >= CODEWORD_LEN {
+            return Err(ModemError::InvalidArgument(format!(
+                "FEC parity must be between 1 and {}, not {}",
+                CODEWORD_LEN - 1,
+                parity
+            )));
+        }
+        let generator = (0..parity).fold(vec![1], |g, i| poly_mul(&g, &[1, gf_pow2(i)]));
+        Ok(ReedSolomon { generator })
+    }
+    /// Number of parity bytes per codeword.
+    pub fn parity(&self) -> usize {
+        self.generator.len() - 1
+    }
+    /// Data bytes carried by a full codeword.
+    pub fn data_len(&self) -> usize {
+        CODEWORD_LEN - self.parity()
+    }
+
+    /// Codeword of at most `data_len()` bytes of `data`.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let mut codeword = data.to_vec();
+        codeword.resize(data.len() + self.parity(), 0);
+        for i in 0..data.len() {
+            let coef = codeword[i];
+            if coef != 0 {
+                for (j, &g) in self.generator.iter().enumerate().skip(1) {
+                    codeword[i + j] ^= gf_mul(g, coef);
+                }
+            }
+        }
+        codeword[..data.len()].copy_from_slice(data);
+        codeword
+    }
+
+    /// Correct `codeword` in place and return the number of corrected bytes.
+    ///
+    /// Fails with `ModemError::Uncorrectable` if it has more errors than the code can repair.
+    pub fn correct(&self, codeword: &mut [u8]) -> Result<usize> {
+        let parity = self.parity();
+        if codeword.len() <= parity || codeword.len() > CODEWORD_LEN {
+            return Err(ModemError::Uncorrectable);
+        }
+        let syndromes = self.syndromes(codeword);
+        if syndromes.iter().all(|&s| s == 0) {
+            return Ok(0);
+        }
+        let locator = error_locator(&syndromes, parity);
+        let errors = locator.len() - 1;
+        if errors * 2 > parity {
+            return Err(ModemError::Uncorrectable);
+        }
+        // Chien search, the roots of the locator give the error positions
+        let reversed: Vec<u8> = locator.iter().rev().copied().collect();
+        let positions: Vec<usize> = (0..codeword.len())
+            .filter(|&i| poly_eval(&reversed, gf_pow2(i)) == 0)
+            .map(|i| codeword.len() - 1 - i)
+            .collect();
+        if positions.len() != errors {
+            return Err(ModemError::Uncorrectable);
+        }
+        correct_errata(codeword, &syndromes, &positions);
+        if self.syndromes(codeword).iter().any(|&s| s != 0) {
+            return Err(ModemError::Uncorrectable);
+        }
+        Ok(errors)
+    }
+
+    // Syndromes with a leading zero, which the locator and evaluator steps expect.
+    fn syndromes(&self, codeword: &[u8]) -> Vec<u8> {
+        let mut syndromes = vec![0];
+        syndromes.extend((0..self.parity()).map(|i| poly_eval(codeword, gf_pow2(i))));
+        syndromes
+    }
+}
+
+// Berlekamp-Massey, error locator polynomial of the syndromes.
+fn error_locator(syndromes: &[u8], parity: usize) -> Vec<u8> {
+    let mut locator = vec![1];
+    let mut old = vec![1];
+    for k in 1..=parity {
+        let mut delta = syndromes[k];
+        for j in 1..locator.len() {
+            delta ^= gf_mul(locator[locator.len() - 1 - j], syndromes[k - j]);
+        }
+        old.push(0);
+        if delta != 0 {
+            if old.len() > locator.len() {
+                let new = poly_scale(&old, delta);
+                old = poly_scale(&locator, gf_inverse(delta));
+                locator = new;
+            }
+            locator = poly_add(&locator, &poly_scale(&old, delta));
+        }
+    }
+    let leading = locator.iter().take_while(|&&c| c == 0).count();
+    locator.split_off(leading)
+}
+
+// Forney algorithm, fix the bytes at the known error `positions`.
+fn correct_errata(codeword: &mut [u8], syndromes: &[u8], positions: &[usize]) {
+    let coef_pos: Vec<usize> = positions.iter().map(|p| codeword.len() - 1 - p).collect();
+    let locator = coef_pos
+        .iter()
+        .fold(vec![1], |l, &i| poly_mul(&l, &[gf_pow2(i), 1]));
+    let reversed: Vec<u8> = syndromes.iter().rev().copied().collect();
+    let product = poly_mul(&reversed, &locator);
+    let evaluator = &product[product.len() - locator.len()..];
+    let x: Vec<u8> = coef_pos.iter().map(|&i| gf_pow2(i)).collect();
+    for (i, &xi) in x.iter().enumerate() {
+        let xi_inv = gf_inverse(xi);
+        let locator_prime = x
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .fold(1, |acc, (_, &xj)| gf_mul(acc, 1 ^ gf_mul(xi_inv, xj)));
+        let y = gf_mul(xi, poly_eval(evaluator, xi_inv));
+        codeword[positions[i]] ^= gf_mul(y, gf_inverse(locator_prime));
+    }
+}
+
+/// Counters of a `FecModem`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FecStats {
+    /// Corrupted bytes repaired
+    pub corrected_symbols: usize,
+    /// Packets that needed at least one correction
+    pub corrected_packets: usize,
+    /// Packets with more errors than could be repaired
+    pub uncorrectable: usize,
+}
+
+/// Protects payloads with Reed-Solomon forward error correction.
+///
+/// Payloads are split into blocks of up to `data_len()` bytes, each sent with its
+/// parity, so a frame of at most `CODEWORD_LEN` bytes is a single codeword.
+/// Packets with too many errors are rejected with `ModemError::Uncorrectable`.
+pub struct FecModem<T: LoraModemDevice> {
+    inner: T,
+    code: ReedSolomon,
+    stats: FecStats,
+}
+
+impl<T: LoraModemDevice> FecModem<T> {
+    /// Wrap `inner` with RS(255,223).
+    pub fn new(inner: T) -> Self {
+        FecModem {
+            inner,
+            code: ReedSolomon::new(DEFAULT_PARITY).unwrap(),
+            stats: FecStats::default(),
+        }
+    }
+    /// Wrap `inner` with `parity` bytes per codeword.
+    pub fn with_parity(inner: T, parity: usize) -> Result<Self> {
+        Ok(FecModem {
+            inner,
+            code: ReedSolomon::new(parity)?,
+            stats: FecStats::default(),
+        })
+    }
+    /// Correction counters since creation.
+    pub fn fec_stats(&self) -> FecStats {
+        self.stats
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    // Codewords carrying `data`.
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(CODEWORD_LEN);
+        for block in data.chunks(self.code.data_len()) {
+            frame.extend_from_slice(&self.code.encode(block));
+        }
+        frame
+    }
+    fn decode(&mut self, frame: &[u8]) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(frame.len());
+        let mut corrected = 0;
+        for block in frame.chunks(CODEWORD_LEN) {
+            let mut codeword = block.to_vec();
+            corrected += self.code.correct(&mut codeword)?;
+            data.extend_from_slice(&codeword[..codeword.len() - self.code.parity()]);
+        }
+        if corrected > 0 {
+            self.stats.corrected_symbols += corrected;
+            self.stats.corrected_packets += 1;
+        }
+        Ok(data)
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for FecModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        let frame = self.inner.max_payload()?;
+        let tail = (frame % CODEWORD_LEN).saturating_sub(self.code.parity());
+        Ok(frame / CODEWORD_LEN * self.code.data_len() + tail)
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    #[cfg(feature = "std")]
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    #[cfg(feature = "std")]
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.send_slice(&data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        let frame = self.encode(data);
+        let overhead = frame.len() - data.len();
+        let report = self.inner.send_data(frame)?;
+        Ok(TxReport {
+            bytes: report.bytes.saturating_sub(overhead),
+            ..report
+        })
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        let encoded: Vec<(Vec<u8>, usize)> = frames
+            .iter()
+            .map(|data| {
+                let frame = self.encode(data);
+                let overhead = frame.len() - data.len();
+                (frame, overhead)
+            })
+            .collect();
+        send_framed(&mut self.inner, &encoded)
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        let mut packet = self.inner.read_packet()?;
+        match self.decode(&packet.data) {
+            Ok(data) => packet.data = data,
+            Err(e) => {
+                self.stats.uncorrectable += 1;
+                return Err(e);
+            }
+        }
+        Ok(packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
@@ -0,0 +1,402 @@
+//! C interface for firmware tooling and Python/ctypes scripts.
+//!
+//! Build a library with `cargo rustc --release --features ffi --crate-type cdylib`,
+//! or `staticlib`, and include `include/lora_modem.h`. The header is generated
+//! with `cbindgen --config cbindgen.toml --output include/lora_modem.h`.
+//!
+//! Functions taking a handle return `LORA_OK` or a negative error code, the
+//! message of the last failure on the calling thread is available from
+//! `lora_modem_last_error`. A handle may be used from several threads at once,
+//! except for `lora_modem_close`.
+
+use crate::rf95::Rf95Modem;
+use crate::transport::Transport;
+use crate::{Frequency, LoraModemDevice, ModemConfig, ModemError, Result, SerialModem, TcpModem};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::{ptr, slice};
+
+/// Success.
+pub const LORA_OK: c_int = 0;
+/// Any failure without a code of its own.
+pub const LORA_ERR: c_int = -1;
+/// No packet arrived or the modem did not answer in time.
+pub const LORA_ERR_TIMEOUT: c_int = -2;
+/// Null pointer or invalid argument.
+pub const LORA_ERR_INVALID: c_int = -3;
+/// Buffer too small for the received packet, which is dropped.
+pub const LORA_ERR_BUFFER: c_int = -4;
+/// Packets are delivered to the RX callback instead.
+pub const LORA_ERR_BUSY: c_int = -5;
+
+// Timeout of a single modem read, bounds how long a send waits for the RX thread.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_error(message: &str) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+// Error code for `result`, recording the message of a failure.
+fn code(result: Result<()>) -> c_int {
+    match result {
+        Ok(()) => LORA_OK,
+        Err(e) => {
+            set_error(&e.to_string());
+            match e {
+                ModemError::Timeout => LORA_ERR_TIMEOUT,
+                ModemError::InvalidArgument(_) => LORA_ERR_INVALID,
+                ModemError::BufferOverflow => LORA_ERR_BUFFER,
+                _ => LORA_ERR,
+            }
+        }
+    }
+}
+
+/// Packet handed to the RX callback or filled in by `lora_modem_read_packet`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LoraRxPacket {
+    /// Payload, for the callback only valid until it returns
+    pub data: *const u8,
+    pub len: usize,
+    /// Signal strength in dBm
+    pub rssi: i16,
+    /// Signal-to-Noise ratio in dB
+    pub snr: f32,
+}
+
+/// Called on a background thread for every received packet.
+pub type LoraRxCallback =
+    Option<unsafe extern "C" fn(packet: *const LoraRxPacket, user_data: *mut c_void)>;
+
+// Pointer given to `lora_modem_set_rx_callback`, passed back to the callback untouched.
+struct UserData(*mut c_void);
+
+// SAFETY: the caller of `lora_modem_set_rx_callback` promises the pointer may be
+// used from the RX thread.
+unsafe impl Send for UserData {}
+
+type Device = Arc<Mutex<Box<dyn LoraModemDevice + Send>>>;
+
+// Thread delivering packets to the RX callback.
+struct Listener {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+    // why the thread ended on its own
+    error: Arc<Mutex<Option<ModemError>>>,
+}
+
+/// Modem opened through the C interface, opaque to C
+pub struct LoraModemHandle {
+    device: Device,
+    // set and read from any thread holding the handle
+    listener: Mutex<Option<Listener>>,
+}
+
+// Stop the thread of `listener`, returns why it ended on its own.
+fn stop_listener(listener: &mut Option<Listener>) -> Option<ModemError> {
+    let listener = listener.take()?;
+    listener.stop.store(true, Ordering::SeqCst);
+    let _ = listener.handle.join();
+    let error = listener.error.lock().ok()?.take();
+    error
+}
+
+impl LoraModemHandle {
+    fn listener(&self) -> MutexGuard<'_, Option<Listener>> {
+        self.listener.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    fn with_device<F: FnOnce(&mut dyn LoraModemDevice) -> Result<()>>(&self, f: F) -> c_int {
+        match self.device.lock() {
+            Ok(mut device) => code(f(&mut **device)),
+            Err(_) => code(Err(ModemError::Disconnected)),
+        }
+    }
+}
+
+/// Hand a Rust device to C code, e.g. when embedding the library.
+///
+/// The handle is released with `lora_modem_close`. Reads of the device should
+/// time out after a short while, so sends are not held up by the RX callback.
+pub fn into_raw<D: LoraModemDevice + Send + 'static>(device: D) -> *mut LoraModemHandle {
+    Box::into_raw(Box::new(LoraModemHandle {
+        device: Arc::new(Mutex::new(Box::new(device))),
+        listener: Mutex::new(None),
+    }))
+}
+
+fn open<T: Transport + Send + 'static>(mut modem: Rf95Modem<T>) -> *mut LoraModemHandle {
+    modem.set_timeout(Some(POLL_INTERVAL));
+    match modem.open() {
+        Ok(()) => into_raw(modem),
+        Err(e) => {
+            code(Err(e));
+            ptr::null_mut()
+        }
+    }
+}
+
+// `ptr` as string, recording an error for a null pointer or invalid UTF-8.
+unsafe fn string<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        set_error("null pointer given");
+        return None;
+    }
+    match CStr::from_ptr(ptr).to_str() {
+        Ok(s) => Some(s),
+        Err(_) => {
+            set_error("string is not valid UTF-8");
+            None
+        }
+    }
+}
+
+/// Open the rf95modem at the serial device `path`, null on failure.
+///
+/// # Safety
+///
+/// `path` is a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lora_modem_open(path: *const c_char, baud: u32) -> *mut LoraModemHandle {
+    match string(path) {
+        Some(path) => open(SerialModem::new(path, baud)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Open the rf95modem behind the serial bridge at `addr` (`host:port`), null on failure.
+///
+/// # Safety
+///
+/// `addr` is a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lora_modem_open_tcp(addr: *const c_char) -> *mut LoraModemHandle {
+    match string(addr) {
+        Some(addr) => open(TcpModem::new(addr)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Stop the RX callback and close the modem, null is ignored.
+///
+/// # Safety
+///
+/// `modem` was returned by this library, no other call on it is in progress
+/// and it is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn lora_modem_close(modem: *mut LoraModemHandle) {
+    if !modem.is_null() {
+        let modem = Box::from_raw(modem);
+        stop_listener(&mut modem.listener());
+    }
+}
+
+/// Message of the last failure on this thread, valid until the next call failing.
+#[no_mangle]
+pub extern "C" fn lora_modem_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Send `len` bytes at `data`.
+///
+/// # Safety
+///
+/// `modem` is a valid handle, `data` points to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn lora_modem_send(
+    modem: *mut LoraModemHandle,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    let modem = match modem.as_ref() {
+        Some(modem) if !data.is_null() || len == 0 => modem,
+        _ => {
+            return code(Err(ModemError::InvalidArgument(
+                "null pointer given".into(),
+            )))
+        }
+    };
+    let frame = if len == 0 {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(data, len).to_vec()
+    };
+    modem.with_device(|device| device.send_data(frame).map(|_| ()))
+}
+
+/// Tune to `hz`.
+///
+/// # Safety
+///
+/// `modem` is a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn lora_modem_set_frequency(modem: *mut LoraModemHandle, hz: u32) -> c_int {
+    match modem.as_ref() {
+        Some(modem) => modem.with_device(|device| device.set_frequency(Frequency::from_hz(hz))),
+        None => code(Err(ModemError::InvalidArgument("null handle".into()))),
+    }
+}
+
+/// Switch to predefined modem config `mode`, numbered as by `AT+MODE`.
+///
+/// # Safety
+///
+/// `modem` is a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn lora_modem_set_mode(modem: *mut LoraModemHandle, mode: u32) -> c_int {
+    match modem.as_ref() {
+        Some(modem) => modem.with_device(|device| {
+            let mode = ModemConfig::try_from(mode as usize)?;
+            device.set_mode(mode)
+        }),
+        None => code(Err(ModemError::InvalidArgument("null handle".into()))),
+    }
+}
+
+/// Wait up to `timeout_ms` for a packet and copy its payload into `buf`.
+///
+/// `packet` is filled in with `data` pointing into `buf`. Fails with
+/// `LORA_ERR_BUSY` while an RX callback is set. Once the callback stopped
+/// because the modem went away, the next call reports why.
+///
+/// # Safety
+///
+/// `modem` is a valid handle, `buf` points to `capacity` writable bytes and
+/// `packet` to a writable `LoraRxPacket`.
+#[no_mangle]
+pub unsafe extern "C" fn lora_modem_read_packet(
+    modem: *mut LoraModemHandle,
+    buf: *mut u8,
+    capacity: usize,
+    packet: *mut LoraRxPacket,
+    timeout_ms: u32,
+) -> c_int {
+    let modem = match modem.as_ref() {
+        Some(modem) if !packet.is_null() && (!buf.is_null() || capacity == 0) => modem,
+        _ => {
+            return code(Err(ModemError::InvalidArgument(
+                "null pointer given".into(),
+            )))
+        }
+    };
+    {
+        let mut listener = modem.listener();
+        if let Some(running) = &*listener {
+            let ended = running.error.lock().map_or(true, |error| error.is_some());
+            if !ended {
+                set_error("packets are delivered to the RX callback");
+                return LORA_ERR_BUSY;
+            }
+            if let Some(e) = stop_listener(&mut listener) {
+                return code(Err(e));
+            }
+        }
+    }
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms.into());
+    modem.with_device(|device| {
+        let received = loop {
+            match device.read_packet() {
+                Ok(received) => break received,
+                Err(ModemError::Timeout) if Instant::now() < deadline => {}
+                Err(e) => return Err(e),
+            }
+        };
+        if received.data.len() > capacity {
+            return Err(ModemError::BufferOverflow);
+        }
+        // SAFETY: `buf` holds `capacity` bytes, checked to fit the payload
+        ptr::copy_nonoverlapping(received.data.as_ptr(), buf, received.data.len());
+        *packet = LoraRxPacket {
+            data: buf,
+            len: received.data.len(),
+            rssi: received.rssi,
+            snr: received.snr,
+        };
+        Ok(())
+    })
+}
+
+/// Deliver received packets to `callback` on a background thread, null stops it.
+///
+/// Lines failing to parse are skipped. Delivery stops for good when the modem
+/// is disconnected, `lora_modem_read_packet` then fails with the reason.
+///
+/// # Safety
+///
+/// `modem` is a valid handle, `callback` may be called with `user_data` from
+/// another thread until replaced or the modem is closed.
+#[no_mangle]
+pub unsafe extern "C" fn lora_modem_set_rx_callback(
+    modem: *mut LoraModemHandle,
+    callback: LoraRxCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let modem = match modem.as_ref() {
+        Some(modem) => modem,
+        None => return code(Err(ModemError::InvalidArgument("null handle".into()))),
+    };
+    // held until the new listener is in place, concurrent calls take turns
+    let mut listener = modem.listener();
+    stop_listener(&mut listener);
+    let callback = match callback {
+        Some(callback) => callback,
+        None => return LORA_OK,
+    };
+    let stop = Arc::new(AtomicBool::new(false));
+    let error = Arc::new(Mutex::new(None));
+    let device = modem.device.clone();
+    let user_data = UserData(user_data);
+    let running = stop.clone();
+    let failed = error.clone();
+    let handle = thread::spawn(move || {
+        while !running.load(Ordering::SeqCst) {
+            let received = match device.lock() {
+                Ok(mut device) => device.read_packet(),
+                Err(_) => Err(ModemError::Disconnected),
+            };
+            match received {
+                Ok(received) => {
+                    let packet = LoraRxPacket {
+                        data: received.data.as_ptr(),
+                        len: received.data.len(),
+                        rssi: received.rssi,
+                        snr: received.snr,
+                    };
+                    // SAFETY: the caller vouches for the callback, the packet
+                    // outlives the call
+                    callback(&packet, user_data.0);
+                }
+                // let senders have the device in between
+                Err(ModemError::Timeout) => thread::yield_now(),
+                // a corrupted line on a noisy channel
+                Err(ModemError::Parse(_)) => {}
+                // nothing more to receive
+                Err(e @ ModemError::Disconnected) | Err(e @ ModemError::NotOpen) => {
+                    if let Ok(mut failed) = failed.lock() {
+                        *failed = Some(e);
+                    }
+                    return;
+                }
+                // e.g. the firmware reported an error, try again after a while
+                Err(_) => thread::sleep(POLL_INTERVAL),
+            }
+        }
+    });
+    *listener = Some(Listener {
+        stop,
+        handle,
+        error,
+    });
+    LORA_OK
+}
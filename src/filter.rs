@@ -0,0 +1,133 @@
+use crate::addressing::HEADER_LEN;
+use crate::RxPacket;
+use alloc::vec::Vec;
+
+/// Criterion of an `RxFilter` a packet failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterReason {
+    /// Source address not accepted
+    Source,
+    /// Payload without an accepted prefix
+    Prefix,
+    /// Signal weaker than the minimum RSSI
+    Rssi,
+    /// Port byte missing or not accepted
+    Port,
+}
+
+/// Rules deciding which received packets reach the application
+///
+/// A packet passes if it meets every criterion set, the default filter passes
+/// everything. Source addresses are taken from the addressing header, so frames
+/// too short to carry one fail a source rule. Prefixes and the port byte are
+/// matched against the received data, header included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RxFilter {
+    /// Source addresses accepted, any if empty
+    pub sources: Vec<u8>,
+    /// Source addresses dropped even if listed in `sources`
+    pub blocked: Vec<u8>,
+    /// Payload prefixes accepted, any payload if empty
+    pub prefixes: Vec<Vec<u8>>,
+    /// Weakest RSSI in dBm accepted
+    pub min_rssi: Option<i16>,
+    /// Offset of a port or message id byte and the values accepted
+    pub port: Option<(usize, Vec<u8>)>,
+}
+
+impl RxFilter {
+    pub fn new() -> Self {
+        RxFilter::default()
+    }
+    /// Only accept frames from `sources`.
+    pub fn sources(mut self, sources: &[u8]) -> Self {
+        self.sources = sources.to_vec();
+        self
+    }
+    /// Drop frames from `source`.
+    pub fn block(mut self, source: u8) -> Self {
+        self.blocked.push(source);
+        self
+    }
+    /// Also accept payloads starting with `prefix`.
+    pub fn prefix(mut self, prefix: &[u8]) -> Self {
+        self.prefixes.push(prefix.to_vec());
+        self
+    }
+    /// Drop packets received with less than `dbm`.
+    pub fn min_rssi(mut self, dbm: i16) -> Self {
+        self.min_rssi = Some(dbm);
+        self
+    }
+    /// Only accept frames carrying one of `ports` at `offset`, e.g. `HEADER_LEN`
+    /// for the first payload byte of addressed frames.
+    pub fn ports(mut self, offset: usize, ports: &[u8]) -> Self {
+        self.port = Some((offset, ports.to_vec()));
+        self
+    }
+
+    /// The first criterion `packet` fails, `None` if it passes.
+    pub fn check(&self, packet: &RxPacket) -> Option<FilterReason> {
+        let data = &packet.data;
+        if !self.sources.is_empty() || !self.blocked.is_empty() {
+            let src = match data.get(1) {
+                Some(&src) if data.len() >= HEADER_LEN => src,
+                _ => return Some(FilterReason::Source),
+            };
+            if self.blocked.contains(&src)
+                || !(self.sources.is_empty() || self.sources.contains(&src))
+            {
+                return Some(FilterReason::Source);
+            }
+        }
+        if !self.prefixes.is_empty() && !self.prefixes.iter().any(|p| data.starts_with(p)) {
+            return Some(FilterReason::Prefix);
+        }
+        if self.min_rssi.is_some_and(|min| packet.rssi < min) {
+            return Some(FilterReason::Rssi);
+        }
+        if let Some((offset, ports)) = &self.port {
+            if !data.get(*offset).is_some_and(|port| ports.contains(port)) {
+                return Some(FilterReason::Port);
+            }
+        }
+        None
+    }
+    /// Whether `packet` passes the filter.
+    pub fn accepts(&self, packet: &RxPacket) -> bool {
+        self.check(packet).is_none()
+    }
+}
+
+/// Counters of the packets an `RxFilter` passed and dropped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilterStats {
+    /// Packets handed on
+    pub passed: u64,
+    /// Packets dropped for their source address
+    pub source: u64,
+    /// Packets dropped for their prefix
+    pub prefix: u64,
+    /// Packets dropped for their RSSI
+    pub rssi: u64,
+    /// Packets dropped for their port byte
+    pub port: u64,
+}
+
+impl FilterStats {
+    /// Count the outcome of `RxFilter::check`.
+    pub fn record(&mut self, outcome: Option<FilterReason>) {
+        let counter = match outcome {
+            None => &mut self.passed,
+            Some(FilterReason::Source) => &mut self.source,
+            Some(FilterReason::Prefix) => &mut self.prefix,
+            Some(FilterReason::Rssi) => &mut self.rssi,
+            Some(FilterReason::Port) => &mut self.port,
+        };
+        *counter += 1;
+    }
+    /// Packets dropped for any reason.
+    pub fn dropped(&self) -> u64 {
+        self.source + self.prefix + self.rssi + self.port
+    }
+}
@@ -0,0 +1,153 @@
+//! Splitting of payloads larger than a LoRa frame and their reassembly.
+//!
+//! Every fragment carries a 4 byte header: message id (big endian u16),
+//! index of the fragment and total number of fragments.
+
+use crate::{LoraModemDevice, ModemError, Result};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Size of the header of every fragment.
+pub const FRAGMENT_HEADER_LEN: usize = 4;
+/// Most fragments a message can be split into.
+pub const MAX_FRAGMENTS: usize = 255;
+
+/// Split `data` into fragments of at most `mtu` bytes including the header.
+pub fn fragment(id: u16, data: &[u8], mtu: usize) -> Result<Vec<Vec<u8>>> {
+    if mtu <= FRAGMENT_HEADER_LEN {
+        return Err(ModemError::InvalidArgument(format!(
+            "MTU of {} bytes leaves no room for fragment data",
+            mtu
+        )));
+    }
+    let chunk = mtu - FRAGMENT_HEADER_LEN;
+    let count = data.len().div_ceil(chunk).max(1);
+    if count > MAX_FRAGMENTS {
+        return Err(ModemError::BufferOverflow);
+    }
+    let id = id.to_be_bytes();
+    let mut fragments = Vec::with_capacity(count);
+    for index in 0..count {
+        let part = &data[(index * chunk).min(data.len())..((index + 1) * chunk).min(data.len())];
+        let mut frame = Vec::with_capacity(FRAGMENT_HEADER_LEN + part.len());
+        frame.extend_from_slice(&[id[0], id[1], index as u8, count as u8]);
+        frame.extend_from_slice(part);
+        fragments.push(frame);
+    }
+    Ok(fragments)
+}
+
+/// Split `data` into fragments fitting the largest payload of `device`.
+///
+/// The size is taken from `max_payload()`, which backends learn from the
+/// modem through `config()` and wrappers reduce by their headers.
+pub fn fragment_for<D: LoraModemDevice + ?Sized>(
+    device: &mut D,
+    id: u16,
+    data: &[u8],
+) -> Result<Vec<Vec<u8>>> {
+    fragment(id, data, device.max_payload()?)
+}
+
+struct Partial {
+    parts: Vec<Option<Vec<u8>>>,
+    missing: usize,
+    started: Instant,
+}
+
+/// Collects fragments until a message is complete.
+///
+/// Fragments may arrive in any order and duplicates are ignored. Messages not
+/// completed within the timeout are dropped, as are the oldest ones beyond
+/// the limit of messages in progress.
+pub struct Reassembler {
+    partial: HashMap<(u8, u16), Partial>,
+    timeout: Duration,
+    max_pending: usize,
+}
+
+impl Reassembler {
+    pub fn new(timeout: Duration, max_pending: usize) -> Self {
+        Reassembler {
+            partial: HashMap::new(),
+            timeout,
+            max_pending,
+        }
+    }
+    /// Messages in progress.
+    pub fn pending(&self) -> usize {
+        self.partial.len()
+    }
+
+    /// Add a fragment, returns the message once complete.
+    pub fn push(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.push_from(0, frame)
+    }
+
+    /// Add a fragment sent by node `src`, message ids are kept apart per sender.
+    pub fn push_from(&mut self, src: u8, frame: &[u8]) -> Result<Option<Vec<u8>>> {
+        if frame.len() < FRAGMENT_HEADER_LEN {
+            return Err(ModemError::Parse(
+                "frame shorter than fragment header!".into(),
+            ));
+        }
+        let id = u16::from_be_bytes([frame[0], frame[1]]);
+        let (index, count) = (frame[2] as usize, frame[3] as usize);
+        if count == 0 || index >= count {
+            return Err(ModemError::Parse(format!(
+                "fragment {} of {} out of range",
+                index, count
+            )));
+        }
+        let data = &frame[FRAGMENT_HEADER_LEN..];
+        if count == 1 {
+            return Ok(Some(data.to_vec()));
+        }
+        self.expire();
+        let key = (src, id);
+        if self
+            .partial
+            .get(&key)
+            .is_some_and(|p| p.parts.len() != count)
+        {
+            // the id was reused for another message
+            self.partial.remove(&key);
+        }
+        if !self.partial.contains_key(&key) {
+            if self.partial.len() >= self.max_pending.max(1) {
+                let oldest = self
+                    .partial
+                    .iter()
+                    .min_by_key(|(_, p)| p.started)
+                    .map(|(k, _)| *k)
+                    .expect("messages in progress");
+                self.partial.remove(&oldest);
+            }
+            self.partial.insert(
+                key,
+                Partial {
+                    parts: vec![None; count],
+                    missing: count,
+                    started: Instant::now(),
+                },
+            );
+        }
+        let partial = self.partial.get_mut(&key).expect("message in progress");
+        if partial.parts[index].is_none() {
+            partial.parts[index] = Some(data.to_vec());
+            partial.missing -= 1;
+        }
+        if partial.missing > 0 {
+            return Ok(None);
+        }
+        let partial = self.partial.remove(&key).expect("message in progress");
+        Ok(Some(
+            partial.parts.into_iter().flatten().flatten().collect(),
+        ))
+    }
+
+    fn expire(&mut self) {
+        let timeout = self.timeout;
+        self.partial.retain(|_, p| p.started.elapsed() < timeout);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{round, LoRaChannels, ModemError};
+use alloc::format;
+use core::fmt;
+use core::str::FromStr;
+
+const HZ_PER_MHZ: u32 = 1_000_000;
+
+/// Radio frequency, kept in whole Hertz
+///
+/// Unlike an `f32` in MHz, 868.1 MHz stays exactly 868 100 000 Hz, so it compares
+/// exactly and is formatted as `868.1` instead of `868.09998`.
+///
+/// `Display` writes the frequency in MHz without trailing zeros, a precision
+/// gives a fixed number of decimals, e.g. `{:.6}` writes `868.100000`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Frequency(u32);
+
+impl Frequency {
+    pub const fn from_hz(hz: u32) -> Self {
+        Frequency(hz)
+    }
+    pub const fn from_khz(khz: u32) -> Self {
+        Frequency(khz * 1000)
+    }
+    /// Whole megahertz, use `from_khz` or `parse` for fractions.
+    pub const fn from_mhz(mhz: u32) -> Self {
+        Frequency(mhz * HZ_PER_MHZ)
+    }
+    /// Nearest frequency to `mhz` megahertz, for values only available as floats.
+    pub fn from_mhz_f64(mhz: f64) -> Self {
+        Frequency(round(mhz * HZ_PER_MHZ as f64).clamp(0.0, u32::MAX as f64) as u32)
+    }
+    pub const fn hz(self) -> u32 {
+        self.0
+    }
+    /// Frequency in MHz, for calculations and formats requiring floats.
+    pub fn mhz(self) -> f64 {
+        self.0 as f64 / HZ_PER_MHZ as f64
+    }
+    /// The frequency shifted by `hz`, saturating at the ends of the range.
+    pub fn offset(self, hz: i32) -> Self {
+        Frequency((self.0 as i64 + hz as i64).clamp(0, u32::MAX as i64) as u32)
+    }
+}
+
+impl From<LoRaChannels> for Frequency {
+    fn from(channel: LoRaChannels) -> Self {
+        Frequency(channel.frequency_hz())
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mhz = self.0 / HZ_PER_MHZ;
+        let hz = self.0 % HZ_PER_MHZ;
+        match f.precision() {
+            None if hz == 0 => write!(f, "{}", mhz),
+            None => {
+                let decimals = format!("{:06}", hz);
+                write!(f, "{}.{}", mhz, decimals.trim_end_matches('0'))
+            }
+            Some(0) => write!(
+                f,
+                "{}",
+                (self.0 as u64 + HZ_PER_MHZ as u64 / 2) / HZ_PER_MHZ as u64
+            ),
+            Some(precision) if precision >= 6 => {
+                write!(f, "{}.{:06}{:0<width$}", mhz, hz, "", width = precision - 6)
+            }
+            Some(precision) => {
+                // round half up to the requested decimals, carrying into the integer part
+                let scale = 10u64.pow(6 - precision as u32);
+                let scaled = (self.0 as u64 + scale / 2) / scale;
+                let unit = 10u64.pow(precision as u32);
+                write!(
+                    f,
+                    "{}.{:0width$}",
+                    scaled / unit,
+                    scaled % unit,
+                    width = precision
+                )
+            }
+        }
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = ModemError;
+
+    /// Parse a decimal number of MHz like `868.1`, optionally followed by `MHz`.
+    ///
+    /// Decimals beyond whole Hertz are rounded.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ModemError::Parse(format!("invalid frequency {:?}", s));
+        let mut number = s.trim();
+        let unit = number.len().checked_sub(3).and_then(|i| number.get(i..));
+        if unit.is_some_and(|unit| unit.eq_ignore_ascii_case("mhz")) {
+            number = number[..number.len() - 3].trim_end();
+        }
+        let (whole, fraction) = match number.find('.') {
+            Some(dot) => (&number[..dot], &number[dot + 1..]),
+            None => (number, ""),
+        };
+        let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty()) || !digits(whole) || !digits(fraction) {
+            return Err(invalid());
+        }
+        let mut hz: u64 = if whole.is_empty() {
+            0
+        } else {
+            whole
+                .parse::<u64>()
+                .ok()
+                .and_then(|mhz| mhz.checked_mul(HZ_PER_MHZ as u64))
+                .ok_or_else(invalid)?
+        };
+        let mut scale = HZ_PER_MHZ as u64;
+        for (i, digit) in fraction.bytes().map(|b| (b - b'0') as u64).enumerate() {
+            if i == 6 {
+                if digit >= 5 {
+                    hz += 1;
+                }
+                break;
+            }
+            scale /= 10;
+            hz += digit * scale;
+        }
+        if hz > u32::MAX as u64 {
+            return Err(invalid());
+        }
+        Ok(Frequency(hz as u32))
+    }
+}
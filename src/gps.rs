@@ -0,0 +1,117 @@
+use crate::{round, LoraModemDevice, ModemError, Result, TxReport};
+#[cfg(feature = "std")]
+use alloc::format;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Size of an encoded fix appended to packets by `send_with_fix`.
+pub const ENCODED_LEN: usize = 12;
+
+/// Position reported by the GPS receiver of the modem
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpsFix {
+    /// Latitude in degrees, north positive
+    pub lat: f64,
+    /// Longitude in degrees, east positive
+    pub lon: f64,
+    /// Altitude above sea level in meters
+    pub alt: f32,
+    /// Number of satellites in use
+    pub sats: u8,
+    /// Horizontal dilution of precision
+    pub hdop: f32,
+    /// Time of the fix, if known
+    #[cfg(feature = "std")]
+    pub time: Option<SystemTime>,
+}
+
+impl GpsFix {
+    /// Parse a `+GPS lat,lon,alt,sats,hdop[,unix time]` line, `None` for `+GPS NOFIX`.
+    pub fn parse(line: &str) -> Result<Option<GpsFix>> {
+        let fields = line
+            .trim()
+            .strip_prefix("+GPS")
+            .ok_or_else(|| ModemError::Parse("not a GPS response!".into()))?
+            .trim_start_matches(':')
+            .trim();
+        if fields.eq_ignore_ascii_case("nofix") {
+            return Ok(None);
+        }
+        let fields: Vec<&str> = fields.split(',').map(str::trim).collect();
+        if fields.len() < 5 {
+            return Err(ModemError::Parse(
+                "GPS response has unexpected length!".into(),
+            ));
+        }
+        #[cfg(feature = "std")]
+        let time = match fields.get(5) {
+            Some(secs) => Some(
+                UNIX_EPOCH
+                    .checked_add(Duration::from_secs(secs.parse()?))
+                    .ok_or_else(|| ModemError::Parse(format!("GPS time {} out of range", secs)))?,
+            ),
+            None => None,
+        };
+        Ok(Some(GpsFix {
+            lat: fields[0].parse()?,
+            lon: fields[1].parse()?,
+            alt: fields[2].parse()?,
+            sats: fields[3].parse()?,
+            hdop: fields[4].parse()?,
+            #[cfg(feature = "std")]
+            time,
+        }))
+    }
+
+    /// Compact binary form: latitude and longitude in 1e-7 degrees, altitude in
+    /// meters, satellites and hdop in tenths, all big endian. The time is not included.
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[0..4].copy_from_slice(&(round(self.lat * 1e7) as i32).to_be_bytes());
+        out[4..8].copy_from_slice(&(round(self.lon * 1e7) as i32).to_be_bytes());
+        out[8..10].copy_from_slice(&(round(self.alt as f64) as i16).to_be_bytes());
+        out[10] = self.sats;
+        out[11] = round(self.hdop as f64 * 10.0).clamp(0.0, 255.0) as u8;
+        out
+    }
+
+    /// Reverse `encode`.
+    pub fn decode(buf: &[u8]) -> Result<GpsFix> {
+        if buf.len() < ENCODED_LEN {
+            return Err(ModemError::Parse("encoded GPS fix too short!".into()));
+        }
+        let i32_at = |i: usize| i32::from_be_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+        Ok(GpsFix {
+            lat: i32_at(0) as f64 / 1e7,
+            lon: i32_at(4) as f64 / 1e7,
+            alt: i16::from_be_bytes([buf[8], buf[9]]) as f32,
+            sats: buf[10],
+            hdop: buf[11] as f32 / 10.0,
+            #[cfg(feature = "std")]
+            time: None,
+        })
+    }
+}
+
+/// Send `data` followed by the current fix, as needed for trackers.
+///
+/// Fails with `ModemError::ModemReported` if the receiver has no fix yet.
+pub fn send_with_fix<D: LoraModemDevice + ?Sized>(device: &mut D, data: &[u8]) -> Result<TxReport> {
+    let fix = device
+        .gps_fix()?
+        .ok_or_else(|| ModemError::ModemReported("no GPS fix".into()))?;
+    let mut frame = Vec::with_capacity(data.len() + ENCODED_LEN);
+    frame.extend_from_slice(data);
+    frame.extend_from_slice(&fix.encode());
+    device.send_data(frame)
+}
+
+/// Split a payload sent by `send_with_fix` into data and fix.
+pub fn split_fix(payload: &[u8]) -> Result<(&[u8], GpsFix)> {
+    if payload.len() < ENCODED_LEN {
+        return Err(ModemError::Parse("payload carries no GPS fix!".into()));
+    }
+    let (data, fix) = payload.split_at(payload.len() - ENCODED_LEN);
+    Ok((data, GpsFix::decode(fix)?))
+}
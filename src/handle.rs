@@ -0,0 +1,169 @@
+use crate::event::ModemEvent;
+use crate::hopping::ChannelPlan;
+use crate::radio::{HeaderMode, RadioParams};
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, Result, RxPacket, Status,
+    TxReport,
+};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Cloneable handle to one modem, usable from several threads at once.
+///
+/// Every call locks the modem for its duration, so commands of different
+/// threads never interleave on the wire. A thread blocked reading holds the
+/// lock until a packet arrives or the read times out, the read timeout of the
+/// modem should therefore be short when others send through the same handle.
+/// The handle is itself a `LoraModemDevice`; `into_dyn` hides the type of the
+/// modem, e.g. to keep handles to different backends side by side.
+pub struct ModemHandle<T: ?Sized> {
+    device: Arc<Mutex<T>>,
+}
+
+impl<T: LoraModemDevice> ModemHandle<T> {
+    pub fn new(device: T) -> Self {
+        ModemHandle {
+            device: Arc::new(Mutex::new(device)),
+        }
+    }
+}
+
+impl<T: LoraModemDevice + Send + 'static> ModemHandle<T> {
+    /// The same handle with the modem type erased.
+    pub fn into_dyn(self) -> ModemHandle<dyn LoraModemDevice + Send> {
+        ModemHandle {
+            device: self.device,
+        }
+    }
+}
+
+impl<T: LoraModemDevice + ?Sized> ModemHandle<T> {
+    /// Lock the modem for a sequence of calls no other thread may come between.
+    ///
+    /// A thread panicking while holding the lock does not make the modem unusable.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.device.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    /// Run `f` on the locked modem, e.g. to use methods of a concrete backend.
+    pub fn with<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        f(&mut self.lock())
+    }
+    /// Number of handles to this modem.
+    pub fn handles(&self) -> usize {
+        Arc::strong_count(&self.device)
+    }
+}
+
+impl<T: ?Sized> Clone for ModemHandle<T> {
+    fn clone(&self) -> Self {
+        ModemHandle {
+            device: self.device.clone(),
+        }
+    }
+}
+
+impl<T: LoraModemDevice + ?Sized> LoraModemDevice for ModemHandle<T> {
+    fn open(&mut self) -> Result<()> {
+        self.lock().open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.lock().set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.lock().set_frequency_offset(hz)
+    }
+    fn hop_next(&mut self, plan: &mut ChannelPlan) -> Result<Option<Frequency>> {
+        self.lock().hop_next(plan)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.lock().config()
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        self.lock().max_payload()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.lock().set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.lock().set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.lock().get_radio_params()
+    }
+    fn set_sync_word(&mut self, word: u8) -> Result<()> {
+        self.lock().set_sync_word(word)
+    }
+    fn sync_word(&mut self) -> Result<u8> {
+        self.lock().sync_word()
+    }
+    fn set_header_mode(&mut self, mode: HeaderMode) -> Result<()> {
+        self.lock().set_header_mode(mode)
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.lock().set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.lock().tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.lock().capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.lock().set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.lock().ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.lock().gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.lock().telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.lock().at_command(cmd)
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.lock().channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.lock().enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.lock().disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.lock().sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.lock().standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.lock().wake()
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.lock().stats()
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.lock().reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.lock().send_data(data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        self.lock().send_slice(data)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        self.lock().send_batch(frames)
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        self.lock().read_packet()
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.lock().read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.lock().next_event()
+    }
+}
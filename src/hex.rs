@@ -0,0 +1,192 @@
+//! Hex encoding of payloads, as used by the firmware for `AT+TX` and `+RX`.
+//!
+//! `encode` and `decode` produce and accept exactly the modem format, a
+//! `HexFormat` adds case, separators and line breaks for display and logs.
+
+use crate::ModemError;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+const LOWER: &[u8; 16] = b"0123456789abcdef";
+const UPPER: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Error decoding a hex string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexError {
+    /// The input ends in the middle of a byte
+    OddLength { digits: usize },
+    /// A character other than a hex digit at byte offset `position`
+    InvalidDigit { position: usize },
+}
+
+impl fmt::Display for HexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HexError::OddLength { digits } => write!(f, "odd number of hex digits ({})", digits),
+            HexError::InvalidDigit { position } => {
+                write!(f, "invalid hex digit at position {}", position)
+            }
+        }
+    }
+}
+
+impl core::error::Error for HexError {}
+
+impl From<HexError> for ModemError {
+    fn from(e: HexError) -> Self {
+        ModemError::Parse(format!("{}", e))
+    }
+}
+
+/// Lower case hex without separators, the encoding of the modem.
+pub fn encode(buf: &[u8]) -> String {
+    HexFormat::default().encode(buf)
+}
+
+/// Decode hex digits of either case without separators.
+pub fn decode(s: &str) -> Result<Vec<u8>, HexError> {
+    HexFormat::default().decode(s)
+}
+
+// Value of the hex digit `b` of either case.
+fn nibble(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+// Check that `s` is hex in the modem format, returns the number of bytes it encodes.
+pub(crate) fn validate(s: &str) -> Result<usize, HexError> {
+    if let Some(position) = s.bytes().position(|b| nibble(b).is_none()) {
+        return Err(HexError::InvalidDigit { position });
+    }
+    if !s.len().is_multiple_of(2) {
+        return Err(HexError::OddLength { digits: s.len() });
+    }
+    Ok(s.len() / 2)
+}
+
+// Decode `s`, accepted by `validate`, into the start of `out`, which is large enough.
+pub(crate) fn decode_valid(s: &str, out: &mut [u8]) {
+    for (byte, pair) in out.iter_mut().zip(s.as_bytes().chunks_exact(2)) {
+        *byte = nibble(pair[0]).unwrap_or(0) << 4 | nibble(pair[1]).unwrap_or(0);
+    }
+}
+
+/// Layout of hex text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HexFormat {
+    /// Use `A`-`F` instead of `a`-`f`
+    pub uppercase: bool,
+    /// Put between two bytes of a line, e.g. `' '` or `':'`
+    pub separator: Option<char>,
+    /// Start a new line after this many bytes
+    pub bytes_per_line: Option<usize>,
+}
+
+impl HexFormat {
+    /// Encode `buf` in this format.
+    pub fn encode(&self, buf: &[u8]) -> String {
+        let mut out = String::with_capacity(buf.len() * 3);
+        let mut encoder = HexEncoder::new(&mut out, *self);
+        // writing into a String cannot fail
+        let _ = encoder.write(buf);
+        out
+    }
+
+    /// Decode `s`, skipping the separator and line breaks of this format.
+    pub fn decode(&self, s: &str) -> Result<Vec<u8>, HexError> {
+        let mut out = Vec::with_capacity(s.len() / 2);
+        let mut high: Option<u8> = None;
+        for (position, c) in s.char_indices() {
+            if Some(c) == self.separator
+                || (self.bytes_per_line.is_some() && (c == '\n' || c == '\r'))
+            {
+                if high.is_some() {
+                    return Err(HexError::InvalidDigit { position });
+                }
+                continue;
+            }
+            let nibble = c.to_digit(16).ok_or(HexError::InvalidDigit { position })? as u8;
+            match high.take() {
+                Some(h) => out.push(h << 4 | nibble),
+                None => high = Some(nibble),
+            }
+        }
+        if high.is_some() {
+            return Err(HexError::OddLength {
+                digits: out.len() * 2 + 1,
+            });
+        }
+        Ok(out)
+    }
+}
+
+/// Hex encoder writing to `out` as data is fed in, for payloads too large to encode at once.
+pub struct HexEncoder<W: Write> {
+    out: W,
+    format: HexFormat,
+    // bytes written so far
+    count: usize,
+}
+
+impl<W: Write> HexEncoder<W> {
+    pub fn new(out: W, format: HexFormat) -> Self {
+        HexEncoder {
+            out,
+            format,
+            count: 0,
+        }
+    }
+
+    /// Encode the next bytes of the payload.
+    pub fn write(&mut self, buf: &[u8]) -> fmt::Result {
+        let digits = if self.format.uppercase { UPPER } else { LOWER };
+        if self.format.separator.is_none() && self.format.bytes_per_line.is_none() {
+            // the modem format, written in batches instead of digit by digit
+            let mut batch = [0u8; 128];
+            for chunk in buf.chunks(batch.len() / 2) {
+                for (i, &b) in chunk.iter().enumerate() {
+                    batch[2 * i] = digits[(b >> 4) as usize];
+                    batch[2 * i + 1] = digits[(b & 0x0f) as usize];
+                }
+                let text =
+                    core::str::from_utf8(&batch[..2 * chunk.len()]).map_err(|_| fmt::Error)?;
+                self.out.write_str(text)?;
+            }
+            self.count += buf.len();
+            return Ok(());
+        }
+        for &b in buf {
+            if self.count > 0 {
+                match self.format.bytes_per_line {
+                    Some(n) if self.count.is_multiple_of(n) => self.out.write_char('\n')?,
+                    _ => {
+                        if let Some(sep) = self.format.separator {
+                            self.out.write_char(sep)?;
+                        }
+                    }
+                }
+            }
+            self.out.write_char(digits[(b >> 4) as usize] as char)?;
+            self.out.write_char(digits[(b & 0x0f) as usize] as char)?;
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    /// Number of bytes encoded so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
@@ -0,0 +1,141 @@
+use crate::rng::Rng;
+use crate::{Frequency, LoRaChannels, LoraModemDevice, Result, TxReport};
+use std::time::{Duration, Instant};
+
+/// Order in which a `ChannelPlan` visits its frequencies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HopStrategy {
+    /// Cycle through the frequencies in the order they were added
+    Sequential,
+    /// Pick a random frequency, never the current one twice in a row
+    Random,
+}
+
+/// Ordered list of frequencies to hop between.
+#[derive(Debug, Clone)]
+pub struct ChannelPlan {
+    frequencies: Vec<Frequency>,
+    strategy: HopStrategy,
+    current: Option<usize>,
+    rng: Rng,
+}
+
+impl ChannelPlan {
+    pub fn new(strategy: HopStrategy) -> Self {
+        ChannelPlan {
+            frequencies: Vec::new(),
+            strategy,
+            current: None,
+            rng: Rng::from_time(),
+        }
+    }
+    /// Plan hopping over the given predefined channels.
+    pub fn from_channels(channels: &[LoRaChannels], strategy: HopStrategy) -> Self {
+        let mut plan = ChannelPlan::new(strategy);
+        for &channel in channels {
+            plan.add_channel(channel);
+        }
+        plan
+    }
+    /// Append a predefined channel.
+    pub fn add_channel(&mut self, channel: LoRaChannels) {
+        self.add_frequency(channel.frequency());
+    }
+    /// Append a raw frequency.
+    pub fn add_frequency(&mut self, freq: Frequency) {
+        self.frequencies.push(freq);
+    }
+    /// All frequencies of the plan.
+    pub fn frequencies(&self) -> &[Frequency] {
+        &self.frequencies
+    }
+    /// Frequency selected by the last hop.
+    pub fn current(&self) -> Option<Frequency> {
+        self.current.map(|i| self.frequencies[i])
+    }
+    /// Select the next frequency, `None` if the plan is empty.
+    pub fn advance(&mut self) -> Option<Frequency> {
+        let len = self.frequencies.len();
+        if len == 0 {
+            return None;
+        }
+        let next = match (self.strategy, self.current) {
+            (HopStrategy::Sequential, Some(i)) => (i + 1) % len,
+            (HopStrategy::Random, Some(i)) if len > 1 => {
+                (i + 1 + self.rng.below(len as u64 - 1) as usize) % len
+            }
+            (HopStrategy::Random, _) => self.rng.below(len as u64) as usize,
+            (HopStrategy::Sequential, None) => 0,
+        };
+        self.current = Some(next);
+        Some(self.frequencies[next])
+    }
+}
+
+/// When a `HopScheduler` moves on to the next channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HopTrigger {
+    /// After the given number of transmitted packets
+    Packets(usize),
+    /// After the given time on a channel
+    Interval(Duration),
+}
+
+/// Rotates a device through a `ChannelPlan`.
+pub struct HopScheduler {
+    plan: ChannelPlan,
+    trigger: HopTrigger,
+    packets: usize,
+    last_hop: Option<Instant>,
+}
+
+impl HopScheduler {
+    pub fn new(plan: ChannelPlan, trigger: HopTrigger) -> Self {
+        HopScheduler {
+            plan,
+            trigger,
+            packets: 0,
+            last_hop: None,
+        }
+    }
+    /// The channel plan being followed.
+    pub fn plan(&self) -> &ChannelPlan {
+        &self.plan
+    }
+    /// Count a packet transmitted on the current channel.
+    pub fn packet_sent(&mut self) {
+        self.packets += 1;
+    }
+    /// Whether the device should move on to the next channel.
+    pub fn due(&self) -> bool {
+        match (self.last_hop, self.trigger) {
+            (None, _) => true,
+            (Some(_), HopTrigger::Packets(n)) => self.packets >= n,
+            (Some(at), HopTrigger::Interval(d)) => at.elapsed() >= d,
+        }
+    }
+    /// Hop `device` to the next channel if due, returning the new frequency.
+    pub fn poll<D: LoraModemDevice + ?Sized>(
+        &mut self,
+        device: &mut D,
+    ) -> Result<Option<Frequency>> {
+        if !self.due() {
+            return Ok(None);
+        }
+        let freq = device.hop_next(&mut self.plan)?;
+        self.packets = 0;
+        self.last_hop = Some(Instant::now());
+        Ok(freq)
+    }
+    /// Send `data`, hopping beforehand if due.
+    pub fn send<D: LoraModemDevice + ?Sized>(
+        &mut self,
+        device: &mut D,
+        data: Vec<u8>,
+    ) -> Result<TxReport> {
+        self.poll(device)?;
+        let report = device.send_data(data)?;
+        self.packet_sent();
+        Ok(report)
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{LoraModemDevice, ModemError, Result, RxPacket};
+
+/// Iterator over packets received by a device, see `LoraModemDevice::incoming`.
+pub struct Incoming<'a, D: LoraModemDevice + ?Sized> {
+    device: &'a mut D,
+    done: bool,
+}
+
+impl<'a, D: LoraModemDevice + ?Sized> Incoming<'a, D> {
+    pub(crate) fn new(device: &'a mut D) -> Self {
+        Incoming {
+            device,
+            done: false,
+        }
+    }
+}
+
+impl<'a, D: LoraModemDevice + ?Sized> Iterator for Incoming<'a, D> {
+    type Item = Result<RxPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.device.read_packet() {
+                Ok(packet) => return Some(Ok(packet)),
+                Err(ModemError::Timeout) => {}
+                Err(e) if bad_packet(&e) => return Some(Err(e)),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+// Whether `e` rejects a single received packet, later ones may still be fine.
+pub(crate) fn bad_packet(e: &ModemError) -> bool {
+    matches!(
+        e,
+        ModemError::Parse(_)
+            | ModemError::ChecksumMismatch
+            | ModemError::AuthenticationFailed
+            | ModemError::Uncorrectable
+    )
+}
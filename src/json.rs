@@ -0,0 +1,449 @@
+// Minimal JSON values for the socket protocols, no external dependency.
+
+use crate::{hex, Frequency, ModemConfig, RadioState, RxPacket, Status, TxReport};
+use core::convert::TryFrom;
+use std::fmt::{self, Write};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub(crate) fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+    pub(crate) fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+    pub(crate) fn as_i64(&self) -> Option<i64> {
+        self.as_f64()
+            .filter(|n| n.fract() == 0.0 && n.abs() < 9e15)
+            .map(|n| n as i64)
+    }
+    pub(crate) fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+    pub(crate) fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            s: s.as_bytes(),
+            pos: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_ws();
+        if parser.pos != parser.s.len() {
+            return Err(format!("trailing characters at {}", parser.pos));
+        }
+        Ok(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Json {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Json {
+        Json::String(s)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json {
+        Json::Bool(b)
+    }
+}
+
+macro_rules! json_number {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Json {
+            fn from(n: $t) -> Json {
+                Json::Number(n as f64)
+            }
+        })*
+    };
+}
+
+json_number!(i8, i16, i32, i64, u8, u16, u32, u64, usize, f32, f64);
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.is_finite() => write!(f, "{}", n),
+            Json::Number(_) => f.write_str("null"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+// Nesting beyond this is rejected instead of risking the stack.
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        while self.pos < self.s.len() && matches!(self.s[self.pos], b' ' | b'\t' | b'\n' | b'\r') {
+            self.pos += 1;
+        }
+    }
+    fn error(&self, what: &str) -> String {
+        format!("{} at {}", what, self.pos)
+    }
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.s[self.pos..].starts_with(token.as_bytes()) {
+            self.pos += token.len();
+            Ok(())
+        } else {
+            Err(self.error("unexpected token"))
+        }
+    }
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_ws();
+        match self.s.get(self.pos) {
+            None => Err(self.error("unexpected end")),
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') | Some(b'{') => {
+                self.depth += 1;
+                if self.depth > MAX_DEPTH {
+                    return Err(self.error("nested too deeply"));
+                }
+                let value = if self.s[self.pos] == b'[' {
+                    self.array()
+                } else {
+                    self.object()
+                };
+                self.depth -= 1;
+                value
+            }
+            Some(_) => self.number(),
+        }
+    }
+    fn array(&mut self) -> Result<Json, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_ws();
+        if self.s.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_ws();
+            match self.s.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.error("expected , or ]")),
+            }
+        }
+    }
+    fn object(&mut self) -> Result<Json, String> {
+        self.pos += 1;
+        let mut fields = Vec::new();
+        self.skip_ws();
+        if self.s.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_ws();
+            if self.s.get(self.pos) != Some(&b'"') {
+                return Err(self.error("expected key"));
+            }
+            let key = self.string()?;
+            self.skip_ws();
+            if self.s.get(self.pos) != Some(&b':') {
+                return Err(self.error("expected :"));
+            }
+            self.pos += 1;
+            fields.push((key, self.value()?));
+            self.skip_ws();
+            match self.s.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                _ => return Err(self.error("expected , or }")),
+            }
+        }
+    }
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            match self.s.get(self.pos) {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return String::from_utf8(out).map_err(|_| self.error("invalid UTF-8"));
+                }
+                Some(b'\\') => {
+                    let escaped = *self
+                        .s
+                        .get(self.pos + 1)
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 2;
+                    let c = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let digits = self
+                                .s
+                                .get(self.pos..self.pos + 4)
+                                .and_then(|d| std::str::from_utf8(d).ok())
+                                .and_then(|d| u32::from_str_radix(d, 16).ok())
+                                .ok_or_else(|| self.error("invalid escape"))?;
+                            self.pos += 4;
+                            // surrogate pairs are not needed by the protocols
+                            char::from_u32(digits).unwrap_or('\u{fffd}')
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut buf = [0u8; 4];
+                    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                Some(&b) => {
+                    out.push(b);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while self.pos < self.s.len()
+            && matches!(
+                self.s[self.pos],
+                b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+            )
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.s[start..self.pos])
+            .ok()
+            .and_then(|n| n.parse().ok())
+            .filter(|n: &f64| n.is_finite())
+            .map(Json::Number)
+            .ok_or_else(|| self.error("invalid value"))
+    }
+}
+
+// Wire form of a confirmed transmission.
+pub(crate) fn tx_report(report: &TxReport) -> Json {
+    let mut fields = vec![("bytes", report.bytes.into())];
+    if let Some(toa) = report.airtime_estimate {
+        fields.push(("airtime_ms", (toa.as_secs_f64() * 1e3).into()));
+    }
+    Json::object(fields)
+}
+
+pub(crate) fn tx_report_from(value: &Json) -> Option<TxReport> {
+    Some(TxReport {
+        bytes: value.get("bytes")?.as_i64()? as usize,
+        airtime_estimate: value
+            .get("airtime_ms")
+            .and_then(Json::as_f64)
+            .filter(|ms| *ms >= 0.0)
+            .map(|ms| Duration::from_secs_f64(ms / 1e3)),
+        timestamp: SystemTime::now(),
+    })
+}
+
+// Wire form of a received packet.
+pub(crate) fn packet(packet: &RxPacket) -> Json {
+    let mut fields = vec![
+        ("len", packet.data.len().into()),
+        ("data", hex::encode(&packet.data).into()),
+        ("rssi", packet.rssi.into()),
+        ("snr", packet.snr.into()),
+    ];
+    if let Some(freq_error) = packet.freq_error {
+        fields.push(("freq_error", freq_error.into()));
+    }
+    if let Some(timestamp) = packet.modem_timestamp {
+        fields.push(("modem_timestamp", timestamp.into()));
+    }
+    Json::object(fields)
+}
+
+pub(crate) fn packet_from(value: &Json) -> Option<RxPacket> {
+    Some(RxPacket {
+        data: hex::decode(value.get("data")?.as_str()?).ok()?,
+        rssi: value.get("rssi")?.as_i64()? as i16,
+        snr: value.get("snr")?.as_f64()? as f32,
+        received_at: SystemTime::now(),
+        freq_error: value
+            .get("freq_error")
+            .and_then(Json::as_i64)
+            .map(|e| e as i32),
+        modem_timestamp: value
+            .get("modem_timestamp")
+            .and_then(Json::as_i64)
+            .map(|t| t as u64),
+    })
+}
+
+// Wire form of a modem status.
+pub(crate) fn status(status: &Status) -> Json {
+    Json::object(vec![
+        ("version", status.version.as_str().into()),
+        ("mode", (status.config as usize).into()),
+        ("max_pkt_size", status.max_pkt_size.into()),
+        ("frequency", status.frequency.mhz().into()),
+        ("rx_listener", status.rx_listener.into()),
+        ("tx_power", status.tx_power.into()),
+        ("sync_word", status.sync_word.into()),
+        ("frequency_offset", status.frequency_offset.into()),
+        ("ble_enabled", status.ble_enabled.into()),
+        ("ble_connected", status.ble_connected.into()),
+        ("rx_bad", status.rx_bad.into()),
+        ("rx_good", status.rx_good.into()),
+        ("tx_good", status.tx_good.into()),
+        ("rx_dropped", status.rx_dropped.into()),
+        (
+            "radio_state",
+            status.radio_state.map(|s| s.to_string()).into(),
+        ),
+    ])
+}
+
+pub(crate) fn status_from(value: &Json) -> Option<Status> {
+    let count = |key: &str| {
+        value
+            .get(key)
+            .and_then(Json::as_i64)
+            .map_or(0, |n| n as usize)
+    };
+    Some(Status {
+        version: value.get("version")?.as_str()?.to_string(),
+        config: ModemConfig::try_from(value.get("mode")?.as_i64()? as usize).ok()?,
+        max_pkt_size: count("max_pkt_size"),
+        frequency: Frequency::from_mhz_f64(value.get("frequency")?.as_f64()?),
+        rx_listener: value.get("rx_listener")?.as_bool()?,
+        tx_power: value
+            .get("tx_power")
+            .and_then(Json::as_i64)
+            .map(|p| p as i8),
+        sync_word: value
+            .get("sync_word")
+            .and_then(Json::as_i64)
+            .map(|w| w as u8),
+        frequency_offset: value
+            .get("frequency_offset")
+            .and_then(Json::as_i64)
+            .map_or(0, |o| o as i32),
+        ble_enabled: value.get("ble_enabled").and_then(Json::as_bool),
+        ble_connected: value.get("ble_connected").and_then(Json::as_bool),
+        rx_bad: count("rx_bad"),
+        rx_good: count("rx_good"),
+        tx_good: count("tx_good"),
+        rx_dropped: count("rx_dropped"),
+        radio_state: match value.get("radio_state").and_then(Json::as_str) {
+            Some("sleep") => Some(RadioState::Sleep),
+            Some("standby") => Some(RadioState::Standby),
+            Some("active") => Some(RadioState::Active),
+            _ => None,
+        },
+    })
+}
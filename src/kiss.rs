@@ -0,0 +1,251 @@
+use crate::{LoraModemDevice, ModemError, Result};
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Frame delimiter
+pub const FEND: u8 = 0xc0;
+/// Escape byte
+pub const FESC: u8 = 0xdb;
+/// Escaped `FEND`
+pub const TFEND: u8 = 0xdc;
+/// Escaped `FESC`
+pub const TFESC: u8 = 0xdd;
+
+// How long the stream is polled for KISS input before checking the modem.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Command of a KISS frame, the low nibble of its type byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KissCommand {
+    /// Frame to be transmitted or received
+    Data,
+    /// Keyup delay in 10ms units
+    TxDelay,
+    /// CSMA persistence parameter
+    Persistence,
+    /// CSMA slot interval in 10ms units
+    SlotTime,
+    /// Time to hold up the transmitter after the frame, in 10ms units
+    TxTail,
+    /// Full duplex operation if non-zero
+    FullDuplex,
+    /// Hardware specific command
+    SetHardware,
+    /// Leave KISS mode, sent as type byte 0xff
+    Return,
+    /// Unknown command nibble
+    Unknown(u8),
+}
+
+impl KissCommand {
+    fn from_type(ty: u8) -> Self {
+        if ty == 0xff {
+            return KissCommand::Return;
+        }
+        match ty & 0x0f {
+            0 => KissCommand::Data,
+            1 => KissCommand::TxDelay,
+            2 => KissCommand::Persistence,
+            3 => KissCommand::SlotTime,
+            4 => KissCommand::TxTail,
+            5 => KissCommand::FullDuplex,
+            6 => KissCommand::SetHardware,
+            n => KissCommand::Unknown(n),
+        }
+    }
+}
+
+/// A decoded KISS frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KissFrame {
+    /// TNC port from the high nibble of the type byte
+    pub port: u8,
+    /// Command from the low nibble of the type byte
+    pub command: KissCommand,
+    /// Frame payload, unescaped
+    pub data: Vec<u8>,
+}
+
+/// Encode `data` as a KISS data frame for `port`.
+pub fn encode(port: u8, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(data.len() + 4);
+    frame.push(FEND);
+    frame.push((port & 0x0f) << 4);
+    for &b in data {
+        match b {
+            FEND => frame.extend_from_slice(&[FESC, TFEND]),
+            FESC => frame.extend_from_slice(&[FESC, TFESC]),
+            b => frame.push(b),
+        }
+    }
+    frame.push(FEND);
+    frame
+}
+
+/// Incremental decoder turning a KISS byte stream into frames.
+#[derive(Debug, Default)]
+pub struct KissDecoder {
+    buf: Vec<u8>,
+    escaped: bool,
+}
+
+impl KissDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Feed one byte, returns a frame once its closing `FEND` arrived.
+    pub fn push(&mut self, byte: u8) -> Option<KissFrame> {
+        match byte {
+            FEND => {
+                self.escaped = false;
+                if self.buf.is_empty() {
+                    return None;
+                }
+                let ty = self.buf[0];
+                let data = self.buf.split_off(1);
+                self.buf.clear();
+                Some(KissFrame {
+                    port: ty >> 4,
+                    command: KissCommand::from_type(ty),
+                    data,
+                })
+            }
+            FESC => {
+                self.escaped = true;
+                None
+            }
+            b => {
+                let b = match (self.escaped, b) {
+                    (true, TFEND) => FEND,
+                    (true, TFESC) => FESC,
+                    (_, b) => b,
+                };
+                self.escaped = false;
+                self.buf.push(b);
+                None
+            }
+        }
+    }
+}
+
+/// Settings received through KISS TNC commands
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KissParams {
+    /// Keyup delay in 10ms units
+    pub tx_delay: u8,
+    /// CSMA persistence parameter
+    pub persistence: u8,
+    /// CSMA slot interval in 10ms units
+    pub slot_time: u8,
+    /// Transmitter hold time in 10ms units
+    pub tx_tail: u8,
+    /// Full duplex operation requested
+    pub full_duplex: bool,
+}
+
+/// Exposes a modem as a KISS TNC.
+///
+/// Data frames from the client are transmitted as LoRa packets, received packets
+/// are returned as data frames on port 0. Since the modem is polled in between
+/// reads from the client, a short device read timeout keeps transmissions responsive.
+pub struct KissTnc<D: LoraModemDevice> {
+    device: D,
+    params: KissParams,
+}
+
+impl<D: LoraModemDevice> KissTnc<D> {
+    pub fn new(device: D) -> Self {
+        KissTnc {
+            device,
+            params: KissParams::default(),
+        }
+    }
+    /// Settings last sent by a client.
+    pub fn params(&self) -> &KissParams {
+        &self.params
+    }
+    /// Access the wrapped device.
+    pub fn device(&mut self) -> &mut D {
+        &mut self.device
+    }
+    /// Unwrap the device.
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+
+    /// Serve a single client on `stream`, e.g. a pty or socket.
+    ///
+    /// Reads on `stream` have to time out (`TimedOut` or `WouldBlock`) so the modem
+    /// gets polled. Returns when the client closes the stream or leaves KISS mode.
+    pub fn serve<S: Read + Write>(&mut self, stream: &mut S) -> Result<()> {
+        let mut decoder = KissDecoder::new();
+        let mut buf = [0u8; 512];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => {
+                    for &b in &buf[..n] {
+                        if let Some(frame) = decoder.push(b) {
+                            if !self.handle(frame)? {
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+                Err(e)
+                    if e.kind() == io::ErrorKind::TimedOut
+                        || e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+            match self.device.read_packet() {
+                Ok(packet) => stream.write_all(&encode(0, &packet.data))?,
+                Err(ModemError::Timeout) | Err(ModemError::Parse(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Accept clients on `addr` and serve them one after another, never returns on success.
+    pub fn serve_tcp<A: ToSocketAddrs>(&mut self, addr: A) -> Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        for stream in listener.incoming() {
+            let mut stream: TcpStream = stream?;
+            stream.set_read_timeout(Some(POLL_INTERVAL))?;
+            match self.serve(&mut stream) {
+                Ok(()) | Err(ModemError::Io(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    // Apply a frame from the client, returns false once the client left KISS mode.
+    fn handle(&mut self, frame: KissFrame) -> Result<bool> {
+        let value = frame.data.first().copied().unwrap_or(0);
+        match frame.command {
+            KissCommand::Data => {
+                if frame.data.is_empty() {
+                    return Ok(true);
+                }
+                // KISS has no way to report errors, frames the modem refuses are dropped
+                match self.device.send_data(frame.data) {
+                    Ok(_)
+                    | Err(ModemError::InvalidArgument(_))
+                    | Err(ModemError::ModemReported(_))
+                    | Err(ModemError::TxRejected(_))
+                    | Err(ModemError::DutyCycleExceeded { .. }) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            KissCommand::TxDelay => self.params.tx_delay = value,
+            KissCommand::Persistence => self.params.persistence = value,
+            KissCommand::SlotTime => self.params.slot_time = value,
+            KissCommand::TxTail => self.params.tx_tail = value,
+            KissCommand::FullDuplex => self.params.full_duplex = value != 0,
+            KissCommand::Return => return Ok(false),
+            KissCommand::SetHardware | KissCommand::Unknown(_) => {}
+        }
+        Ok(true)
+    }
+}
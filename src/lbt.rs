@@ -0,0 +1,182 @@
+use crate::radio::RadioParams;
+use crate::rng::Rng;
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError, ModemEvent, Result,
+    RxPacket, Status, TxReport,
+};
+use std::thread;
+use std::time::Duration;
+
+/// Listen-before-talk settings of an `LbtModem`
+#[derive(Debug, Clone)]
+pub struct LbtPolicy {
+    /// Channel checks before giving up with `ModemError::ChannelBusy`
+    pub max_attempts: usize,
+    /// Shortest wait after finding the channel busy
+    pub min_backoff: Duration,
+    /// Longest wait after finding the channel busy
+    pub max_backoff: Duration,
+}
+
+impl Default for LbtPolicy {
+    fn default() -> Self {
+        LbtPolicy {
+            max_attempts: 5,
+            min_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Checks the channel with CAD before every transmission and backs off while it is busy.
+///
+/// The wait after a busy check is random between the policy bounds, so nodes
+/// deferring from the same transmission do not collide again. Devices without
+/// CAD support transmit right away. A batch is sent back to back after one check.
+pub struct LbtModem<T: LoraModemDevice> {
+    inner: T,
+    policy: LbtPolicy,
+    rng: Rng,
+    backoffs: usize,
+}
+
+impl<T: LoraModemDevice> LbtModem<T> {
+    pub fn new(inner: T, policy: LbtPolicy) -> Self {
+        LbtModem {
+            inner,
+            policy,
+            rng: Rng::from_time(),
+            backoffs: 0,
+        }
+    }
+    /// Total number of times a transmission was delayed by a busy channel.
+    pub fn backoffs(&self) -> usize {
+        self.backoffs
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn backoff(&mut self) -> Duration {
+        let min = self.policy.min_backoff.as_millis() as u64;
+        let max = (self.policy.max_backoff.as_millis() as u64).max(min);
+        Duration::from_millis(min + self.rng.below(max - min + 1))
+    }
+    // Wait for a clear channel, failing with `ModemError::ChannelBusy` after all attempts.
+    fn listen(&mut self) -> Result<()> {
+        for _ in 0..self.policy.max_attempts {
+            match self.inner.channel_busy() {
+                Ok(true) => {}
+                Ok(false)
+                | Err(ModemError::Unsupported(_))
+                | Err(ModemError::UnsupportedCommand(_)) => return Ok(()),
+                Err(e) => return Err(e),
+            }
+            self.backoffs += 1;
+            let wait = self.backoff();
+            thread::sleep(wait);
+        }
+        Err(ModemError::ChannelBusy)
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for LbtModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        self.inner.max_payload()
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.listen()?;
+        self.inner.send_data(data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        self.listen()?;
+        self.inner.send_slice(data)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        if let Err(e) = self.listen() {
+            return vec![Err(e)];
+        }
+        self.inner.send_batch(frames)
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        self.inner.read_packet()
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
@@ -1,26 +1,369 @@
-use anyhow::{anyhow, Error, Result};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use core::convert::TryFrom;
-//use std::io;
-//use thiserror::Error;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+pub mod addressing;
+#[cfg(feature = "std")]
+pub mod adr;
+#[cfg(feature = "async")]
+pub mod async_modem;
+#[cfg(feature = "std")]
+pub mod beacon;
+#[cfg(feature = "serial")]
+pub mod builder;
+pub mod capabilities;
+#[cfg(feature = "std")]
+pub mod capture;
+pub mod checksum;
+#[cfg(feature = "compress")]
+pub mod compress;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+pub mod decode;
+#[cfg(feature = "std")]
+pub mod downlink;
+#[cfg(feature = "dtn")]
+pub mod dtn;
+#[cfg(feature = "std")]
+pub mod duty_cycle;
+#[cfg(feature = "std")]
+pub mod ebyte;
+pub mod embedded;
+pub mod error;
+pub mod event;
+#[cfg(feature = "fec")]
+pub mod fec;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+#[cfg(feature = "std")]
+pub mod fragment;
+pub mod frequency;
+pub mod gps;
+#[cfg(feature = "std")]
+pub mod handle;
+pub mod hex;
+#[cfg(feature = "std")]
+pub mod hopping;
+pub mod incoming;
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "std")]
+pub mod kiss;
+#[cfg(feature = "std")]
+pub mod lbt;
+mod line;
+#[cfg(feature = "mesh")]
+pub mod mesh;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod mock;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "std")]
+pub mod neighbors;
+#[cfg(feature = "std")]
+pub mod outbox;
+#[cfg(feature = "std")]
+pub mod pool;
+#[cfg(feature = "std")]
+pub mod port;
+#[cfg(feature = "std")]
+pub mod power;
+#[cfg(feature = "serial")]
+pub mod probe;
+#[cfg(feature = "std")]
+pub mod profile;
+pub mod quality;
+#[cfg(feature = "std")]
+pub mod queue;
+pub mod radio;
+#[cfg(feature = "std")]
+pub mod rak;
+#[cfg(feature = "std")]
+pub mod redundancy;
+pub mod region;
+#[cfg(feature = "std")]
+pub mod reliable;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod rf95;
+#[cfg(feature = "std")]
+pub mod rn2xx3;
+#[cfg(feature = "std")]
+mod rng;
+#[cfg(feature = "std")]
+pub mod scan;
+#[cfg(feature = "serial")]
+pub mod serial;
+#[cfg(all(feature = "std", any(unix, feature = "rpc")))]
+pub mod server;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod survey;
+#[cfg(feature = "sx127x")]
+pub mod sx127x;
+#[cfg(feature = "std")]
+pub mod tcp;
+pub mod telemetry;
+#[cfg(feature = "std")]
+pub mod timesync;
+#[cfg(feature = "std")]
+mod toml;
+mod trace;
+#[cfg(feature = "std")]
+pub mod transfer;
+#[cfg(feature = "std")]
+pub mod transport;
+#[cfg(all(feature = "tun", target_os = "linux"))]
+pub mod tun;
+pub mod uart;
+#[cfg(feature = "std")]
+pub mod virtual_modem;
+#[cfg(feature = "std")]
+pub mod wirelog;
+#[cfg(feature = "std")]
+pub mod worker;
+
+pub use addressing::{AddressedModem, AddressedPacket, PingReport};
+#[cfg(feature = "std")]
+pub use adr::{AdrConfig, AdrModem};
+#[cfg(feature = "async")]
+pub use async_modem::{AsyncLoraModemDevice, AsyncModem, PacketStream};
+#[cfg(feature = "std")]
+pub use beacon::{Beacon, BeaconReport};
+#[cfg(feature = "serial")]
+pub use builder::{LoraModem, ModemBuilder};
+pub use capabilities::Capabilities;
+#[cfg(feature = "std")]
+pub use capture::{CaptureModem, CaptureWriter};
+pub use checksum::{Checksum, ChecksumModem};
+#[cfg(feature = "compress")]
+pub use compress::CompressedModem;
+#[cfg(feature = "crypto")]
+pub use crypto::SecureModem;
+#[cfg(feature = "std")]
+pub use downlink::{DownlinkScheduler, ListenSchedule, QueuedFrame};
+#[cfg(feature = "dtn")]
+pub use dtn::{BundleTransport, LoraBundleTransport};
+#[cfg(feature = "std")]
+pub use duty_cycle::{DutyCycleModem, DutyCyclePolicy, DutyCycleTracker, SubBand};
+#[cfg(feature = "std")]
+pub use ebyte::{EbyteModem, EbyteVariant, ModePins};
+pub use embedded::EmbeddedModem;
+pub use error::{ModemError, Result, TxRejection};
+pub use event::ModemEvent;
+#[cfg(feature = "fec")]
+pub use fec::{FecModem, FecStats};
+pub use filter::{FilterReason, FilterStats, RxFilter};
+#[cfg(feature = "std")]
+pub use fragment::Reassembler;
+pub use frequency::Frequency;
+pub use gps::GpsFix;
+#[cfg(feature = "std")]
+pub use handle::ModemHandle;
+#[cfg(feature = "std")]
+pub use hopping::{ChannelPlan, HopScheduler, HopStrategy, HopTrigger};
+pub use incoming::Incoming;
+#[cfg(feature = "std")]
+pub use kiss::KissTnc;
+#[cfg(feature = "std")]
+pub use lbt::{LbtModem, LbtPolicy};
+#[cfg(feature = "mesh")]
+pub use mesh::{MeshConfig, MeshModem, MeshPacket};
+#[cfg(feature = "metrics")]
+pub use metrics::{Metrics, MetricsExporter};
+pub use mock::MockModem;
+#[cfg(feature = "mqtt")]
+pub use mqtt::{MqttBridge, MqttConfig, PayloadFormat};
+#[cfg(feature = "std")]
+pub use neighbors::{Neighbor, NeighborTable};
+#[cfg(feature = "std")]
+pub use outbox::{Outbox, OutboxMessage};
+#[cfg(feature = "std")]
+pub use pool::{ModemPool, PoolPacket, TxStrategy};
+#[cfg(feature = "std")]
+pub use port::{PortHandle, PortMux};
+#[cfg(feature = "std")]
+pub use power::{AutoSleepModem, SleepPolicy};
+#[cfg(feature = "serial")]
+pub use probe::{probe, Firmware, ProbeResult};
+#[cfg(feature = "std")]
+pub use profile::ModemProfile;
+pub use quality::LinkQuality;
+#[cfg(feature = "std")]
+pub use queue::{PacketQueue, Priority, QueueLimits, TxHandle};
+pub use radio::{
+    airtime, Bandwidth, CodingRate, HeaderMode, RadioParams, SYNC_WORD_LORAWAN, SYNC_WORD_PRIVATE,
+};
+#[cfg(feature = "std")]
+pub use rak::RakModem;
+#[cfg(feature = "std")]
+pub use redundancy::{Redundancy, RedundantModem};
+pub use region::{BandRule, Region, RegionModem, RegulatoryViolation};
+#[cfg(feature = "std")]
+pub use reliable::{ArqConfig, Delivery, ReliableModem};
+#[cfg(feature = "std")]
+pub use replay::{RecordingTransport, ReplayModem, ReplayTransport};
+#[cfg(feature = "std")]
+pub use rf95::{LineProtocol, OverflowPolicy, Rf95Modem, Timeouts, WritePacing};
+#[cfg(feature = "std")]
+pub use rn2xx3::Rn2xx3Modem;
+#[cfg(feature = "std")]
+pub use scan::{scan_channels, ChannelReport};
+#[cfg(feature = "serial")]
+pub use serial::{
+    enumerate_modems, serial_ports, DetectedModem, FlowControl, SerialModem, SerialPort,
+    SerialPortInfo,
+};
+#[cfg(all(feature = "std", any(unix, feature = "rpc")))]
+pub use server::{ModemServer, RemoteModem};
+#[cfg(feature = "std")]
+pub use stats::{LinkStats, SignalStats, StatsModem};
+#[cfg(feature = "std")]
+pub use survey::{SampleSource, SiteSurvey, SurveySample};
+#[cfg(feature = "sx127x")]
+pub use sx127x::Sx127xModem;
+#[cfg(feature = "std")]
+pub use tcp::{TcpModem, TcpTransport};
+pub use telemetry::ModemTelemetry;
+#[cfg(feature = "std")]
+pub use timesync::{ClockOffset, SyncedModem};
+#[cfg(feature = "std")]
+pub use transfer::{receive_file, send_file};
+#[cfg(feature = "std")]
+pub use transport::{ReconnectPolicy, Transport};
+#[cfg(all(feature = "tun", target_os = "linux"))]
+pub use tun::{IpTunnel, TunDevice};
+pub use uart::{Uart, UartModem};
+#[cfg(feature = "std")]
+pub use virtual_modem::{LinkModel, VirtualModem};
+#[cfg(feature = "std")]
+pub use wirelog::{WireLog, WireLogTransport};
+#[cfg(feature = "std")]
+pub use worker::ModemWorker;
+
+// Parse a field of a modem output line, errors name the field.
+fn parse_field<T: core::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| ModemError::Parse(format!("invalid {} `{}` in received packet", name, value)))
+}
+
+// Split `key: value` lines of an AT+INFO response, keys are lowercased.
+pub(crate) fn info_fields(lines: &[String]) -> impl Iterator<Item = (String, &str)> {
+    lines.iter().filter_map(|line| {
+        let mut kv = line.splitn(2, ':');
+        let key = kv.next()?.trim().trim_start_matches('+').to_lowercase();
+        let value = kv.next()?.trim();
+        if key.is_empty() || value.is_empty() {
+            return None;
+        }
+        Some((key, value))
+    })
+}
+
+// Numeric prefix of a value followed by a unit or description, e.g. "14 dBm".
+pub(crate) fn leading_number(value: &str) -> &str {
+    let end = value
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && (c == '-' || c == '+'))))
+        .map_or(value.len(), |(i, _)| i);
+    &value[..end]
+}
+
+// Float rounding without the `std` math functions, exact for the magnitudes used here.
+pub(crate) fn round(x: f64) -> f64 {
+    if x < 0.0 {
+        -round(-x)
+    } else {
+        (x + 0.5) as i64 as f64
+    }
+}
+
+pub(crate) fn ceil(x: f64) -> f64 {
+    let t = x as i64 as f64;
+    if t < x {
+        t + 1.0
+    } else {
+        t
+    }
+}
 
-// Convert byte slice into a hex string
-fn hexify(buf: &[u8]) -> String {
-    let mut hexstr = String::new();
-    for &b in buf {
-        hexstr.push_str(&format!("{:02x?}", b));
+// Send `frames` one by one with `send_slice`, as `send_batch` does by default.
+pub(crate) fn send_each<D: LoraModemDevice + ?Sized>(
+    device: &mut D,
+    frames: &[&[u8]],
+) -> Vec<Result<TxReport>> {
+    let mut results = Vec::with_capacity(frames.len());
+    for frame in frames {
+        let result = device.send_slice(frame);
+        let go_on = matches!(
+            result,
+            Ok(_) | Err(ModemError::TxRejected(_)) | Err(ModemError::PayloadTooLarge { .. })
+        );
+        results.push(result);
+        if !go_on {
+            break;
+        }
     }
-    hexstr
+    results
 }
 
-// Convert a hex string into a byte vector
-fn unhexify(s: &str) -> Result<Vec<u8>, core::num::ParseIntError> {
-    (0..s.len())
-        .step_by(2)
-        .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+// Send frames built by a wrapper, each with the bytes it added, as one batch of `device`.
+//
+// The reports count payload bytes without the added ones.
+pub(crate) fn send_framed<D: LoraModemDevice + ?Sized>(
+    device: &mut D,
+    frames: &[(Vec<u8>, usize)],
+) -> Vec<Result<TxReport>> {
+    let sent: Vec<&[u8]> = frames.iter().map(|(frame, _)| frame.as_slice()).collect();
+    device
+        .send_batch(&sent)
+        .into_iter()
+        .zip(frames)
+        .map(|(result, (_, overhead))| {
+            result.map(|report| TxReport {
+                bytes: report.bytes.saturating_sub(*overhead),
+                ..report
+            })
+        })
         .collect()
 }
 
+// Fail with `ModemError::PayloadTooLarge` if `len` bytes exceed `max`.
+pub(crate) fn check_payload(len: usize, max: usize) -> Result<()> {
+    if len > max {
+        Err(ModemError::PayloadTooLarge { max })
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn flag(value: &str) -> bool {
+    matches!(
+        value.to_lowercase().as_str(),
+        "1" | "on" | "yes" | "true" | "enabled"
+    )
+}
+
 /// Predefined LoRa channels and frequencies
+///
+/// The discriminants hold the frequency in units of 10 kHz, use
+/// `frequency_hz` instead of casting. More channel plans may be added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum LoRaChannels {
     // 868MHz EU TTN Channels 1-9
     Ch01_868 = 86810,
@@ -58,54 +401,276 @@ pub enum LoRaChannels {
     Ch12_900 = 91500,
 }
 
+const CHANNELS: &[LoRaChannels] = &[
+    LoRaChannels::Ch01_868,
+    LoRaChannels::Ch02_868,
+    LoRaChannels::Ch03_868,
+    LoRaChannels::Ch04_868,
+    LoRaChannels::Ch05_868,
+    LoRaChannels::Ch06_868,
+    LoRaChannels::Ch07_868,
+    LoRaChannels::Ch08_868,
+    LoRaChannels::Ch09_868,
+    LoRaChannels::Ch10_868,
+    LoRaChannels::Ch11_868,
+    LoRaChannels::Ch12_868,
+    LoRaChannels::Ch13_868,
+    LoRaChannels::Ch14_868,
+    LoRaChannels::Ch15_868,
+    LoRaChannels::Ch16_868,
+    LoRaChannels::Ch17_868,
+    LoRaChannels::Ch00_900,
+    LoRaChannels::Ch01_900,
+    LoRaChannels::Ch02_900,
+    LoRaChannels::Ch03_900,
+    LoRaChannels::Ch04_900,
+    LoRaChannels::Ch05_900,
+    LoRaChannels::Ch06_900,
+    LoRaChannels::Ch07_900,
+    LoRaChannels::Ch08_900,
+    LoRaChannels::Ch09_900,
+    LoRaChannels::Ch10_900,
+    LoRaChannels::Ch11_900,
+    LoRaChannels::Ch12_900,
+];
+
+impl LoRaChannels {
+    /// Center frequency in Hz.
+    pub fn frequency_hz(self) -> u32 {
+        self as u32 * 10_000
+    }
+    /// Center frequency, as taken by `set_frequency`.
+    pub fn frequency(self) -> Frequency {
+        Frequency::from_hz(self.frequency_hz())
+    }
+    /// Predefined channel centered on `hz`.
+    pub fn from_frequency(hz: u32) -> Option<Self> {
+        CHANNELS
+            .iter()
+            .copied()
+            .find(|channel| channel.frequency_hz() == hz)
+    }
+    /// All predefined channels.
+    pub fn iter() -> impl Iterator<Item = LoRaChannels> {
+        CHANNELS.iter().copied()
+    }
+    /// Predefined channels inside the bands of `region`.
+    pub fn iter_region(region: Region) -> impl Iterator<Item = LoRaChannels> {
+        LoRaChannels::iter().filter(move |channel| region.band(channel.frequency()).is_some())
+    }
+}
+
+impl fmt::Display for LoRaChannels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let khz10 = *self as u32;
+        write!(f, "{:?} ({}.{:02} MHz)", self, khz10 / 100, khz10 % 100)
+    }
+}
+
 /// A LoRa packet received from the modem
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RxPacket {
     /// Signal strength
     pub rssi: i16,
-    /// Signal-to-Noise ratio
-    pub snr: i16,
+    /// Signal-to-Noise ratio in dB, fractional on radios reporting quarter dB
+    pub snr: f32,
     /// Received binary data
     pub data: Vec<u8>,
+    /// Time the packet was read from the modem
+    #[cfg(feature = "std")]
+    pub received_at: SystemTime,
+    /// Frequency error of the received signal in Hz, if reported by the firmware
+    pub freq_error: Option<i32>,
+    /// Reception timestamp reported by the firmware (`ts=` field), in milliseconds since modem boot
+    pub modem_timestamp: Option<u64>,
 }
 impl TryFrom<&str> for RxPacket {
-    type Error = anyhow::Error;
+    type Error = ModemError;
 
     fn try_from(item: &str) -> Result<Self> {
-        let item_payload = if &item[0..4] == "+RX " {
-            &item[4..]
-        } else {
-            item
+        RxPacketRef::try_from(item).map(|packet| packet.to_packet())
+    }
+}
+
+/// A `+RX` line parsed in place, the payload is decoded on demand.
+///
+/// Parsing checks the whole line like `RxPacket::try_from` but keeps the hex
+/// digits borrowed, `decode_into` writes the payload into a buffer of the
+/// caller. A receive loop can so reuse one line and one payload buffer for
+/// every packet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RxPacketRef<'a> {
+    /// Signal strength
+    pub rssi: i16,
+    /// Signal-to-Noise ratio in dB
+    pub snr: f32,
+    /// Frequency error of the received signal in Hz, if reported by the firmware
+    pub freq_error: Option<i32>,
+    /// Reception timestamp reported by the firmware, in milliseconds since modem boot
+    pub modem_timestamp: Option<u64>,
+    hex: &'a str,
+}
+
+impl<'a> TryFrom<&'a str> for RxPacketRef<'a> {
+    type Error = ModemError;
+
+    fn try_from(item: &'a str) -> Result<Self> {
+        RxPacketRef::parse(item, HeaderMode::Explicit)
+    }
+}
+
+impl<'a> RxPacketRef<'a> {
+    /// Parse a `+RX` line received with the header mode `header`.
+    ///
+    /// Without a header the radio cannot tell the length, so in implicit mode
+    /// the length field may be empty or 0. The payload has to have the
+    /// configured length.
+    pub fn parse(item: &'a str, header: HeaderMode) -> Result<Self> {
+        let item_payload = item.strip_prefix("+RX ").unwrap_or(item).trim();
+        let mut fields = item_payload.split(',');
+        let mut next = || {
+            fields.next().ok_or_else(|| {
+                ModemError::Parse(format!(
+                    "received packet has {} fields, expected at least 4",
+                    item_payload.split(',').count()
+                ))
+            })
         };
-        let fields: Vec<&str> = item_payload.trim().split(',').collect();
-        if fields.len() != 4 {
-            //return Err(Error::Parsing("output from modem has unexpected length!".into()).into());
-            return Err(anyhow!("output from modem has unexpected length!"));
-        }
-        let len: usize = fields[0].parse().unwrap();
-        let data = unhexify(fields[1]).unwrap();
-        if data.len() != len {
-            //return Err(Error::Parsing("payload length not matching actual payload!".into()).into(),);
-            return Err(anyhow!("payload length not matching actual payload!"));
-        }
-        let rssi: i16 = fields[2].parse().unwrap();
-        let snr: i16 = fields[3].parse().unwrap();
-        /*let recv_time = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();*/
-
-        Ok(RxPacket {
+        let (len, hex, rssi, snr) = (next()?, next()?, next()?, next()?);
+        let len: usize = match header.implicit_len() {
+            Some(implicit) if matches!(len.trim(), "" | "0") => implicit,
+            _ => parse_field("length", len)?,
+        };
+        let hex = hex.trim();
+        let decoded = hex::validate(hex)?;
+        if decoded != len {
+            return Err(ModemError::Parse(format!(
+                "payload length {} not matching actual payload of {} bytes",
+                len, decoded
+            )));
+        }
+        let rssi: i16 = parse_field("rssi", rssi)?;
+        let snr: f32 = parse_field("snr", snr)?;
+        // newer firmware may append the frequency error and optional tagged fields
+        let mut freq_error = None;
+        let mut modem_timestamp = None;
+        for (i, field) in fields.enumerate() {
+            if let Some(ts) = field.trim().strip_prefix("ts=") {
+                modem_timestamp = Some(parse_field("timestamp", ts)?);
+            } else if i == 0 && !field.contains('=') {
+                freq_error = Some(parse_field("frequency error", field)?);
+            }
+        }
+        Ok(RxPacketRef {
             rssi,
             snr,
-            data,
-            //recv_time,
+            freq_error,
+            modem_timestamp,
+            hex,
         })
     }
+    /// Payload length in bytes.
+    pub fn len(&self) -> usize {
+        self.hex.len() / 2
+    }
+    pub fn is_empty(&self) -> bool {
+        self.hex.is_empty()
+    }
+    /// Payload as sent by the firmware, lower or upper case hex.
+    pub fn hex(&self) -> &'a str {
+        self.hex
+    }
+    /// Decode the payload into the start of `buf`, returns its length.
+    ///
+    /// Fails with `ModemError::BufferOverflow` if `buf` is shorter than `len()`.
+    pub fn decode_into(&self, buf: &mut [u8]) -> Result<usize> {
+        let len = self.len();
+        if buf.len() < len {
+            return Err(ModemError::BufferOverflow);
+        }
+        hex::decode_valid(self.hex, &mut buf[..len]);
+        Ok(len)
+    }
+    /// Copy into an owned `RxPacket`, received now.
+    pub fn to_packet(&self) -> RxPacket {
+        let mut data = alloc::vec![0; self.len()];
+        hex::decode_valid(self.hex, &mut data);
+        RxPacket {
+            rssi: self.rssi,
+            snr: self.snr,
+            data,
+            #[cfg(feature = "std")]
+            received_at: SystemTime::now(),
+            freq_error: self.freq_error,
+            modem_timestamp: self.modem_timestamp,
+        }
+    }
+}
+
+impl RxPacket {
+    /// The packet in the `+RX len,hex,rssi,snr[,freq_error][,ts=timestamp]` format of the firmware.
+    ///
+    /// Parsing the line with `RxPacket::try_from` gives back the same packet,
+    /// except for the time it was received.
+    pub fn to_modem_line(&self) -> String {
+        self.to_string()
+    }
+    /// Signal quality of the packet, see `LinkQuality::from`.
+    pub fn quality(&self) -> LinkQuality {
+        LinkQuality::from(self.rssi, self.snr)
+    }
+    /// Estimated link margin in dB, given the spreading factor the packet was sent with.
+    pub fn link_margin(&self, spreading_factor: u8) -> f32 {
+        quality::link_margin(self.snr, spreading_factor)
+    }
+}
+
+impl fmt::Display for RxPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "+RX {},{},{},{}",
+            self.data.len(),
+            hex::encode(&self.data),
+            self.rssi,
+            self.snr
+        )?;
+        if let Some(freq_error) = self.freq_error {
+            write!(f, ",{}", freq_error)?;
+        }
+        if let Some(ts) = self.modem_timestamp {
+            write!(f, ",ts={}", ts)?;
+        }
+        Ok(())
+    }
+}
+
+/// A transmission confirmed by the modem
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TxReport {
+    /// Payload bytes sent, as confirmed by the modem
+    pub bytes: usize,
+    /// Estimated time on air, `None` if the radio settings are unknown
+    pub airtime_estimate: Option<core::time::Duration>,
+    /// Time the confirmation was read from the modem
+    #[cfg(feature = "std")]
+    pub timestamp: SystemTime,
+}
+
+impl TxReport {
+    /// Report `bytes` sent just now, estimating the time on air from `params` if known.
+    pub fn new(bytes: usize, params: Option<RadioParams>) -> Self {
+        TxReport {
+            bytes,
+            airtime_estimate: params.and_then(|params| airtime(bytes, &params).ok()),
+            #[cfg(feature = "std")]
+            timestamp: SystemTime::now(),
+        }
+    }
 }
 
 /// Default LoRa modem configs
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ModemConfig {
     /// Medium Range (Default)
     MediumBw125Cr45Sf128Crc = 0,
@@ -117,8 +682,15 @@ pub enum ModemConfig {
     SlowLongBw125Cr48Sf4096Crc = 3,
 }
 
+impl ModemConfig {
+    /// Time on air of a packet carrying `payload_len` bytes in this mode.
+    pub fn airtime(self, payload_len: usize) -> core::time::Duration {
+        airtime(payload_len, &self.into()).expect("presets are valid settings")
+    }
+}
+
 impl TryFrom<usize> for ModemConfig {
-    type Error = &'static str;
+    type Error = ModemError;
 
     fn try_from(value: usize) -> Result<Self, Self::Error> {
         if value == 0 {
@@ -130,23 +702,33 @@ impl TryFrom<usize> for ModemConfig {
         } else if value == 3 {
             Ok(ModemConfig::SlowLongBw125Cr48Sf4096Crc)
         } else {
-            Err("Unknown modem config code!")
+            Err(ModemError::Parse("Unknown modem config code!".into()))
         }
     }
 }
-/*
-#[derive(Error, Debug)]
-pub enum Error {
-    #[error("IoError: {0}")]
-    InputOutput(#[from] std::io::Error),
-    #[error("could not parse packet: {0}")]
-    Parsing(String),
-    #[error("unknown lora modem error")]
-    Unknown,
-}*/
+/// Power state of the radio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RadioState {
+    /// Lowest power, settings are kept but nothing is received
+    Sleep,
+    /// Oscillator running and ready to transmit quickly, nothing is received
+    Standby,
+    /// Normal operation, listening for packets while receiving is enabled
+    Active,
+}
+
+impl fmt::Display for RadioState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RadioState::Sleep => "sleep",
+            RadioState::Standby => "standby",
+            RadioState::Active => "active",
+        })
+    }
+}
 
 /// Current rf95modem status
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Status {
     /// firmware version running on modem
     pub version: String,
@@ -155,9 +737,19 @@ pub struct Status {
     /// maximum packet size supported
     pub max_pkt_size: usize,
     /// current frequency configured on modem
-    pub frequency: f32,
+    pub frequency: Frequency,
     /// receiving of incoming packets activated
     pub rx_listener: bool,
+    /// transmit power in dBm, if reported by the firmware
+    pub tx_power: Option<i8>,
+    /// sync word, if reported by the firmware
+    pub sync_word: Option<u8>,
+    /// correction in Hz applied to all configured frequencies
+    pub frequency_offset: i32,
+    /// BLE bridge enabled, if the firmware was built with BLE support
+    pub ble_enabled: Option<bool>,
+    /// a BLE client is connected to the bridge, if reported by the firmware
+    pub ble_connected: Option<bool>,
 
     /// number of receive errors
     pub rx_bad: usize,
@@ -165,6 +757,10 @@ pub struct Status {
     pub rx_good: usize,
     /// number of successfully transmitted packets
     pub tx_good: usize,
+    /// number of received packets dropped by the driver because its buffer was full
+    pub rx_dropped: usize,
+    /// power state of the radio, if tracked by the driver
+    pub radio_state: Option<RadioState>,
 }
 
 impl Default for Status {
@@ -174,16 +770,205 @@ impl Default for Status {
 }
 
 impl Status {
+    /// Parse the multi-line `AT+INFO` response.
+    ///
+    /// Fields may appear in any order and keys are matched case-insensitively.
+    /// Unknown lines are ignored, units and descriptions after a value are skipped,
+    /// so output of different firmware versions is understood. Fields missing from
+    /// the output keep their defaults.
+    pub fn parse(lines: &[String]) -> Result<Status> {
+        let mut status = Status::new();
+        for (key, value) in info_fields(lines) {
+            match key.as_str() {
+                "firmware" | "version" => status.version = value.to_string(),
+                "modem config" | "mode" => {
+                    let code: usize = leading_number(value).parse()?;
+                    status.config = ModemConfig::try_from(code)?;
+                }
+                "max pkt size" => status.max_pkt_size = leading_number(value).parse()?,
+                "frequency" => status.frequency = leading_number(value).parse()?,
+                "rx listener" => status.rx_listener = flag(value),
+                "tx power" => status.tx_power = Some(leading_number(value).parse()?),
+                "sync word" => status.sync_word = Some(radio::parse_sync_word(value)?),
+                "ble" => status.ble_enabled = Some(flag(value)),
+                "ble connected" => status.ble_connected = Some(flag(value)),
+                "rx bad" => status.rx_bad = leading_number(value).parse()?,
+                "rx good" => status.rx_good = leading_number(value).parse()?,
+                "tx good" => status.tx_good = leading_number(value).parse()?,
+                _ => {}
+            }
+        }
+        Ok(status)
+    }
+
+    /// Fields differing from `old` to `new`, in field order.
+    ///
+    /// Whether a BLE client is connected is not considered part of the modem state.
+    pub fn diff(old: &Status, new: &Status) -> Vec<StatusChange> {
+        let mut changes = Vec::new();
+        if old.version != new.version {
+            changes.push(StatusChange::Version {
+                old: old.version.clone(),
+                new: new.version.clone(),
+            });
+        }
+        macro_rules! compare {
+            ($($field:ident => $change:ident),*) => {
+                $(if old.$field != new.$field {
+                    changes.push(StatusChange::$change {
+                        old: old.$field,
+                        new: new.$field,
+                    });
+                })*
+            };
+        }
+        compare!(
+            config => Config,
+            max_pkt_size => MaxPktSize,
+            frequency => Frequency,
+            rx_listener => RxListener,
+            tx_power => TxPower,
+            sync_word => SyncWord,
+            frequency_offset => FrequencyOffset,
+            ble_enabled => BleEnabled,
+            radio_state => RadioState
+        );
+        let counters = [
+            ("rx_bad", old.rx_bad, new.rx_bad),
+            ("rx_good", old.rx_good, new.rx_good),
+            ("tx_good", old.tx_good, new.tx_good),
+            ("rx_dropped", old.rx_dropped, new.rx_dropped),
+        ];
+        for (name, old, new) in counters {
+            if old != new {
+                changes.push(StatusChange::Counter { name, old, new });
+            }
+        }
+        changes
+    }
+
     pub fn new() -> Self {
         Status {
             version: "0.0".to_string(),
             config: ModemConfig::MediumBw125Cr45Sf128Crc,
             max_pkt_size: 0,
-            frequency: 0.0,
+            frequency: Frequency::default(),
             rx_listener: false,
+            tx_power: None,
+            sync_word: None,
+            frequency_offset: 0,
+            ble_enabled: None,
+            ble_connected: None,
             rx_bad: 0,
             rx_good: 0,
             tx_good: 0,
+            rx_dropped: 0,
+            radio_state: None,
+        }
+    }
+}
+
+/// A field differing between two `Status` reports, see `Status::diff`
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatusChange {
+    Version {
+        old: String,
+        new: String,
+    },
+    Config {
+        old: ModemConfig,
+        new: ModemConfig,
+    },
+    MaxPktSize {
+        old: usize,
+        new: usize,
+    },
+    Frequency {
+        old: Frequency,
+        new: Frequency,
+    },
+    RxListener {
+        old: bool,
+        new: bool,
+    },
+    TxPower {
+        old: Option<i8>,
+        new: Option<i8>,
+    },
+    SyncWord {
+        old: Option<u8>,
+        new: Option<u8>,
+    },
+    FrequencyOffset {
+        old: i32,
+        new: i32,
+    },
+    BleEnabled {
+        old: Option<bool>,
+        new: Option<bool>,
+    },
+    RadioState {
+        old: Option<RadioState>,
+        new: Option<RadioState>,
+    },
+    /// One of the counters named like the `Status` fields, e.g. `rx_bad`,
+    /// counters going backwards indicate a restart of the firmware
+    Counter {
+        name: &'static str,
+        old: usize,
+        new: usize,
+    },
+}
+
+impl StatusChange {
+    /// Whether a setting changed rather than a counter.
+    pub fn is_config(&self) -> bool {
+        !matches!(self, StatusChange::Counter { .. })
+    }
+}
+
+impl fmt::Display for StatusChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // unset optional fields are shown as "-"
+        fn opt<T: fmt::Display>(value: &Option<T>) -> String {
+            value.as_ref().map_or("-".to_string(), T::to_string)
+        }
+        match self {
+            StatusChange::Version { old, new } => {
+                write!(f, "firmware changed from {} to {}", old, new)
+            }
+            StatusChange::Config { old, new } => {
+                write!(f, "mode changed from {:?} to {:?}", old, new)
+            }
+            StatusChange::MaxPktSize { old, new } => {
+                write!(f, "max packet size changed from {} to {}", old, new)
+            }
+            StatusChange::Frequency { old, new } => {
+                write!(f, "frequency changed from {} MHz to {} MHz", old, new)
+            }
+            StatusChange::RxListener { old, new } => {
+                write!(f, "rx listener changed from {} to {}", old, new)
+            }
+            StatusChange::TxPower { old, new } => {
+                write!(f, "tx power changed from {} to {} dBm", opt(old), opt(new))
+            }
+            StatusChange::SyncWord { old, new } => {
+                let hex =
+                    |word: &Option<u8>| word.map_or("-".to_string(), |w| format!("{:#04x}", w));
+                write!(f, "sync word changed from {} to {}", hex(old), hex(new))
+            }
+            StatusChange::FrequencyOffset { old, new } => {
+                write!(f, "frequency offset changed from {} Hz to {} Hz", old, new)
+            }
+            StatusChange::BleEnabled { old, new } => {
+                write!(f, "BLE changed from {} to {}", opt(old), opt(new))
+            }
+            StatusChange::RadioState { old, new } => {
+                write!(f, "radio changed from {} to {}", opt(old), opt(new))
+            }
+            StatusChange::Counter { name, old, new } => {
+                write!(f, "{} changed from {} to {}", name, old, new)
+            }
         }
     }
 }
@@ -193,19 +978,219 @@ pub trait LoraModemDevice {
     fn open(&mut self) -> Result<()>;
     /// Set channel on rf95modem.
     fn set_channel(&mut self, channel: LoRaChannels) -> Result<()> {
-        let freq = (channel as i32) as f32 / 100.0;
-        self.set_frequency(freq)
+        self.set_frequency(channel.frequency())
     }
     /// Set frequency on rf95modem.
-    fn set_frequency(&mut self, freq: f32) -> Result<()>;
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()>;
+    /// Correct all configured frequencies by `hz` to compensate crystal drift.
+    ///
+    /// A positive offset tunes the radio above the nominal frequency.
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        let _ = hz;
+        Err(ModemError::UnsupportedCommand(
+            "set_frequency_offset".into(),
+        ))
+    }
+    #[cfg(feature = "std")]
+    /// Tune to the next frequency of a channel plan, `None` if the plan is empty.
+    fn hop_next(&mut self, plan: &mut ChannelPlan) -> Result<Option<Frequency>> {
+        match plan.advance() {
+            Some(freq) => {
+                self.set_frequency(freq)?;
+                Ok(Some(freq))
+            }
+            None => Ok(None),
+        }
+    }
     /// Get current configuration of modem firmware.
-    fn config(&mut self) -> Result<Status, Error>;
+    fn config(&mut self) -> Result<Status>;
+    /// Largest payload `send_data` accepts, as reported by `config()`.
+    ///
+    /// Wrappers adding headers report the room left for the payload they are given.
+    fn max_payload(&mut self) -> Result<usize> {
+        Ok(self.config()?.max_pkt_size)
+    }
     /// Set config mode on rf95modem.
     fn set_mode(&mut self, mode: ModemConfig) -> Result<()>;
+    /// Set individual radio parameters.
+    ///
+    /// By default only settings matching one of the predefined modem configs are supported.
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        params.validate()?;
+        match params.preset() {
+            Some(mode) => self.set_mode(mode),
+            None => Err(ModemError::UnsupportedCommand("set_radio_params".into())),
+        }
+    }
+    /// Get current radio parameters.
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        Ok(self.config()?.config.into())
+    }
+    /// Set the sync word, keeping the other settings.
+    ///
+    /// Private networks pick another word than `SYNC_WORD_LORAWAN` so their
+    /// radios ignore LoRaWAN traffic, and the gateways their packets.
+    fn set_sync_word(&mut self, word: u8) -> Result<()> {
+        let params = RadioParams {
+            sync_word: word,
+            ..self.get_radio_params()?
+        };
+        self.set_radio_params(params)
+    }
+    /// Current sync word.
+    fn sync_word(&mut self) -> Result<u8> {
+        Ok(self.get_radio_params()?.sync_word)
+    }
+    /// Switch between explicit and implicit (fixed-length) headers, keeping the other settings.
+    ///
+    /// Both ends have to agree on the mode and, without headers, on the payload length.
+    fn set_header_mode(&mut self, mode: HeaderMode) -> Result<()> {
+        let params = RadioParams {
+            header: mode,
+            ..self.get_radio_params()?
+        };
+        self.set_radio_params(params)
+    }
+    /// Set transmit power in dBm.
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        let _ = dbm;
+        Err(ModemError::UnsupportedCommand("set_tx_power".into()))
+    }
+    /// Get current transmit power in dBm.
+    fn tx_power(&mut self) -> Result<i8> {
+        self.config()?
+            .tx_power
+            .ok_or_else(|| ModemError::UnsupportedCommand("tx_power".into()))
+    }
+    /// Optional features supported by the firmware.
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        Err(ModemError::UnsupportedCommand("capabilities".into()))
+    }
+    /// Enable or disable the BLE bridge of the firmware.
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        let _ = enabled;
+        Err(ModemError::UnsupportedCommand("set_ble".into()))
+    }
+    /// Whether the BLE bridge is enabled.
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.config()?
+            .ble_enabled
+            .ok_or_else(|| ModemError::UnsupportedCommand("ble_enabled".into()))
+    }
+    /// Current position of the GPS receiver, `None` while it has no fix.
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        Err(ModemError::UnsupportedCommand("gps_fix".into()))
+    }
+    /// Supply voltage and chip temperature, as far as the device measures them.
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        Err(ModemError::UnsupportedCommand("telemetry".into()))
+    }
+    /// Send a raw AT command and return all response lines including the final `+OK`.
+    ///
+    /// Allows using firmware commands not covered by this crate. A `+ERROR` response
+    /// fails with `ModemError::ModemReported`.
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        let _ = cmd;
+        Err(ModemError::UnsupportedCommand("at_command".into()))
+    }
+    /// Run a channel activity detection, true if a LoRa transmission is under way.
+    fn channel_busy(&mut self) -> Result<bool> {
+        Err(ModemError::UnsupportedCommand("channel_busy".into()))
+    }
+    /// Start listening for incoming packets (`AT+RX=1`).
+    fn enable_rx(&mut self) -> Result<()> {
+        Err(ModemError::UnsupportedCommand("enable_rx".into()))
+    }
+    /// Stop listening, `read_packet` fails with `ModemError::RxDisabled` until enabled again.
+    fn disable_rx(&mut self) -> Result<()> {
+        Err(ModemError::UnsupportedCommand("disable_rx".into()))
+    }
+    /// Put the radio into its lowest power mode.
+    ///
+    /// Nothing is received until `wake`, `read_packet` fails with `ModemError::RxDisabled`.
+    /// Transmitting wakes the radio.
+    fn sleep(&mut self) -> Result<()> {
+        Err(ModemError::UnsupportedCommand("sleep".into()))
+    }
+    /// Put the radio into standby, ready to transmit with little delay but not receiving.
+    fn standby(&mut self) -> Result<()> {
+        Err(ModemError::UnsupportedCommand("standby".into()))
+    }
+    /// Return from sleep or standby to normal operation.
+    fn wake(&mut self) -> Result<()> {
+        Err(ModemError::UnsupportedCommand("wake".into()))
+    }
+    #[cfg(feature = "std")]
+    /// Traffic statistics, collected by a `StatsModem` at or below this layer.
+    fn stats(&mut self) -> Result<LinkStats> {
+        Err(ModemError::UnsupportedCommand("stats".into()))
+    }
+    #[cfg(feature = "std")]
+    /// Start collecting statistics from scratch.
+    fn reset_stats(&mut self) -> Result<()> {
+        Err(ModemError::UnsupportedCommand("reset_stats".into()))
+    }
     /// Send data via configured serial device.
-    fn send_data(&mut self, data: Vec<u8>) -> Result<usize>;
+    ///
+    /// A transmission refused by the modem fails with `ModemError::TxRejected`.
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport>;
+    /// Send `data` without giving up a buffer, like `send_data`.
+    ///
+    /// Backends encoding the payload as text write it without copying, the
+    /// default copies it into a `Vec` for `send_data`.
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        self.send_data(data.to_vec())
+    }
+    /// Send several frames back to back, returning a result per frame.
+    ///
+    /// Sending goes on after a frame was refused, but stops at any other
+    /// failure, the frames after it have no result. Wrappers pass the batch on
+    /// to the device below, so a `DutyCycleModem` anywhere in the stack reserves
+    /// duty-cycle budget once for the whole batch.
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        send_each(self, frames)
+    }
     /// Read a packet from the modem.
     fn read_packet(&mut self) -> Result<RxPacket>;
     /// Read a raw line from the serial device.
     fn read_line(&mut self) -> Result<String>;
+    /// Read the next line of modem output and classify it.
+    ///
+    /// Unlike `read_packet` nothing is skipped. Wrappers altering payloads report
+    /// packets as received from the device below.
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.read_line().map(ModemEvent::from_line)
+    }
+    /// Iterate over received packets, as read by `read_packet`.
+    ///
+    /// Timeouts are skipped, packets failing to parse or rejected by a wrapper, e.g.
+    /// for a checksum mismatch, are yielded as errors. Iteration ends after any
+    /// other error has been yielded.
+    fn incoming(&mut self) -> Incoming<'_, Self>
+    where
+        Self: Sized,
+    {
+        Incoming::new(self)
+    }
+    /// Pass received packets to `callback` until it returns false.
+    ///
+    /// Packets failing to parse or rejected by a wrapper are skipped, other errors
+    /// end listening.
+    fn listen<F: FnMut(RxPacket) -> bool>(&mut self, mut callback: F) -> Result<()>
+    where
+        Self: Sized,
+    {
+        for packet in self.incoming() {
+            match packet {
+                Ok(packet) => {
+                    if !callback(packet) {
+                        break;
+                    }
+                }
+                Err(e) if incoming::bad_packet(&e) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
 }
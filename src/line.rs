@@ -0,0 +1,52 @@
+use crate::{flag, ModemError, Result};
+use alloc::string::String;
+
+/// Classification of a line of modem output, used to route it to its consumer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LineKind {
+    /// A received packet (`+RX`)
+    Rx,
+    /// Transmission confirmation (`+SENT`)
+    Sent,
+    /// Successful end of a command response (`+OK`)
+    Ok,
+    /// Command failure (`+ERROR` or `+FAIL`)
+    Error,
+    /// Anything else, e.g. parts of a multi-line response
+    Other,
+}
+
+impl LineKind {
+    pub(crate) fn of(line: &str) -> Self {
+        if line.starts_with("+RX ") {
+            LineKind::Rx
+        } else if line.starts_with("+SENT") {
+            LineKind::Sent
+        } else if line.starts_with("+OK") {
+            LineKind::Ok
+        } else if line.starts_with("+ERROR") || line.starts_with("+FAIL") {
+            LineKind::Error
+        } else {
+            LineKind::Other
+        }
+    }
+}
+
+// Extract the byte count from a `+SENT <n> bytes` confirmation.
+pub(crate) fn parse_sent(line: &str) -> Result<usize> {
+    let sent = line
+        .split_whitespace()
+        .nth(1)
+        .ok_or_else(|| ModemError::Parse("modem did not confirm transmission!".into()))?;
+    Ok(sent.parse()?)
+}
+
+// Extract the result of a channel activity detection from `+CAD <1|0|busy|free>`.
+pub(crate) fn parse_cad(lines: &[String]) -> Result<bool> {
+    let line = lines
+        .iter()
+        .find(|line| line.starts_with("+CAD"))
+        .ok_or_else(|| ModemError::Parse("modem did not report channel activity!".into()))?;
+    let value = line["+CAD".len()..].trim_start_matches(':').trim();
+    Ok(value.eq_ignore_ascii_case("busy") || flag(value))
+}
@@ -0,0 +1,283 @@
+use crate::radio::RadioParams;
+use crate::rng::Rng;
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    send_framed, Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError,
+    ModemEvent, Result, RxPacket, Status, TxReport,
+};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Size of the mesh header prepended to every payload.
+pub const MESH_HEADER_LEN: usize = 4;
+
+/// Flooding settings of a `MeshModem`
+#[derive(Debug, Clone)]
+pub struct MeshConfig {
+    /// Hops a message originating here may travel
+    pub ttl: u8,
+    /// Longest random delay before a received message is rebroadcast
+    pub max_delay: Duration,
+    /// Number of message ids remembered to drop duplicates
+    pub cache_size: usize,
+}
+
+impl Default for MeshConfig {
+    fn default() -> Self {
+        MeshConfig {
+            ttl: 3,
+            max_delay: Duration::from_millis(500),
+            cache_size: 64,
+        }
+    }
+}
+
+/// A message received through the mesh
+#[derive(Debug, Clone)]
+pub struct MeshPacket {
+    /// Node the message originates from
+    pub origin: u8,
+    /// Id of the message, unique per origin
+    pub msg_id: u16,
+    /// Hops the message may still travel
+    pub ttl: u8,
+    /// The packet as received from the last hop, `data` holds the payload without header
+    pub packet: RxPacket,
+}
+
+/// Controlled flooding for multi-hop reach in small networks.
+///
+/// Every payload is prefixed with TTL, origin and message id. Messages not seen
+/// before are delivered and, while their TTL allows, rebroadcast after a random
+/// delay so neighbors relaying the same message do not collide. Pending relays
+/// are sent from `read_packet`, `send_data` and `poll`, so their delay is only
+/// kept as accurately as these are called.
+pub struct MeshModem<T: LoraModemDevice> {
+    inner: T,
+    node_id: u8,
+    config: MeshConfig,
+    next_id: u16,
+    seen: VecDeque<(u8, u16)>,
+    relays: VecDeque<(Instant, Vec<u8>)>,
+    rng: Rng,
+    relayed: usize,
+}
+
+impl<T: LoraModemDevice> MeshModem<T> {
+    pub fn new(inner: T, node_id: u8, config: MeshConfig) -> Self {
+        let mut rng = Rng::from_time();
+        let next_id = rng.next_u64() as u16;
+        MeshModem {
+            inner,
+            node_id,
+            config,
+            next_id,
+            seen: VecDeque::new(),
+            relays: VecDeque::new(),
+            rng,
+            relayed: 0,
+        }
+    }
+    /// Id of this node.
+    pub fn node_id(&self) -> u8 {
+        self.node_id
+    }
+    /// Access the wrapped device.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    /// Unwrap the inner device, pending relays are dropped.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+    /// Total number of messages rebroadcast so far.
+    pub fn relayed(&self) -> usize {
+        self.relayed
+    }
+    /// Number of messages waiting to be rebroadcast.
+    pub fn pending(&self) -> usize {
+        self.relays.len()
+    }
+
+    /// Rebroadcast messages whose delay passed, returns number of messages sent.
+    pub fn poll(&mut self) -> Result<usize> {
+        let now = Instant::now();
+        let mut sent = 0;
+        while self.relays.front().is_some_and(|(due, _)| *due <= now) {
+            let (_, frame) = self.relays.pop_front().expect("relay queued");
+            self.inner.send_data(frame)?;
+            self.relayed += 1;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    /// Read the next message not seen before, together with its mesh header.
+    pub fn read_mesh(&mut self) -> Result<MeshPacket> {
+        loop {
+            self.poll()?;
+            let mut packet = self.inner.read_packet()?;
+            if packet.data.len() < MESH_HEADER_LEN {
+                continue;
+            }
+            let ttl = packet.data[0];
+            let origin = packet.data[1];
+            let msg_id = u16::from_be_bytes([packet.data[2], packet.data[3]]);
+            if origin == self.node_id || !self.remember(origin, msg_id) {
+                continue;
+            }
+            if ttl > 1 {
+                let mut frame = packet.data.clone();
+                frame[0] = ttl - 1;
+                let delay = self.rng.below(self.config.max_delay.as_millis() as u64 + 1);
+                let due = Instant::now() + Duration::from_millis(delay);
+                // keep the queue ordered by due time
+                let at = self.relays.partition_point(|(d, _)| *d <= due);
+                self.relays.insert(at, (due, frame));
+            }
+            packet.data.drain(..MESH_HEADER_LEN);
+            return Ok(MeshPacket {
+                origin,
+                msg_id,
+                ttl,
+                packet,
+            });
+        }
+    }
+
+    // Frame `data` as a new message of this node.
+    fn originate(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        if self.config.ttl == 0 {
+            return Err(ModemError::InvalidArgument("mesh TTL of 0".into()));
+        }
+        let msg_id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.remember(self.node_id, msg_id);
+        let mut frame = Vec::with_capacity(MESH_HEADER_LEN + data.len());
+        frame.extend_from_slice(&[self.config.ttl, self.node_id]);
+        frame.extend_from_slice(&msg_id.to_be_bytes());
+        frame.extend_from_slice(data);
+        Ok(frame)
+    }
+    // Add a message to the dedupe cache, false if it was seen before.
+    fn remember(&mut self, origin: u8, msg_id: u16) -> bool {
+        if self.seen.contains(&(origin, msg_id)) {
+            return false;
+        }
+        if self.seen.len() >= self.config.cache_size.max(1) {
+            self.seen.pop_front();
+        }
+        self.seen.push_back((origin, msg_id));
+        true
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for MeshModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        Ok(self.inner.max_payload()?.saturating_sub(MESH_HEADER_LEN))
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.send_slice(&data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        self.poll()?;
+        let frame = self.originate(data)?;
+        let report = self.inner.send_data(frame)?;
+        Ok(TxReport {
+            bytes: report.bytes.saturating_sub(MESH_HEADER_LEN),
+            ..report
+        })
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        if let Err(e) = self.poll() {
+            return vec![Err(e)];
+        }
+        let mut framed = Vec::with_capacity(frames.len());
+        for data in frames {
+            match self.originate(data) {
+                Ok(frame) => framed.push((frame, MESH_HEADER_LEN)),
+                Err(e) => return vec![Err(e)],
+            }
+        }
+        send_framed(&mut self.inner, &framed)
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        Ok(self.read_mesh()?.packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
@@ -0,0 +1,270 @@
+//! Prometheus exporter for link statistics.
+//!
+//! The application keeps the shared `Metrics` up to date, e.g. after every
+//! `step` of its main loop, and `MetricsExporter` serves the latest values in
+//! the Prometheus text format on `GET /metrics`.
+
+use crate::duty_cycle::DutyCycleTracker;
+use crate::stats::{LinkStats, SignalStats};
+use crate::{Frequency, Status};
+use core::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Longest request head read from a client.
+const MAX_REQUEST: usize = 8 * 1024;
+
+/// Values published by a `MetricsExporter`
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    /// Traffic and signal quality collected by a `StatsModem`
+    pub stats: LinkStats,
+    /// Counters of the firmware, from the latest `config()`
+    pub status: Option<Status>,
+    /// Frames waiting for transmission
+    pub queue_depth: Option<usize>,
+    /// Share of the duty-cycle budget used per band, keyed by the band edges
+    pub duty_cycle: Vec<((Frequency, Frequency), f32)>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Take over the statistics of a link.
+    pub fn update_stats(&mut self, stats: &LinkStats) {
+        self.stats = stats.clone();
+    }
+    /// Take over the firmware counters.
+    pub fn update_status(&mut self, status: &Status) {
+        self.status = Some(status.clone());
+    }
+    /// Share of the budget used on the band of `freq`, bands without a limit are skipped.
+    pub fn update_duty_cycle(&mut self, tracker: &mut DutyCycleTracker, freq: Frequency) {
+        let (edges, budget) = match tracker.band(freq) {
+            Some(band) => (
+                (band.min_freq, band.max_freq),
+                tracker.window().mul_f32(band.duty_cycle),
+            ),
+            None => return,
+        };
+        let used = if budget.is_zero() {
+            1.0
+        } else {
+            tracker.used(freq).as_secs_f32() / budget.as_secs_f32()
+        };
+        match self.duty_cycle.iter_mut().find(|(e, _)| *e == edges) {
+            Some((_, share)) => *share = used,
+            None => self.duty_cycle.push((edges, used)),
+        }
+    }
+
+    /// All metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let stats = &self.stats;
+        counter(
+            &mut out,
+            "lora_rx_packets_total",
+            "Packets received",
+            stats.packets_in,
+        );
+        counter(
+            &mut out,
+            "lora_tx_packets_total",
+            "Packets transmitted",
+            stats.packets_out,
+        );
+        counter(
+            &mut out,
+            "lora_rx_bytes_total",
+            "Payload bytes received",
+            stats.bytes_in,
+        );
+        counter(
+            &mut out,
+            "lora_tx_bytes_total",
+            "Payload bytes transmitted",
+            stats.bytes_out,
+        );
+        counter(
+            &mut out,
+            "lora_crc_errors_total",
+            "Packets dropped because of CRC failures",
+            stats.crc_errors as u64,
+        );
+        counter(
+            &mut out,
+            "lora_rx_dropped_total",
+            "Packets dropped because the receive buffer was full",
+            stats.rx_dropped as u64,
+        );
+        counter(
+            &mut out,
+            "lora_retransmits_total",
+            "Retransmissions of the ARQ layer",
+            stats.retransmits as u64,
+        );
+        header(
+            &mut out,
+            "lora_airtime_seconds_total",
+            "Time on air of all transmissions",
+            "counter",
+        );
+        let _ = writeln!(
+            out,
+            "lora_airtime_seconds_total {}",
+            stats.airtime.as_secs_f64()
+        );
+        signal(
+            &mut out,
+            "lora_rssi_dbm",
+            "Signal strength of received packets",
+            &stats.rssi,
+        );
+        signal(
+            &mut out,
+            "lora_snr_db",
+            "Signal-to-Noise ratio of received packets",
+            &stats.snr,
+        );
+        if let Some(status) = &self.status {
+            counter(
+                &mut out,
+                "lora_firmware_rx_good_total",
+                "Packets received as counted by the firmware",
+                status.rx_good as u64,
+            );
+            counter(
+                &mut out,
+                "lora_firmware_rx_bad_total",
+                "Receive errors as counted by the firmware",
+                status.rx_bad as u64,
+            );
+            counter(
+                &mut out,
+                "lora_firmware_tx_good_total",
+                "Packets transmitted as counted by the firmware",
+                status.tx_good as u64,
+            );
+        }
+        if let Some(depth) = self.queue_depth {
+            header(
+                &mut out,
+                "lora_queue_depth",
+                "Frames waiting for transmission",
+                "gauge",
+            );
+            let _ = writeln!(out, "lora_queue_depth {}", depth);
+        }
+        if !self.duty_cycle.is_empty() {
+            header(
+                &mut out,
+                "lora_duty_cycle_used_ratio",
+                "Share of the duty-cycle budget used within the window",
+                "gauge",
+            );
+            for ((min, max), used) in &self.duty_cycle {
+                let _ = writeln!(
+                    out,
+                    "lora_duty_cycle_used_ratio{{band=\"{}-{}\"}} {}",
+                    min, max, used
+                );
+            }
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, help, "counter");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn signal(out: &mut String, name: &str, help: &str, stats: &Option<SignalStats>) {
+    let stats = match stats {
+        Some(stats) => stats,
+        None => return,
+    };
+    header(out, name, help, "gauge");
+    for (stat, value) in [
+        ("min", stats.min),
+        ("max", stats.max),
+        ("avg", stats.avg),
+        ("ema", stats.ema),
+    ] {
+        let _ = writeln!(out, "{}{{stat=\"{}\"}} {}", name, stat, value);
+    }
+}
+
+/// Serves `Metrics` on an HTTP `/metrics` endpoint.
+///
+/// Requests are answered one after another on the thread calling `run`.
+pub struct MetricsExporter {
+    listener: TcpListener,
+    metrics: Arc<Mutex<Metrics>>,
+}
+
+impl MetricsExporter {
+    /// Listen on `addr`, e.g. `0.0.0.0:9873`.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(MetricsExporter {
+            listener: TcpListener::bind(addr)?,
+            metrics: Arc::new(Mutex::new(Metrics::new())),
+        })
+    }
+    /// Address the exporter listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+    /// The published values, shared with the application updating them.
+    pub fn metrics(&self) -> Arc<Mutex<Metrics>> {
+        self.metrics.clone()
+    }
+
+    /// Answer requests until accepting connections fails.
+    pub fn run(self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            // a misbehaving client only loses its own response
+            let _ = self.respond(stream?);
+        }
+        Ok(())
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut head = Vec::new();
+        let mut chunk = [0u8; 1024];
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut chunk)?;
+            if n == 0 || head.len() + n > MAX_REQUEST {
+                break;
+            }
+            head.extend_from_slice(&chunk[..n]);
+        }
+        let head = String::from_utf8_lossy(&head);
+        let mut request = head.split_whitespace();
+        let (status, body) = match (request.next(), request.next()) {
+            (Some("GET"), Some(path)) if path == "/metrics" || path.starts_with("/metrics?") => {
+                ("200 OK", self.metrics.lock().unwrap().render())
+            }
+            (Some("GET"), Some(_)) => ("404 Not Found", "not found\n".to_string()),
+            _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+}
@@ -0,0 +1,266 @@
+use crate::hex;
+use crate::line::LineKind;
+use crate::radio::{validate_tx_power, RadioParams};
+use crate::{
+    check_payload, Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError,
+    ModemTelemetry, RadioState, Result, RxPacket, Status, TxReport,
+};
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Scripted output of a `MockModem`.
+#[derive(Debug)]
+pub enum MockResponse {
+    /// A raw line as printed by the modem
+    Line(String),
+    /// The modem stays silent, reads fail with `ModemError::Timeout`
+    Timeout,
+}
+
+/// Modem double for testing applications without hardware.
+///
+/// Reads are served from a script of responses, every transmitted frame is recorded.
+/// Once the script is exhausted reads fail with `ModemError::Disconnected`.
+#[derive(Debug, Default)]
+pub struct MockModem {
+    responses: VecDeque<MockResponse>,
+    sent: Vec<Vec<u8>>,
+    status: Status,
+    radio: RadioParams,
+    gps: Option<GpsFix>,
+    telemetry: ModemTelemetry,
+    channel_busy: bool,
+    capabilities: Capabilities,
+    open: bool,
+}
+
+impl MockModem {
+    pub fn new() -> Self {
+        MockModem {
+            status: Status {
+                version: "mock".to_string(),
+                max_pkt_size: 251,
+                frequency: Frequency::from_khz(868_100),
+                tx_power: Some(14),
+                rx_listener: true,
+                radio_state: Some(RadioState::Active),
+                ..Status::new()
+            },
+            capabilities: Capabilities::all(),
+            ..Default::default()
+        }
+    }
+    /// Queue a raw line of modem output.
+    pub fn push_line(&mut self, line: &str) {
+        self.responses
+            .push_back(MockResponse::Line(line.to_string()));
+    }
+    /// Queue a received packet in the `+RX` format of the firmware.
+    pub fn push_rx(&mut self, data: &[u8], rssi: i16, snr: f32) {
+        let line = format!("+RX {},{},{},{}", data.len(), hex::encode(data), rssi, snr);
+        self.responses.push_back(MockResponse::Line(line));
+    }
+    /// Queue a read that times out.
+    pub fn push_timeout(&mut self) {
+        self.responses.push_back(MockResponse::Timeout);
+    }
+    /// Number of scripted responses not consumed yet.
+    pub fn remaining(&self) -> usize {
+        self.responses.len()
+    }
+    /// All frames transmitted so far.
+    pub fn sent(&self) -> &[Vec<u8>] {
+        &self.sent
+    }
+    /// Status reported by `config()`, also tracks frequency and mode changes.
+    pub fn status_mut(&mut self) -> &mut Status {
+        &mut self.status
+    }
+
+    /// Position reported by `gps_fix()`.
+    pub fn set_gps_fix(&mut self, fix: Option<GpsFix>) {
+        self.gps = fix;
+    }
+
+    /// Readings reported by `telemetry()`.
+    pub fn set_telemetry(&mut self, telemetry: ModemTelemetry) {
+        self.telemetry = telemetry;
+    }
+
+    /// Result of `channel_busy()`.
+    pub fn set_channel_busy(&mut self, busy: bool) {
+        self.channel_busy = busy;
+    }
+
+    /// Features reported by `capabilities()`, all by default.
+    ///
+    /// Operations needing a missing feature fail with `ModemError::Unsupported`.
+    pub fn set_capabilities(&mut self, caps: Capabilities) {
+        self.capabilities = caps;
+    }
+
+    fn require(&self, cap: Capabilities) -> Result<()> {
+        if self.capabilities.contains(cap) {
+            Ok(())
+        } else {
+            Err(ModemError::Unsupported(cap))
+        }
+    }
+
+    fn check_open(&self) -> Result<()> {
+        if self.open {
+            Ok(())
+        } else {
+            Err(ModemError::NotOpen)
+        }
+    }
+}
+
+impl LoraModemDevice for MockModem {
+    fn open(&mut self) -> Result<()> {
+        self.open = true;
+        Ok(())
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.check_open()?;
+        self.status.frequency = freq;
+        Ok(())
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.check_open()?;
+        self.status.frequency_offset = hz;
+        Ok(())
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.check_open()?;
+        Ok(self.status.clone())
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.check_open()?;
+        self.status.config = mode;
+        self.radio = mode.into();
+        Ok(())
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.check_open()?;
+        params.validate()?;
+        self.require(Capabilities::RADIO_PARAMS)?;
+        self.radio = params;
+        Ok(())
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.check_open()?;
+        Ok(self.radio)
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.check_open()?;
+        validate_tx_power(dbm)?;
+        self.require(Capabilities::TX_POWER)?;
+        self.status.tx_power = Some(dbm);
+        Ok(())
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.check_open()?;
+        Ok(self.capabilities)
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.check_open()?;
+        self.require(Capabilities::BLE)?;
+        self.status.ble_enabled = Some(enabled);
+        Ok(())
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.check_open()?;
+        self.require(Capabilities::GPS)?;
+        Ok(self.gps.clone())
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.check_open()?;
+        self.require(Capabilities::TELEMETRY)?;
+        Ok(self.telemetry)
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.check_open()?;
+        self.require(Capabilities::CAD)?;
+        Ok(self.channel_busy)
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.check_open()?;
+        let _ = cmd;
+        // the response is taken from the script
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line()?;
+            match LineKind::of(&line) {
+                LineKind::Error => return Err(ModemError::ModemReported(line)),
+                LineKind::Ok | LineKind::Sent => {
+                    lines.push(line);
+                    return Ok(lines);
+                }
+                LineKind::Rx | LineKind::Other => lines.push(line),
+            }
+        }
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.check_open()?;
+        self.status.rx_listener = true;
+        Ok(())
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.check_open()?;
+        self.status.rx_listener = false;
+        Ok(())
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.check_open()?;
+        self.require(Capabilities::POWER_MODES)?;
+        self.status.radio_state = Some(RadioState::Sleep);
+        Ok(())
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.check_open()?;
+        self.require(Capabilities::POWER_MODES)?;
+        self.status.radio_state = Some(RadioState::Standby);
+        Ok(())
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.check_open()?;
+        self.status.radio_state = Some(RadioState::Active);
+        Ok(())
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.check_open()?;
+        check_payload(data.len(), self.status.max_pkt_size)?;
+        self.status.radio_state = Some(RadioState::Active);
+        let len = data.len();
+        self.sent.push(data);
+        self.status.tx_good += 1;
+        Ok(TxReport::new(len, Some(self.radio)))
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        let asleep = matches!(
+            self.status.radio_state,
+            Some(RadioState::Sleep) | Some(RadioState::Standby)
+        );
+        if !self.status.rx_listener || asleep {
+            return Err(ModemError::RxDisabled);
+        }
+        loop {
+            let line = self.read_line()?;
+            if line.starts_with("+RX ") {
+                return RxPacket::try_from(line.as_str());
+            }
+        }
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.check_open()?;
+        match self.responses.pop_front() {
+            Some(MockResponse::Line(line)) => Ok(line),
+            Some(MockResponse::Timeout) => Err(ModemError::Timeout),
+            None => Err(ModemError::Disconnected),
+        }
+    }
+}
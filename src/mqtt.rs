@@ -0,0 +1,306 @@
+//! LoRa to MQTT gateway, speaking a minimal subset of MQTT 3.1.1 with QoS 0.
+
+use crate::json;
+use crate::{hex, LoraModemDevice, ModemError, Result, RxPacket};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+/// How received packets are published
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    /// The payload bytes as they are
+    Raw,
+    /// The payload as lower case hex
+    Hex,
+    /// A JSON object with hex payload, length, RSSI and SNR
+    Json,
+}
+
+/// Broker and topics of a `MqttBridge`
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    /// Broker address as `host:port`
+    pub broker: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Topic received packets are published to
+    pub rx_topic: String,
+    /// Topic whose messages are transmitted, as raw payload bytes
+    pub tx_topic: String,
+    pub format: PayloadFormat,
+    /// Interval the broker expects to hear from the bridge
+    pub keep_alive: Duration,
+}
+
+impl MqttConfig {
+    /// Settings for the broker at `broker` using topics `lora/rx` and `lora/tx`.
+    pub fn new(broker: &str) -> Self {
+        MqttConfig {
+            broker: broker.to_string(),
+            client_id: "lora-modem-hal".to_string(),
+            username: None,
+            password: None,
+            rx_topic: "lora/rx".to_string(),
+            tx_topic: "lora/tx".to_string(),
+            format: PayloadFormat::Json,
+            keep_alive: Duration::from_secs(60),
+        }
+    }
+}
+
+/// JSON object published for `packet` in `PayloadFormat::Json`.
+pub fn packet_json(packet: &RxPacket) -> String {
+    json::packet(packet).to_string()
+}
+
+fn protocol_error(msg: &str) -> ModemError {
+    ModemError::Io(io::Error::new(io::ErrorKind::InvalidData, msg.to_string()))
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+// Connection to the broker, reading packets as they become complete.
+struct Connection {
+    stream: TcpStream,
+    buf: Vec<u8>,
+    last_sent: Instant,
+}
+
+impl Connection {
+    fn send(&mut self, kind: u8, body: &[u8]) -> Result<()> {
+        let mut packet = vec![kind];
+        let mut len = body.len();
+        loop {
+            let mut byte = (len % 128) as u8;
+            len /= 128;
+            if len > 0 {
+                byte |= 0x80;
+            }
+            packet.push(byte);
+            if len == 0 {
+                break;
+            }
+        }
+        packet.extend_from_slice(body);
+        self.stream.write_all(&packet)?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    // Next complete packet as type byte and body, `None` if no data arrived in time.
+    fn receive(&mut self) -> Result<Option<(u8, Vec<u8>)>> {
+        loop {
+            if let Some((header_len, body_len)) = self.complete()? {
+                let kind = self.buf[0];
+                let body = self.buf[header_len..header_len + body_len].to_vec();
+                self.buf.drain(..header_len + body_len);
+                return Ok(Some((kind, body)));
+            }
+            let mut chunk = [0u8; 512];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(ModemError::Disconnected),
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    // Length of fixed header and body once a whole packet is buffered.
+    fn complete(&self) -> Result<Option<(usize, usize)>> {
+        let mut len = 0usize;
+        for i in 1..self.buf.len().min(5) {
+            let byte = self.buf[i];
+            len |= ((byte & 0x7f) as usize) << (7 * (i - 1));
+            if byte & 0x80 == 0 {
+                let header_len = i + 1;
+                return Ok((self.buf.len() >= header_len + len).then_some((header_len, len)));
+            }
+        }
+        if self.buf.len() >= 5 {
+            return Err(protocol_error("malformed MQTT remaining length"));
+        }
+        Ok(None)
+    }
+}
+
+/// Publishes received packets to MQTT and transmits messages of a topic.
+///
+/// Every packet read from the device is published to `rx_topic` in the
+/// configured format, messages on `tx_topic` are sent as they are. Reading
+/// from the device is bounded by its timeout, which also sets how quickly
+/// messages to transmit are picked up.
+pub struct MqttBridge<T: LoraModemDevice> {
+    device: T,
+    config: MqttConfig,
+    connection: Option<Connection>,
+    published: usize,
+    transmitted: usize,
+}
+
+impl<T: LoraModemDevice> MqttBridge<T> {
+    pub fn new(device: T, config: MqttConfig) -> Self {
+        MqttBridge {
+            device,
+            config,
+            connection: None,
+            published: 0,
+            transmitted: 0,
+        }
+    }
+    /// Access the bridged device.
+    pub fn device(&mut self) -> &mut T {
+        &mut self.device
+    }
+    /// Packets published so far.
+    pub fn published(&self) -> usize {
+        self.published
+    }
+    /// Messages transmitted so far.
+    pub fn transmitted(&self) -> usize {
+        self.transmitted
+    }
+
+    /// Connect to the broker and subscribe to the transmit topic.
+    pub fn connect(&mut self) -> Result<()> {
+        self.connection = None;
+        let stream = TcpStream::connect(&self.config.broker)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut connection = Connection {
+            stream,
+            buf: Vec::new(),
+            last_sent: Instant::now(),
+        };
+        let mut flags = 0x02; // clean session
+        let mut body = Vec::new();
+        put_str(&mut body, "MQTT");
+        body.push(4);
+        if self.config.username.is_some() {
+            flags |= 0x80;
+        }
+        if self.config.password.is_some() {
+            flags |= 0x40;
+        }
+        body.push(flags);
+        let keep_alive = self.config.keep_alive.as_secs().min(u16::MAX as u64) as u16;
+        body.extend_from_slice(&keep_alive.to_be_bytes());
+        put_str(&mut body, &self.config.client_id);
+        if let Some(username) = &self.config.username {
+            put_str(&mut body, username);
+        }
+        if let Some(password) = &self.config.password {
+            put_str(&mut body, password);
+        }
+        connection.send(0x10, &body)?;
+        match connection.receive()? {
+            Some((0x20, ack)) if ack.len() == 2 && ack[1] == 0 => {}
+            Some((0x20, ack)) if ack.len() == 2 => {
+                return Err(ModemError::Io(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!("MQTT broker refused connection with code {}", ack[1]),
+                )))
+            }
+            _ => return Err(protocol_error("expected MQTT CONNACK")),
+        }
+        let mut body = vec![0, 1];
+        put_str(&mut body, &self.config.tx_topic);
+        body.push(0);
+        connection.send(0x82, &body)?;
+        connection
+            .stream
+            .set_read_timeout(Some(Duration::from_millis(10)))?;
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    /// Publish `payload` to `topic`.
+    pub fn publish(&mut self, topic: &str, payload: &[u8]) -> Result<()> {
+        let connection = self.connection.as_mut().ok_or(ModemError::NotOpen)?;
+        let mut body = Vec::with_capacity(2 + topic.len() + payload.len());
+        put_str(&mut body, topic);
+        body.extend_from_slice(payload);
+        connection.send(0x30, &body)
+    }
+
+    /// Forward one packet from the device, if any, and transmit pending messages.
+    pub fn step(&mut self) -> Result<()> {
+        if self.connection.is_none() {
+            return Err(ModemError::NotOpen);
+        }
+        match self.device.read_packet() {
+            Ok(packet) => {
+                let payload = match self.config.format {
+                    PayloadFormat::Raw => packet.data.clone(),
+                    PayloadFormat::Hex => hex::encode(&packet.data).into_bytes(),
+                    PayloadFormat::Json => packet_json(&packet).into_bytes(),
+                };
+                let topic = self.config.rx_topic.clone();
+                self.publish(&topic, &payload)?;
+                self.published += 1;
+            }
+            Err(ModemError::Timeout) => {}
+            Err(e) => return Err(e),
+        }
+        self.process_incoming()
+    }
+
+    /// Run the bridge until an error occurs, connecting first if needed.
+    pub fn run(&mut self) -> Result<()> {
+        if self.connection.is_none() {
+            self.connect()?;
+        }
+        loop {
+            self.step()?;
+        }
+    }
+
+    fn process_incoming(&mut self) -> Result<()> {
+        let keep_alive = self.config.keep_alive;
+        let connection = self.connection.as_mut().ok_or(ModemError::NotOpen)?;
+        if !keep_alive.is_zero() && connection.last_sent.elapsed() >= keep_alive / 2 {
+            connection.send(0xc0, &[])?;
+        }
+        let mut outgoing = Vec::new();
+        while let Some((kind, body)) = connection.receive()? {
+            if kind & 0xf0 != 0x30 {
+                // CONNACK, SUBACK, PINGRESP carry nothing for the bridge
+                continue;
+            }
+            if body.len() < 2 {
+                return Err(protocol_error("truncated MQTT PUBLISH"));
+            }
+            let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+            let mut start = 2 + topic_len;
+            let qos = (kind >> 1) & 0x03;
+            if qos > 0 {
+                let id = body
+                    .get(start..start + 2)
+                    .ok_or_else(|| protocol_error("truncated MQTT PUBLISH"))?
+                    .to_vec();
+                connection.send(0x40, &id)?;
+                start += 2;
+            }
+            let topic = body
+                .get(2..2 + topic_len)
+                .ok_or_else(|| protocol_error("truncated MQTT PUBLISH"))?;
+            if topic == self.config.tx_topic.as_bytes() {
+                outgoing.push(body[start.min(body.len())..].to_vec());
+            }
+        }
+        for payload in outgoing {
+            self.device.send_data(payload)?;
+            self.transmitted += 1;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::addressing::AddressedPacket;
+use crate::beacon::BeaconReport;
+use crate::RxPacket;
+use std::time::{Duration, Instant};
+
+/// A node overheard recently, with the quality of the link from it
+#[derive(Debug, Clone)]
+pub struct Neighbor {
+    /// Node id of the peer
+    pub node_id: u8,
+    /// Signal strength of the last packet
+    pub rssi: i16,
+    /// Signal-to-Noise ratio of the last packet
+    pub snr: f32,
+    /// Packets received from the peer since it was added
+    pub packets: usize,
+    /// Last beacon received from the peer
+    pub beacon: Option<BeaconReport>,
+    first_seen: Instant,
+    last_seen: Instant,
+}
+
+impl Neighbor {
+    /// Time since the last packet from the peer.
+    pub fn age(&self) -> Duration {
+        self.last_seen.elapsed()
+    }
+    /// Time since the peer was first heard.
+    pub fn known_for(&self) -> Duration {
+        self.first_seen.elapsed()
+    }
+}
+
+/// Peers seen recently, built from received traffic for routing layers.
+///
+/// Entries not heard from within `max_age` are dropped, and once `capacity`
+/// peers are known the one heard from least recently makes room for a new one.
+pub struct NeighborTable {
+    entries: Vec<Neighbor>,
+    max_age: Duration,
+    capacity: usize,
+}
+
+impl NeighborTable {
+    pub fn new(max_age: Duration, capacity: usize) -> Self {
+        NeighborTable {
+            entries: Vec::new(),
+            max_age,
+            capacity,
+        }
+    }
+    /// Record a packet from `node_id`.
+    pub fn observe(&mut self, node_id: u8, packet: &RxPacket) -> &Neighbor {
+        self.evict();
+        let now = Instant::now();
+        let index = match self.entries.iter().position(|n| n.node_id == node_id) {
+            Some(index) => index,
+            None => {
+                if self.entries.len() >= self.capacity.max(1) {
+                    let oldest = self
+                        .entries
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, n)| n.last_seen)
+                        .map(|(i, _)| i)
+                        .expect("table not empty");
+                    self.entries.swap_remove(oldest);
+                }
+                self.entries.push(Neighbor {
+                    node_id,
+                    rssi: packet.rssi,
+                    snr: packet.snr,
+                    packets: 0,
+                    beacon: None,
+                    first_seen: now,
+                    last_seen: now,
+                });
+                self.entries.len() - 1
+            }
+        };
+        let neighbor = &mut self.entries[index];
+        neighbor.rssi = packet.rssi;
+        neighbor.snr = packet.snr;
+        neighbor.packets += 1;
+        neighbor.last_seen = now;
+        neighbor
+    }
+    /// Record a packet received through an `AddressedModem`.
+    pub fn observe_addressed(&mut self, packet: &AddressedPacket) -> &Neighbor {
+        self.observe(packet.header.src, &packet.packet)
+    }
+    /// Record `packet` if it is a beacon, returns the sender.
+    pub fn observe_beacon(&mut self, packet: &RxPacket) -> Option<&Neighbor> {
+        let report = BeaconReport::from_packet(packet).ok()?;
+        let node_id = report.node_id;
+        self.observe(node_id, packet);
+        let neighbor = self.entries.iter_mut().find(|n| n.node_id == node_id)?;
+        neighbor.beacon = Some(report);
+        Some(neighbor)
+    }
+    /// Peers heard from within the maximum age, most recently heard first.
+    pub fn neighbors(&mut self) -> Vec<&Neighbor> {
+        self.evict();
+        let mut neighbors: Vec<&Neighbor> = self.entries.iter().collect();
+        neighbors.sort_by_key(|n| n.age());
+        neighbors
+    }
+    /// The entry of `node_id`, if heard from within the maximum age.
+    pub fn get(&self, node_id: u8) -> Option<&Neighbor> {
+        self.entries
+            .iter()
+            .find(|n| n.node_id == node_id && n.age() <= self.max_age)
+    }
+    /// Forget `node_id`, returns its entry.
+    pub fn remove(&mut self, node_id: u8) -> Option<Neighbor> {
+        let index = self.entries.iter().position(|n| n.node_id == node_id)?;
+        Some(self.entries.swap_remove(index))
+    }
+    /// Drop entries older than the maximum age.
+    pub fn evict(&mut self) {
+        let max_age = self.max_age;
+        self.entries.retain(|n| n.age() <= max_age);
+    }
+    /// Number of entries, including ones not evicted yet.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
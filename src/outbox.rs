@@ -0,0 +1,273 @@
+use crate::beacon::BeaconReport;
+use crate::checksum::crc32;
+use crate::reliable::ReliableModem;
+use crate::{LoraModemDevice, ModemError, Result, RxPacket};
+use std::convert::TryInto;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const RECORD_ADD: u8 = b'A';
+const RECORD_DELETE: u8 = b'D';
+// kind and body length before the body, CRC-32 after it
+const RECORD_OVERHEAD: usize = 9;
+// deleted messages tolerated in the log before it is rewritten
+const COMPACT_AFTER: usize = 64;
+
+/// Message waiting in an `Outbox` for its peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxMessage {
+    /// Identifier of the message within the outbox
+    pub id: u64,
+    /// Node id of the recipient
+    pub peer: u8,
+    pub data: Vec<u8>,
+    /// Time after which the message is dropped undelivered
+    pub expires_at: SystemTime,
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+fn record(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(RECORD_OVERHEAD + body.len());
+    out.push(kind);
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    out.extend_from_slice(body);
+    let crc = crc32(&out);
+    out.extend_from_slice(&crc.to_be_bytes());
+    out
+}
+
+fn add_record(message: &OutboxMessage) -> Vec<u8> {
+    let mut body = Vec::with_capacity(17 + message.data.len());
+    body.extend_from_slice(&message.id.to_be_bytes());
+    body.push(message.peer);
+    body.extend_from_slice(&unix_secs(message.expires_at).to_be_bytes());
+    body.extend_from_slice(&message.data);
+    record(RECORD_ADD, &body)
+}
+
+fn be64(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes(bytes[..8].try_into().unwrap_or_default())
+}
+
+/// Store-and-forward queue for peers that are only reachable now and then.
+///
+/// Messages are kept in an append-only log file, so they survive restarts
+/// until delivered or expired. When a beacon of a peer shows it is back in
+/// range, `on_beacon` hands its messages to the ARQ layer, oldest first, and
+/// stops at the first one not acknowledged. A log cut short by a crash is
+/// truncated to its last complete record when opened.
+pub struct Outbox {
+    path: PathBuf,
+    file: File,
+    messages: Vec<OutboxMessage>,
+    next_id: u64,
+    capacity: usize,
+    ttl: Duration,
+    // deleted messages still in the log
+    dead: usize,
+}
+
+impl Outbox {
+    /// Open the log at `path`, creating it if missing.
+    ///
+    /// At most `capacity` messages are held, each for `ttl` unless given otherwise.
+    pub fn open<P: AsRef<Path>>(path: P, capacity: usize, ttl: Duration) -> Result<Outbox> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let mut log = Vec::new();
+        file.read_to_end(&mut log)?;
+        let mut messages: Vec<OutboxMessage> = Vec::new();
+        let mut next_id = 0;
+        let mut dead = 0;
+        let mut pos = 0;
+        while let Some(header) = log.get(pos..pos + 5) {
+            let len = u32::from_be_bytes(header[1..5].try_into().unwrap_or_default()) as usize;
+            let end = pos + RECORD_OVERHEAD + len;
+            let rec = match log.get(pos..end) {
+                Some(rec) => rec,
+                None => break,
+            };
+            let (content, crc) = rec.split_at(rec.len() - 4);
+            if crc32(content) != u32::from_be_bytes(crc.try_into().unwrap_or_default()) {
+                break;
+            }
+            let body = &content[5..];
+            match (content[0], body.len()) {
+                (RECORD_ADD, n) if n >= 17 => {
+                    let id = be64(body);
+                    next_id = next_id.max(id + 1);
+                    messages.push(OutboxMessage {
+                        id,
+                        peer: body[8],
+                        data: body[17..].to_vec(),
+                        expires_at: UNIX_EPOCH + Duration::from_secs(be64(&body[9..])),
+                    });
+                }
+                (RECORD_DELETE, 8) => {
+                    let id = be64(body);
+                    messages.retain(|m| m.id != id);
+                    dead += 1;
+                }
+                _ => break,
+            }
+            pos = end;
+        }
+        if pos < log.len() {
+            // incomplete record of an interrupted write
+            file.set_len(pos as u64)?;
+        }
+        let mut outbox = Outbox {
+            path,
+            file,
+            messages,
+            next_id,
+            capacity,
+            ttl,
+            dead,
+        };
+        outbox.expire()?;
+        Ok(outbox)
+    }
+
+    fn append(&mut self, record: &[u8]) -> Result<()> {
+        self.file.write_all(record)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Queue `data` for `peer`, kept for the default time to live.
+    pub fn push(&mut self, peer: u8, data: &[u8]) -> Result<u64> {
+        self.push_with_ttl(peer, data, self.ttl)
+    }
+    /// Queue `data` for `peer`, dropped undelivered after `ttl`.
+    ///
+    /// Fails with `ModemError::QueueFull` while `capacity` messages are waiting.
+    pub fn push_with_ttl(&mut self, peer: u8, data: &[u8], ttl: Duration) -> Result<u64> {
+        self.expire()?;
+        if self.messages.len() >= self.capacity {
+            return Err(ModemError::QueueFull);
+        }
+        let message = OutboxMessage {
+            id: self.next_id,
+            peer,
+            data: data.to_vec(),
+            expires_at: SystemTime::now() + ttl,
+        };
+        self.append(&add_record(&message))?;
+        self.next_id += 1;
+        self.messages.push(message);
+        Ok(self.next_id - 1)
+    }
+
+    /// All waiting messages, oldest first.
+    pub fn messages(&self) -> &[OutboxMessage] {
+        &self.messages
+    }
+    /// Messages waiting for `peer`, oldest first.
+    pub fn pending(&self, peer: u8) -> impl Iterator<Item = &OutboxMessage> {
+        self.messages.iter().filter(move |m| m.peer == peer)
+    }
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Drop message `id`, e.g. once delivered, returns whether it was waiting.
+    pub fn remove(&mut self, id: u64) -> Result<bool> {
+        let index = match self.messages.iter().position(|m| m.id == id) {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        self.append(&record(RECORD_DELETE, &id.to_be_bytes()))?;
+        self.messages.remove(index);
+        self.dead += 1;
+        if self.dead >= COMPACT_AFTER && self.dead > self.messages.len() {
+            self.compact()?;
+        }
+        Ok(true)
+    }
+    /// Drop messages past their time to live, returns how many.
+    pub fn expire(&mut self) -> Result<usize> {
+        let now = SystemTime::now();
+        let expired: Vec<u64> = self
+            .messages
+            .iter()
+            .filter(|m| m.expires_at <= now)
+            .map(|m| m.id)
+            .collect();
+        for &id in &expired {
+            self.remove(id)?;
+        }
+        Ok(expired.len())
+    }
+    /// Rewrite the log with only the waiting messages.
+    pub fn compact(&mut self) -> Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let mut log = Vec::new();
+        for message in &self.messages {
+            log.extend_from_slice(&add_record(message));
+        }
+        {
+            let mut file = File::create(&tmp)?;
+            file.write_all(&log)?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.dead = 0;
+        Ok(())
+    }
+
+    /// Deliver the messages waiting for `peer`, returns how many were acknowledged.
+    ///
+    /// Stops at the first message not acknowledged, it stays queued with the rest.
+    pub fn deliver<T: LoraModemDevice>(
+        &mut self,
+        modem: &mut ReliableModem<T>,
+        peer: u8,
+    ) -> Result<usize> {
+        self.expire()?;
+        let waiting: Vec<(u64, Vec<u8>)> =
+            self.pending(peer).map(|m| (m.id, m.data.clone())).collect();
+        let mut delivered = 0;
+        for (id, data) in waiting {
+            match modem.send_reliable(peer, &data) {
+                Ok(_) => {
+                    self.remove(id)?;
+                    delivered += 1;
+                }
+                Err(ModemError::NotAcknowledged { .. }) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(delivered)
+    }
+    /// Deliver the messages for the sender of `packet` if it is a beacon,
+    /// returns how many were acknowledged.
+    pub fn on_beacon<T: LoraModemDevice>(
+        &mut self,
+        modem: &mut ReliableModem<T>,
+        packet: &RxPacket,
+    ) -> Result<usize> {
+        match BeaconReport::from_packet(packet) {
+            Ok(report) if self.pending(report.node_id).next().is_some() => {
+                self.deliver(modem, report.node_id)
+            }
+            _ => Ok(0),
+        }
+    }
+}
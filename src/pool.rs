@@ -0,0 +1,216 @@
+use crate::radio::{airtime, RadioParams};
+use crate::stats::LinkStats;
+use crate::{Frequency, LoraModemDevice, ModemError, Result, RxPacket, TxReport};
+
+/// How a `ModemPool` picks the modem for `send`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStrategy {
+    /// Take turns in the order the modems were added
+    RoundRobin,
+    /// The modem with the least time on air so far
+    LeastAirtime,
+}
+
+/// Packet received by a member of a `ModemPool`
+#[derive(Debug, Clone)]
+pub struct PoolPacket {
+    /// Index of the modem that received the packet
+    pub modem: usize,
+    /// Frequency that modem was tuned to, if known
+    pub frequency: Option<Frequency>,
+    pub packet: RxPacket,
+}
+
+struct Member {
+    device: Box<dyn LoraModemDevice>,
+    frequency: Option<Frequency>,
+    params: Option<RadioParams>,
+    stats: LinkStats,
+}
+
+/// Several modems operated together, e.g. one per channel of a listening post.
+///
+/// Modems are addressed by the index `add` returned. Reads visit the modems in
+/// turn, so their receive timeouts should be short. Transmissions either follow
+/// the `TxStrategy` or are pinned to a modem or to the modem tuned to a frequency.
+/// Traffic is counted per modem and in total.
+pub struct ModemPool {
+    members: Vec<Member>,
+    strategy: TxStrategy,
+    next_tx: usize,
+    next_rx: usize,
+}
+
+impl ModemPool {
+    pub fn new(strategy: TxStrategy) -> Self {
+        ModemPool {
+            members: Vec::new(),
+            strategy,
+            next_tx: 0,
+            next_rx: 0,
+        }
+    }
+    /// Add an opened device, returns its index.
+    ///
+    /// The frequency it is tuned to is queried once, use `set_frequency` of
+    /// the pool to retune it.
+    pub fn add<D: LoraModemDevice + 'static>(&mut self, mut device: D) -> usize {
+        let frequency = device.config().ok().map(|status| status.frequency);
+        self.members.push(Member {
+            device: Box::new(device),
+            frequency,
+            params: None,
+            stats: LinkStats::new(),
+        });
+        self.members.len() - 1
+    }
+    /// Number of modems in the pool.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+    /// The modem at `index`, for settings the pool does not track.
+    pub fn modem(&mut self, index: usize) -> Option<&mut dyn LoraModemDevice> {
+        match self.members.get_mut(index) {
+            Some(member) => Some(member.device.as_mut()),
+            None => None,
+        }
+    }
+    /// Frequency modem `index` is tuned to, if known.
+    pub fn frequency(&self, index: usize) -> Option<Frequency> {
+        self.members.get(index).and_then(|member| member.frequency)
+    }
+    /// Tune modem `index` to `freq`.
+    pub fn set_frequency(&mut self, index: usize, freq: Frequency) -> Result<()> {
+        let member = self.member(index)?;
+        member.device.set_frequency(freq)?;
+        member.frequency = Some(freq);
+        Ok(())
+    }
+    /// Set the radio settings of modem `index`.
+    pub fn set_radio_params(&mut self, index: usize, params: RadioParams) -> Result<()> {
+        let member = self.member(index)?;
+        member.device.set_radio_params(params)?;
+        member.params = Some(params);
+        Ok(())
+    }
+    /// Enable receiving on all modems.
+    pub fn enable_rx(&mut self) -> Result<()> {
+        for member in &mut self.members {
+            member.device.enable_rx()?;
+        }
+        Ok(())
+    }
+
+    /// Transmit `data` on the modem picked by the strategy, returns its index with the report.
+    pub fn send(&mut self, data: Vec<u8>) -> Result<(usize, TxReport)> {
+        if self.members.is_empty() {
+            return Err(ModemError::InvalidArgument("modem pool is empty".into()));
+        }
+        let index = match self.strategy {
+            TxStrategy::RoundRobin => {
+                let index = self.next_tx % self.members.len();
+                self.next_tx = index + 1;
+                index
+            }
+            TxStrategy::LeastAirtime => (0..self.members.len())
+                .min_by_key(|&i| self.members[i].stats.airtime)
+                .unwrap_or(0),
+        };
+        self.send_via(index, data).map(|report| (index, report))
+    }
+    /// Transmit `data` on the first modem tuned to `freq`.
+    pub fn send_on(&mut self, freq: Frequency, data: Vec<u8>) -> Result<(usize, TxReport)> {
+        let index = self
+            .members
+            .iter()
+            .position(|member| member.frequency == Some(freq))
+            .ok_or_else(|| {
+                ModemError::InvalidArgument(format!("no modem in the pool tuned to {} MHz", freq))
+            })?;
+        self.send_via(index, data).map(|report| (index, report))
+    }
+    /// Transmit `data` on modem `index`.
+    pub fn send_via(&mut self, index: usize, data: Vec<u8>) -> Result<TxReport> {
+        let member = self.member(index)?;
+        let len = data.len();
+        let report = member.device.send_data(data)?;
+        let toa = match report.airtime_estimate {
+            Some(toa) => toa,
+            None => {
+                if member.params.is_none() {
+                    member.params = member.device.get_radio_params().ok();
+                }
+                member
+                    .params
+                    .and_then(|params| airtime(len, &params).ok())
+                    .unwrap_or_default()
+            }
+        };
+        member.stats.record_tx(report.bytes, toa);
+        Ok(report)
+    }
+
+    /// Next packet received by any modem, asking one modem after the other.
+    ///
+    /// Fails with `ModemError::Timeout` once every modem timed out. Any other
+    /// error of a modem is returned right away, the next call continues with
+    /// the modem after it.
+    pub fn read_packet(&mut self) -> Result<PoolPacket> {
+        for _ in 0..self.members.len() {
+            let index = self.next_rx % self.members.len();
+            self.next_rx = index + 1;
+            let member = &mut self.members[index];
+            match member.device.read_packet() {
+                Ok(packet) => {
+                    member.stats.record_rx(&packet);
+                    return Ok(PoolPacket {
+                        modem: index,
+                        frequency: member.frequency,
+                        packet,
+                    });
+                }
+                Err(ModemError::Timeout) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Err(ModemError::Timeout)
+    }
+
+    /// Traffic of modem `index` since it was added or the stats were reset.
+    pub fn stats(&self, index: usize) -> Option<&LinkStats> {
+        self.members.get(index).map(|member| &member.stats)
+    }
+    /// Traffic of all modems together.
+    pub fn total_stats(&self) -> LinkStats {
+        let mut total = match self.members.first() {
+            Some(member) => member.stats.clone(),
+            None => LinkStats::new(),
+        };
+        for member in self.members.iter().skip(1) {
+            total.merge(&member.stats);
+        }
+        total
+    }
+    /// Start counting traffic from scratch on all modems.
+    pub fn reset_stats(&mut self) {
+        for member in &mut self.members {
+            member.stats = LinkStats::new();
+        }
+    }
+    /// Unwrap the modems in the order they were added.
+    pub fn into_inner(self) -> Vec<Box<dyn LoraModemDevice>> {
+        self.members
+            .into_iter()
+            .map(|member| member.device)
+            .collect()
+    }
+
+    fn member(&mut self, index: usize) -> Result<&mut Member> {
+        self.members
+            .get_mut(index)
+            .ok_or_else(|| ModemError::InvalidArgument(format!("no modem {} in the pool", index)))
+    }
+}
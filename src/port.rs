@@ -0,0 +1,169 @@
+use crate::{LoraModemDevice, ModemError, Result, RxPacket, TxReport};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Size of the port byte prepended to every payload.
+pub const PORT_LEN: usize = 1;
+
+struct Shared<T> {
+    modem: T,
+    queues: HashMap<u8, VecDeque<RxPacket>>,
+    limit: usize,
+    // frames for ports nobody opened, or pushed out of a full queue
+    dropped: usize,
+}
+
+impl<T: LoraModemDevice> Shared<T> {
+    // Read one frame from the modem and queue it for its port, returns the port.
+    fn dispatch(&mut self) -> Result<Option<u8>> {
+        let mut packet = self.modem.read_packet()?;
+        if packet.data.is_empty() {
+            self.dropped += 1;
+            return Ok(None);
+        }
+        let port = packet.data.remove(0);
+        match self.queues.get_mut(&port) {
+            Some(queue) => {
+                if queue.len() >= self.limit {
+                    queue.pop_front();
+                    self.dropped += 1;
+                }
+                queue.push_back(packet);
+                Ok(Some(port))
+            }
+            None => {
+                self.dropped += 1;
+                Ok(None)
+            }
+        }
+    }
+}
+
+// Lock the shared state, a thread panicking while holding it does not make it unusable.
+fn lock<T>(shared: &Mutex<Shared<T>>) -> MutexGuard<'_, Shared<T>> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Shares one link between applications, like UDP ports.
+///
+/// Every payload carries a port byte in front, `open_port` hands out a
+/// `PortHandle` sending on and receiving from one port, with its own receive
+/// queue. Whichever handle reads from the modem queues the frames of the other
+/// ports, frames for ports nobody opened are dropped. Handles can be moved to
+/// other threads, the modem is locked while one of them reads or sends. A thread
+/// panicking meanwhile does not make the other handles unusable.
+pub struct PortMux<T: LoraModemDevice> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T: LoraModemDevice> PortMux<T> {
+    pub fn new(modem: T) -> Self {
+        PortMux {
+            shared: Arc::new(Mutex::new(Shared {
+                modem,
+                queues: HashMap::new(),
+                limit: 32,
+                dropped: 0,
+            })),
+        }
+    }
+    fn lock(&self) -> MutexGuard<'_, Shared<T>> {
+        lock(&self.shared)
+    }
+    /// Frames kept per port until received, 32 by default; the oldest is dropped beyond.
+    pub fn set_queue_limit(&self, frames: usize) {
+        self.lock().limit = frames.max(1);
+    }
+    /// Frames dropped for ports not open or with a full queue.
+    pub fn dropped(&self) -> usize {
+        self.lock().dropped
+    }
+    /// Ports currently open.
+    pub fn ports(&self) -> Vec<u8> {
+        let mut ports: Vec<u8> = self.lock().queues.keys().copied().collect();
+        ports.sort_unstable();
+        ports
+    }
+    /// Claim `port`, failing with `ModemError::InvalidArgument` while it is open.
+    pub fn open_port(&self, port: u8) -> Result<PortHandle<T>> {
+        let mut shared = self.lock();
+        if shared.queues.contains_key(&port) {
+            return Err(ModemError::InvalidArgument(format!(
+                "port {} is already open",
+                port
+            )));
+        }
+        shared.queues.insert(port, VecDeque::new());
+        Ok(PortHandle {
+            port,
+            shared: self.shared.clone(),
+        })
+    }
+    /// Read one frame from the modem and queue it for its port.
+    ///
+    /// Returns the port, `None` if the frame was dropped.
+    pub fn poll(&self) -> Result<Option<u8>> {
+        self.lock().dispatch()
+    }
+    /// Run `f` on the modem, e.g. to configure it.
+    pub fn with_modem<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        f(&mut self.lock().modem)
+    }
+}
+
+/// One port of a `PortMux`, closed when dropped
+pub struct PortHandle<T: LoraModemDevice> {
+    port: u8,
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T: LoraModemDevice> PortHandle<T> {
+    fn lock(&self) -> MutexGuard<'_, Shared<T>> {
+        lock(&self.shared)
+    }
+    pub fn port(&self) -> u8 {
+        self.port
+    }
+    /// Send `data` to the same port of the other end.
+    pub fn send(&self, data: &[u8]) -> Result<TxReport> {
+        let mut frame = Vec::with_capacity(PORT_LEN + data.len());
+        frame.push(self.port);
+        frame.extend_from_slice(data);
+        let report = self.lock().modem.send_data(frame)?;
+        Ok(TxReport {
+            bytes: report.bytes.saturating_sub(PORT_LEN),
+            ..report
+        })
+    }
+    /// Next frame received on this port, without waiting for the modem.
+    pub fn try_receive(&self) -> Option<RxPacket> {
+        self.lock().queues.get_mut(&self.port)?.pop_front()
+    }
+    /// Next frame received on this port, `data` holds the payload without port byte.
+    ///
+    /// Reads from the modem, queueing frames for other ports, until one for
+    /// this port arrives or the read times out.
+    pub fn receive(&self) -> Result<RxPacket> {
+        let mut shared = self.lock();
+        loop {
+            if let Some(packet) = shared
+                .queues
+                .get_mut(&self.port)
+                .and_then(VecDeque::pop_front)
+            {
+                return Ok(packet);
+            }
+            shared.dispatch()?;
+        }
+    }
+    /// Frames waiting on this port.
+    pub fn queued(&self) -> usize {
+        self.lock().queues.get(&self.port).map_or(0, VecDeque::len)
+    }
+}
+
+impl<T: LoraModemDevice> Drop for PortHandle<T> {
+    fn drop(&mut self) {
+        lock(&self.shared).queues.remove(&self.port);
+    }
+}
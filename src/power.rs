@@ -0,0 +1,198 @@
+use crate::radio::RadioParams;
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemEvent, RadioState, Result,
+    RxPacket, Status, TxReport,
+};
+use std::time::{Duration, Instant};
+
+/// When and how deeply an `AutoSleepModem` powers down the radio
+#[derive(Debug, Clone)]
+pub struct SleepPolicy {
+    /// Time without transmissions or received packets before powering down
+    pub idle: Duration,
+    /// State entered once idle, `RadioState::Active` never powers down
+    pub state: RadioState,
+}
+
+impl Default for SleepPolicy {
+    fn default() -> Self {
+        SleepPolicy {
+            idle: Duration::from_secs(30),
+            state: RadioState::Sleep,
+        }
+    }
+}
+
+/// Puts the radio to sleep after a time without traffic and wakes it to transmit.
+///
+/// The idle time is checked by `poll` and before every read. A sleeping radio
+/// receives nothing, `read_packet` fails with `ModemError::RxDisabled` until the
+/// next transmission or an explicit `wake`, so this suits nodes that mostly send.
+pub struct AutoSleepModem<T: LoraModemDevice> {
+    inner: T,
+    policy: SleepPolicy,
+    last_activity: Instant,
+    asleep: bool,
+    sleeps: usize,
+}
+
+impl<T: LoraModemDevice> AutoSleepModem<T> {
+    pub fn new(inner: T, policy: SleepPolicy) -> Self {
+        AutoSleepModem {
+            inner,
+            policy,
+            last_activity: Instant::now(),
+            asleep: false,
+            sleeps: 0,
+        }
+    }
+    /// Whether the radio was powered down, by the policy or explicitly.
+    pub fn is_asleep(&self) -> bool {
+        self.asleep
+    }
+    /// Number of times the policy powered down the radio.
+    pub fn sleeps(&self) -> usize {
+        self.sleeps
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Power down the radio if it has been idle for long enough, true if it just did.
+    pub fn poll(&mut self) -> Result<bool> {
+        if self.asleep || self.last_activity.elapsed() < self.policy.idle {
+            return Ok(false);
+        }
+        match self.policy.state {
+            RadioState::Sleep => self.inner.sleep()?,
+            RadioState::Standby => self.inner.standby()?,
+            RadioState::Active => return Ok(false),
+        }
+        self.asleep = true;
+        self.sleeps += 1;
+        Ok(true)
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for AutoSleepModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        self.inner.max_payload()
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()?;
+        self.asleep = true;
+        Ok(())
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()?;
+        self.asleep = true;
+        Ok(())
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()?;
+        self.asleep = false;
+        self.last_activity = Instant::now();
+        Ok(())
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        if self.asleep {
+            self.wake()?;
+        }
+        let report = self.inner.send_data(data)?;
+        self.last_activity = Instant::now();
+        Ok(report)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        if self.asleep {
+            self.wake()?;
+        }
+        let report = self.inner.send_slice(data)?;
+        self.last_activity = Instant::now();
+        Ok(report)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        if self.asleep {
+            if let Err(e) = self.wake() {
+                return vec![Err(e)];
+            }
+        }
+        let results = self.inner.send_batch(frames);
+        self.last_activity = Instant::now();
+        results
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        self.poll()?;
+        let packet = self.inner.read_packet()?;
+        self.last_activity = Instant::now();
+        Ok(packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
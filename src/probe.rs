@@ -0,0 +1,208 @@
+use crate::ebyte::EbyteVariant;
+use crate::hex;
+use crate::rak::RakModem;
+use crate::rn2xx3::Rn2xx3Modem;
+use crate::serial::{SerialModem, SerialPort};
+use crate::transport::Transport;
+use crate::{LoraModemDevice, ModemError, Result, Status};
+use std::fmt;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+
+/// Baud rates tried by `probe`, the defaults of the supported firmwares first.
+pub const PROBE_BAUDS: &[u32] = &[115_200, 57_600, 9_600, 38_400, 19_200, 230_400];
+
+// Time each identification command may take to be answered.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Firmware or protocol found by `probe`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Firmware {
+    /// rf95modem AT firmware, see `Rf95Modem`
+    Rf95Modem,
+    /// RAK module with the RUI3 AT firmware, see `RakModem`
+    Rak,
+    /// Microchip RN2483/RN2903, see `Rn2xx3Modem`
+    Rn2xx3,
+    /// EBYTE module in configuration mode, see `EbyteModem`
+    Ebyte(EbyteVariant),
+}
+
+impl fmt::Display for Firmware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Firmware::Rf95Modem => f.write_str("rf95modem"),
+            Firmware::Rak => f.write_str("RAK RUI3"),
+            Firmware::Rn2xx3 => f.write_str("RN2xx3"),
+            Firmware::Ebyte(variant) => write!(f, "EBYTE {:?}", variant),
+        }
+    }
+}
+
+/// Modem identified by `probe`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeResult {
+    /// Serial device the modem answered on
+    pub path: String,
+    /// Baud rate the modem answered at
+    pub baud: u32,
+    pub firmware: Firmware,
+    /// Version reported by the firmware, in hex for EBYTE modules
+    pub version: String,
+}
+
+impl ProbeResult {
+    /// Open the backend for the detected firmware.
+    ///
+    /// EBYTE modules need their mode pins and are constructed with `EbyteModem::new`.
+    pub fn open(&self) -> Result<Box<dyn LoraModemDevice + Send>> {
+        let port = SerialPort::new(&self.path, self.baud);
+        let timeout = Some(Duration::from_secs(5));
+        let mut modem: Box<dyn LoraModemDevice + Send> = match self.firmware {
+            Firmware::Rf95Modem => Box::new(SerialModem::new(&self.path, self.baud)),
+            Firmware::Rak => {
+                let mut modem = RakModem::from_transport(port);
+                modem.set_timeout(timeout);
+                Box::new(modem)
+            }
+            Firmware::Rn2xx3 => {
+                let mut modem = Rn2xx3Modem::from_transport(port);
+                modem.set_timeout(timeout);
+                Box::new(modem)
+            }
+            Firmware::Ebyte(_) => {
+                return Err(ModemError::InvalidArgument(
+                    "EBYTE modules need their mode pins, use EbyteModem::new".into(),
+                ))
+            }
+        };
+        modem.open()?;
+        Ok(modem)
+    }
+}
+
+// Write `request` and collect the answer until `done` or `timeout`.
+fn exchange<T: Transport, F: Fn(&[u8]) -> bool>(
+    transport: &mut T,
+    request: &[u8],
+    timeout: Duration,
+    done: F,
+) -> Result<Vec<u8>> {
+    let deadline = Instant::now() + timeout;
+    transport.write_all(request)?;
+    transport.flush()?;
+    let mut answer = Vec::new();
+    let mut chunk = [0u8; 256];
+    while !done(&answer) && Instant::now() < deadline {
+        match transport.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => answer.extend_from_slice(&chunk[..n]),
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => {}
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(answer)
+}
+
+// Complete, non-empty lines of `answer`.
+fn lines(answer: &[u8]) -> Vec<String> {
+    let text = String::from_utf8_lossy(answer);
+    let complete = text.rfind('\n').map_or("", |end| &text[..end]);
+    complete
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+// Whether `line` ends a command response of any of the AT firmwares.
+fn is_final(line: &str) -> bool {
+    matches!(line, "+OK" | "OK" | "ERROR" | "invalid_param")
+        || line.starts_with("+ERROR")
+        || line.starts_with("+FAIL")
+        || (line.starts_with("AT_") && (line.ends_with("ERROR") || line.ends_with("NOT_FOUND")))
+}
+
+// Lines of the answer to the text command `cmd`, up to its final line.
+fn text_command<T: Transport>(
+    transport: &mut T,
+    cmd: &str,
+    timeout: Duration,
+) -> Result<Vec<String>> {
+    let request = format!("{}\r\n", cmd);
+    let answer = exchange(transport, request.as_bytes(), timeout, |answer| {
+        lines(answer).iter().any(|line| is_final(line))
+    })?;
+    // the echo of the command, if enabled, is no part of the answer
+    Ok(lines(&answer)
+        .into_iter()
+        .filter(|line| line != cmd)
+        .collect())
+}
+
+/// Identify the firmware behind `transport`, already opened at the baud rate to try.
+///
+/// Sends the identification command of every supported firmware in turn,
+/// waiting up to `timeout` for each answer. Returns the firmware and the
+/// version it reported, `None` if nothing answered sensibly.
+pub fn identify<T: Transport>(
+    transport: &mut T,
+    timeout: Duration,
+) -> Result<Option<(Firmware, String)>> {
+    let answer = text_command(transport, "AT+INFO", timeout)?;
+    if let Some(end) = answer.iter().position(|line| line.starts_with("+OK")) {
+        if let Ok(status) = Status::parse(&answer[..end]) {
+            return Ok(Some((Firmware::Rf95Modem, status.version)));
+        }
+    }
+    let answer = text_command(transport, "AT+VER=?", timeout)?;
+    if answer.last().is_some_and(|line| line == "OK") && answer.len() >= 2 {
+        let version = &answer[answer.len() - 2];
+        let version = version.strip_prefix("AT+VER=").unwrap_or(version);
+        return Ok(Some((Firmware::Rak, version.to_string())));
+    }
+    let request = b"sys get ver\r\n";
+    let answer = exchange(transport, request, timeout, |answer| {
+        !lines(answer).is_empty()
+    })?;
+    if let Some(version) = lines(&answer).into_iter().find(|l| l.starts_with("RN2")) {
+        return Ok(Some((Firmware::Rn2xx3, version)));
+    }
+    // EBYTE modules in configuration mode answer binary frames, others send them on air
+    let answer = exchange(transport, &[0xc3; 3], timeout, |answer| answer.len() >= 4)?;
+    if answer.len() == 4 && answer[0] == 0xc3 {
+        let version = hex::encode(&answer[1..]);
+        return Ok(Some((Firmware::Ebyte(EbyteVariant::E32), version)));
+    }
+    let answer = exchange(transport, &[0xc1, 0x00, 0x07], timeout, |answer| {
+        answer.len() >= 10
+    })?;
+    if answer.len() == 10 && answer.starts_with(&[0xc1, 0x00, 0x07]) {
+        return Ok(Some((Firmware::Ebyte(EbyteVariant::E22), String::new())));
+    }
+    Ok(None)
+}
+
+/// Find out which modem is attached to the serial device `path` and at which baud rate.
+///
+/// Tries every rate of `PROBE_BAUDS` with `identify`, which may take a few
+/// seconds per rate for a device that does not answer. Fails with
+/// `ModemError::Timeout` if no firmware was recognized. EBYTE modules are only
+/// found with their mode pins set to configuration mode; in transparent mode
+/// their identification frames are transmitted on air.
+pub fn probe(path: &str) -> Result<ProbeResult> {
+    for &baud in PROBE_BAUDS {
+        let mut port = SerialPort::new(path, baud);
+        port.open()?;
+        if let Some((firmware, version)) = identify(&mut port, PROBE_TIMEOUT)? {
+            return Ok(ProbeResult {
+                path: path.to_string(),
+                baud,
+                firmware,
+                version,
+            });
+        }
+    }
+    Err(ModemError::Timeout)
+}
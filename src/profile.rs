@@ -0,0 +1,217 @@
+use crate::radio::{self, Bandwidth, CodingRate, HeaderMode, RadioParams};
+use crate::region::Region;
+use crate::toml::{self, Entry, Value};
+use crate::{Frequency, LoraModemDevice, Result};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Settings of a modem and its node, kept in a TOML file
+///
+/// Every setting is optional, `apply` changes only what the profile gives.
+/// A complete profile reads
+///
+/// ```toml
+/// port = "/dev/ttyUSB0"
+/// baud = 115200
+/// frequency = 868.1    # MHz
+/// tx_power = 14        # dBm
+/// node_id = 3
+/// region = "EU868"
+///
+/// [radio]
+/// bandwidth = 125000   # Hz
+/// spreading_factor = 7
+/// coding_rate = "4/5"
+/// preamble_len = 8
+/// crc = true
+/// sync_word = "0x12"
+/// implicit_len = 16    # fixed payload length, headers are explicit without it
+/// ```
+///
+/// Radio settings missing from `[radio]` take the values of `RadioParams::default()`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModemProfile {
+    /// Serial device of the modem
+    pub port: Option<String>,
+    /// Baud rate of the serial device
+    pub baud: Option<u32>,
+    pub frequency: Option<Frequency>,
+    pub radio: Option<RadioParams>,
+    /// Transmit power in dBm
+    pub tx_power: Option<i8>,
+    /// Address of the node, used by the addressing layers rather than the modem
+    pub node_id: Option<u8>,
+    /// Region the frequency and transmit power are checked against
+    pub region: Option<Region>,
+}
+
+// Number of an entry parsed as `T`, failing with the line it was defined on.
+fn number<T: FromStr>(entry: &Entry) -> Result<T> {
+    entry
+        .value
+        .as_number()
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| toml::error(entry.line, &format!("invalid {}", entry.key)))
+}
+
+fn string(entry: &Entry) -> Result<&str> {
+    entry
+        .value
+        .as_str()
+        .ok_or_else(|| toml::error(entry.line, &format!("{} must be a string", entry.key)))
+}
+
+impl ModemProfile {
+    /// Parse a profile, unknown keys and tables are rejected to catch typos.
+    pub fn from_toml(text: &str) -> Result<ModemProfile> {
+        let mut profile = ModemProfile::default();
+        let mut radio: Option<RadioParams> = None;
+        for entry in toml::parse(text)? {
+            let invalid = |what: &str| toml::error(entry.line, &format!("invalid {}", what));
+            match (entry.table.as_str(), entry.key.as_str()) {
+                ("", "port") => profile.port = Some(string(&entry)?.to_string()),
+                ("", "baud") => profile.baud = Some(number(&entry)?),
+                ("", "frequency") => {
+                    let freq = entry
+                        .value
+                        .as_number()
+                        .ok_or_else(|| invalid("frequency"))?;
+                    profile.frequency = Some(freq.parse().map_err(|_| invalid("frequency"))?);
+                }
+                ("", "tx_power") => profile.tx_power = Some(number(&entry)?),
+                ("", "node_id") => profile.node_id = Some(number(&entry)?),
+                ("", "region") => profile.region = Some(string(&entry)?.parse()?),
+                ("radio", key) => {
+                    let params = radio.get_or_insert_with(RadioParams::default);
+                    match key {
+                        "bandwidth" => {
+                            params.bandwidth = Bandwidth::from_hz(number(&entry)?)
+                                .ok_or_else(|| invalid("bandwidth"))?
+                        }
+                        "spreading_factor" => params.spreading_factor = number(&entry)?,
+                        "coding_rate" => {
+                            // "4/5" or just the denominator
+                            let denominator = match &entry.value {
+                                Value::String(rate) => rate.strip_prefix("4/").unwrap_or(rate),
+                                Value::Number(rate) => rate,
+                                Value::Bool(_) => "",
+                            };
+                            params.coding_rate = denominator
+                                .parse()
+                                .ok()
+                                .and_then(CodingRate::from_denominator)
+                                .ok_or_else(|| invalid("coding_rate"))?;
+                        }
+                        "preamble_len" => params.preamble_len = number(&entry)?,
+                        "crc" => {
+                            params.crc = entry.value.as_bool().ok_or_else(|| invalid("crc"))?
+                        }
+                        "sync_word" => {
+                            // "0x12" or a number
+                            params.sync_word = match &entry.value {
+                                Value::String(word) | Value::Number(word) => {
+                                    radio::parse_sync_word(word).ok()
+                                }
+                                Value::Bool(_) => None,
+                            }
+                            .ok_or_else(|| invalid("sync_word"))?
+                        }
+                        "implicit_len" => {
+                            params.header = HeaderMode::Implicit {
+                                len: number(&entry)?,
+                            }
+                        }
+                        _ => return Err(toml::error(entry.line, "unknown key")),
+                    }
+                }
+                ("", _) => return Err(toml::error(entry.line, "unknown key")),
+                _ => return Err(toml::error(entry.line, "unknown table")),
+            }
+        }
+        if let Some(params) = radio {
+            params.validate()?;
+            profile.radio = Some(params);
+        }
+        Ok(profile)
+    }
+    /// The profile as TOML, settings not given are left out.
+    pub fn to_toml(&self) -> String {
+        let mut top: Vec<(&str, Value)> = Vec::new();
+        if let Some(port) = &self.port {
+            top.push(("port", port.as_str().into()));
+        }
+        if let Some(baud) = self.baud {
+            top.push(("baud", baud.into()));
+        }
+        if let Some(freq) = self.frequency {
+            // a float, so the value reads back as MHz everywhere
+            let mhz = freq.to_string();
+            let mhz = if mhz.contains('.') {
+                mhz
+            } else {
+                format!("{}.0", mhz)
+            };
+            top.push(("frequency", Value::Number(mhz)));
+        }
+        if let Some(dbm) = self.tx_power {
+            top.push(("tx_power", dbm.into()));
+        }
+        if let Some(id) = self.node_id {
+            top.push(("node_id", id.into()));
+        }
+        if let Some(region) = self.region {
+            top.push(("region", region.name().into()));
+        }
+        let mut tables = Vec::new();
+        if let Some(params) = self.radio {
+            let coding_rate = format!("4/{}", params.coding_rate.denominator());
+            let sync_word = format!("{:#04x}", params.sync_word);
+            let mut radio = vec![
+                ("bandwidth", params.bandwidth.hz().into()),
+                ("spreading_factor", params.spreading_factor.into()),
+                ("coding_rate", coding_rate.as_str().into()),
+                ("preamble_len", params.preamble_len.into()),
+                ("crc", params.crc.into()),
+                ("sync_word", sync_word.as_str().into()),
+            ];
+            if let HeaderMode::Implicit { len } = params.header {
+                radio.push(("implicit_len", len.into()));
+            }
+            tables.push(("radio", radio));
+        }
+        toml::write(&top, &tables)
+    }
+    /// Read the profile stored at `path`.
+    pub fn load_toml<P: AsRef<Path>>(path: P) -> Result<ModemProfile> {
+        ModemProfile::from_toml(&fs::read_to_string(path)?)
+    }
+    /// Store the profile at `path`, replacing the file.
+    pub fn save_toml<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Ok(fs::write(path, self.to_toml())?)
+    }
+
+    /// Configure `device` with the radio settings, frequency and transmit power
+    /// of the profile.
+    ///
+    /// With a region given, frequency and transmit power are checked before
+    /// anything is changed on the device.
+    pub fn apply<D: LoraModemDevice + ?Sized>(&self, device: &mut D) -> Result<()> {
+        if let (Some(region), Some(freq)) = (self.region, self.frequency) {
+            region.check_frequency(freq)?;
+            if let Some(dbm) = self.tx_power {
+                region.check_tx_power(freq, dbm)?;
+            }
+        }
+        if let Some(params) = self.radio {
+            device.set_radio_params(params)?;
+        }
+        if let Some(freq) = self.frequency {
+            device.set_frequency(freq)?;
+        }
+        if let Some(dbm) = self.tx_power {
+            device.set_tx_power(dbm)?;
+        }
+        Ok(())
+    }
+}
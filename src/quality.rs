@@ -0,0 +1,67 @@
+use core::fmt;
+
+/// How well a packet was received, for showing signal bars
+///
+/// Ordered from worst to best, so qualities can be compared and the worst of a
+/// set found with `min`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LinkQuality {
+    Poor,
+    Fair,
+    Good,
+    Excellent,
+}
+
+impl LinkQuality {
+    /// Classify a reception by its RSSI in dBm and SNR in dB.
+    ///
+    /// Both are rated on their own and the worse rating wins, a strong signal
+    /// buried in noise is no better than a weak clean one.
+    pub fn from(rssi: i16, snr: f32) -> LinkQuality {
+        let by_rssi = match rssi {
+            r if r >= -90 => LinkQuality::Excellent,
+            r if r >= -105 => LinkQuality::Good,
+            r if r >= -115 => LinkQuality::Fair,
+            _ => LinkQuality::Poor,
+        };
+        let by_snr = if snr >= 5.0 {
+            LinkQuality::Excellent
+        } else if snr >= 0.0 {
+            LinkQuality::Good
+        } else if snr >= -7.5 {
+            LinkQuality::Fair
+        } else {
+            LinkQuality::Poor
+        };
+        by_rssi.min(by_snr)
+    }
+    /// Number of signal bars from 1 to 4.
+    pub fn bars(self) -> u8 {
+        self as u8 + 1
+    }
+}
+
+impl fmt::Display for LinkQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LinkQuality::Poor => "poor",
+            LinkQuality::Fair => "fair",
+            LinkQuality::Good => "good",
+            LinkQuality::Excellent => "excellent",
+        })
+    }
+}
+
+/// Lowest SNR in dB that packets sent with `spreading_factor` can be
+/// demodulated at, -7.5 dB for SF7 down to -20 dB for SF12.
+pub fn snr_limit(spreading_factor: u8) -> f32 {
+    -2.5 * (f32::from(spreading_factor) - 4.0)
+}
+
+/// Estimated link margin in dB of a packet received with `snr` at `spreading_factor`.
+///
+/// How much weaker the signal could get before packets are lost, negative
+/// margins mean packets arrived just by luck.
+pub fn link_margin(snr: f32, spreading_factor: u8) -> f32 {
+    snr - snr_limit(spreading_factor)
+}
@@ -0,0 +1,319 @@
+use crate::rf95::OverflowPolicy;
+use crate::{ModemError, Result, RxPacket, TxReport};
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Condvar, Mutex};
+use std::task::Waker;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Urgency of an outgoing frame, higher priorities are transmitted first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Large transfers, e.g. fragments of a file
+    Bulk,
+    /// Regular application data
+    Data,
+    /// Small control frames such as acknowledgements
+    Control,
+}
+
+impl Priority {
+    const LEVELS: usize = 3;
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Bounds of a transmit queue, pushing beyond either fails with `ModemError::QueueFull`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueLimits {
+    /// Frames waiting over all priorities
+    pub max_frames: usize,
+    /// Payload bytes waiting over all priorities
+    pub max_bytes: usize,
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        QueueLimits {
+            max_frames: 64,
+            max_bytes: 16 * 1024,
+        }
+    }
+}
+
+// Frames waiting for transmission, first in first out within a priority.
+// `T` is carried along with each frame, e.g. to report its outcome.
+pub(crate) struct TxQueue<T = ()> {
+    levels: [VecDeque<(Vec<u8>, T)>; Priority::LEVELS],
+    limits: QueueLimits,
+    frames: usize,
+    bytes: usize,
+}
+
+impl<T> TxQueue<T> {
+    pub(crate) fn new(limits: QueueLimits) -> Self {
+        TxQueue {
+            levels: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+            limits,
+            frames: 0,
+            bytes: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, priority: Priority, frame: Vec<u8>, tag: T) -> Result<()> {
+        if self.frames >= self.limits.max_frames || self.bytes + frame.len() > self.limits.max_bytes
+        {
+            return Err(ModemError::QueueFull);
+        }
+        self.frames += 1;
+        self.bytes += frame.len();
+        self.levels[priority.index()].push_back((frame, tag));
+        Ok(())
+    }
+
+    // Oldest frame of the highest priority waiting.
+    pub(crate) fn pop(&mut self) -> Option<(Vec<u8>, T)> {
+        let (frame, tag) = self
+            .levels
+            .iter_mut()
+            .rev()
+            .find_map(|level| level.pop_front())?;
+        self.frames -= 1;
+        self.bytes -= frame.len();
+        Some((frame, tag))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.frames
+    }
+}
+
+/// Ticket for a submitted frame, redeemed for the outcome of its transmission
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TxHandle(u64);
+
+#[derive(Default)]
+struct CompletionState {
+    next: u64,
+    done: HashMap<TxHandle, Result<TxReport>>,
+    wakers: HashMap<TxHandle, Waker>,
+    // no outcomes follow, e.g. the device thread ended
+    closed: bool,
+}
+
+// Outcomes of submitted frames, kept until collected through their handle.
+#[derive(Default)]
+pub(crate) struct Completions {
+    state: Mutex<CompletionState>,
+    changed: Condvar,
+}
+
+impl Completions {
+    pub(crate) fn handle(&self) -> TxHandle {
+        let mut state = self.state.lock().unwrap();
+        state.next += 1;
+        TxHandle(state.next)
+    }
+
+    pub(crate) fn complete(&self, handle: TxHandle, result: Result<TxReport>) {
+        let mut state = self.state.lock().unwrap();
+        state.done.insert(handle, result);
+        if let Some(waker) = state.wakers.remove(&handle) {
+            waker.wake();
+        }
+        self.changed.notify_all();
+    }
+
+    // Fail every outcome not known yet with `ModemError::Disconnected`.
+    pub(crate) fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        for (_, waker) in state.wakers.drain() {
+            waker.wake();
+        }
+        self.changed.notify_all();
+    }
+
+    fn take_from(state: &mut CompletionState, handle: TxHandle) -> Option<Result<TxReport>> {
+        match state.done.remove(&handle) {
+            Some(result) => Some(result),
+            None if state.closed => Some(Err(ModemError::Disconnected)),
+            None => None,
+        }
+    }
+
+    // Outcome of `handle` if known, collecting it.
+    pub(crate) fn take(&self, handle: TxHandle) -> Option<Result<TxReport>> {
+        Self::take_from(&mut self.state.lock().unwrap(), handle)
+    }
+
+    // Block for the outcome of `handle`, failing with a timeout after `timeout`.
+    pub(crate) fn wait(&self, handle: TxHandle, timeout: Option<Duration>) -> Result<TxReport> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(result) = Self::take_from(&mut state, handle) {
+                return result;
+            }
+            state = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return Err(ModemError::Timeout);
+                    }
+                    self.changed.wait_timeout(state, left).unwrap().0
+                }
+                None => self.changed.wait(state).unwrap(),
+            };
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn poll(&self, handle: TxHandle, cx: &mut Context<'_>) -> Poll<Result<TxReport>> {
+        let mut state = self.state.lock().unwrap();
+        match Self::take_from(&mut state, handle) {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.wakers.insert(handle, cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct PacketState {
+    packets: VecDeque<RxPacket>,
+    dropped: usize,
+    // no packets follow, e.g. the device thread ended
+    closed: bool,
+}
+
+/// Packets received on a device thread, waiting to be read
+///
+/// Holds as many packets as the receive buffer of the modem, what happens with
+/// packets arriving while it is full is set by the same `OverflowPolicy`. Reading
+/// works like with a `Receiver` of a channel, once the device thread ended and
+/// all packets were read it reports disconnection.
+pub struct PacketQueue {
+    state: Mutex<PacketState>,
+    changed: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+impl PacketQueue {
+    pub(crate) fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        PacketQueue {
+            state: Mutex::new(PacketState::default()),
+            changed: Condvar::new(),
+            capacity: capacity.max(1),
+            policy,
+        }
+    }
+
+    // Queue `packet`, with `OverflowPolicy::Block` waiting up to `timeout` for
+    // room and handing it back if there is none.
+    pub(crate) fn push(&self, packet: RxPacket, timeout: Duration) -> Option<RxPacket> {
+        let mut state = self.state.lock().unwrap();
+        if state.packets.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    state.packets.pop_front();
+                    state.dropped += 1;
+                }
+                OverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    return None;
+                }
+                OverflowPolicy::Block => {
+                    state = self
+                        .changed
+                        .wait_timeout_while(state, timeout, |s| s.packets.len() >= self.capacity)
+                        .unwrap()
+                        .0;
+                    if state.packets.len() >= self.capacity {
+                        return Some(packet);
+                    }
+                }
+            }
+        }
+        state.packets.push_back(packet);
+        self.changed.notify_all();
+        None
+    }
+
+    // Let readers know no packets follow.
+    pub(crate) fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+
+    fn pop(&self, state: &mut PacketState) -> Option<RxPacket> {
+        let packet = state.packets.pop_front();
+        if packet.is_some() {
+            self.changed.notify_all();
+        }
+        packet
+    }
+
+    /// Next packet, waiting for one to arrive.
+    pub fn recv(&self) -> core::result::Result<RxPacket, RecvError> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(packet) = self.pop(&mut state) {
+                return Ok(packet);
+            }
+            if state.closed {
+                return Err(RecvError);
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+    /// Next packet, waiting up to `timeout` for one to arrive.
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> core::result::Result<RxPacket, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(packet) = self.pop(&mut state) {
+                return Ok(packet);
+            }
+            if state.closed {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self.changed.wait_timeout(state, left).unwrap().0;
+        }
+    }
+    /// Next packet if one is waiting.
+    pub fn try_recv(&self) -> core::result::Result<RxPacket, TryRecvError> {
+        let mut state = self.state.lock().unwrap();
+        match self.pop(&mut state) {
+            Some(packet) => Ok(packet),
+            None if state.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+    /// Number of packets waiting to be read.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().packets.len()
+    }
+    /// Whether no packet is waiting.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Packets dropped so far because the queue was full.
+    pub fn dropped(&self) -> usize {
+        self.state.lock().unwrap().dropped
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{ceil, info_fields, leading_number, ModemConfig, ModemError, Result, Status};
+use alloc::format;
+use alloc::string::String;
+use core::ops::RangeInclusive;
+use core::time::Duration;
+
+/// Output power range in dBm of SX127x radios transmitting via the PA_BOOST pin.
+pub const PA_BOOST_POWER_RANGE: RangeInclusive<i8> = 2..=20;
+
+/// Check that `dbm` lies within the output power range of the radio.
+pub fn validate_tx_power(dbm: i8) -> Result<()> {
+    if PA_BOOST_POWER_RANGE.contains(&dbm) {
+        Ok(())
+    } else {
+        Err(ModemError::InvalidArgument(format!(
+            "tx power {} dBm out of range {}-{} dBm",
+            dbm,
+            PA_BOOST_POWER_RANGE.start(),
+            PA_BOOST_POWER_RANGE.end()
+        )))
+    }
+}
+
+fn validate_spreading_factor(sf: u8) -> Result<()> {
+    if (6..=12).contains(&sf) {
+        Ok(())
+    } else {
+        Err(spreading_factor_out_of_range(sf))
+    }
+}
+
+fn spreading_factor_out_of_range(sf: u8) -> ModemError {
+    ModemError::InvalidArgument(format!("spreading factor {} out of range 6-12", sf))
+}
+
+/// LoRa signal bandwidth
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bandwidth {
+    Bw7_8kHz,
+    Bw10_4kHz,
+    Bw15_6kHz,
+    Bw20_8kHz,
+    Bw31_25kHz,
+    Bw41_7kHz,
+    Bw62_5kHz,
+    Bw125kHz,
+    Bw250kHz,
+    Bw500kHz,
+}
+
+impl Bandwidth {
+    /// Bandwidth in Hz.
+    pub fn hz(self) -> u32 {
+        match self {
+            Bandwidth::Bw7_8kHz => 7_800,
+            Bandwidth::Bw10_4kHz => 10_400,
+            Bandwidth::Bw15_6kHz => 15_600,
+            Bandwidth::Bw20_8kHz => 20_800,
+            Bandwidth::Bw31_25kHz => 31_250,
+            Bandwidth::Bw41_7kHz => 41_700,
+            Bandwidth::Bw62_5kHz => 62_500,
+            Bandwidth::Bw125kHz => 125_000,
+            Bandwidth::Bw250kHz => 250_000,
+            Bandwidth::Bw500kHz => 500_000,
+        }
+    }
+    /// Bandwidth for a value in Hz, if supported by the radio.
+    pub fn from_hz(hz: u32) -> Option<Self> {
+        [
+            Bandwidth::Bw7_8kHz,
+            Bandwidth::Bw10_4kHz,
+            Bandwidth::Bw15_6kHz,
+            Bandwidth::Bw20_8kHz,
+            Bandwidth::Bw31_25kHz,
+            Bandwidth::Bw41_7kHz,
+            Bandwidth::Bw62_5kHz,
+            Bandwidth::Bw125kHz,
+            Bandwidth::Bw250kHz,
+            Bandwidth::Bw500kHz,
+        ]
+        .iter()
+        .copied()
+        .find(|bw| bw.hz() == hz)
+    }
+}
+
+/// Forward error correction coding rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodingRate {
+    Cr4_5 = 5,
+    Cr4_6 = 6,
+    Cr4_7 = 7,
+    Cr4_8 = 8,
+}
+
+impl CodingRate {
+    /// Coding rate for the denominator of 4/x.
+    pub fn from_denominator(denominator: u8) -> Option<Self> {
+        match denominator {
+            5 => Some(CodingRate::Cr4_5),
+            6 => Some(CodingRate::Cr4_6),
+            7 => Some(CodingRate::Cr4_7),
+            8 => Some(CodingRate::Cr4_8),
+            _ => None,
+        }
+    }
+    /// Denominator x of the coding rate 4/x.
+    pub fn denominator(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Sync word of private LoRa networks, the default of SX127x radios.
+pub const SYNC_WORD_PRIVATE: u8 = 0x12;
+/// Sync word of public LoRaWAN networks.
+pub const SYNC_WORD_LORAWAN: u8 = 0x34;
+
+/// Whether packets carry a LoRa header announcing their length and coding rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HeaderMode {
+    /// Every packet starts with a header, the default
+    #[default]
+    Explicit,
+    /// No header, every packet has `len` bytes known to both ends in advance
+    ///
+    /// Saves the header symbols on air and is required for spreading factor 6.
+    Implicit { len: u8 },
+}
+
+impl HeaderMode {
+    /// Fixed payload length in implicit mode.
+    pub fn implicit_len(self) -> Option<usize> {
+        match self {
+            HeaderMode::Explicit => None,
+            HeaderMode::Implicit { len } => Some(len as usize),
+        }
+    }
+}
+
+/// Individual LoRa radio settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RadioParams {
+    /// Signal bandwidth
+    pub bandwidth: Bandwidth,
+    /// Spreading factor (6-12), i.e. 2^sf chips per symbol
+    pub spreading_factor: u8,
+    /// Coding rate
+    pub coding_rate: CodingRate,
+    /// Number of preamble symbols
+    pub preamble_len: u16,
+    /// Payload CRC enabled
+    pub crc: bool,
+    /// Explicit or implicit (fixed-length) header
+    pub header: HeaderMode,
+    /// Radios only receive packets sent with their own sync word
+    pub sync_word: u8,
+}
+
+impl Default for RadioParams {
+    fn default() -> Self {
+        RadioParams::from(ModemConfig::MediumBw125Cr45Sf128Crc)
+    }
+}
+
+impl RadioParams {
+    /// Check that the settings can be applied to an SX127x radio.
+    pub fn validate(&self) -> Result<()> {
+        validate_spreading_factor(self.spreading_factor)?;
+        if self.preamble_len < 6 {
+            return Err(ModemError::InvalidArgument(format!(
+                "preamble length {} shorter than 6 symbols",
+                self.preamble_len
+            )));
+        }
+        if self.header == (HeaderMode::Implicit { len: 0 }) {
+            return Err(ModemError::InvalidArgument(
+                "implicit header mode needs a payload length".into(),
+            ));
+        }
+        Ok(())
+    }
+    /// Predefined modem config matching these settings, if any.
+    pub fn preset(&self) -> Option<ModemConfig> {
+        [
+            ModemConfig::MediumBw125Cr45Sf128Crc,
+            ModemConfig::FastShortBw500Cr45Sf128Crc,
+            ModemConfig::SlowLongBw3125Cr48Sf512Crc,
+            ModemConfig::SlowLongBw125Cr48Sf4096Crc,
+        ]
+        .iter()
+        .copied()
+        .find(|&preset| RadioParams::from(preset) == *self)
+    }
+    /// Time on air of a packet carrying `payload_len` bytes with these settings.
+    pub fn airtime(&self, payload_len: usize) -> Result<Duration> {
+        airtime(payload_len, self)
+    }
+}
+
+impl From<ModemConfig> for RadioParams {
+    fn from(config: ModemConfig) -> Self {
+        let (bandwidth, spreading_factor, coding_rate) = match config {
+            ModemConfig::MediumBw125Cr45Sf128Crc => (Bandwidth::Bw125kHz, 7, CodingRate::Cr4_5),
+            ModemConfig::FastShortBw500Cr45Sf128Crc => (Bandwidth::Bw500kHz, 7, CodingRate::Cr4_5),
+            ModemConfig::SlowLongBw3125Cr48Sf512Crc => {
+                (Bandwidth::Bw31_25kHz, 9, CodingRate::Cr4_8)
+            }
+            ModemConfig::SlowLongBw125Cr48Sf4096Crc => (Bandwidth::Bw125kHz, 12, CodingRate::Cr4_8),
+        };
+        RadioParams {
+            bandwidth,
+            spreading_factor,
+            coding_rate,
+            preamble_len: 8,
+            crc: true,
+            header: HeaderMode::Explicit,
+            sync_word: SYNC_WORD_PRIVATE,
+        }
+    }
+}
+
+/// Duration of a single LoRa symbol, 2^SF / BW.
+///
+/// Fails with `ModemError::InvalidArgument` for a spreading factor out of range.
+pub fn symbol_time(params: &RadioParams) -> Result<Duration> {
+    to_duration(symbol_secs(params)?)
+}
+
+fn symbol_secs(params: &RadioParams) -> Result<f64> {
+    validate_spreading_factor(params.spreading_factor)?;
+    let chips = 1u64
+        .checked_shl(params.spreading_factor.into())
+        .ok_or_else(|| spreading_factor_out_of_range(params.spreading_factor))?;
+    Ok(chips as f64 / params.bandwidth.hz() as f64)
+}
+
+fn to_duration(secs: f64) -> Result<Duration> {
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| ModemError::InvalidArgument(format!("time on air of {} s", secs)))
+}
+
+/// Time on air of a packet carrying `payload_len` bytes.
+///
+/// Implements the formula of the Semtech SX1276 datasheet (section 4.1.1.7).
+/// Low data rate optimization is assumed to be enabled for
+/// symbols longer than 16ms, as done by the rf95modem firmware.
+/// Fails with `ModemError::InvalidArgument` for a spreading factor out of range.
+pub fn airtime(payload_len: usize, params: &RadioParams) -> Result<Duration> {
+    let sf = params.spreading_factor as f64;
+    let symbol = symbol_secs(params)?;
+    // low data rate optimization is mandated for symbols longer than 16ms
+    let de = if symbol > 0.016 { 1.0 } else { 0.0 };
+    let crc = if params.crc { 1.0 } else { 0.0 };
+    let cr = (params.coding_rate.denominator() - 4) as f64;
+    let ih = match params.header {
+        HeaderMode::Explicit => 0.0,
+        HeaderMode::Implicit { .. } => 1.0,
+    };
+    let preamble = (params.preamble_len as f64 + 4.25) * symbol;
+    let bits = 8.0 * payload_len as f64 - 4.0 * sf + 28.0 + 16.0 * crc - 20.0 * ih;
+    let payload_symbols = 8.0 + (ceil(bits / (4.0 * (sf - 2.0 * de))) * (cr + 4.0)).max(0.0);
+    to_duration(preamble + payload_symbols * symbol)
+}
+
+// Parse radio settings reported by AT+INFO, starting from the active preset
+// for firmware not reporting individual settings.
+pub(crate) fn parse_radio_params(lines: &[String]) -> Result<RadioParams> {
+    let mut params = RadioParams::from(Status::parse(lines)?.config);
+    for (key, value) in info_fields(lines) {
+        match key.as_str() {
+            "bandwidth" => {
+                params.bandwidth = Bandwidth::from_hz(leading_number(value).parse()?)
+                    .ok_or_else(|| ModemError::Parse(format!("unknown bandwidth {}", value)))?
+            }
+            "spreading factor" => params.spreading_factor = value.parse()?,
+            "coding rate" => {
+                let denominator = value.trim_start_matches("4/").parse()?;
+                params.coding_rate = CodingRate::from_denominator(denominator)
+                    .ok_or_else(|| ModemError::Parse(format!("unknown coding rate {}", value)))?
+            }
+            "preamble" => params.preamble_len = value.parse()?,
+            "crc" => params.crc = value == "1",
+            "sync word" => params.sync_word = parse_sync_word(value)?,
+            // payload length of implicit header mode, 0 while explicit
+            "implicit header" => {
+                params.header = match value.parse()? {
+                    0 => HeaderMode::Explicit,
+                    len => HeaderMode::Implicit { len },
+                }
+            }
+            _ => {}
+        }
+    }
+    params.validate()?;
+    Ok(params)
+}
+
+// Sync word as reported by firmware, `0x12` or decimal.
+pub(crate) fn parse_sync_word(value: &str) -> Result<u8> {
+    let value = value.trim();
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u8::from_str_radix(hex, 16)
+            .map_err(|_| ModemError::Parse(format!("invalid sync word {}", value))),
+        None => Ok(value.parse()?),
+    }
+}
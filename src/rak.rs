@@ -0,0 +1,391 @@
+use crate::error::tx_rejected;
+use crate::hex;
+use crate::radio::{Bandwidth, CodingRate, HeaderMode, RadioParams};
+#[cfg(feature = "serial")]
+use crate::serial::SerialPort;
+use crate::trace;
+use crate::transport::Transport;
+use crate::{
+    check_payload, Capabilities, Frequency, LoraModemDevice, ModemConfig, ModemError, Result,
+    RxPacket, Status, TxReport,
+};
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Default baud rate of RAK modules.
+pub const RAK_BAUD: u32 = 115_200;
+// Largest payload of a single `AT+PSEND`.
+const MAX_PAYLOAD: usize = 255;
+// `AT+PRECV` timeout selecting continuous reception.
+const RX_CONTINUOUS: u16 = 65_535;
+
+/// RAK module running the RUI3 AT firmware, e.g. a RAK3172, in LoRa P2P mode.
+///
+/// The module is switched to P2P mode on `open()`, which reboots it if it was
+/// in LoRaWAN mode. Received packets arrive as `+EVT:RXP2P` notifications
+/// while continuous reception is active, it is paused for every transmission.
+pub struct RakModem<T: Transport> {
+    transport: T,
+    timeout: Option<Duration>,
+    buf: Vec<u8>,
+    version: String,
+    // notifications read while waiting for a command answer
+    events: VecDeque<String>,
+    // continuous reception is active
+    listening: bool,
+    rx_enabled: bool,
+}
+
+#[cfg(feature = "serial")]
+impl RakModem<SerialPort> {
+    /// Create a modem for the module at the serial device `path`, opened by `open()`.
+    pub fn serial(path: &str) -> Self {
+        let mut modem = RakModem::from_transport(SerialPort::new(path, RAK_BAUD));
+        modem.set_timeout(Some(Duration::from_secs(5)));
+        modem
+    }
+}
+
+impl<T: Transport> RakModem<T> {
+    /// Create a modem on top of an (unopened) transport.
+    pub fn from_transport(transport: T) -> Self {
+        RakModem {
+            transport,
+            timeout: None,
+            buf: Vec::new(),
+            version: String::new(),
+            events: VecDeque::new(),
+            listening: false,
+            rx_enabled: true,
+        }
+    }
+    /// Bound commands and reads by `timeout`, `None` blocks forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+    /// Consume the modem and return the underlying transport.
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        if !self.transport.is_open() {
+            return Err(ModemError::NotOpen);
+        }
+        trace::line_out(line);
+        self.transport.write_all(line.as_bytes())?;
+        self.transport.write_all(b"\r\n")?;
+        self.transport.flush()?;
+        Ok(())
+    }
+
+    // Read the next non-empty line, failing with a timeout once `deadline` passed.
+    fn next_line(&mut self, deadline: Option<Instant>) -> Result<String> {
+        if !self.transport.is_open() {
+            return Err(ModemError::NotOpen);
+        }
+        let mut byte = [0u8; 1];
+        loop {
+            match self.transport.read(&mut byte) {
+                Ok(0) => return Err(ModemError::Disconnected),
+                Ok(_) => {
+                    if byte[0] == b'\n' {
+                        let line = String::from_utf8_lossy(&self.buf).trim().to_string();
+                        self.buf.clear();
+                        trace::line_in(&line);
+                        if !line.is_empty() {
+                            return Ok(line);
+                        }
+                    } else {
+                        self.buf.push(byte[0]);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock => {
+                    if deadline.is_some_and(|d| Instant::now() >= d) {
+                        return Err(ModemError::Timeout);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|t| Instant::now() + t)
+    }
+
+    // Send a command and return the lines answered before `OK`, error answers fail.
+    fn command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        let span = trace::command(cmd);
+        let _entered = span.enter();
+        let started = Instant::now();
+        let result = self.exchange(cmd);
+        trace::finished(started, &result);
+        result
+    }
+
+    // Exchange of `command` without the instrumentation.
+    fn exchange(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.write_line(cmd)?;
+        let deadline = self.deadline();
+        let mut lines = Vec::new();
+        loop {
+            let line = self.next_line(deadline)?;
+            match line.as_str() {
+                "OK" => return Ok(lines),
+                "AT_PARAM_ERROR" => return Err(ModemError::InvalidArgument(cmd.to_string())),
+                "AT_COMMAND_NOT_FOUND" => return Err(ModemError::UnsupportedCommand(cmd.into())),
+                _ if line.starts_with("AT_") && line.ends_with("ERROR") => {
+                    return Err(ModemError::ModemReported(line))
+                }
+                _ if line.starts_with("+EVT:") => self.events.push_back(line),
+                // echo of the command
+                _ if line == cmd => {}
+                _ => lines.push(line),
+            }
+        }
+    }
+
+    // Value of a `AT+X=?` query, answered as `AT+X=value` or just `value`.
+    fn query(&mut self, cmd: &str) -> Result<String> {
+        let lines = self.command(&format!("{}=?", cmd))?;
+        let answer = lines
+            .into_iter()
+            .last()
+            .ok_or_else(|| ModemError::Parse(format!("no answer to {}", cmd)))?;
+        let value = answer
+            .strip_prefix(cmd)
+            .and_then(|rest| rest.strip_prefix('='))
+            .unwrap_or(&answer);
+        Ok(value.trim().to_string())
+    }
+
+    fn set(&mut self, cmd: &str, value: &str) -> Result<()> {
+        self.stop_listening()?;
+        self.command(&format!("{}={}", cmd, value))?;
+        Ok(())
+    }
+
+    // Leave continuous reception, the module rejects transmissions meanwhile.
+    fn stop_listening(&mut self) -> Result<()> {
+        if self.listening {
+            self.listening = false;
+            self.command("AT+PRECV=0")?;
+        }
+        Ok(())
+    }
+
+    // Switch to P2P mode, waiting for the reboot this causes.
+    fn enter_p2p(&mut self) -> Result<()> {
+        if self.query("AT+NWM")? == "0" {
+            return Ok(());
+        }
+        self.command("AT+NWM=0")?;
+        let deadline = self.deadline();
+        loop {
+            thread::sleep(Duration::from_millis(500));
+            match self.command("AT") {
+                Ok(_) => return Ok(()),
+                Err(ModemError::Timeout) | Err(ModemError::ModemReported(_))
+                    if deadline.is_none_or(|d| Instant::now() < d) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+// Parse a `+EVT:RXP2P:<rssi>:<snr>:<hex>` notification.
+fn parse_rx_event(event: &str) -> Result<RxPacket> {
+    let invalid = || ModemError::Parse(format!("invalid P2P receive event: {}", event));
+    let mut fields = event
+        .strip_prefix("+EVT:RXP2P:")
+        .ok_or_else(invalid)?
+        .splitn(3, ':');
+    let rssi = fields.next().ok_or_else(invalid)?.trim().parse()?;
+    let snr = fields.next().ok_or_else(invalid)?.trim().parse()?;
+    let payload = fields.next().ok_or_else(invalid)?.trim();
+    let data = hex::decode(payload)?;
+    Ok(RxPacket {
+        rssi,
+        snr,
+        data,
+        received_at: SystemTime::now(),
+        freq_error: None,
+        modem_timestamp: None,
+    })
+}
+
+// The two byte SX126x sync word for the SX127x one, e.g. 0x1424 for 0x12.
+fn sx126x_sync_word(word: u8) -> u16 {
+    (word as u16 & 0xf0) << 8 | (word as u16 & 0x0f) << 4 | 0x0404
+}
+
+// The SX127x sync word for a hex SX126x one reported by `AT+SYNCWORD`.
+fn sx127x_sync_word(value: &str) -> Result<u8> {
+    let word = u16::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|_| ModemError::Parse(format!("invalid sync word {}", value)))?;
+    Ok((word >> 8) as u8 & 0xf0 | (word >> 4) as u8 & 0x0f)
+}
+
+impl<T: Transport> LoraModemDevice for RakModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.transport.open()?;
+        self.buf.clear();
+        self.events.clear();
+        self.listening = false;
+        self.rx_enabled = true;
+        self.version = self.query("AT+VER")?;
+        self.enter_p2p()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.set("AT+PFREQ", &freq.hz().to_string())
+    }
+    fn config(&mut self) -> Result<Status> {
+        let params = self.get_radio_params()?;
+        let hz: u32 = self.query("AT+PFREQ")?.parse()?;
+        let pwr = self.tx_power()?;
+        Ok(Status {
+            version: self.version.clone(),
+            config: params
+                .preset()
+                .unwrap_or(ModemConfig::MediumBw125Cr45Sf128Crc),
+            max_pkt_size: MAX_PAYLOAD,
+            frequency: Frequency::from_hz(hz),
+            rx_listener: self.rx_enabled,
+            tx_power: Some(pwr),
+            ..Status::new()
+        })
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        Ok(MAX_PAYLOAD)
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.set_radio_params(mode.into())
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        params.validate()?;
+        let bw = match params.bandwidth {
+            Bandwidth::Bw125kHz | Bandwidth::Bw250kHz | Bandwidth::Bw500kHz => {
+                params.bandwidth.hz() / 1000
+            }
+            bw => {
+                return Err(ModemError::InvalidArgument(format!(
+                    "bandwidth {} Hz not supported by RAK",
+                    bw.hz()
+                )))
+            }
+        };
+        if params.header != HeaderMode::Explicit {
+            return Err(ModemError::InvalidArgument(
+                "implicit header mode not supported by RAK".into(),
+            ));
+        }
+        if !params.crc {
+            return Err(ModemError::InvalidArgument(
+                "disabling the CRC not supported by RAK".into(),
+            ));
+        }
+        self.set("AT+PSF", &params.spreading_factor.to_string())?;
+        self.set("AT+PBW", &bw.to_string())?;
+        // coding rates are numbered from 0 for 4/5
+        self.set(
+            "AT+PCR",
+            &(params.coding_rate.denominator() - 5).to_string(),
+        )?;
+        self.set("AT+PPL", &params.preamble_len.to_string())?;
+        self.set(
+            "AT+SYNCWORD",
+            &format!("{:04X}", sx126x_sync_word(params.sync_word)),
+        )
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        let bw: u32 = self.query("AT+PBW")?.parse()?;
+        let cr: u8 = self.query("AT+PCR")?.parse()?;
+        let bandwidth = Bandwidth::from_hz(bw * 1000)
+            .ok_or_else(|| ModemError::Parse(format!("unknown bandwidth {}", bw)))?;
+        let coding_rate = CodingRate::from_denominator(cr + 5)
+            .ok_or_else(|| ModemError::Parse(format!("unknown coding rate {}", cr)))?;
+        Ok(RadioParams {
+            bandwidth,
+            spreading_factor: self.query("AT+PSF")?.parse()?,
+            coding_rate,
+            preamble_len: self.query("AT+PPL")?.parse()?,
+            crc: true,
+            header: HeaderMode::Explicit,
+            sync_word: sx127x_sync_word(&self.query("AT+SYNCWORD")?)?,
+        })
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.set("AT+PTP", &dbm.to_string())
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        Ok(self.query("AT+PTP")?.parse()?)
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        Ok(Capabilities::RADIO_PARAMS | Capabilities::TX_POWER)
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.command(cmd)
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.rx_enabled = true;
+        Ok(())
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.stop_listening()?;
+        self.rx_enabled = false;
+        Ok(())
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        check_payload(data.len(), MAX_PAYLOAD)?;
+        self.stop_listening()?;
+        self.command(&format!("AT+PSEND={}", hex::encode(&data)))
+            .map_err(tx_rejected)?;
+        let deadline = self.deadline();
+        loop {
+            let line = self.next_line(deadline)?;
+            if line.starts_with("+EVT:TXP2P") {
+                return Ok(TxReport::new(data.len(), None));
+            }
+            if line.starts_with("+EVT:") {
+                self.events.push_back(line);
+            }
+        }
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        if !self.rx_enabled {
+            return Err(ModemError::RxDisabled);
+        }
+        let deadline = self.deadline();
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                if event.starts_with("+EVT:RXP2P:") {
+                    return trace::parsed(&event, parse_rx_event(&event));
+                }
+                if event.starts_with("+EVT:RXP2P") {
+                    // reception ended, e.g. with `RECEIVE TIMEOUT`
+                    self.listening = false;
+                }
+                continue;
+            }
+            if !self.listening {
+                self.command(&format!("AT+PRECV={}", RX_CONTINUOUS))?;
+                self.listening = true;
+            }
+            let line = self.next_line(deadline)?;
+            if line.starts_with("+EVT:") {
+                self.events.push_back(line);
+            }
+        }
+    }
+    fn read_line(&mut self) -> Result<String> {
+        if let Some(event) = self.events.pop_front() {
+            return Ok(event);
+        }
+        let deadline = self.deadline();
+        self.next_line(deadline)
+    }
+}
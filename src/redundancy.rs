@@ -0,0 +1,270 @@
+use crate::hopping::ChannelPlan;
+use crate::radio::RadioParams;
+use crate::rng::Rng;
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    send_each, Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError,
+    ModemEvent, Result, RxPacket, Status, TxReport,
+};
+use std::collections::VecDeque;
+use std::thread;
+use std::time::Duration;
+
+/// Size of the message id prepended to every payload.
+pub const REDUNDANCY_HEADER_LEN: usize = 4;
+
+/// How often and where a `RedundantModem` repeats each frame
+#[derive(Debug, Clone)]
+pub struct Redundancy {
+    /// Number of times every frame is transmitted
+    pub copies: usize,
+    /// Pause between two copies, spreading them over bursts of interference
+    pub gap: Duration,
+    /// Hop to the next channel of the plan before every copy
+    pub plan: Option<ChannelPlan>,
+    /// Number of message ids remembered to drop duplicates
+    pub cache_size: usize,
+}
+
+impl Default for Redundancy {
+    fn default() -> Self {
+        Redundancy {
+            copies: 2,
+            gap: Duration::ZERO,
+            plan: None,
+            cache_size: 64,
+        }
+    }
+}
+
+/// Transmits every frame several times and delivers only the first copy received.
+///
+/// Payloads are prefixed with a 32 bit message id the receiver dedupes on, counted
+/// up from a random start so a restarted sender is not taken for a duplicate. This
+/// trades airtime for delivery probability where latency matters less. With a
+/// channel plan the copies are spread over its frequencies and the device is left
+/// on the channel of the last copy. The airtime of the returned `TxReport` is the
+/// sum over all copies.
+pub struct RedundantModem<T: LoraModemDevice> {
+    inner: T,
+    policy: Redundancy,
+    next_id: u32,
+    seen: VecDeque<u32>,
+    duplicates: usize,
+}
+
+impl<T: LoraModemDevice> RedundantModem<T> {
+    pub fn new(inner: T, policy: Redundancy) -> Self {
+        RedundantModem {
+            inner,
+            policy,
+            next_id: Rng::from_time().next_u64() as u32,
+            seen: VecDeque::new(),
+            duplicates: 0,
+        }
+    }
+    /// The policy frames are sent with.
+    pub fn policy(&self) -> &Redundancy {
+        &self.policy
+    }
+    /// Number of received copies dropped as duplicates.
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    // Prefix `data` with the id of a new message.
+    fn frame(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        if self.policy.copies == 0 {
+            return Err(ModemError::InvalidArgument("redundancy of 0 copies".into()));
+        }
+        let msg_id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        // peers repeating our own frames back must not deliver it to us
+        self.remember(msg_id);
+        let mut frame = Vec::with_capacity(REDUNDANCY_HEADER_LEN + data.len());
+        frame.extend_from_slice(&msg_id.to_be_bytes());
+        frame.extend_from_slice(data);
+        Ok(frame)
+    }
+    // Add a message to the dedupe cache, false if it was seen before.
+    fn remember(&mut self, msg_id: u32) -> bool {
+        if self.seen.contains(&msg_id) {
+            return false;
+        }
+        if self.seen.len() >= self.policy.cache_size.max(1) {
+            self.seen.pop_front();
+        }
+        self.seen.push_back(msg_id);
+        true
+    }
+}
+
+// One report for all copies of a frame, the first failed copy fails it.
+fn combine(copies: Vec<Result<TxReport>>) -> Result<TxReport> {
+    let mut airtime = Some(Duration::ZERO);
+    let mut last = None;
+    for copy in copies {
+        let report = copy?;
+        airtime = airtime.zip(report.airtime_estimate).map(|(a, b)| a + b);
+        last = Some(report);
+    }
+    let report = last.expect("at least one copy sent");
+    Ok(TxReport {
+        bytes: report.bytes.saturating_sub(REDUNDANCY_HEADER_LEN),
+        airtime_estimate: airtime,
+        ..report
+    })
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for RedundantModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        let frame = self.inner.max_payload()?;
+        Ok(frame.saturating_sub(REDUNDANCY_HEADER_LEN))
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.send_slice(&data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        let frame = self.frame(data)?;
+        let mut copies = Vec::with_capacity(self.policy.copies);
+        for copy in 0..self.policy.copies {
+            if copy > 0 && !self.policy.gap.is_zero() {
+                thread::sleep(self.policy.gap);
+            }
+            if let Some(plan) = self.policy.plan.as_mut() {
+                self.inner.hop_next(plan)?;
+            }
+            copies.push(Ok(self.inner.send_slice(&frame)?));
+        }
+        combine(copies)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        if self.policy.plan.is_some() || !self.policy.gap.is_zero() {
+            // spaced or hopping copies cannot go out back to back
+            return send_each(self, frames);
+        }
+        let copies = self.policy.copies;
+        let mut sent = Vec::with_capacity(frames.len() * copies);
+        for data in frames {
+            match self.frame(data) {
+                Ok(frame) => sent.extend(std::iter::repeat_n(frame, copies)),
+                Err(e) => return vec![Err(e)],
+            }
+        }
+        let sent: Vec<&[u8]> = sent.iter().map(Vec::as_slice).collect();
+        let mut results = self.inner.send_batch(&sent).into_iter();
+        let mut reports = Vec::with_capacity(frames.len());
+        for _ in frames {
+            let frame: Vec<Result<TxReport>> = results.by_ref().take(copies).collect();
+            if frame.is_empty() {
+                break;
+            }
+            let complete = frame.len() == copies;
+            reports.push(combine(frame));
+            if !complete {
+                break;
+            }
+        }
+        reports
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        loop {
+            let mut packet = self.inner.read_packet()?;
+            if packet.data.len() < REDUNDANCY_HEADER_LEN {
+                continue;
+            }
+            let msg_id = u32::from_be_bytes([
+                packet.data[0],
+                packet.data[1],
+                packet.data[2],
+                packet.data[3],
+            ]);
+            if !self.remember(msg_id) {
+                self.duplicates += 1;
+                continue;
+            }
+            packet.data.drain(..REDUNDANCY_HEADER_LEN);
+            return Ok(packet);
+        }
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
@@ -0,0 +1,421 @@
+#[cfg(feature = "std")]
+use crate::duty_cycle::{DutyCyclePolicy, DutyCycleTracker, SubBand};
+use crate::radio::{airtime, RadioParams};
+#[cfg(feature = "std")]
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    send_each, Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError,
+    ModemEvent, Result, RxPacket, Status, TxReport,
+};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+use core::time::Duration;
+
+/// Frequency range of a region with the rules applying to it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandRule {
+    /// Lower edge
+    pub min_freq: Frequency,
+    /// Upper edge
+    pub max_freq: Frequency,
+    /// Highest transmit power in dBm
+    pub max_tx_power: i8,
+    /// Allowed fraction of time on air, `None` if unrestricted
+    pub duty_cycle: Option<f32>,
+}
+
+impl BandRule {
+    const fn new(min_khz: u32, max_khz: u32, max_tx_power: i8, duty_cycle: Option<f32>) -> Self {
+        BandRule {
+            min_freq: Frequency::from_khz(min_khz),
+            max_freq: Frequency::from_khz(max_khz),
+            max_tx_power,
+            duty_cycle,
+        }
+    }
+    fn contains(&self, freq: Frequency) -> bool {
+        freq >= self.min_freq && freq <= self.max_freq
+    }
+}
+
+const EU868: &[BandRule] = &[
+    BandRule::new(863_000, 868_000, 14, Some(0.01)),
+    BandRule::new(868_000, 868_600, 14, Some(0.01)),
+    BandRule::new(868_700, 869_200, 14, Some(0.001)),
+    BandRule::new(869_400, 869_650, 27, Some(0.1)),
+    BandRule::new(869_700, 870_000, 14, Some(0.01)),
+];
+const EU433: &[BandRule] = &[BandRule::new(433_050, 434_790, 10, Some(0.1))];
+const US915: &[BandRule] = &[BandRule::new(902_000, 928_000, 30, None)];
+const AU915: &[BandRule] = &[BandRule::new(915_000, 928_000, 30, None)];
+const AS923: &[BandRule] = &[BandRule::new(915_000, 928_000, 16, None)];
+const IN865: &[BandRule] = &[BandRule::new(865_000, 867_000, 30, None)];
+const KR920: &[BandRule] = &[BandRule::new(920_900, 923_300, 14, None)];
+const CN470: &[BandRule] = &[BandRule::new(470_000, 510_000, 17, None)];
+
+/// Regulatory region, following the frequency plans of the LoRaWAN regional parameters
+///
+/// The limits are those for end devices without listen-before-talk and are meant
+/// to catch misconfiguration, they do not replace checking the local regulations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Region {
+    EU868,
+    EU433,
+    US915,
+    AU915,
+    AS923,
+    IN865,
+    KR920,
+    CN470,
+}
+
+const REGIONS: &[Region] = &[
+    Region::EU868,
+    Region::EU433,
+    Region::US915,
+    Region::AU915,
+    Region::AS923,
+    Region::IN865,
+    Region::KR920,
+    Region::CN470,
+];
+
+impl Region {
+    /// Name of the region as in the regional parameters, e.g. `EU868`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Region::EU868 => "EU868",
+            Region::EU433 => "EU433",
+            Region::US915 => "US915",
+            Region::AU915 => "AU915",
+            Region::AS923 => "AS923",
+            Region::IN865 => "IN865",
+            Region::KR920 => "KR920",
+            Region::CN470 => "CN470",
+        }
+    }
+    /// Frequency ranges usable in this region.
+    pub fn bands(self) -> &'static [BandRule] {
+        match self {
+            Region::EU868 => EU868,
+            Region::EU433 => EU433,
+            Region::US915 => US915,
+            Region::AU915 => AU915,
+            Region::AS923 => AS923,
+            Region::IN865 => IN865,
+            Region::KR920 => KR920,
+            Region::CN470 => CN470,
+        }
+    }
+    /// Rules applying on `freq`, `None` if transmitting there is not allowed.
+    pub fn band(self, freq: Frequency) -> Option<&'static BandRule> {
+        self.bands().iter().find(|band| band.contains(freq))
+    }
+    /// Longest allowed single transmission, `None` if unrestricted.
+    pub fn max_dwell_time(self) -> Option<Duration> {
+        match self {
+            Region::US915 | Region::AU915 | Region::AS923 => Some(Duration::from_millis(400)),
+            _ => None,
+        }
+    }
+
+    pub fn check_frequency(self, freq: Frequency) -> Result<&'static BandRule> {
+        self.band(freq).ok_or(ModemError::RegulatoryViolation(
+            RegulatoryViolation::FrequencyNotAllowed { region: self, freq },
+        ))
+    }
+    pub fn check_tx_power(self, freq: Frequency, dbm: i8) -> Result<()> {
+        let band = self.check_frequency(freq)?;
+        if dbm > band.max_tx_power {
+            return Err(ModemError::RegulatoryViolation(
+                RegulatoryViolation::TxPowerTooHigh {
+                    region: self,
+                    dbm,
+                    max: band.max_tx_power,
+                },
+            ));
+        }
+        Ok(())
+    }
+    pub fn check_airtime(self, airtime: Duration) -> Result<()> {
+        match self.max_dwell_time() {
+            Some(max) if airtime > max => Err(ModemError::RegulatoryViolation(
+                RegulatoryViolation::DwellTimeExceeded {
+                    region: self,
+                    airtime,
+                    max,
+                },
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Tracker enforcing the duty-cycle limits of this region.
+    #[cfg(feature = "std")]
+    pub fn duty_cycle_tracker(self, policy: DutyCyclePolicy) -> DutyCycleTracker {
+        let mut tracker = DutyCycleTracker::new();
+        for band in self.bands() {
+            if let Some(duty_cycle) = band.duty_cycle {
+                tracker.add_band(SubBand::new(
+                    band.min_freq,
+                    band.max_freq,
+                    duty_cycle,
+                    policy,
+                ));
+            }
+        }
+        tracker
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Region {
+    type Err = ModemError;
+
+    /// Region by its name, ignoring case.
+    fn from_str(s: &str) -> Result<Self> {
+        REGIONS
+            .iter()
+            .copied()
+            .find(|region| region.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| ModemError::InvalidArgument(format!("unknown region {}", s)))
+    }
+}
+
+/// Setting or transmission not permitted in a region
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegulatoryViolation {
+    /// The frequency lies outside the bands of the region
+    FrequencyNotAllowed { region: Region, freq: Frequency },
+    /// The transmit power exceeds the limit of the band
+    TxPowerTooHigh { region: Region, dbm: i8, max: i8 },
+    /// A single transmission would exceed the dwell time limit
+    DwellTimeExceeded {
+        region: Region,
+        airtime: Duration,
+        max: Duration,
+    },
+}
+
+impl fmt::Display for RegulatoryViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegulatoryViolation::FrequencyNotAllowed { region, freq } => {
+                write!(f, "{} MHz not allowed in {:?}", freq, region)
+            }
+            RegulatoryViolation::TxPowerTooHigh { region, dbm, max } => write!(
+                f,
+                "{} dBm exceed the {} dBm allowed in {:?}",
+                dbm, max, region
+            ),
+            RegulatoryViolation::DwellTimeExceeded {
+                region,
+                airtime,
+                max,
+            } => write!(
+                f,
+                "{:?} on air exceed the dwell time of {:?} in {:?}",
+                airtime, max, region
+            ),
+        }
+    }
+}
+
+/// Validates frequency, transmit power and dwell time against a region.
+///
+/// Settings violating the rules fail with `ModemError::RegulatoryViolation`
+/// before reaching the device. Duty-cycle limits are left to a `DutyCycleModem`,
+/// which can be set up with `Region::duty_cycle_tracker`.
+pub struct RegionModem<T: LoraModemDevice> {
+    inner: T,
+    region: Region,
+    // settings as last applied, queried from the device when unknown
+    frequency: Option<Frequency>,
+    tx_power: Option<i8>,
+    params: Option<RadioParams>,
+}
+
+impl<T: LoraModemDevice> RegionModem<T> {
+    pub fn new(inner: T, region: Region) -> Self {
+        RegionModem {
+            inner,
+            region,
+            frequency: None,
+            tx_power: None,
+            params: None,
+        }
+    }
+    pub fn region(&self) -> Region {
+        self.region
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn frequency(&mut self) -> Result<Frequency> {
+        match self.frequency {
+            Some(freq) => Ok(freq),
+            None => {
+                let freq = self.inner.config()?.frequency;
+                self.frequency = Some(freq);
+                Ok(freq)
+            }
+        }
+    }
+    fn params(&mut self) -> Result<RadioParams> {
+        if let Some(params) = self.params {
+            return Ok(params);
+        }
+        let params = match self.inner.get_radio_params() {
+            Ok(params) => params,
+            Err(ModemError::Unsupported(_)) | Err(ModemError::UnsupportedCommand(_)) => {
+                self.inner.config()?.config.into()
+            }
+            Err(e) => return Err(e),
+        };
+        self.params = Some(params);
+        Ok(params)
+    }
+    // Whether a frame of `len` bytes may be sent on the current frequency.
+    fn check_send(&mut self, len: usize) -> Result<()> {
+        let freq = self.frequency()?;
+        self.region.check_frequency(freq)?;
+        if self.region.max_dwell_time().is_some() {
+            let params = self.params()?;
+            self.region.check_airtime(airtime(len, &params)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for RegionModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.frequency = None;
+        self.tx_power = None;
+        self.params = None;
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.region.check_frequency(freq)?;
+        if let Some(dbm) = self.tx_power {
+            self.region.check_tx_power(freq, dbm)?;
+        }
+        self.inner.set_frequency(freq)?;
+        self.frequency = Some(freq);
+        Ok(())
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.inner.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)?;
+        self.params = Some(mode.into());
+        Ok(())
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)?;
+        self.params = Some(params);
+        Ok(())
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        let freq = self.frequency()?;
+        self.region.check_tx_power(freq, dbm)?;
+        self.inner.set_tx_power(dbm)?;
+        self.tx_power = Some(dbm);
+        Ok(())
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        self.inner.max_payload()
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    #[cfg(feature = "std")]
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.inner.stats()
+    }
+    #[cfg(feature = "std")]
+    fn reset_stats(&mut self) -> Result<()> {
+        self.inner.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.check_send(data.len())?;
+        self.inner.send_data(data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        self.check_send(data.len())?;
+        self.inner.send_slice(data)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        if frames
+            .iter()
+            .all(|frame| self.check_send(frame.len()).is_ok())
+        {
+            self.inner.send_batch(frames)
+        } else {
+            // the violating frame fails on its own
+            send_each(self, frames)
+        }
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        self.inner.read_packet()
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
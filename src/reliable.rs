@@ -0,0 +1,302 @@
+use crate::addressing::{AddressedModem, AddressedPacket, BROADCAST};
+use crate::incoming::bad_packet;
+use crate::radio::RadioParams;
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError, ModemEvent, Result,
+    RxPacket, Status, TxReport,
+};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Header flag marking a frame that has to be acknowledged.
+pub const FLAG_RELIABLE: u8 = 0x01;
+/// Header flag marking an acknowledgement.
+///
+/// Acknowledgements carry the sequence number of the frame and the SNR it was
+/// received with, in quarter dB.
+pub const FLAG_ACK: u8 = 0x02;
+
+/// Retransmission settings of a `ReliableModem`
+#[derive(Debug, Clone)]
+pub struct ArqConfig {
+    /// Retransmissions after the first attempt
+    pub max_retries: usize,
+    /// Time to wait for the first acknowledgement
+    pub ack_timeout: Duration,
+    /// Factor the timeout grows by with every retransmission
+    pub backoff: u32,
+    /// Longest the timeout grows to
+    pub max_ack_timeout: Duration,
+}
+
+impl Default for ArqConfig {
+    fn default() -> Self {
+        ArqConfig {
+            max_retries: 3,
+            ack_timeout: Duration::from_secs(2),
+            backoff: 2,
+            max_ack_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// How a frame sent with `ReliableModem::deliver` was acknowledged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Delivery {
+    /// Transmissions it took, 1 without retransmission
+    pub attempts: usize,
+    /// SNR in dB the peer received the frame with, `None` for peers not reporting it
+    pub peer_snr: Option<f32>,
+}
+
+/// Stop-and-wait ARQ on top of the addressing layer.
+///
+/// Frames sent with `send_reliable` carry a sequence number and are retransmitted
+/// until the peer acknowledges them. Received reliable frames are acknowledged
+/// automatically and duplicates are dropped. Waiting for acknowledgements is
+/// bounded by the read timeout of the underlying device.
+pub struct ReliableModem<T: LoraModemDevice> {
+    link: AddressedModem<T>,
+    config: ArqConfig,
+    next_seq: HashMap<u8, u8>,
+    last_seen: HashMap<u8, u8>,
+    inbox: VecDeque<AddressedPacket>,
+    retransmits: usize,
+    retransmits_base: usize,
+}
+
+impl<T: LoraModemDevice> ReliableModem<T> {
+    pub fn new(link: AddressedModem<T>, config: ArqConfig) -> Self {
+        ReliableModem {
+            link,
+            config,
+            next_seq: HashMap::new(),
+            last_seen: HashMap::new(),
+            inbox: VecDeque::new(),
+            retransmits: 0,
+            retransmits_base: 0,
+        }
+    }
+    /// The addressing layer below.
+    pub fn link(&mut self) -> &mut AddressedModem<T> {
+        &mut self.link
+    }
+    /// Unwrap the addressing layer.
+    pub fn into_inner(self) -> AddressedModem<T> {
+        self.link
+    }
+    /// Total number of retransmissions so far.
+    pub fn retransmits(&self) -> usize {
+        self.retransmits
+    }
+
+    /// Send `data` to `dst` and wait until it has been acknowledged.
+    pub fn send_reliable(&mut self, dst: u8, data: &[u8]) -> Result<usize> {
+        self.deliver(dst, 0, data).map(|_| data.len())
+    }
+    /// Send `data` to `dst` with additional header `flags` and wait until it has been acknowledged.
+    pub fn deliver(&mut self, dst: u8, flags: u8, data: &[u8]) -> Result<Delivery> {
+        if dst == BROADCAST {
+            return Err(ModemError::InvalidArgument(
+                "reliable delivery to broadcast address".into(),
+            ));
+        }
+        let seq = {
+            let next = self.next_seq.entry(dst).or_insert(0);
+            let seq = *next;
+            *next = next.wrapping_add(1);
+            seq
+        };
+        let mut frame = Vec::with_capacity(data.len() + 1);
+        frame.push(seq);
+        frame.extend_from_slice(data);
+        let mut timeout = self.config.ack_timeout;
+        for attempt in 0..=self.config.max_retries {
+            if attempt > 0 {
+                self.retransmits += 1;
+            }
+            self.link
+                .send_with_flags(dst, flags | FLAG_RELIABLE, &frame)?;
+            if let Some(ack) = self.wait_for_ack(dst, seq, timeout)? {
+                return Ok(Delivery {
+                    attempts: attempt + 1,
+                    peer_snr: ack.packet.data.get(1).map(|&q| f32::from(q as i8) / 4.0),
+                });
+            }
+            timeout = timeout
+                .checked_mul(self.config.backoff)
+                .unwrap_or(Duration::MAX)
+                .min(self.config.max_ack_timeout);
+        }
+        Err(ModemError::NotAcknowledged {
+            attempts: self.config.max_retries + 1,
+        })
+    }
+
+    // Process incoming frames until the ack for `seq` from `peer` arrives or `timeout` passed.
+    fn wait_for_ack(
+        &mut self,
+        peer: u8,
+        seq: u8,
+        timeout: Duration,
+    ) -> Result<Option<AddressedPacket>> {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            let frame = match self.link.read_packet_for_me() {
+                Ok(frame) => frame,
+                Err(ModemError::Timeout) => continue,
+                // a corrupted frame may have been the ack, a retransmission follows
+                Err(e) if bad_packet(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            if frame.header.flags & FLAG_ACK != 0 {
+                if frame.header.src == peer && frame.packet.data.first() == Some(&seq) {
+                    return Ok(Some(frame));
+                }
+            } else if let Some(frame) = self.accept(frame)? {
+                self.inbox.push_back(frame);
+            }
+        }
+        Ok(None)
+    }
+
+    // Acknowledge reliable frames and filter duplicates, returns frames for the application.
+    fn accept(&mut self, mut frame: AddressedPacket) -> Result<Option<AddressedPacket>> {
+        let header = frame.header;
+        if header.flags & FLAG_ACK != 0 {
+            return Ok(None);
+        }
+        if header.flags & FLAG_RELIABLE == 0 {
+            return Ok(Some(frame));
+        }
+        let seq = match frame.packet.data.first() {
+            Some(&seq) => seq,
+            None => return Ok(None),
+        };
+        if header.dst != self.link.node_id() {
+            // overheard in promiscuous mode, not ours to acknowledge
+            frame.packet.data.remove(0);
+            return Ok(Some(frame));
+        }
+        let snr = (frame.packet.snr * 4.0).round().clamp(-128.0, 127.0) as i8;
+        self.link
+            .send_with_flags(header.src, FLAG_ACK, &[seq, snr as u8])?;
+        if self.last_seen.insert(header.src, seq) == Some(seq) {
+            return Ok(None);
+        }
+        frame.packet.data.remove(0);
+        Ok(Some(frame))
+    }
+
+    /// Receive the next frame for this node, acknowledging it if required.
+    pub fn receive(&mut self) -> Result<AddressedPacket> {
+        if let Some(frame) = self.inbox.pop_front() {
+            return Ok(frame);
+        }
+        loop {
+            let frame = self.link.read_packet_for_me()?;
+            if let Some(frame) = self.accept(frame)? {
+                return Ok(frame);
+            }
+        }
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for ReliableModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.link.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.link.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.link.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.link.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.link.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.link.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.link.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.link.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.link.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.link.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.link.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.link.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.link.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.link.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.link.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        self.link.max_payload()
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.link.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.link.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.link.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.link.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.link.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.link.wake()
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        let mut stats = self.link.stats()?;
+        stats.retransmits += self.retransmits - self.retransmits_base;
+        Ok(stats)
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.link.reset_stats()?;
+        self.retransmits_base = self.retransmits;
+        Ok(())
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.link.send_data(data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        self.link.send_slice(data)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        self.link.send_batch(frames)
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        self.receive().map(|frame| frame.packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.link.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.link.next_event()
+    }
+}
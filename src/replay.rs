@@ -0,0 +1,269 @@
+use crate::rf95::Rf95Modem;
+use crate::transport::Transport;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+// Prefixes of lines sent to and received from the modem in a trace.
+pub(crate) const SENT: &str = "> ";
+pub(crate) const RECEIVED: &str = "< ";
+
+/// rf95modem replaying a session recorded by a `RecordingTransport`.
+pub type ReplayModem = Rf95Modem<ReplayTransport>;
+
+impl ReplayModem {
+    /// Replay the trace file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Rf95Modem::from_transport(ReplayTransport::from_file(path)?))
+    }
+    /// Replay a trace given as text.
+    pub fn from_trace(trace: &str) -> Self {
+        Rf95Modem::from_transport(ReplayTransport::from_trace(trace))
+    }
+}
+
+// Splits a byte stream into lines, the newline is dropped.
+#[derive(Default)]
+pub(crate) struct LineSplitter {
+    buf: Vec<u8>,
+}
+
+impl LineSplitter {
+    pub(crate) fn push(&mut self, bytes: &[u8], mut line: impl FnMut(String)) {
+        for &byte in bytes {
+            if byte == b'\n' {
+                line(
+                    String::from_utf8_lossy(&self.buf)
+                        .trim_end_matches('\r')
+                        .to_string(),
+                );
+                self.buf.clear();
+            } else {
+                self.buf.push(byte);
+            }
+        }
+    }
+}
+
+/// Transport recording every line exchanged with the modem into a trace.
+///
+/// The trace holds one line per line of the session, prefixed with `> ` when
+/// sent to the modem and `< ` when received from it, as read by `ReplayTransport`.
+pub struct RecordingTransport<T: Transport, W: Write> {
+    inner: T,
+    trace: W,
+    sent: LineSplitter,
+    received: LineSplitter,
+}
+
+impl<T: Transport> RecordingTransport<T, BufWriter<File>> {
+    /// Record into the file at `path`, created or truncated.
+    pub fn create<P: AsRef<Path>>(inner: T, path: P) -> io::Result<Self> {
+        Ok(RecordingTransport::new(
+            inner,
+            BufWriter::new(File::create(path)?),
+        ))
+    }
+}
+
+impl<T: Transport, W: Write> RecordingTransport<T, W> {
+    pub fn new(inner: T, trace: W) -> Self {
+        RecordingTransport {
+            inner,
+            trace,
+            sent: LineSplitter::default(),
+            received: LineSplitter::default(),
+        }
+    }
+    /// Flush the trace and return transport and trace writer.
+    pub fn into_inner(mut self) -> io::Result<(T, W)> {
+        self.trace.flush()?;
+        Ok((self.inner, self.trace))
+    }
+
+    fn record(trace: &mut W, prefix: &str, lines: &mut Vec<String>) -> io::Result<()> {
+        for line in lines.drain(..) {
+            writeln!(trace, "{}{}", prefix, line)?;
+        }
+        trace.flush()
+    }
+}
+
+impl<T: Transport, W: Write> Transport for RecordingTransport<T, W> {
+    fn open(&mut self) -> io::Result<()> {
+        self.inner.open()
+    }
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+    fn set_open_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_open_timeout(timeout)
+    }
+    fn take_reconnected(&mut self) -> bool {
+        self.inner.take_reconnected()
+    }
+}
+
+impl<T: Transport, W: Write> Read for RecordingTransport<T, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let mut lines = Vec::new();
+        self.received.push(&buf[..n], |line| lines.push(line));
+        Self::record(&mut self.trace, RECEIVED, &mut lines)?;
+        Ok(n)
+    }
+}
+
+impl<T: Transport, W: Write> Write for RecordingTransport<T, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        let mut lines = Vec::new();
+        self.sent.push(&buf[..n], |line| lines.push(line));
+        Self::record(&mut self.trace, SENT, &mut lines)?;
+        Ok(n)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// `line` without a leading `<seconds>.<millis> ` timestamp.
+fn strip_timestamp(line: &str) -> &str {
+    match line.split_once(' ') {
+        Some((time, rest))
+            if !time.is_empty() && time.bytes().all(|b| b.is_ascii_digit() || b == b'.') =>
+        {
+            rest
+        }
+        _ => line,
+    }
+}
+
+enum TraceLine {
+    Sent(String),
+    Received(String),
+}
+
+/// Transport playing back a trace recorded by a `RecordingTransport`.
+///
+/// Received lines are handed out once every line sent before them in the
+/// trace was written, reads in between time out. Written lines are compared
+/// with the trace, a divergence fails with `io::ErrorKind::InvalidData` unless
+/// strict checking is disabled. Past the end of the trace reads return no data.
+pub struct ReplayTransport {
+    lines: VecDeque<TraceLine>,
+    // bytes of the current received line not read yet
+    out: VecDeque<u8>,
+    written: LineSplitter,
+    strict: bool,
+    open: bool,
+}
+
+impl ReplayTransport {
+    /// Load the trace file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(ReplayTransport::from_trace(&std::fs::read_to_string(path)?))
+    }
+    /// Parse a trace given as text, lines without a known prefix are ignored.
+    ///
+    /// Lines may start with the timestamp of a `WireLog`.
+    pub fn from_trace(trace: &str) -> Self {
+        let lines = trace
+            .lines()
+            .map(strip_timestamp)
+            .filter_map(|line| {
+                if let Some(sent) = line.strip_prefix(SENT) {
+                    Some(TraceLine::Sent(sent.to_string()))
+                } else {
+                    line.strip_prefix(RECEIVED)
+                        .map(|received| TraceLine::Received(received.to_string()))
+                }
+            })
+            .collect();
+        ReplayTransport {
+            lines,
+            out: VecDeque::new(),
+            written: LineSplitter::default(),
+            strict: true,
+            open: false,
+        }
+    }
+    /// Compare written lines with the trace, enabled by default.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+    /// Lines of the trace not replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.lines.len()
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn open(&mut self) -> io::Result<()> {
+        self.open = true;
+        Ok(())
+    }
+    fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+impl Read for ReplayTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.out.is_empty() {
+            match self.lines.front() {
+                Some(TraceLine::Received(_)) => {
+                    if let Some(TraceLine::Received(line)) = self.lines.pop_front() {
+                        self.out.extend(line.bytes().chain(b"\r\n".iter().copied()));
+                    }
+                }
+                // the modem answers once the command was sent
+                Some(TraceLine::Sent(_)) => return Err(io::ErrorKind::TimedOut.into()),
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.out.len());
+        for (slot, byte) in buf.iter_mut().zip(self.out.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for ReplayTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut lines = Vec::new();
+        self.written.push(buf, |line| lines.push(line));
+        for line in lines {
+            // received lines still queued before the command are dropped
+            while let Some(TraceLine::Received(_)) = self.lines.front() {
+                self.lines.pop_front();
+            }
+            match self.lines.pop_front() {
+                Some(TraceLine::Sent(expected)) if !self.strict || expected == line => {}
+                Some(TraceLine::Sent(expected)) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "replay diverged, expected `{}` but got `{}`",
+                            expected, line
+                        ),
+                    ))
+                }
+                _ if self.strict => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("replay diverged, `{}` sent past the end of the trace", line),
+                    ))
+                }
+                _ => {}
+            }
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,778 @@
+use crate::error::tx_rejected;
+use crate::event::ModemEvent;
+use crate::hex::{HexEncoder, HexFormat};
+use crate::line::{parse_cad, parse_sent, LineKind};
+use crate::radio::{
+    parse_radio_params, validate_tx_power, HeaderMode, RadioParams, SYNC_WORD_PRIVATE,
+};
+use crate::trace;
+use crate::transport::Transport;
+use crate::{
+    check_payload, Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError,
+    ModemTelemetry, RadioState, Result, RxPacket, RxPacketRef, Status, TxRejection, TxReport,
+};
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Deadlines for the blocking operations of an `Rf95Modem`, `None` waits forever
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Opening the transport, if the transport can bound it
+    pub open: Option<Duration>,
+    /// Complete response to a command
+    pub command: Option<Duration>,
+    /// Waiting for a received packet or line
+    pub rx: Option<Duration>,
+    /// Confirmation of a transmission by `+SENT`
+    pub tx_confirm: Option<Duration>,
+}
+
+impl Timeouts {
+    /// The same timeout for every operation.
+    pub fn all(timeout: Option<Duration>) -> Self {
+        Timeouts {
+            open: timeout,
+            command: timeout,
+            rx: timeout,
+            tx_confirm: timeout,
+        }
+    }
+}
+
+// Received packets buffered by default.
+const DEFAULT_RX_CAPACITY: usize = 256;
+
+/// What an `Rf95Modem` does with a packet arriving while its receive buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Make room by dropping the oldest buffered packet
+    #[default]
+    DropOldest,
+    /// Drop the arriving packet
+    DropNewest,
+    /// Stop reading, commands fail with `ModemError::BufferOverflow` until packets are read.
+    /// Packets arriving during a command already running are dropped.
+    Block,
+}
+
+/// How commands are written to firmware with small receive buffers
+///
+/// The default writes every line at once and does not retry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WritePacing {
+    /// Bytes written at once, 0 writes the whole line
+    pub chunk: usize,
+    /// Pause after every chunk, giving the firmware time to drain its buffer
+    pub delay: Duration,
+    /// Times a transmission refused with a full buffer or a bare `+ERROR` is repeated
+    pub retries: usize,
+    /// Pause before repeating a transmission
+    pub retry_delay: Duration,
+}
+
+impl WritePacing {
+    /// Write `chunk` bytes at a time with `delay` in between.
+    pub fn chunked(chunk: usize, delay: Duration) -> Self {
+        WritePacing {
+            chunk,
+            delay,
+            ..WritePacing::default()
+        }
+    }
+    /// Repeat refused transmissions up to `retries` times, `delay` apart.
+    pub fn with_retries(self, retries: usize, delay: Duration) -> Self {
+        WritePacing {
+            retries,
+            retry_delay: delay,
+            ..self
+        }
+    }
+}
+
+// Whether a transmission failed because the firmware lost part of the command line.
+fn is_overrun(e: &ModemError) -> bool {
+    match e {
+        ModemError::TxRejected(TxRejection::BufferFull) => true,
+        // a truncated line fails to parse without further explanation
+        ModemError::TxRejected(TxRejection::Other(msg)) => {
+            matches!(msg.trim(), "+ERROR" | "ERROR" | "+ERROR:")
+        }
+        _ => false,
+    }
+}
+
+// How long probing for a command echo waits if commands have no timeout.
+const ECHO_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Framing of the lines exchanged with the firmware
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineProtocol {
+    /// Sent after every command, received lines may end in `\n` or `\r\n` either way
+    pub terminator: String,
+    /// Whether the firmware echoes commands, `None` probes with `AT` on `open()`
+    pub echo: Option<bool>,
+    /// Prompt the firmware prints in front of lines, e.g. `"> "`
+    pub prompt: Option<String>,
+}
+
+impl Default for LineProtocol {
+    fn default() -> Self {
+        LineProtocol {
+            terminator: "\n".to_string(),
+            echo: Some(false),
+            prompt: None,
+        }
+    }
+}
+
+/// Modem running the rf95modem firmware, reachable over any `Transport`.
+///
+/// Every command is acknowledged with `+OK` or rejected with `+ERROR`/`+FAIL`,
+/// except `AT+TX` which is confirmed with `+SENT <n> bytes`. Packets received
+/// while waiting for a command response are kept and handed out by later reads.
+/// This buffer holds up to 256 packets by default, what happens beyond is set by
+/// an `OverflowPolicy` and dropped packets are counted in `Status::rx_dropped`.
+///
+/// After the transport reconnected, frequency, radio settings and transmit power
+/// are restored and `ModemEvent::Reconnected` is queued. A command interrupted by
+/// the reconnect fails with `ModemError::Disconnected`.
+pub struct Rf95Modem<T: Transport> {
+    transport: T,
+    timeouts: Timeouts,
+    protocol: LineProtocol,
+    pacing: WritePacing,
+    // `AT+TX` line reused for every transmission
+    tx_line: String,
+    echo: bool,
+    buf: Vec<u8>,
+    pending: VecDeque<(String, SystemTime)>,
+    rx_capacity: usize,
+    overflow: OverflowPolicy,
+    rx_dropped: usize,
+    last_line_at: SystemTime,
+    frequency: Option<Frequency>,
+    frequency_offset: i32,
+    mode: Option<ModemConfig>,
+    radio_params: Option<RadioParams>,
+    // header mode set by the latest `set_radio_params`, `+RX` lines are parsed for it
+    header: HeaderMode,
+    // sync word set by the latest `set_radio_params`
+    sync_word: u8,
+    tx_power: Option<i8>,
+    rx_enabled: bool,
+    radio_state: RadioState,
+    capabilities: Option<Capabilities>,
+    // largest payload reported by the latest `config()`
+    max_payload: Option<usize>,
+    reconnects: usize,
+    events: VecDeque<ModemEvent>,
+}
+
+impl<T: Transport> Rf95Modem<T> {
+    /// Create a modem on top of an (unopened) transport.
+    pub fn from_transport(transport: T) -> Self {
+        Rf95Modem {
+            transport,
+            timeouts: Timeouts::default(),
+            protocol: LineProtocol::default(),
+            pacing: WritePacing::default(),
+            tx_line: String::new(),
+            echo: false,
+            buf: Vec::new(),
+            pending: VecDeque::new(),
+            rx_capacity: DEFAULT_RX_CAPACITY,
+            overflow: OverflowPolicy::default(),
+            rx_dropped: 0,
+            last_line_at: SystemTime::now(),
+            frequency: None,
+            frequency_offset: 0,
+            mode: None,
+            radio_params: None,
+            header: HeaderMode::Explicit,
+            sync_word: SYNC_WORD_PRIVATE,
+            tx_power: None,
+            rx_enabled: true,
+            radio_state: RadioState::Active,
+            capabilities: None,
+            max_payload: None,
+            reconnects: 0,
+            events: VecDeque::new(),
+        }
+    }
+    /// Bound commands, transmissions and reads by `timeout`, `None` blocks forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeouts = Timeouts {
+            open: self.timeouts.open,
+            ..Timeouts::all(timeout)
+        };
+    }
+    /// Currently configured read timeout.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeouts.rx
+    }
+    /// Set the deadlines of all blocking operations.
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+    /// Currently configured deadlines.
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+    /// Set the line framing, takes effect on the next `open()`.
+    pub fn set_line_protocol(&mut self, protocol: LineProtocol) {
+        self.protocol = protocol;
+    }
+    /// Configured line framing.
+    pub fn line_protocol(&self) -> &LineProtocol {
+        &self.protocol
+    }
+    /// Set how lines are written and refused transmissions repeated.
+    pub fn set_write_pacing(&mut self, pacing: WritePacing) {
+        self.pacing = pacing;
+    }
+    /// Configured write pacing.
+    pub fn write_pacing(&self) -> WritePacing {
+        self.pacing
+    }
+    /// Whether commands are echoed, as configured or detected on `open()`.
+    pub fn echo(&self) -> bool {
+        self.echo
+    }
+    /// Buffer up to `capacity` received packets, at least one, handling more by `policy`.
+    pub fn set_rx_buffer(&mut self, capacity: usize, policy: OverflowPolicy) {
+        self.rx_capacity = capacity.max(1);
+        self.overflow = policy;
+    }
+    /// Packets dropped so far because the receive buffer was full.
+    pub fn rx_dropped(&self) -> usize {
+        self.rx_dropped
+    }
+    // Capacity and overflow policy set with `set_rx_buffer`.
+    pub(crate) fn rx_buffer(&self) -> (usize, OverflowPolicy) {
+        (self.rx_capacity, self.overflow)
+    }
+    /// Move packets already waiting on the transport into the receive buffer
+    /// without blocking, returns the number of buffered packets.
+    ///
+    /// Call it regularly while packets are not read, other output is discarded.
+    /// With `OverflowPolicy::Block` reading stops once the buffer is full and
+    /// the rest is left to the transport.
+    pub fn buffer_rx(&mut self) -> Result<usize> {
+        while !self.rx_blocked() {
+            match self.poll_line(Some(Instant::now()))? {
+                Some(line) if LineKind::of(&line) == LineKind::Rx => self.buffer_packet(line),
+                Some(_) => {}
+                None => break,
+            }
+        }
+        Ok(self.pending.len())
+    }
+    /// Next queued event, if any, without reading from the modem.
+    pub fn poll_event(&mut self) -> Option<ModemEvent> {
+        self.events.pop_front()
+    }
+    /// Read a packet, waiting at most `timeout` regardless of the configured read timeout.
+    pub fn read_packet_timeout(&mut self, timeout: Duration) -> Result<RxPacket> {
+        self.read_packet_until(Some(Instant::now() + timeout))
+    }
+    /// Access the underlying transport.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+    /// Mutably access the underlying transport.
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+    /// Consume the modem and return the underlying transport.
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    pub(crate) fn write_line(&mut self, line: &str) -> Result<()> {
+        if !self.transport.is_open() {
+            return Err(ModemError::NotOpen);
+        }
+        self.check_reconnect()?;
+        trace::line_out(line);
+        if self.pacing.chunk == 0 {
+            self.transport.write_all(line.as_bytes())?;
+            self.transport
+                .write_all(self.protocol.terminator.as_bytes())?;
+            self.transport.flush()?;
+            return Ok(());
+        }
+        let mut bytes = line.as_bytes().to_vec();
+        bytes.extend_from_slice(self.protocol.terminator.as_bytes());
+        for chunk in bytes.chunks(self.pacing.chunk) {
+            self.transport.write_all(chunk)?;
+            self.transport.flush()?;
+            if !self.pacing.delay.is_zero() {
+                thread::sleep(self.pacing.delay);
+            }
+        }
+        Ok(())
+    }
+
+    // Read the next line from the transport, failing with a timeout once `deadline` passed.
+    fn next_line(&mut self, deadline: Option<Instant>) -> Result<String> {
+        match self.poll_line(deadline)? {
+            Some(line) => Ok(line),
+            None => Err(ModemError::Timeout),
+        }
+    }
+
+    // Header mode `+RX` lines are parsed for, as set by `set_radio_params`.
+    pub(crate) fn header(&self) -> HeaderMode {
+        self.header
+    }
+
+    // Hand over the buffered `+RX` lines with the time each was read.
+    pub(crate) fn take_pending(&mut self) -> VecDeque<(String, SystemTime)> {
+        core::mem::take(&mut self.pending)
+    }
+
+    // Read the next line, returning `None` once `deadline` passed without a complete line.
+    pub(crate) fn poll_line(&mut self, deadline: Option<Instant>) -> Result<Option<String>> {
+        if !self.transport.is_open() {
+            return Err(ModemError::NotOpen);
+        }
+        let mut byte = [0u8; 1];
+        loop {
+            match self.transport.read(&mut byte) {
+                Ok(0) => return Err(ModemError::Disconnected),
+                Ok(_) => {
+                    if byte[0] == b'\n' {
+                        let mut line = String::from_utf8_lossy(&self.buf)
+                            .trim_end_matches('\r')
+                            .to_string();
+                        self.buf.clear();
+                        trace::line_in(&line);
+                        if let Some(prompt) = self.protocol.prompt.as_deref() {
+                            while !prompt.is_empty() && line.starts_with(prompt) {
+                                line.drain(..prompt.len());
+                            }
+                        }
+                        return Ok(Some(line));
+                    }
+                    self.buf.push(byte[0]);
+                }
+                Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock => {
+                    // output of the old connection is lost, stop waiting for it
+                    if self.check_reconnect()? {
+                        return Ok(None);
+                    }
+                    if deadline.is_some_and(|d| Instant::now() >= d) {
+                        return Ok(None);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    // Restore the configuration if the transport reconnected, returns whether it had.
+    fn check_reconnect(&mut self) -> Result<bool> {
+        if !self.transport.take_reconnected() {
+            return Ok(false);
+        }
+        self.buf.clear();
+        self.capabilities = None;
+        self.reconnects += 1;
+        if let Some(freq) = self.frequency {
+            self.set_frequency(freq)?;
+        }
+        if let Some(params) = self.radio_params {
+            self.set_radio_params(params)?;
+        } else if let Some(mode) = self.mode {
+            self.set_mode(mode)?;
+        }
+        if let Some(dbm) = self.tx_power {
+            self.set_tx_power(dbm)?;
+        }
+        if !self.rx_enabled {
+            self.disable_rx()?;
+        }
+        match self.radio_state {
+            RadioState::Sleep => self.sleep()?,
+            RadioState::Standby => self.standby()?,
+            RadioState::Active => {}
+        }
+        self.events.push_back(ModemEvent::Reconnected);
+        Ok(true)
+    }
+
+    // Keep a received packet for later reads, dropping one if the buffer is full.
+    fn buffer_packet(&mut self, line: String) {
+        if self.pending.len() >= self.rx_capacity {
+            match self.overflow {
+                OverflowPolicy::DropOldest => {
+                    self.pending.pop_front();
+                    self.rx_dropped += 1;
+                }
+                // a packet read in the middle of a command cannot be left to the transport
+                OverflowPolicy::DropNewest | OverflowPolicy::Block => {
+                    self.rx_dropped += 1;
+                    return;
+                }
+            }
+        }
+        self.pending.push_back((line, SystemTime::now()));
+    }
+
+    // The buffer is full and no packet may be dropped.
+    fn rx_blocked(&self) -> bool {
+        self.overflow == OverflowPolicy::Block && self.pending.len() >= self.rx_capacity
+    }
+
+    // Probe with `AT` whether the firmware echoes commands, silence counts as no echo.
+    fn detect_echo(&mut self) -> Result<bool> {
+        let timeout = self.timeouts.command.unwrap_or(ECHO_PROBE_TIMEOUT);
+        let deadline = Some(Instant::now() + timeout);
+        self.write_line("AT")?;
+        let mut echo = false;
+        while let Some(line) = self.poll_line(deadline)? {
+            match LineKind::of(&line) {
+                LineKind::Ok | LineKind::Error => break,
+                LineKind::Rx => self.buffer_packet(line),
+                _ if line == "AT" => echo = true,
+                _ => {}
+            }
+        }
+        Ok(echo)
+    }
+
+    // Fail with `ModemError::Unsupported` unless the firmware has `cap`.
+    fn require(&mut self, cap: Capabilities) -> Result<()> {
+        if self.capabilities()?.contains(cap) {
+            Ok(())
+        } else {
+            Err(ModemError::Unsupported(cap))
+        }
+    }
+
+    // Next line, buffered ones first, waiting for new output until `deadline`.
+    fn read_line_until(&mut self, deadline: Option<Instant>) -> Result<String> {
+        if let Some((line, at)) = self.pending.pop_front() {
+            self.last_line_at = at;
+            return Ok(line);
+        }
+        let line = self.next_line(deadline)?;
+        self.last_line_at = SystemTime::now();
+        Ok(line)
+    }
+
+    // Next received packet, skipping other output, waiting until `deadline`.
+    fn read_packet_until(&mut self, deadline: Option<Instant>) -> Result<RxPacket> {
+        let listening = self.rx_enabled && self.radio_state == RadioState::Active;
+        if !listening && self.pending.is_empty() {
+            return Err(ModemError::RxDisabled);
+        }
+        loop {
+            let line = self.read_line_until(deadline)?;
+            if LineKind::of(&line) == LineKind::Rx {
+                let parsed = RxPacketRef::parse(&line, self.header).map(|p| p.to_packet());
+                let mut packet = trace::parsed(&line, parsed)?;
+                packet.received_at = self.last_line_at;
+                return Ok(packet);
+            }
+        }
+    }
+
+    // Send a command and collect all response lines up to and including the final one.
+    fn command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.command_within(cmd, self.timeouts.command)
+    }
+
+    // Like `command`, but the whole response has to arrive within `timeout`.
+    fn command_within(&mut self, cmd: &str, timeout: Option<Duration>) -> Result<Vec<String>> {
+        let span = trace::command(cmd);
+        let _entered = span.enter();
+        let started = Instant::now();
+        let result = self.exchange(cmd, timeout);
+        trace::finished(started, &result);
+        result
+    }
+
+    // Exchange of `command_within` without the instrumentation.
+    fn exchange(&mut self, cmd: &str, timeout: Option<Duration>) -> Result<Vec<String>> {
+        if self.rx_blocked() {
+            return Err(ModemError::BufferOverflow);
+        }
+        let deadline = timeout.map(|t| Instant::now() + t);
+        self.write_line(cmd)?;
+        let reconnects = self.reconnects;
+        let mut echo = self.echo;
+        let mut lines = Vec::new();
+        loop {
+            let line = match self.next_line(deadline) {
+                Err(ModemError::Timeout) if self.reconnects != reconnects => {
+                    return Err(ModemError::Disconnected)
+                }
+                result => result?,
+            };
+            match LineKind::of(&line) {
+                LineKind::Rx => self.buffer_packet(line),
+                LineKind::Error => return Err(ModemError::ModemReported(line)),
+                LineKind::Ok | LineKind::Sent => {
+                    lines.push(line);
+                    return Ok(lines);
+                }
+                LineKind::Other if echo && line == cmd => echo = false,
+                LineKind::Other => lines.push(line),
+            }
+        }
+    }
+}
+
+impl<T: Transport> LoraModemDevice for Rf95Modem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.transport.set_open_timeout(self.timeouts.open);
+        self.transport.open()?;
+        // a fresh connection is configured by the caller
+        self.transport.take_reconnected();
+        self.buf.clear();
+        self.pending.clear();
+        self.capabilities = None;
+        self.max_payload = None;
+        self.rx_enabled = true;
+        self.radio_state = RadioState::Active;
+        self.echo = match self.protocol.echo {
+            Some(echo) => echo,
+            None => self.detect_echo()?,
+        };
+        Ok(())
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        let tuned = freq.offset(self.frequency_offset);
+        self.command(&format!("AT+FREQ={:.6}", tuned))?;
+        self.frequency = Some(freq);
+        Ok(())
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.frequency_offset = hz;
+        match self.frequency {
+            Some(freq) => self.set_frequency(freq),
+            None => Ok(()),
+        }
+    }
+    fn config(&mut self) -> Result<Status> {
+        let lines = self.command("AT+INFO")?;
+        let mut status = trace::parsed_lines(&lines, Status::parse(&lines))?;
+        status.frequency = status.frequency.offset(-self.frequency_offset);
+        status.frequency_offset = self.frequency_offset;
+        status.rx_dropped = self.rx_dropped;
+        status.radio_state = Some(self.radio_state);
+        if status.max_pkt_size > 0 {
+            self.max_payload = Some(status.max_pkt_size);
+        }
+        Ok(status)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        match self.max_payload {
+            Some(max) => Ok(max),
+            None => Ok(self.config()?.max_pkt_size),
+        }
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.command(&format!("AT+MODE={}", mode as usize))?;
+        self.mode = Some(mode);
+        self.radio_params = None;
+        Ok(())
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        params.validate()?;
+        if !self.capabilities()?.contains(Capabilities::RADIO_PARAMS) {
+            // older firmware only knows the predefined configs
+            return match params.preset() {
+                Some(mode) => self.set_mode(mode),
+                None => Err(ModemError::Unsupported(Capabilities::RADIO_PARAMS)),
+            };
+        }
+        // optional settings are only sent when used, so firmware without them
+        // keeps working with the defaults
+        let implicit = params.header != HeaderMode::Explicit || self.header != HeaderMode::Explicit;
+        if implicit {
+            self.require(Capabilities::IMPLICIT_HEADER)?;
+        }
+        let sync_word =
+            params.sync_word != SYNC_WORD_PRIVATE || self.sync_word != SYNC_WORD_PRIVATE;
+        if sync_word {
+            self.require(Capabilities::SYNC_WORD)?;
+        }
+        self.command(&format!("AT+BW={}", params.bandwidth.hz()))?;
+        self.command(&format!("AT+SF={}", params.spreading_factor))?;
+        self.command(&format!("AT+CR={}", params.coding_rate.denominator()))?;
+        self.command(&format!("AT+PREAMBLE={}", params.preamble_len))?;
+        self.command(&format!("AT+CRC={}", params.crc as u8))?;
+        if implicit {
+            let len = params.header.implicit_len().unwrap_or(0);
+            self.command(&format!("AT+IMPLICIT={}", len))?;
+            self.header = params.header;
+        }
+        if sync_word {
+            self.command(&format!("AT+SYNCWORD={:#04x}", params.sync_word))?;
+            self.sync_word = params.sync_word;
+        }
+        self.radio_params = Some(params);
+        Ok(())
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        let lines = self.command("AT+INFO")?;
+        trace::parsed_lines(&lines, parse_radio_params(&lines))
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        validate_tx_power(dbm)?;
+        self.require(Capabilities::TX_POWER)?;
+        self.command(&format!("AT+TXPOWER={}", dbm))?;
+        self.tx_power = Some(dbm);
+        Ok(())
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        if let Some(caps) = self.capabilities {
+            return Ok(caps);
+        }
+        let caps = match self.command("AT+HELP") {
+            Ok(lines) => Capabilities::from_help(&lines),
+            Err(ModemError::ModemReported(_)) => Capabilities::empty(),
+            Err(e) => return Err(e),
+        };
+        let caps = if caps.is_empty() {
+            Capabilities::from_info(&self.command("AT+INFO")?)?
+        } else {
+            caps
+        };
+        self.capabilities = Some(caps);
+        Ok(caps)
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.require(Capabilities::BLE)?;
+        self.command(&format!("AT+BLE={}", enabled as u8))?;
+        Ok(())
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.require(Capabilities::GPS)?;
+        let lines = self.command("AT+GPS")?;
+        match lines.iter().find(|line| line.starts_with("+GPS")) {
+            Some(line) => trace::parsed(line, GpsFix::parse(line)),
+            None => Err(ModemError::Parse(
+                "modem did not report a GPS position!".into(),
+            )),
+        }
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.require(Capabilities::TELEMETRY)?;
+        let lines = self.command("AT+TELEMETRY")?;
+        trace::parsed_lines(&lines, ModemTelemetry::parse(&lines))
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.require(Capabilities::CAD)?;
+        let lines = self.command("AT+CAD")?;
+        trace::parsed_lines(&lines, parse_cad(&lines))
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        if cmd.contains(['\r', '\n']) {
+            return Err(ModemError::InvalidArgument(
+                "AT command must be a single line".into(),
+            ));
+        }
+        self.command(cmd)
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.command("AT+RX=1")?;
+        self.rx_enabled = true;
+        Ok(())
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.command("AT+RX=0")?;
+        self.rx_enabled = false;
+        Ok(())
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.require(Capabilities::POWER_MODES)?;
+        self.command("AT+SLEEP")?;
+        self.radio_state = RadioState::Sleep;
+        Ok(())
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.require(Capabilities::POWER_MODES)?;
+        self.command("AT+STANDBY")?;
+        self.radio_state = RadioState::Standby;
+        Ok(())
+    }
+    fn wake(&mut self) -> Result<()> {
+        if self.radio_state == RadioState::Active {
+            return Ok(());
+        }
+        // restarting reception brings the radio out of sleep or standby
+        self.command(if self.rx_enabled {
+            "AT+RX=1"
+        } else {
+            "AT+STANDBY"
+        })?;
+        self.radio_state = RadioState::Active;
+        Ok(())
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.send_slice(&data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        if let Some(max) = self.max_payload {
+            check_payload(data.len(), max)?;
+        }
+        self.wake()?;
+        let timeout = self.timeouts.tx_confirm;
+        let mut cmd = std::mem::take(&mut self.tx_line);
+        cmd.clear();
+        cmd.reserve(6 + 2 * data.len());
+        cmd.push_str("AT+TX=");
+        // writing into a String cannot fail
+        let _ = HexEncoder::new(&mut cmd, HexFormat::default()).write(data);
+        let mut attempts = 0;
+        let result = loop {
+            match self.command_within(&cmd, timeout).map_err(tx_rejected) {
+                Err(e) if is_overrun(&e) && attempts < self.pacing.retries => {
+                    attempts += 1;
+                    thread::sleep(self.pacing.retry_delay);
+                }
+                result => break result,
+            }
+        };
+        self.tx_line = cmd;
+        let lines = result?;
+        match lines.last() {
+            Some(line) if LineKind::of(line) == LineKind::Sent => {
+                let sent = trace::parsed(line, parse_sent(line))?;
+                let params = self.radio_params.or_else(|| self.mode.map(Into::into));
+                Ok(TxReport::new(sent, params))
+            }
+            _ => Err(ModemError::Parse(
+                "modem did not confirm transmission!".into(),
+            )),
+        }
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        let deadline = self.timeouts.rx.map(|t| Instant::now() + t);
+        self.read_packet_until(deadline)
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        if let Some(event) = self.events.pop_front() {
+            return Ok(event);
+        }
+        let deadline = self.timeouts.rx.map(|t| Instant::now() + t);
+        let line = self.read_line_until(deadline)?;
+        Ok(match ModemEvent::from_line_with(line, self.header) {
+            ModemEvent::PacketReceived(mut packet) => {
+                packet.received_at = self.last_line_at;
+                ModemEvent::PacketReceived(packet)
+            }
+            event => event,
+        })
+    }
+    fn read_line(&mut self) -> Result<String> {
+        let deadline = self.timeouts.rx.map(|t| Instant::now() + t);
+        self.read_line_until(deadline)
+    }
+}
@@ -0,0 +1,323 @@
+use crate::hex;
+use crate::radio::{Bandwidth, CodingRate, HeaderMode, RadioParams};
+#[cfg(feature = "serial")]
+use crate::serial::SerialPort;
+use crate::trace;
+use crate::transport::Transport;
+use crate::{
+    check_payload, Capabilities, Frequency, LoraModemDevice, ModemConfig, ModemError, Result,
+    RxPacket, Status, TxRejection, TxReport,
+};
+use std::io::ErrorKind;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Default baud rate of RN2483 and RN2903 modules.
+pub const RN2XX3_BAUD: u32 = 57_600;
+// Largest payload of a single `radio tx`.
+const MAX_PAYLOAD: usize = 255;
+
+/// Microchip RN2483/RN2903 module, e.g. a LoStik, driven in raw radio mode.
+///
+/// The LoRaWAN stack is paused on `open()` and the receive watchdog disabled,
+/// so `read_packet` keeps listening until the read timeout. Settings the module
+/// cannot apply, such as bandwidths below 125kHz, fail with `ModemError::InvalidArgument`.
+pub struct Rn2xx3Modem<T: Transport> {
+    transport: T,
+    timeout: Option<Duration>,
+    buf: Vec<u8>,
+    version: String,
+    // a `radio rx` is outstanding
+    listening: bool,
+    rx_enabled: bool,
+}
+
+#[cfg(feature = "serial")]
+impl Rn2xx3Modem<SerialPort> {
+    /// Create a modem for the module at the serial device `path`, opened by `open()`.
+    pub fn serial(path: &str) -> Self {
+        let mut modem = Rn2xx3Modem::from_transport(SerialPort::new(path, RN2XX3_BAUD));
+        modem.set_timeout(Some(Duration::from_secs(5)));
+        modem
+    }
+}
+
+impl<T: Transport> Rn2xx3Modem<T> {
+    /// Create a modem on top of an (unopened) transport.
+    pub fn from_transport(transport: T) -> Self {
+        Rn2xx3Modem {
+            transport,
+            timeout: None,
+            buf: Vec::new(),
+            version: String::new(),
+            listening: false,
+            rx_enabled: true,
+        }
+    }
+    /// Bound commands and reads by `timeout`, `None` blocks forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+    /// Consume the modem and return the underlying transport.
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        if !self.transport.is_open() {
+            return Err(ModemError::NotOpen);
+        }
+        trace::line_out(line);
+        self.transport.write_all(line.as_bytes())?;
+        self.transport.write_all(b"\r\n")?;
+        self.transport.flush()?;
+        Ok(())
+    }
+
+    // Read the next line, failing with a timeout once `deadline` passed.
+    fn next_line(&mut self, deadline: Option<Instant>) -> Result<String> {
+        if !self.transport.is_open() {
+            return Err(ModemError::NotOpen);
+        }
+        let mut byte = [0u8; 1];
+        loop {
+            match self.transport.read(&mut byte) {
+                Ok(0) => return Err(ModemError::Disconnected),
+                Ok(_) => {
+                    if byte[0] == b'\n' {
+                        let line = String::from_utf8_lossy(&self.buf)
+                            .trim_end_matches('\r')
+                            .to_string();
+                        self.buf.clear();
+                        trace::line_in(&line);
+                        return Ok(line);
+                    }
+                    self.buf.push(byte[0]);
+                }
+                Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock => {
+                    if deadline.is_some_and(|d| Instant::now() >= d) {
+                        return Err(ModemError::Timeout);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|t| Instant::now() + t)
+    }
+
+    // Send a command and return its single line answer, error answers fail.
+    fn command(&mut self, cmd: &str) -> Result<String> {
+        let span = trace::command(cmd);
+        let _entered = span.enter();
+        let started = Instant::now();
+        let result = self.exchange(cmd);
+        trace::finished(started, &result);
+        result
+    }
+
+    // Exchange of `command` without the instrumentation.
+    fn exchange(&mut self, cmd: &str) -> Result<String> {
+        self.write_line(cmd)?;
+        let answer = self.next_line(self.deadline())?;
+        match answer.as_str() {
+            "invalid_param" => Err(ModemError::InvalidArgument(cmd.to_string())),
+            "busy" | "err" | "radio_err" => Err(ModemError::ModemReported(answer)),
+            _ => Ok(answer),
+        }
+    }
+
+    // Send a `radio set` command, which is acknowledged with `ok`.
+    fn set(&mut self, param: &str, value: &str) -> Result<()> {
+        self.stop_listening()?;
+        let answer = self.command(&format!("radio set {} {}", param, value))?;
+        if answer == "ok" {
+            Ok(())
+        } else {
+            Err(ModemError::ModemReported(answer))
+        }
+    }
+
+    fn get(&mut self, param: &str) -> Result<String> {
+        self.stop_listening()?;
+        self.command(&format!("radio get {}", param))
+    }
+
+    // Abort an outstanding `radio rx`, the module rejects other radio commands meanwhile.
+    fn stop_listening(&mut self) -> Result<()> {
+        if self.listening {
+            self.listening = false;
+            self.command("radio rxstop")?;
+        }
+        Ok(())
+    }
+
+    // Signal quality of the last received packet, 0 if the firmware cannot report it.
+    fn last_signal(&mut self) -> (i16, f32) {
+        let mut query = |param: &str| {
+            self.command(&format!("radio get {}", param))
+                .ok()
+                .and_then(|value| value.trim().parse::<f32>().ok())
+                .unwrap_or(0.0)
+        };
+        let snr = query("snr");
+        let rssi = query("rssi") as i16;
+        (rssi, snr)
+    }
+}
+
+impl<T: Transport> LoraModemDevice for Rn2xx3Modem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.transport.open()?;
+        self.buf.clear();
+        self.listening = false;
+        self.rx_enabled = true;
+        self.version = self.command("sys get ver")?;
+        // the LoRaWAN stack has to stay out of the way of the raw radio
+        self.command("mac pause")?;
+        self.set("wdt", "0")?;
+        Ok(())
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.set("freq", &freq.hz().to_string())
+    }
+    fn config(&mut self) -> Result<Status> {
+        let params = self.get_radio_params()?;
+        let hz: u32 = self.get("freq")?.trim().parse()?;
+        let pwr = self.get("pwr")?.trim().parse()?;
+        Ok(Status {
+            version: self.version.clone(),
+            config: params
+                .preset()
+                .unwrap_or(ModemConfig::MediumBw125Cr45Sf128Crc),
+            max_pkt_size: MAX_PAYLOAD,
+            frequency: Frequency::from_hz(hz),
+            rx_listener: self.rx_enabled,
+            tx_power: Some(pwr),
+            ..Status::new()
+        })
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        Ok(MAX_PAYLOAD)
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.set_radio_params(mode.into())
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        params.validate()?;
+        let bw = match params.bandwidth {
+            Bandwidth::Bw125kHz | Bandwidth::Bw250kHz | Bandwidth::Bw500kHz => {
+                params.bandwidth.hz() / 1000
+            }
+            bw => {
+                return Err(ModemError::InvalidArgument(format!(
+                    "bandwidth {} Hz not supported by RN2xx3",
+                    bw.hz()
+                )))
+            }
+        };
+        if params.header != HeaderMode::Explicit {
+            return Err(ModemError::InvalidArgument(
+                "implicit header mode not supported by RN2xx3".into(),
+            ));
+        }
+        if params.spreading_factor < 7 {
+            return Err(ModemError::InvalidArgument(
+                "spreading factor 6 not supported by RN2xx3".into(),
+            ));
+        }
+        self.set("mod", "lora")?;
+        self.set("bw", &bw.to_string())?;
+        self.set("sf", &format!("sf{}", params.spreading_factor))?;
+        self.set("cr", &format!("4/{}", params.coding_rate.denominator()))?;
+        self.set("prlen", &params.preamble_len.to_string())?;
+        self.set("crc", if params.crc { "on" } else { "off" })?;
+        self.set("sync", &format!("{:02x}", params.sync_word))
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        let bw: u32 = self.get("bw")?.trim().parse()?;
+        let sf = self.get("sf")?;
+        let cr = self.get("cr")?;
+        let sync = self.get("sync")?;
+        let bandwidth = Bandwidth::from_hz(bw * 1000)
+            .ok_or_else(|| ModemError::Parse(format!("unknown bandwidth {}", bw)))?;
+        let coding_rate = CodingRate::from_denominator(cr.trim().trim_start_matches("4/").parse()?)
+            .ok_or_else(|| ModemError::Parse(format!("unknown coding rate {}", cr)))?;
+        Ok(RadioParams {
+            bandwidth,
+            spreading_factor: sf.trim().trim_start_matches("sf").parse()?,
+            coding_rate,
+            preamble_len: self.get("prlen")?.trim().parse()?,
+            crc: self.get("crc")?.trim() == "on",
+            header: HeaderMode::Explicit,
+            sync_word: u8::from_str_radix(sync.trim(), 16)
+                .map_err(|_| ModemError::Parse(format!("invalid sync word {}", sync)))?,
+        })
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        // the valid range differs between RN2483 and RN2903, the module checks it
+        self.set("pwr", &dbm.to_string())
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        Ok(self.get("pwr")?.trim().parse()?)
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        Ok(Capabilities::RADIO_PARAMS | Capabilities::TX_POWER)
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.rx_enabled = true;
+        Ok(())
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.stop_listening()?;
+        self.rx_enabled = false;
+        Ok(())
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        check_payload(data.len(), MAX_PAYLOAD)?;
+        self.stop_listening()?;
+        let answer = self.command(&format!("radio tx {}", hex::encode(&data)))?;
+        if answer != "ok" {
+            return Err(ModemError::TxRejected(TxRejection::from_message(&answer)));
+        }
+        match self.next_line(self.deadline())?.as_str() {
+            "radio_tx_ok" => Ok(TxReport::new(data.len(), None)),
+            other => Err(ModemError::ModemReported(other.to_string())),
+        }
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        if !self.rx_enabled {
+            return Err(ModemError::RxDisabled);
+        }
+        if !self.listening {
+            let answer = self.command("radio rx 0")?;
+            if answer != "ok" {
+                return Err(ModemError::ModemReported(answer));
+            }
+            self.listening = true;
+        }
+        let line = self.next_line(self.deadline())?;
+        self.listening = false;
+        let payload = match line.strip_prefix("radio_rx") {
+            Some(payload) => payload.trim(),
+            None => return Err(ModemError::ModemReported(line)),
+        };
+        let data = trace::parsed(&line, hex::decode(payload).map_err(ModemError::from))?;
+        let received_at = SystemTime::now();
+        let (rssi, snr) = self.last_signal();
+        Ok(RxPacket {
+            rssi,
+            snr,
+            data,
+            received_at,
+            freq_error: None,
+            modem_timestamp: None,
+        })
+    }
+    fn read_line(&mut self) -> Result<String> {
+        let deadline = self.deadline();
+        self.next_line(deadline)
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Distinguishes generators seeded within the same clock tick.
+static SEED_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Small xorshift64* generator for jitter and channel selection, not for cryptography.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        // the state must never be zero
+        Rng((seed ^ 0x9e37_79b9_7f4a_7c15) | 1)
+    }
+    pub(crate) fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        let count = SEED_COUNTER.fetch_add(1, Ordering::Relaxed);
+        Rng::new(nanos.wrapping_add(count.wrapping_mul(0x2545_f491_4f6c_dd1d)))
+    }
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+    /// Uniformly distributed value below `bound`, which must not be zero.
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
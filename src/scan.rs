@@ -0,0 +1,75 @@
+use crate::stats::{record_signal, SignalStats};
+use crate::{LoRaChannels, LoraModemDevice, ModemError, Result};
+use std::time::{Duration, Instant};
+
+/// Activity observed on one channel by `scan_channels`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelReport {
+    pub channel: LoRaChannels,
+    /// Packets received while listening
+    pub packets: usize,
+    /// Signal strength of the received packets, `None` if there were none
+    pub rssi: Option<SignalStats>,
+    /// Estimated noise floor in dBm, RSSI minus SNR averaged over the received packets
+    pub noise_floor: Option<f32>,
+}
+
+impl ChannelReport {
+    fn new(channel: LoRaChannels) -> Self {
+        ChannelReport {
+            channel,
+            packets: 0,
+            rssi: None,
+            noise_floor: None,
+        }
+    }
+}
+
+/// Listen on each channel for `dwell` and report the activity seen there.
+///
+/// The firmware offers no raw RSSI sampling, so the estimates are based on the
+/// received packets only. A single read may exceed `dwell` by up to the read
+/// timeout of the device. The previous frequency is restored afterwards.
+pub fn scan_channels<D: LoraModemDevice + ?Sized>(
+    device: &mut D,
+    channels: &[LoRaChannels],
+    dwell: Duration,
+) -> Result<Vec<ChannelReport>> {
+    let previous = device.config()?.frequency;
+    let mut reports = Vec::with_capacity(channels.len());
+    for &channel in channels {
+        device.set_channel(channel)?;
+        let mut report = ChannelReport::new(channel);
+        let mut noise_sum = 0.0;
+        let end = Instant::now() + dwell;
+        while Instant::now() < end {
+            let packet = match device.read_packet() {
+                Ok(packet) => packet,
+                Err(ModemError::Timeout) | Err(ModemError::Parse(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            report.packets += 1;
+            record_signal(&mut report.rssi, packet.rssi as f32);
+            noise_sum += packet.rssi as f32 - packet.snr;
+        }
+        if report.packets > 0 {
+            report.noise_floor = Some(noise_sum / report.packets as f32);
+        }
+        reports.push(report);
+    }
+    device.set_frequency(previous)?;
+    Ok(reports)
+}
+
+/// The channel with the fewest packets, ties broken by the lower noise floor.
+pub fn quietest(reports: &[ChannelReport]) -> Option<LoRaChannels> {
+    reports
+        .iter()
+        .min_by(|a, b| {
+            let noise = |r: &ChannelReport| r.noise_floor.unwrap_or(f32::NEG_INFINITY);
+            a.packets
+                .cmp(&b.packets)
+                .then(noise(a).total_cmp(&noise(b)))
+        })
+        .map(|report| report.channel)
+}
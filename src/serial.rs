@@ -0,0 +1,574 @@
+use crate::rf95::Rf95Modem;
+use crate::transport::{ReconnectPolicy, Transport};
+#[cfg(unix)]
+use crate::ModemError;
+use crate::{LoraModemDevice, Status};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// Default baud rate of the rf95modem firmware.
+pub const DEFAULT_BAUD: u32 = 115_200;
+
+/// USB vendor and product ids of serial adapters common on rf95modem boards
+pub const KNOWN_ADAPTERS: &[(u16, u16)] = &[
+    // Silicon Labs CP210x
+    (0x10c4, 0xea60),
+    // WCH CH340 and CH9102
+    (0x1a86, 0x7523),
+    (0x1a86, 0x55d4),
+    // FTDI FT232R and FT231X
+    (0x0403, 0x6001),
+    (0x0403, 0x6015),
+    // Prolific PL2303
+    (0x067b, 0x2303),
+    // native USB of the ESP32-S3 and ESP32-C3
+    (0x303a, 0x1001),
+];
+
+/// Flow control of a `SerialPort`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlowControl {
+    /// No flow control, the default of rf95modem boards
+    #[default]
+    None,
+    /// RTS/CTS handshake, needs the lines wired to the modem
+    Hardware,
+    /// XON/XOFF sent in band by the firmware
+    Software,
+}
+
+/// rf95modem attached to a local serial device such as /dev/ttyUSB0 or COM5.
+pub type SerialModem = Rf95Modem<SerialPort>;
+
+impl SerialModem {
+    /// Create a modem for the serial device at `path`, opened lazily by `open()`.
+    pub fn new(path: &str, baud: u32) -> Self {
+        let mut modem = Rf95Modem::from_transport(SerialPort::new(path, baud));
+        modem.set_timeout(Some(Duration::from_secs(5)));
+        modem
+    }
+}
+
+/// Serial device configured as a raw tty.
+///
+/// Line settings are applied through the `stty` utility, on Windows through the
+/// comm API for `COM5`-style paths, instead of the `serialport` crate, so the
+/// crate builds without dependencies. Opening fails with `ModemError::Transport`
+/// where `stty` is not installed. Reads return after `poll_interval` if no data arrived.
+/// On Unix the settings found when first opening the device are restored when
+/// the port is dropped. When the device disappears, e.g. an unplugged USB adapter,
+/// it is reopened according to the reconnect policy. A stable `/dev/serial/by-id/`
+/// path or a USB id set with `set_usb_id` finds the adapter again under a new name.
+pub struct SerialPort {
+    path: String,
+    baud: u32,
+    poll_interval: Duration,
+    reconnect: ReconnectPolicy,
+    usb_id: Option<(u16, u16)>,
+    flow_control: FlowControl,
+    // `stty -g` output of the device before it was first configured
+    saved_settings: Option<String>,
+    reconnected: bool,
+    file: Option<File>,
+}
+
+impl SerialPort {
+    pub fn new(path: &str, baud: u32) -> Self {
+        SerialPort {
+            path: path.to_string(),
+            baud,
+            poll_interval: Duration::from_millis(100),
+            reconnect: ReconnectPolicy::default(),
+            usb_id: None,
+            flow_control: FlowControl::None,
+            saved_settings: None,
+            reconnected: false,
+            file: None,
+        }
+    }
+    /// Path of the serial device.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// Path opened for the device, on Windows `\\.\` is prepended to a bare `COMn`.
+    pub fn device_path(&self) -> String {
+        device_path(&self.path)
+    }
+    /// Configured baud rate.
+    pub fn baud(&self) -> u32 {
+        self.baud
+    }
+    /// Set how long a single read waits for data, applied on next `open()`.
+    ///
+    /// The tty driver only supports intervals between 0.1s and 25.5s.
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval;
+    }
+    /// Set the flow control, applied on next `open()`.
+    pub fn set_flow_control(&mut self, flow_control: FlowControl) {
+        self.flow_control = flow_control;
+    }
+    /// Configured flow control.
+    pub fn flow_control(&self) -> FlowControl {
+        self.flow_control
+    }
+
+    /// Set the policy for recovering from a vanished device.
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect = policy;
+    }
+    /// Look the device up by USB vendor and product id when reconnecting (Linux only).
+    pub fn set_usb_id(&mut self, vid: u16, pid: u16) {
+        self.usb_id = Some((vid, pid));
+    }
+
+    // Try to reopen a vanished device according to the reconnect policy.
+    fn reconnect(&mut self, cause: io::Error) -> io::Result<()> {
+        self.file = None;
+        let mut result = Err(cause);
+        for _ in 0..self.reconnect.max_attempts {
+            thread::sleep(self.reconnect.delay);
+            if let Some(path) = self.usb_id.and_then(|(vid, pid)| find_usb_tty(vid, pid)) {
+                self.path = path;
+            }
+            result = self.open();
+            if result.is_ok() {
+                self.reconnected = true;
+                break;
+            }
+        }
+        result
+    }
+
+    #[cfg(unix)]
+    fn stty(&self) -> std::process::Command {
+        let flag = if cfg!(target_os = "macos") {
+            "-f"
+        } else {
+            "-F"
+        };
+        let mut stty = std::process::Command::new("stty");
+        stty.arg(flag).arg(&self.path);
+        stty
+    }
+    #[cfg(unix)]
+    fn configure(&mut self, _file: &File) -> io::Result<()> {
+        if self.saved_settings.is_none() {
+            let output = run(self.stty().arg("-g"))?;
+            if output.status.success() {
+                let settings = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                self.saved_settings = Some(settings);
+            }
+        }
+        let deciseconds = (self.poll_interval.as_millis() / 100).clamp(1, 255);
+        let flow = match self.flow_control {
+            FlowControl::None => ["-crtscts", "-ixon", "-ixoff"],
+            FlowControl::Hardware => ["crtscts", "-ixon", "-ixoff"],
+            FlowControl::Software => ["-crtscts", "ixon", "ixoff"],
+        };
+        let output = run(self
+            .stty()
+            .arg(self.baud.to_string())
+            .args(["raw", "-echo", "min", "0", "time"])
+            .arg(deciseconds.to_string())
+            .args(flow))?;
+        if !output.status.success() {
+            return Err(io::Error::other(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(())
+    }
+    #[cfg(windows)]
+    fn configure(&mut self, file: &File) -> io::Result<()> {
+        windows::configure(file, self.baud, self.poll_interval, self.flow_control)
+    }
+    #[cfg(not(any(unix, windows)))]
+    fn configure(&mut self, _file: &File) -> io::Result<()> {
+        Err(io::Error::other(
+            "serial port configuration not supported on this platform",
+        ))
+    }
+
+    fn file(&mut self) -> io::Result<&mut File> {
+        self.file
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "serial port not open"))
+    }
+}
+
+// Run `stty`, reporting a missing binary as such rather than as a bare I/O error.
+#[cfg(unix)]
+fn run(stty: &mut std::process::Command) -> io::Result<std::process::Output> {
+    stty.output().map_err(|e| {
+        if e.kind() == io::ErrorKind::NotFound {
+            io::Error::other(ModemError::Transport(
+                "`stty` not found, it is needed to configure serial ports".into(),
+            ))
+        } else {
+            e
+        }
+    })
+}
+
+// Whether `path` names a Windows COM port, e.g. `COM5` or `com12`.
+fn is_com_port(path: &str) -> bool {
+    path.len() > 3
+        && path[..3].eq_ignore_ascii_case("COM")
+        && path[3..].bytes().all(|b| b.is_ascii_digit())
+}
+
+// COM ports beyond COM9 can only be opened under the device namespace.
+fn device_path(path: &str) -> String {
+    if cfg!(windows) && is_com_port(path) {
+        format!(r"\\.\{}", path)
+    } else {
+        path.to_string()
+    }
+}
+
+// Errors reported once the device behind an open file is gone.
+fn is_unplugged(e: &io::Error) -> bool {
+    // EIO, ENXIO and ENODEV
+    cfg!(unix) && matches!(e.raw_os_error(), Some(5) | Some(6) | Some(19))
+}
+
+/// Serial port found by `serial_ports`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerialPortInfo {
+    /// Path to open the port with
+    pub path: String,
+    /// USB vendor and product id, if known
+    pub usb_id: Option<(u16, u16)>,
+    /// Product name reported by the adapter or driver
+    pub product: Option<String>,
+}
+
+impl SerialPortInfo {
+    /// Whether the port may have an rf95modem attached, going by its USB id.
+    ///
+    /// Ports without known id only qualify where the platform does not report ids.
+    pub fn is_candidate(&self) -> bool {
+        match self.usb_id {
+            Some(id) => KNOWN_ADAPTERS.contains(&id),
+            None => !cfg!(target_os = "linux"),
+        }
+    }
+}
+
+/// A port answering `AT+INFO` like an rf95modem
+#[derive(Debug, Clone)]
+pub struct DetectedModem {
+    pub port: SerialPortInfo,
+    /// Status the modem reported when probed
+    pub status: Status,
+}
+
+/// Serial ports of the system, on Linux with their USB ids.
+pub fn serial_ports() -> Vec<SerialPortInfo> {
+    let mut ports = platform_ports();
+    ports.sort_by(|a, b| a.path.cmp(&b.path));
+    ports
+}
+
+#[cfg(target_os = "linux")]
+fn platform_ports() -> Vec<SerialPortInfo> {
+    let read = |dir: &Path, name: &str| {
+        std::fs::read_to_string(dir.join(name))
+            .ok()
+            .map(|value| value.trim().to_string())
+    };
+    let read_id =
+        |dir: &Path, name: &str| read(dir, name).and_then(|id| u16::from_str_radix(&id, 16).ok());
+    let entries = match std::fs::read_dir("/sys/class/tty") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut ports = Vec::new();
+    for entry in entries.flatten() {
+        // virtual terminals and ptys have no device behind them
+        let device = match std::fs::canonicalize(entry.path().join("device")) {
+            Ok(device) => device,
+            Err(_) => continue,
+        };
+        let mut port = SerialPortInfo {
+            path: format!("/dev/{}", entry.file_name().to_string_lossy()),
+            usb_id: None,
+            product: None,
+        };
+        // the ids live in the usb device, some levels above the tty interface
+        for dir in device.ancestors().take(4) {
+            if let (Some(vid), Some(pid)) = (read_id(dir, "idVendor"), read_id(dir, "idProduct")) {
+                port.usb_id = Some((vid, pid));
+                port.product = read(dir, "product");
+                break;
+            }
+        }
+        ports.push(port);
+    }
+    ports
+}
+
+#[cfg(target_os = "macos")]
+fn platform_ports() -> Vec<SerialPortInfo> {
+    const USB_NAMES: &[&str] = &["usbserial", "usbmodem", "SLAB_USBtoUART", "wchusbserial"];
+    let entries = match std::fs::read_dir("/dev") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // the call-out devices do not wait for carrier detect
+            let usb = name.starts_with("cu.") && USB_NAMES.iter().any(|n| name.contains(n));
+            usb.then(|| SerialPortInfo {
+                path: format!("/dev/{}", name),
+                usb_id: None,
+                product: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn platform_ports() -> Vec<SerialPortInfo> {
+    let output = std::process::Command::new("reg")
+        .args(["query", r"HKLM\HARDWARE\DEVICEMAP\SERIALCOMM"])
+        .output();
+    match output {
+        Ok(output) => parse_serialcomm(&String::from_utf8_lossy(&output.stdout)),
+        Err(_) => Vec::new(),
+    }
+}
+
+// Ports listed by `reg query`, lines like `    \Device\Silabser0    REG_SZ    COM5`.
+#[cfg(windows)]
+fn parse_serialcomm(output: &str) -> Vec<SerialPortInfo> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                [driver, "REG_SZ", port] if is_com_port(port) => Some(SerialPortInfo {
+                    path: port.to_string(),
+                    usb_id: None,
+                    product: Some(driver.trim_start_matches(r"\Device\").to_string()),
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn platform_ports() -> Vec<SerialPortInfo> {
+    Vec::new()
+}
+
+// Path of the tty belonging to the USB device with the given ids.
+fn find_usb_tty(vid: u16, pid: u16) -> Option<String> {
+    serial_ports()
+        .into_iter()
+        .find(|port| port.usb_id == Some((vid, pid)))
+        .map(|port| port.path)
+}
+
+// Ask the device at `path` for its status, waiting up to `timeout` for the answer.
+fn query_status(path: &str, baud: u32, timeout: Duration) -> crate::Result<Status> {
+    let mut modem = SerialModem::new(path, baud);
+    modem.set_timeout(Some(timeout));
+    modem.open()?;
+    modem.config()
+}
+
+/// rf95modems attached to candidate ports, see `SerialPortInfo::is_candidate`.
+///
+/// Every candidate is probed with `AT+INFO` at the default baud rate, ports
+/// that are busy or do not answer within a second are skipped.
+pub fn enumerate_modems() -> Vec<DetectedModem> {
+    serial_ports()
+        .into_iter()
+        .filter(SerialPortInfo::is_candidate)
+        .filter_map(|port| {
+            let status = query_status(&port.path, DEFAULT_BAUD, Duration::from_secs(1)).ok()?;
+            Some(DetectedModem { port, status })
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::FlowControl;
+    use std::fs::File;
+    use std::io;
+    use std::os::raw::c_void;
+    use std::os::windows::io::AsRawHandle;
+    use std::time::Duration;
+
+    // DCB of the Win32 comm API, most fields are only read by the system
+    #[allow(dead_code)]
+    #[repr(C)]
+    #[derive(Default)]
+    struct Dcb {
+        length: u32,
+        baud_rate: u32,
+        flags: u32,
+        reserved: u16,
+        xon_lim: u16,
+        xoff_lim: u16,
+        byte_size: u8,
+        parity: u8,
+        stop_bits: u8,
+        xon_char: i8,
+        xoff_char: i8,
+        error_char: i8,
+        eof_char: i8,
+        evt_char: i8,
+        reserved1: u16,
+    }
+
+    // COMMTIMEOUTS, all in milliseconds
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct CommTimeouts {
+        read_interval: u32,
+        read_multiplier: u32,
+        read_constant: u32,
+        write_multiplier: u32,
+        write_constant: u32,
+    }
+
+    const F_BINARY: u32 = 0x0001;
+    // parity check, CTS, DSR and XON/XOFF flow control, DSR sensitivity, abort on
+    // error and RTS handshaking
+    const F_CLEARED: u32 = 0x0002 | 0x0004 | 0x0008 | 0x0040 | 0x0100 | 0x0200 | 0x4000 | 0x3000;
+    const RTS_CONTROL_ENABLE: u32 = 0x1000;
+    const RTS_CONTROL_HANDSHAKE: u32 = 0x2000;
+    const F_OUTX_CTS_FLOW: u32 = 0x0004;
+    const F_OUTX: u32 = 0x0100;
+    const F_INX: u32 = 0x0200;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCommState(file: *mut c_void, dcb: *mut Dcb) -> i32;
+        fn SetCommState(file: *mut c_void, dcb: *const Dcb) -> i32;
+        fn SetCommTimeouts(file: *mut c_void, timeouts: *const CommTimeouts) -> i32;
+    }
+
+    // Raw 8N1, reads returning after `poll_interval` without data.
+    pub(super) fn configure(
+        file: &File,
+        baud: u32,
+        poll_interval: Duration,
+        flow_control: FlowControl,
+    ) -> io::Result<()> {
+        let handle = file.as_raw_handle() as *mut c_void;
+        let mut dcb = Dcb {
+            length: std::mem::size_of::<Dcb>() as u32,
+            ..Dcb::default()
+        };
+        // SAFETY: `handle` belongs to the open port and the structs have the Win32 layout
+        unsafe {
+            if GetCommState(handle, &mut dcb) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            dcb.baud_rate = baud;
+            dcb.byte_size = 8;
+            dcb.parity = 0;
+            dcb.stop_bits = 0;
+            let flow = match flow_control {
+                FlowControl::None => RTS_CONTROL_ENABLE,
+                FlowControl::Hardware => F_OUTX_CTS_FLOW | RTS_CONTROL_HANDSHAKE,
+                FlowControl::Software => {
+                    dcb.xon_char = 0x11;
+                    dcb.xoff_char = 0x13;
+                    RTS_CONTROL_ENABLE | F_OUTX | F_INX
+                }
+            };
+            dcb.flags = (dcb.flags & !F_CLEARED) | F_BINARY | flow;
+            if SetCommState(handle, &dcb) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // like VMIN=0 and VTIME: return what arrived, otherwise wait for the first byte
+            let timeouts = CommTimeouts {
+                read_interval: u32::MAX,
+                read_multiplier: u32::MAX,
+                read_constant: (poll_interval.as_millis() as u32).max(1),
+                write_multiplier: 0,
+                write_constant: 0,
+            };
+            if SetCommTimeouts(handle, &timeouts) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Transport for SerialPort {
+    fn open(&mut self) -> io::Result<()> {
+        self.file = None;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(self.device_path())?;
+        self.configure(&file)?;
+        self.file = Some(file);
+        Ok(())
+    }
+    fn is_open(&self) -> bool {
+        self.file.is_some()
+    }
+    fn take_reconnected(&mut self) -> bool {
+        std::mem::take(&mut self.reconnected)
+    }
+}
+
+impl Drop for SerialPort {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let (Some(_), Some(settings)) = (&self.file, &self.saved_settings) {
+            let _ = self.stty().arg(settings).output();
+        }
+    }
+}
+
+impl Read for SerialPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.file()?.read(buf) {
+            // with VMIN=0 the tty signals an expired VTIME by returning no data,
+            // a hung up device does the same but its node is gone
+            // device nodes of COM ports cannot be looked up like files
+            Ok(0) if !buf.is_empty() && cfg!(unix) && !Path::new(&self.path).exists() => {
+                self.reconnect(io::ErrorKind::NotFound.into())?;
+                Err(io::ErrorKind::TimedOut.into())
+            }
+            Ok(0) if !buf.is_empty() => Err(io::ErrorKind::TimedOut.into()),
+            Err(e) if is_unplugged(&e) => {
+                // nothing was read from the reopened device yet
+                self.reconnect(e)?;
+                Err(io::ErrorKind::TimedOut.into())
+            }
+            result => result,
+        }
+    }
+}
+
+impl Write for SerialPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.file()?.write(buf) {
+            Err(e) if is_unplugged(&e) => {
+                self.reconnect(e)?;
+                self.file()?.write(buf)
+            }
+            result => result,
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.file()?.flush()
+    }
+}
@@ -0,0 +1,672 @@
+//! Sharing one modem between processes over a Unix socket, or over TCP with
+//! the `rpc` feature to drive a modem of a headless gateway remotely.
+//!
+//! Clients exchange one JSON object per line with the server, shaped like
+//! JSON-RPC 2.0: requests `{"id":1,"method":"send","params":{"data":"cafe"}}`
+//! are answered with `{"id":1,"result":{"bytes":2,"airtime_ms":41.216}}` or
+//! `{"id":1,"error":{"code":-32000,"message":"...","data":"timeout"}}`.
+//!
+//! | method                 | params                 | result                  |
+//! |------------------------|------------------------|-------------------------|
+//! | `send`                 | `data`: hex payload    | `bytes`, `airtime_ms`   |
+//! | `config`               |                        | status object           |
+//! | `set_frequency`        | `freq`: MHz            |                         |
+//! | `set_frequency_offset` | `hz`                   |                         |
+//! | `set_mode`             | `mode`: config number  |                         |
+//! | `set_tx_power`         | `dbm`                  |                         |
+//! | `tx_power`             |                        | dBm                     |
+//! | `capabilities`         |                        | capability bits         |
+//! | `at_command`           | `cmd`                  | array of response lines |
+//! | `channel_busy`         |                        | bool                    |
+//! | `enable_rx`            |                        |                         |
+//! | `disable_rx`           |                        |                         |
+//! | `sleep`                |                        |                         |
+//! | `standby`              |                        |                         |
+//! | `wake`                 |                        |                         |
+//! | `subscribe`            |                        |                         |
+//! | `unsubscribe`          |                        |                         |
+//!
+//! Subscribed clients receive every packet as a notification
+//! `{"method":"rx","params":{"len":2,"data":"cafe","rssi":-80,"snr":7.5}}`.
+//!
+//! There is no authentication, a TCP server should only listen on trusted
+//! networks or behind a tunnel.
+
+use crate::json::{self, Json};
+use crate::{
+    hex, Capabilities, Frequency, LoraModemDevice, ModemConfig, ModemError, Result, RxPacket,
+    Status, TxRejection, TxReport,
+};
+use core::convert::TryFrom;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+#[cfg(feature = "rpc")]
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long the device thread waits for requests while no client is subscribed.
+const IDLE_POLL: Duration = Duration::from_millis(200);
+
+// How long a request may take before the server is considered gone.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Error codes and kinds carried in error responses.
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const MODEM_ERROR: i32 = -32000;
+
+fn error_kind(e: &ModemError) -> &'static str {
+    match e {
+        ModemError::Timeout => "timeout",
+        ModemError::InvalidArgument(_) => "invalid_argument",
+        ModemError::UnsupportedCommand(_) | ModemError::Unsupported(_) => "unsupported",
+        ModemError::BufferOverflow => "buffer_overflow",
+        ModemError::PayloadTooLarge { .. } => "payload_too_large",
+        ModemError::ChannelBusy => "channel_busy",
+        ModemError::RxDisabled => "rx_disabled",
+        ModemError::QueueFull => "queue_full",
+        ModemError::TxRejected(_) => "tx_rejected",
+        ModemError::DutyCycleExceeded { .. } | ModemError::RegulatoryViolation(_) => "rejected",
+        _ => "modem",
+    }
+}
+
+fn error_from(kind: &str, message: String) -> ModemError {
+    match kind {
+        "timeout" => ModemError::Timeout,
+        "invalid_argument" => ModemError::InvalidArgument(message),
+        "unsupported" => ModemError::UnsupportedCommand(message),
+        "buffer_overflow" => ModemError::BufferOverflow,
+        "payload_too_large" => match message.split_whitespace().find_map(|w| w.parse().ok()) {
+            Some(max) => ModemError::PayloadTooLarge { max },
+            None => ModemError::ModemReported(message),
+        },
+        "channel_busy" => ModemError::ChannelBusy,
+        "rx_disabled" => ModemError::RxDisabled,
+        "queue_full" => ModemError::QueueFull,
+        "tx_rejected" => {
+            let reason = message
+                .strip_prefix("transmission rejected: ")
+                .unwrap_or(&message);
+            ModemError::TxRejected(TxRejection::from_message(reason))
+        }
+        _ => ModemError::ModemReported(message),
+    }
+}
+
+fn error_response(id: Json, code: i32, message: String, kind: &str) -> Json {
+    Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", id),
+        (
+            "error",
+            Json::object(vec![
+                ("code", code.into()),
+                ("message", message.into()),
+                ("data", kind.into()),
+            ]),
+        ),
+    ])
+}
+
+// Connection between server and client.
+enum Stream {
+    #[cfg(unix)]
+    Unix(UnixStream),
+    #[cfg(feature = "rpc")]
+    Tcp(TcpStream),
+}
+
+impl Stream {
+    fn try_clone(&self) -> io::Result<Stream> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(s) => s.try_clone().map(Stream::Unix),
+            #[cfg(feature = "rpc")]
+            Stream::Tcp(s) => s.try_clone().map(Stream::Tcp),
+        }
+    }
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(s) => s.set_read_timeout(timeout),
+            #[cfg(feature = "rpc")]
+            Stream::Tcp(s) => s.set_read_timeout(timeout),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(s) => s.read(buf),
+            #[cfg(feature = "rpc")]
+            Stream::Tcp(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(s) => s.write(buf),
+            #[cfg(feature = "rpc")]
+            Stream::Tcp(s) => s.write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Stream::Unix(s) => s.flush(),
+            #[cfg(feature = "rpc")]
+            Stream::Tcp(s) => s.flush(),
+        }
+    }
+}
+
+enum Listener {
+    #[cfg(unix)]
+    Unix(UnixListener),
+    #[cfg(feature = "rpc")]
+    Tcp(TcpListener),
+}
+
+impl Listener {
+    fn accept(&self) -> io::Result<Stream> {
+        match self {
+            #[cfg(unix)]
+            Listener::Unix(l) => l.accept().map(|(s, _)| Stream::Unix(s)),
+            #[cfg(feature = "rpc")]
+            Listener::Tcp(l) => {
+                let (s, _) = l.accept()?;
+                s.set_nodelay(true)?;
+                Ok(Stream::Tcp(s))
+            }
+        }
+    }
+}
+
+// Sending half of a client connection, shared with the device thread.
+type Writer = Arc<Mutex<Stream>>;
+
+fn send_line(writer: &Writer, message: &Json) -> io::Result<()> {
+    let mut line = message.to_string();
+    line.push('\n');
+    writer.lock().unwrap().write_all(line.as_bytes())
+}
+
+enum Job {
+    Request {
+        client: usize,
+        writer: Writer,
+        id: Json,
+        method: String,
+        params: Json,
+    },
+    Gone(usize),
+}
+
+/// Owns a device and serves it to clients over a Unix socket or TCP.
+///
+/// Requests of all clients are executed one after another on a dedicated
+/// thread. While a client is subscribed the thread keeps reading packets, each
+/// read blocking for the timeout of the device, so a short device timeout
+/// keeps requests responsive.
+pub struct ModemServer<D: LoraModemDevice + Send + 'static> {
+    listener: Listener,
+    device: D,
+}
+
+impl<D: LoraModemDevice + Send + 'static> ModemServer<D> {
+    /// Listen on `path`, replacing a stale socket no server answers on anymore.
+    #[cfg(unix)]
+    pub fn bind<P: AsRef<Path>>(path: P, device: D) -> io::Result<Self> {
+        let path = path.as_ref();
+        if path.exists() && UnixStream::connect(path).is_err() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(ModemServer {
+            listener: Listener::Unix(UnixListener::bind(path)?),
+            device,
+        })
+    }
+
+    /// Listen for TCP connections on `addr`, e.g. `0.0.0.0:7373`.
+    #[cfg(feature = "rpc")]
+    pub fn bind_tcp<A: ToSocketAddrs>(addr: A, device: D) -> io::Result<Self> {
+        Ok(ModemServer {
+            listener: Listener::Tcp(TcpListener::bind(addr)?),
+            device,
+        })
+    }
+
+    /// Serve clients until accepting connections fails.
+    pub fn run(self) -> io::Result<()> {
+        let (jobs, job_rx) = mpsc::channel();
+        let mut device = self.device;
+        thread::spawn(move || serve_device(&mut device, job_rx));
+        for client in 0.. {
+            let stream = self.listener.accept()?;
+            let writer = Arc::new(Mutex::new(stream.try_clone()?));
+            let jobs = jobs.clone();
+            thread::spawn(move || serve_client(client, stream, writer, jobs));
+        }
+        Ok(())
+    }
+}
+
+fn serve_client(client: usize, stream: Stream, writer: Writer, jobs: Sender<Job>) {
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match Json::parse(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let response = error_response(Json::Null, PARSE_ERROR, e, "parse");
+                if send_line(&writer, &response).is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+        let job = Job::Request {
+            client,
+            writer: writer.clone(),
+            id: request.get("id").cloned().unwrap_or(Json::Null),
+            method: request
+                .get("method")
+                .and_then(Json::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            params: request.get("params").cloned().unwrap_or(Json::Null),
+        };
+        if jobs.send(job).is_err() {
+            break;
+        }
+    }
+    let _ = jobs.send(Job::Gone(client));
+}
+
+fn serve_device<D: LoraModemDevice>(device: &mut D, jobs: Receiver<Job>) {
+    let mut subscribers: Vec<(usize, Writer)> = Vec::new();
+    loop {
+        let wait = if subscribers.is_empty() {
+            IDLE_POLL
+        } else {
+            Duration::from_millis(0)
+        };
+        match jobs.recv_timeout(wait) {
+            Ok(Job::Request {
+                client,
+                writer,
+                id,
+                method,
+                params,
+            }) => {
+                let response = match method.as_str() {
+                    "subscribe" => {
+                        if !subscribers.iter().any(|(c, _)| *c == client) {
+                            subscribers.push((client, writer.clone()));
+                        }
+                        Ok(Json::Null)
+                    }
+                    "unsubscribe" => {
+                        subscribers.retain(|(c, _)| *c != client);
+                        Ok(Json::Null)
+                    }
+                    _ => handle(device, &method, &params),
+                };
+                let response = match response {
+                    Ok(result) => Json::object(vec![
+                        ("jsonrpc", "2.0".into()),
+                        ("id", id),
+                        ("result", result),
+                    ]),
+                    Err((code, e)) => error_response(id, code, e.to_string(), error_kind(&e)),
+                };
+                let _ = send_line(&writer, &response);
+                continue;
+            }
+            Ok(Job::Gone(client)) => {
+                subscribers.retain(|(c, _)| *c != client);
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        if subscribers.is_empty() {
+            continue;
+        }
+        match device.read_packet() {
+            Ok(packet) => {
+                let notification = Json::object(vec![
+                    ("jsonrpc", "2.0".into()),
+                    ("method", "rx".into()),
+                    ("params", json::packet(&packet)),
+                ]);
+                subscribers.retain(|(_, writer)| send_line(writer, &notification).is_ok());
+            }
+            Err(ModemError::RxDisabled) => thread::sleep(IDLE_POLL),
+            Err(_) => {}
+        }
+    }
+}
+
+fn handle<D: LoraModemDevice>(
+    device: &mut D,
+    method: &str,
+    params: &Json,
+) -> core::result::Result<Json, (i32, ModemError)> {
+    let param = |key: &str| {
+        params.get(key).ok_or_else(|| {
+            (
+                INVALID_PARAMS,
+                ModemError::InvalidArgument(format!("missing parameter {}", key)),
+            )
+        })
+    };
+    let invalid = |key: &str| {
+        (
+            INVALID_PARAMS,
+            ModemError::InvalidArgument(format!("invalid parameter {}", key)),
+        )
+    };
+    let modem = |e: ModemError| (MODEM_ERROR, e);
+    let done = |r: Result<()>| r.map(|_| Json::Null).map_err(modem);
+    match method {
+        "send" => {
+            let data = param("data")?.as_str().ok_or_else(|| invalid("data"))?;
+            let data = hex::decode(data).map_err(|_| invalid("data"))?;
+            device
+                .send_data(data)
+                .map(|r| json::tx_report(&r))
+                .map_err(modem)
+        }
+        "config" => device.config().map(|s| json::status(&s)).map_err(modem),
+        "set_frequency" => {
+            let freq = param("freq")?.as_f64().ok_or_else(|| invalid("freq"))?;
+            done(device.set_frequency(Frequency::from_mhz_f64(freq)))
+        }
+        "set_frequency_offset" => {
+            let hz = param("hz")?.as_i64().ok_or_else(|| invalid("hz"))?;
+            done(device.set_frequency_offset(hz as i32))
+        }
+        "set_mode" => {
+            let mode = param("mode")?.as_i64().ok_or_else(|| invalid("mode"))?;
+            let mode = ModemConfig::try_from(mode as usize).map_err(|_| invalid("mode"))?;
+            done(device.set_mode(mode))
+        }
+        "set_tx_power" => {
+            let dbm = param("dbm")?.as_i64().ok_or_else(|| invalid("dbm"))?;
+            done(device.set_tx_power(dbm.clamp(i8::MIN as i64, i8::MAX as i64) as i8))
+        }
+        "tx_power" => device.tx_power().map(Json::from).map_err(modem),
+        "capabilities" => device
+            .capabilities()
+            .map(|c| Json::from(c.bits()))
+            .map_err(modem),
+        "at_command" => {
+            let cmd = param("cmd")?.as_str().ok_or_else(|| invalid("cmd"))?;
+            device
+                .at_command(cmd)
+                .map(|lines| Json::Array(lines.into_iter().map(Json::from).collect()))
+                .map_err(modem)
+        }
+        "channel_busy" => device.channel_busy().map(Json::from).map_err(modem),
+        "enable_rx" => done(device.enable_rx()),
+        "disable_rx" => done(device.disable_rx()),
+        "sleep" => done(device.sleep()),
+        "standby" => done(device.standby()),
+        "wake" => done(device.wake()),
+        _ => Err((
+            METHOD_NOT_FOUND,
+            ModemError::UnsupportedCommand(method.to_string()),
+        )),
+    }
+}
+
+/// Client of a `ModemServer`, usable like a local device.
+///
+/// `read_packet` subscribes to received packets on first use, packets arriving
+/// while waiting for a response are kept for later reads.
+pub struct RemoteModem {
+    stream: Stream,
+    reader: BufReader<Stream>,
+    buf: Vec<u8>,
+    next_id: u64,
+    packets: VecDeque<RxPacket>,
+    subscribed: bool,
+    timeout: Option<Duration>,
+}
+
+impl RemoteModem {
+    /// Connect to the server listening on `path`.
+    #[cfg(unix)]
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(Stream::Unix(UnixStream::connect(path)?))
+    }
+    /// Connect to the server listening for TCP connections on `addr`.
+    #[cfg(feature = "rpc")]
+    pub fn connect_tcp<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Self::new(Stream::Tcp(stream))
+    }
+
+    fn new(stream: Stream) -> Result<Self> {
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(RemoteModem {
+            stream,
+            reader,
+            buf: Vec::new(),
+            next_id: 1,
+            packets: VecDeque::new(),
+            subscribed: false,
+            timeout: Some(Duration::from_secs(5)),
+        })
+    }
+    /// Bound `read_packet` by `timeout`, `None` waits indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    // Next message from the server, `None` once `deadline` passed.
+    fn next_message(&mut self, deadline: Option<Instant>) -> Result<Option<Json>> {
+        loop {
+            let wait = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Ok(None);
+                    }
+                    Some(deadline - now)
+                }
+                None => None,
+            };
+            self.reader.get_ref().set_read_timeout(wait)?;
+            match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) => return Err(ModemError::Disconnected),
+                Ok(_) if self.buf.ends_with(b"\n") => {
+                    let line = String::from_utf8_lossy(&self.buf).to_string();
+                    self.buf.clear();
+                    match Json::parse(line.trim()) {
+                        Ok(message) => return Ok(Some(message)),
+                        Err(e) => return Err(ModemError::Parse(e)),
+                    }
+                }
+                Ok(_) => {}
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    // Queue a notification, returns whether `message` was one.
+    fn notification(&mut self, message: &Json) -> bool {
+        if message.get("method").and_then(Json::as_str) != Some("rx") {
+            return false;
+        }
+        if let Some(packet) = message.get("params").and_then(json::packet_from) {
+            self.packets.push_back(packet);
+        }
+        true
+    }
+
+    fn call(&mut self, method: &str, params: Json) -> Result<Json> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = Json::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("id", id.into()),
+            ("method", method.into()),
+            ("params", params),
+        ]);
+        let mut line = request.to_string();
+        line.push('\n');
+        self.stream.write_all(line.as_bytes())?;
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        loop {
+            let message = self
+                .next_message(Some(deadline))?
+                .ok_or(ModemError::Timeout)?;
+            if self.notification(&message) {
+                continue;
+            }
+            if message.get("id").and_then(Json::as_i64) != Some(id as i64) {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                let text = |key: &str| error.get(key).and_then(Json::as_str).unwrap_or_default();
+                return Err(error_from(text("data"), text("message").to_string()));
+            }
+            return Ok(message.get("result").cloned().unwrap_or(Json::Null));
+        }
+    }
+
+    fn call_unit(&mut self, method: &str, params: Json) -> Result<()> {
+        self.call(method, params).map(|_| ())
+    }
+}
+
+fn unexpected(method: &str) -> ModemError {
+    ModemError::Parse(format!("unexpected result for {}", method))
+}
+
+impl LoraModemDevice for RemoteModem {
+    fn open(&mut self) -> Result<()> {
+        Ok(())
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.call_unit(
+            "set_frequency",
+            Json::object(vec![("freq", freq.mhz().into())]),
+        )
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.call_unit(
+            "set_frequency_offset",
+            Json::object(vec![("hz", hz.into())]),
+        )
+    }
+    fn config(&mut self) -> Result<Status> {
+        let status = self.call("config", Json::Null)?;
+        json::status_from(&status).ok_or_else(|| unexpected("config"))
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.call_unit(
+            "set_mode",
+            Json::object(vec![("mode", (mode as usize).into())]),
+        )
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.call_unit("set_tx_power", Json::object(vec![("dbm", dbm.into())]))
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        let dbm = self.call("tx_power", Json::Null)?;
+        dbm.as_i64()
+            .map(|dbm| dbm as i8)
+            .ok_or_else(|| unexpected("tx_power"))
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        let bits = self.call("capabilities", Json::Null)?;
+        bits.as_i64()
+            .map(|bits| Capabilities::from_bits_truncate(bits as u32))
+            .ok_or_else(|| unexpected("capabilities"))
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        let lines = self.call("at_command", Json::object(vec![("cmd", cmd.into())]))?;
+        lines
+            .as_array()
+            .and_then(|lines| {
+                lines
+                    .iter()
+                    .map(|l| l.as_str().map(str::to_string))
+                    .collect()
+            })
+            .ok_or_else(|| unexpected("at_command"))
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        let busy = self.call("channel_busy", Json::Null)?;
+        busy.as_bool().ok_or_else(|| unexpected("channel_busy"))
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.call_unit("enable_rx", Json::Null)
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.call_unit("disable_rx", Json::Null)
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.call_unit("sleep", Json::Null)
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.call_unit("standby", Json::Null)
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.call_unit("wake", Json::Null)
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        let sent = self.call(
+            "send",
+            Json::object(vec![("data", hex::encode(&data).into())]),
+        )?;
+        json::tx_report_from(&sent).ok_or_else(|| unexpected("send"))
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        if !self.subscribed {
+            self.call_unit("subscribe", Json::Null)?;
+            self.subscribed = true;
+        }
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        loop {
+            if let Some(packet) = self.packets.pop_front() {
+                return Ok(packet);
+            }
+            match self.next_message(deadline)? {
+                Some(message) => {
+                    self.notification(&message);
+                }
+                None => return Err(ModemError::Timeout),
+            }
+        }
+    }
+    fn read_line(&mut self) -> Result<String> {
+        Ok(self.read_packet()?.to_modem_line())
+    }
+}
@@ -0,0 +1,343 @@
+use crate::radio::{airtime, RadioParams};
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemEvent, Result, RxPacket,
+    Status, TxReport,
+};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Weight of a new sample in the moving averages.
+const EMA_ALPHA: f32 = 0.1;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Minimum, maximum and averages of a signal quality figure
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalStats {
+    pub min: f32,
+    pub max: f32,
+    /// Mean of all samples
+    pub avg: f32,
+    /// Exponential moving average, following recent changes
+    pub ema: f32,
+    /// Number of samples
+    pub samples: usize,
+}
+
+impl SignalStats {
+    fn new(value: f32) -> Self {
+        SignalStats {
+            min: value,
+            max: value,
+            avg: value,
+            ema: value,
+            samples: 1,
+        }
+    }
+    // Combine with the samples of `other`, averages are weighted by sample count.
+    fn merge(&mut self, other: &SignalStats) {
+        let total = (self.samples + other.samples) as f32;
+        let (own, theirs) = (self.samples as f32 / total, other.samples as f32 / total);
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.avg = self.avg * own + other.avg * theirs;
+        self.ema = self.ema * own + other.ema * theirs;
+        self.samples += other.samples;
+    }
+    fn record(&mut self, value: f32) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.samples += 1;
+        self.avg += (value - self.avg) / self.samples as f32;
+        self.ema += EMA_ALPHA * (value - self.ema);
+    }
+}
+
+pub(crate) fn record_signal(stats: &mut Option<SignalStats>, value: f32) {
+    match stats {
+        Some(stats) => stats.record(value),
+        None => *stats = Some(SignalStats::new(value)),
+    }
+}
+
+/// Traffic and signal quality of a link since `since`
+#[derive(Debug, Clone)]
+pub struct LinkStats {
+    /// Start of the collection, creation or last reset
+    pub since: Instant,
+    /// Payload bytes received
+    pub bytes_in: u64,
+    /// Payload bytes transmitted
+    pub bytes_out: u64,
+    pub packets_in: u64,
+    pub packets_out: u64,
+    /// Signal strength of received packets, `None` before the first
+    pub rssi: Option<SignalStats>,
+    /// Signal-to-Noise ratio of received packets, `None` before the first
+    pub snr: Option<SignalStats>,
+    /// Packets dropped by the firmware because of CRC failures
+    pub crc_errors: usize,
+    /// Packets dropped by the driver because its receive buffer was full
+    pub rx_dropped: usize,
+    /// Retransmissions of an ARQ layer, if there is one
+    pub retransmits: usize,
+    /// Time on air of all transmissions
+    pub airtime: Duration,
+    recent_in: VecDeque<Instant>,
+    recent_out: VecDeque<Instant>,
+}
+
+impl LinkStats {
+    pub fn new() -> Self {
+        LinkStats {
+            since: Instant::now(),
+            bytes_in: 0,
+            bytes_out: 0,
+            packets_in: 0,
+            packets_out: 0,
+            rssi: None,
+            snr: None,
+            crc_errors: 0,
+            rx_dropped: 0,
+            retransmits: 0,
+            airtime: Duration::from_secs(0),
+            recent_in: VecDeque::new(),
+            recent_out: VecDeque::new(),
+        }
+    }
+    /// Count a received packet.
+    pub fn record_rx(&mut self, packet: &RxPacket) {
+        self.bytes_in += packet.data.len() as u64;
+        self.packets_in += 1;
+        record_signal(&mut self.rssi, packet.rssi as f32);
+        record_signal(&mut self.snr, packet.snr);
+        record_recent(&mut self.recent_in);
+    }
+    /// Count a transmission of `bytes` taking `toa` on air.
+    pub fn record_tx(&mut self, bytes: usize, toa: Duration) {
+        self.bytes_out += bytes as u64;
+        self.packets_out += 1;
+        self.airtime += toa;
+        record_recent(&mut self.recent_out);
+    }
+    /// Add the traffic of `other`, e.g. to total several links.
+    ///
+    /// The collection start becomes the earlier of both.
+    pub fn merge(&mut self, other: &LinkStats) {
+        self.since = self.since.min(other.since);
+        self.bytes_in += other.bytes_in;
+        self.bytes_out += other.bytes_out;
+        self.packets_in += other.packets_in;
+        self.packets_out += other.packets_out;
+        for (own, theirs) in [(&mut self.rssi, &other.rssi), (&mut self.snr, &other.snr)] {
+            match (own.as_mut(), theirs) {
+                (Some(own), Some(theirs)) => own.merge(theirs),
+                (None, Some(theirs)) => *own = Some(*theirs),
+                _ => {}
+            }
+        }
+        self.crc_errors += other.crc_errors;
+        self.rx_dropped += other.rx_dropped;
+        self.retransmits += other.retransmits;
+        self.airtime += other.airtime;
+        self.recent_in.extend(other.recent_in.iter().copied());
+        self.recent_out.extend(other.recent_out.iter().copied());
+        self.recent_in.make_contiguous().sort();
+        self.recent_out.make_contiguous().sort();
+    }
+    /// Packets received within the last minute.
+    pub fn rx_per_minute(&self) -> usize {
+        count_recent(&self.recent_in)
+    }
+    /// Packets transmitted within the last minute.
+    pub fn tx_per_minute(&self) -> usize {
+        count_recent(&self.recent_out)
+    }
+}
+
+impl Default for LinkStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn record_recent(recent: &mut VecDeque<Instant>) {
+    let now = Instant::now();
+    while recent.front().is_some_and(|&t| now - t > RATE_WINDOW) {
+        recent.pop_front();
+    }
+    recent.push_back(now);
+}
+
+fn count_recent(recent: &VecDeque<Instant>) -> usize {
+    let now = Instant::now();
+    recent.iter().filter(|&&t| now - t <= RATE_WINDOW).count()
+}
+
+/// Collects `LinkStats` for all traffic passing through a device.
+///
+/// CRC errors and dropped packets are taken from the `rx bad` and `rx dropped`
+/// counters of the `Status` whenever `config()` is called, counted from the
+/// first call after a reset.
+pub struct StatsModem<T: LoraModemDevice> {
+    inner: T,
+    stats: LinkStats,
+    params: Option<RadioParams>,
+    rx_bad_base: Option<usize>,
+    rx_dropped_base: Option<usize>,
+}
+
+impl<T: LoraModemDevice> StatsModem<T> {
+    pub fn new(inner: T) -> Self {
+        StatsModem {
+            inner,
+            stats: LinkStats::new(),
+            params: None,
+            rx_bad_base: None,
+            rx_dropped_base: None,
+        }
+    }
+    /// Unwrap the inner device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    // Time on air of `len` bytes, zero if the radio settings cannot be determined.
+    fn airtime(&mut self, len: usize) -> Duration {
+        if self.params.is_none() {
+            self.params = self.inner.get_radio_params().ok();
+        }
+        self.params
+            .and_then(|params| airtime(len, &params).ok())
+            .unwrap_or_default()
+    }
+    // Count a frame of `len` bytes sent as `report`.
+    fn record_tx(&mut self, len: usize, report: &TxReport) {
+        let toa = match report.airtime_estimate {
+            Some(toa) => toa,
+            None => self.airtime(len),
+        };
+        self.stats.record_tx(report.bytes, toa);
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for StatsModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.params = None;
+        self.rx_bad_base = None;
+        self.rx_dropped_base = None;
+        self.inner.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.inner.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.inner.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        let status = self.inner.config()?;
+        let base = *self.rx_bad_base.get_or_insert(status.rx_bad);
+        self.stats.crc_errors = status.rx_bad.saturating_sub(base);
+        let base = *self.rx_dropped_base.get_or_insert(status.rx_dropped);
+        self.stats.rx_dropped = status.rx_dropped.saturating_sub(base);
+        Ok(status)
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.inner.set_mode(mode)?;
+        self.params = Some(mode.into());
+        Ok(())
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.inner.set_radio_params(params)?;
+        self.params = Some(params);
+        Ok(())
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.inner.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.inner.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.inner.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.inner.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.inner.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.inner.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.inner.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.inner.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        self.inner.max_payload()
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.inner.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.inner.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.inner.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.inner.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.inner.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.inner.wake()
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        Ok(self.stats.clone())
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.stats = LinkStats::new();
+        self.rx_bad_base = None;
+        self.rx_dropped_base = None;
+        Ok(())
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        let len = data.len();
+        let report = self.inner.send_data(data)?;
+        self.record_tx(len, &report);
+        Ok(report)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        let report = self.inner.send_slice(data)?;
+        self.record_tx(data.len(), &report);
+        Ok(report)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        let results = self.inner.send_batch(frames);
+        for (frame, result) in frames.iter().zip(&results) {
+            if let Ok(report) = result {
+                self.record_tx(frame.len(), report);
+            }
+        }
+        results
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        let packet = self.inner.read_packet()?;
+        self.stats.record_rx(&packet);
+        Ok(packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.inner.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.inner.next_event()
+    }
+}
@@ -0,0 +1,231 @@
+use crate::addressing::AddressedModem;
+use crate::beacon::BeaconReport;
+use crate::{GpsFix, LoraModemDevice, ModemError, Result, RxPacket};
+use std::fmt;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What a survey sample was measured with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleSource {
+    /// Echo exchange started by the surveying node
+    Ping,
+    /// Beacon of another node
+    Beacon,
+    /// Any other received packet
+    Packet,
+}
+
+impl fmt::Display for SampleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SampleSource::Ping => "ping",
+            SampleSource::Beacon => "beacon",
+            SampleSource::Packet => "packet",
+        })
+    }
+}
+
+/// Signal measured at one position during a site survey
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurveySample {
+    /// Time of the GPS fix, or when the sample was taken if the fix has none
+    pub time: SystemTime,
+    /// Position of the surveying modem
+    pub fix: GpsFix,
+    pub source: SampleSource,
+    /// Node the signal was exchanged with, if known
+    pub node: Option<u8>,
+    /// RSSI in dBm received at the position, `None` if a ping went unanswered
+    pub rssi: Option<i16>,
+    /// SNR in dB received at the position, `None` if a ping went unanswered
+    pub snr: Option<f32>,
+    /// RSSI in dBm the peer received a ping with
+    pub rssi_fwd: Option<i16>,
+    /// SNR in dB the peer received a ping with
+    pub snr_fwd: Option<f32>,
+}
+
+// `time` as ISO 8601 UTC with second resolution, e.g. `2024-05-01T12:00:00Z`.
+fn utc(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64);
+    let (days, secs) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    // civil date from days since 1970-01-01, after Howard Hinnant
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+// Empty for `None`, as CSV leaves missing values.
+fn opt<T: fmt::Display>(value: Option<T>) -> String {
+    value.map_or(String::new(), |value| value.to_string())
+}
+
+/// Recorder for coverage maps, taken while moving around with a modem that has a GPS.
+///
+/// Every sample pairs the position of the modem with the signal of a ping
+/// answered by a fixed node or of a received beacon or packet. Unanswered pings
+/// are kept as samples without signal, marking spots without coverage. Samples
+/// are exported as CSV for spreadsheets and GIS tools or as a GPX track.
+#[derive(Debug, Clone, Default)]
+pub struct SiteSurvey {
+    samples: Vec<SurveySample>,
+}
+
+impl SiteSurvey {
+    pub fn new() -> Self {
+        SiteSurvey::default()
+    }
+    /// All samples in the order they were taken.
+    pub fn samples(&self) -> &[SurveySample] {
+        &self.samples
+    }
+    /// Add a sample measured elsewhere.
+    pub fn record(&mut self, sample: SurveySample) {
+        self.samples.push(sample);
+    }
+    /// Add a packet received at `fix`, beacons are attributed to their sender.
+    pub fn record_packet(&mut self, fix: GpsFix, packet: &RxPacket) {
+        let (source, node) = match BeaconReport::from_packet(packet) {
+            Ok(report) => (SampleSource::Beacon, Some(report.node_id)),
+            Err(_) => (SampleSource::Packet, None),
+        };
+        self.samples.push(SurveySample {
+            time: fix.time.unwrap_or(packet.received_at),
+            fix,
+            source,
+            node,
+            rssi: Some(packet.rssi),
+            snr: Some(packet.snr),
+            rssi_fwd: None,
+            snr_fwd: None,
+        });
+    }
+
+    /// Ping `peer` and record the result at the current position of the modem.
+    ///
+    /// Returns the sample, or `None` without pinging while the modem has no fix.
+    pub fn ping<T: LoraModemDevice>(
+        &mut self,
+        modem: &mut AddressedModem<T>,
+        peer: u8,
+    ) -> Result<Option<&SurveySample>> {
+        let fix = match modem.gps_fix()? {
+            Some(fix) => fix,
+            None => return Ok(None),
+        };
+        let report = match modem.ping(peer) {
+            Ok(report) => Some(report),
+            Err(ModemError::Timeout) => None,
+            Err(e) => return Err(e),
+        };
+        self.samples.push(SurveySample {
+            time: fix.time.unwrap_or_else(SystemTime::now),
+            fix,
+            source: SampleSource::Ping,
+            node: Some(peer),
+            rssi: report.map(|r| r.rssi_back),
+            snr: report.map(|r| r.snr_back),
+            rssi_fwd: report.map(|r| r.rssi_fwd),
+            snr_fwd: report.map(|r| r.snr_fwd),
+        });
+        Ok(self.samples.last())
+    }
+    /// Wait for the next packet and record it at the current position of the modem.
+    ///
+    /// Returns the sample, or `None` if the modem had no fix when the packet arrived.
+    pub fn receive<D: LoraModemDevice + ?Sized>(
+        &mut self,
+        device: &mut D,
+    ) -> Result<Option<&SurveySample>> {
+        let packet = device.read_packet()?;
+        match device.gps_fix()? {
+            Some(fix) => {
+                self.record_packet(fix, &packet);
+                Ok(self.samples.last())
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Write the samples as CSV with a header line, missing values are left empty.
+    pub fn write_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(
+            out,
+            "time,lat,lon,alt,sats,hdop,source,node,rssi,snr,rssi_fwd,snr_fwd"
+        )?;
+        for sample in &self.samples {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{},{},{},{}",
+                utc(sample.time),
+                sample.fix.lat,
+                sample.fix.lon,
+                sample.fix.alt,
+                sample.fix.sats,
+                sample.fix.hdop,
+                sample.source,
+                opt(sample.node),
+                opt(sample.rssi),
+                opt(sample.snr),
+                opt(sample.rssi_fwd),
+                opt(sample.snr_fwd)
+            )?;
+        }
+        Ok(())
+    }
+    /// Write the samples as a GPX 1.1 track, the signal of each point given in its description.
+    pub fn write_gpx<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<gpx version="1.1" creator="lora-modem-hal" xmlns="http://www.topografix.com/GPX/1/1">"#
+        )?;
+        writeln!(
+            out,
+            "  <trk>\n    <name>LoRa site survey</name>\n    <trkseg>"
+        )?;
+        for sample in &self.samples {
+            let signal = match (sample.rssi, sample.snr) {
+                (Some(rssi), Some(snr)) => format!("RSSI {} dBm, SNR {} dB", rssi, snr),
+                _ => "no reply".to_string(),
+            };
+            let node = sample
+                .node
+                .map_or(String::new(), |n| format!(" node {}", n));
+            writeln!(
+                out,
+                r#"      <trkpt lat="{}" lon="{}">"#,
+                sample.fix.lat, sample.fix.lon
+            )?;
+            writeln!(out, "        <ele>{}</ele>", sample.fix.alt)?;
+            writeln!(out, "        <time>{}</time>", utc(sample.time))?;
+            writeln!(
+                out,
+                "        <desc>{}{}: {}</desc>",
+                sample.source, node, signal
+            )?;
+            writeln!(out, "        <sat>{}</sat>", sample.fix.sats)?;
+            writeln!(out, "        <hdop>{}</hdop>", sample.fix.hdop)?;
+            writeln!(out, "      </trkpt>")?;
+        }
+        writeln!(out, "    </trkseg>\n  </trk>\n</gpx>")
+    }
+}
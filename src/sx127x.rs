@@ -0,0 +1,522 @@
+use crate::radio::{validate_tx_power, Bandwidth, CodingRate, HeaderMode, RadioParams};
+use crate::trace;
+use crate::{
+    check_payload, Capabilities, Frequency, LoraModemDevice, ModemConfig, ModemError,
+    ModemTelemetry, RadioState, Result, RxPacket, Status, TxReport,
+};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+/// SPI device with its chip select handled, mirroring `embedded_hal::spi::SpiDevice`.
+pub trait SpiDevice {
+    type Error: Debug;
+    /// Exchange `words` in a single transaction, replacing them with the bytes read.
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> core::result::Result<(), Self::Error>;
+}
+
+/// Digital input, mirroring `embedded_hal::digital::InputPin`.
+pub trait InputPin {
+    type Error: Debug;
+    fn is_high(&mut self) -> core::result::Result<bool, Self::Error>;
+}
+
+/// Blocking delay, mirroring `embedded_hal::delay::DelayNs`.
+pub trait DelayMs {
+    fn delay_ms(&mut self, ms: u32);
+}
+
+const REG_FIFO: u8 = 0x00;
+const REG_OP_MODE: u8 = 0x01;
+const REG_FRF_MSB: u8 = 0x06;
+const REG_PA_CONFIG: u8 = 0x09;
+const REG_FIFO_ADDR_PTR: u8 = 0x0d;
+const REG_FIFO_TX_BASE_ADDR: u8 = 0x0e;
+const REG_FIFO_RX_BASE_ADDR: u8 = 0x0f;
+const REG_FIFO_RX_CURRENT_ADDR: u8 = 0x10;
+const REG_IRQ_FLAGS: u8 = 0x12;
+const REG_RX_NB_BYTES: u8 = 0x13;
+const REG_PKT_SNR_VALUE: u8 = 0x19;
+const REG_PKT_RSSI_VALUE: u8 = 0x1a;
+const REG_MODEM_CONFIG_1: u8 = 0x1d;
+const REG_MODEM_CONFIG_2: u8 = 0x1e;
+const REG_PREAMBLE_MSB: u8 = 0x20;
+const REG_PAYLOAD_LENGTH: u8 = 0x22;
+const REG_MODEM_CONFIG_3: u8 = 0x26;
+const REG_DETECTION_OPTIMIZE: u8 = 0x31;
+const REG_DETECTION_THRESHOLD: u8 = 0x37;
+const REG_SYNC_WORD: u8 = 0x39;
+const REG_IMAGE_CAL: u8 = 0x3b;
+const REG_TEMP: u8 = 0x3c;
+const REG_DIO_MAPPING_1: u8 = 0x40;
+const REG_VERSION: u8 = 0x42;
+const REG_PA_DAC: u8 = 0x4d;
+
+const MODE_LONG_RANGE: u8 = 0x80;
+const MODE_SLEEP: u8 = 0x00;
+const MODE_STDBY: u8 = 0x01;
+const MODE_TX: u8 = 0x03;
+const MODE_RX_CONTINUOUS: u8 = 0x05;
+const MODE_CAD: u8 = 0x07;
+// frequency synthesis towards receive in FSK mode, runs the temperature sensor
+const MODE_FSK_FS_RX: u8 = 0x04;
+const TEMP_MONITOR_OFF: u8 = 0x01;
+
+const IRQ_RX_DONE: u8 = 0x40;
+const IRQ_PAYLOAD_CRC_ERROR: u8 = 0x20;
+const IRQ_TX_DONE: u8 = 0x08;
+const IRQ_CAD_DONE: u8 = 0x04;
+const IRQ_CAD_DETECTED: u8 = 0x01;
+
+const DIO0_RX_DONE: u8 = 0x00;
+const DIO0_TX_DONE: u8 = 0x40;
+const DIO0_CAD_DONE: u8 = 0x80;
+
+const SX127X_VERSION: u8 = 0x12;
+const FXOSC: u64 = 32_000_000;
+const MAX_PAYLOAD: usize = 255;
+
+// Bandwidths in the order of their register codes.
+const BANDWIDTHS: [Bandwidth; 10] = [
+    Bandwidth::Bw7_8kHz,
+    Bandwidth::Bw10_4kHz,
+    Bandwidth::Bw15_6kHz,
+    Bandwidth::Bw20_8kHz,
+    Bandwidth::Bw31_25kHz,
+    Bandwidth::Bw41_7kHz,
+    Bandwidth::Bw62_5kHz,
+    Bandwidth::Bw125kHz,
+    Bandwidth::Bw250kHz,
+    Bandwidth::Bw500kHz,
+];
+
+fn transport_error<E: Debug>(e: E) -> ModemError {
+    ModemError::Transport(format!("{:?}", e))
+}
+
+/// Semtech SX1276/77/78/79 radio, e.g. on a Raspberry Pi hat, driven over SPI.
+///
+/// The PA_BOOST output is used, as on HopeRF RFM95 modules. DIO0 signals
+/// finished transmissions, receptions and channel activity detections.
+/// Waits are bounded by a timeout counted in milliseconds of `delay`, `read_packet`
+/// returns `ModemError::Timeout` when nothing was received in time.
+pub struct Sx127xModem<SPI, DIO0, D> {
+    spi: SPI,
+    dio0: DIO0,
+    delay: D,
+    timeout_ms: Option<u32>,
+    version: u8,
+    frequency: Frequency,
+    params: RadioParams,
+    tx_power: i8,
+    rx_enabled: bool,
+    radio_state: RadioState,
+    // the radio is in continuous receive mode
+    listening: bool,
+    rx_good: usize,
+    rx_bad: usize,
+    tx_good: usize,
+}
+
+impl<SPI: SpiDevice, DIO0: InputPin, D: DelayMs> Sx127xModem<SPI, DIO0, D> {
+    /// Create a driver for the radio on `spi`, set up on `open()` to 868.1 MHz,
+    /// the medium range modem config and 13 dBm.
+    pub fn new(spi: SPI, dio0: DIO0, delay: D) -> Self {
+        Sx127xModem {
+            spi,
+            dio0,
+            delay,
+            timeout_ms: Some(1000),
+            version: 0,
+            frequency: Frequency::from_khz(868_100),
+            params: ModemConfig::MediumBw125Cr45Sf128Crc.into(),
+            tx_power: 13,
+            rx_enabled: true,
+            radio_state: RadioState::Active,
+            listening: false,
+            rx_good: 0,
+            rx_bad: 0,
+            tx_good: 0,
+        }
+    }
+    /// Bound transmissions and reads by `timeout_ms`, `None` waits forever.
+    pub fn set_timeout_ms(&mut self, timeout_ms: Option<u32>) {
+        self.timeout_ms = timeout_ms;
+    }
+    /// Consume the driver and return SPI device, DIO0 pin and delay.
+    pub fn into_inner(self) -> (SPI, DIO0, D) {
+        (self.spi, self.dio0, self.delay)
+    }
+
+    fn read_register(&mut self, reg: u8) -> Result<u8> {
+        let mut words = [reg & 0x7f, 0];
+        self.spi
+            .transfer_in_place(&mut words)
+            .map_err(transport_error)?;
+        trace::register(reg, words[1], false);
+        Ok(words[1])
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<()> {
+        trace::register(reg, value, true);
+        let mut words = [reg | 0x80, value];
+        self.spi
+            .transfer_in_place(&mut words)
+            .map_err(transport_error)
+    }
+
+    fn set_op_mode(&mut self, mode: u8) -> Result<()> {
+        self.write_register(REG_OP_MODE, MODE_LONG_RANGE | mode)
+    }
+
+    // Poll DIO0 until it rises, failing with a timeout once `timeout_ms` passed.
+    fn wait_dio0(&mut self) -> Result<()> {
+        let mut waited = 0;
+        while !self.dio0.is_high().map_err(transport_error)? {
+            if self.timeout_ms.is_some_and(|t| waited >= t) {
+                return Err(ModemError::Timeout);
+            }
+            self.delay.delay_ms(1);
+            waited += 1;
+        }
+        Ok(())
+    }
+
+    fn apply_frequency(&mut self) -> Result<()> {
+        let frf = ((self.frequency.hz() as u64) << 19) / FXOSC;
+        self.write_register(REG_FRF_MSB, (frf >> 16) as u8)?;
+        self.write_register(REG_FRF_MSB + 1, (frf >> 8) as u8)?;
+        self.write_register(REG_FRF_MSB + 2, frf as u8)
+    }
+
+    fn apply_params(&mut self) -> Result<()> {
+        let params = self.params;
+        let bw = BANDWIDTHS
+            .iter()
+            .position(|&bw| bw == params.bandwidth)
+            .unwrap_or(7) as u8;
+        let cr = params.coding_rate.denominator() - 4;
+        let implicit = params.header.implicit_len().is_some() as u8;
+        self.write_register(REG_MODEM_CONFIG_1, bw << 4 | cr << 1 | implicit)?;
+        let crc = if params.crc { 0x04 } else { 0x00 };
+        self.write_register(REG_MODEM_CONFIG_2, params.spreading_factor << 4 | crc)?;
+        // low data rate optimization is mandated above 16ms symbol time
+        let symbol_us =
+            (1u64 << params.spreading_factor) * 1_000_000 / params.bandwidth.hz() as u64;
+        let ldro = if symbol_us > 16_000 { 0x08 } else { 0x00 };
+        self.write_register(REG_MODEM_CONFIG_3, ldro | 0x04)?;
+        // spreading factor 6 needs its own detection settings
+        if params.spreading_factor == 6 {
+            self.write_register(REG_DETECTION_OPTIMIZE, 0xc5)?;
+            self.write_register(REG_DETECTION_THRESHOLD, 0x0c)?;
+        } else {
+            self.write_register(REG_DETECTION_OPTIMIZE, 0xc3)?;
+            self.write_register(REG_DETECTION_THRESHOLD, 0x0a)?;
+        }
+        self.write_register(REG_SYNC_WORD, params.sync_word)?;
+        self.write_register(REG_PREAMBLE_MSB, (params.preamble_len >> 8) as u8)?;
+        self.write_register(REG_PREAMBLE_MSB + 1, params.preamble_len as u8)
+    }
+
+    fn apply_tx_power(&mut self) -> Result<()> {
+        // above 17 dBm the high power DAC adds 3 dB
+        let (pa_dac, level) = if self.tx_power > 17 {
+            (0x87, self.tx_power - 5)
+        } else {
+            (0x84, self.tx_power - 2)
+        };
+        self.write_register(REG_PA_DAC, pa_dac)?;
+        self.write_register(REG_PA_CONFIG, 0x80 | level as u8)
+    }
+
+    // Switch to LoRa mode and apply the configuration, leaves the radio asleep.
+    fn enter_lora(&mut self) -> Result<()> {
+        // LoRa mode can only be entered from sleep
+        self.write_register(REG_OP_MODE, MODE_SLEEP)?;
+        self.set_op_mode(MODE_SLEEP)?;
+        self.write_register(REG_FIFO_TX_BASE_ADDR, 0)?;
+        self.write_register(REG_FIFO_RX_BASE_ADDR, 0)?;
+        self.apply_frequency()?;
+        self.apply_params()?;
+        self.apply_tx_power()
+    }
+
+    // Leave continuous receive mode before using the radio otherwise,
+    // registers can be written in sleep so a sleeping radio stays asleep.
+    fn idle(&mut self) -> Result<()> {
+        self.listening = false;
+        if self.radio_state == RadioState::Sleep {
+            return Ok(());
+        }
+        self.set_op_mode(MODE_STDBY)
+    }
+
+    fn start_listening(&mut self) -> Result<()> {
+        // without a header the receiver has to be told the length to expect
+        if let HeaderMode::Implicit { len } = self.params.header {
+            self.write_register(REG_PAYLOAD_LENGTH, len)?;
+        }
+        self.write_register(REG_DIO_MAPPING_1, DIO0_RX_DONE)?;
+        self.set_op_mode(MODE_RX_CONTINUOUS)?;
+        self.listening = true;
+        Ok(())
+    }
+}
+
+impl<SPI: SpiDevice, DIO0: InputPin, D: DelayMs> LoraModemDevice for Sx127xModem<SPI, DIO0, D> {
+    fn open(&mut self) -> Result<()> {
+        self.version = self.read_register(REG_VERSION)?;
+        if self.version != SX127X_VERSION {
+            return Err(ModemError::ModemReported(format!(
+                "unexpected SX127x version 0x{:02x}",
+                self.version
+            )));
+        }
+        self.enter_lora()?;
+        self.rx_enabled = true;
+        self.radio_state = RadioState::Active;
+        self.idle()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        if !(Frequency::from_mhz(137)..=Frequency::from_mhz(1020)).contains(&freq) {
+            return Err(ModemError::InvalidArgument(format!(
+                "frequency {} MHz outside the SX127x range",
+                freq
+            )));
+        }
+        self.idle()?;
+        self.frequency = freq;
+        self.apply_frequency()
+    }
+    fn config(&mut self) -> Result<Status> {
+        Ok(Status {
+            version: format!("SX127x 0x{:02x}", self.version),
+            config: self
+                .params
+                .preset()
+                .unwrap_or(ModemConfig::MediumBw125Cr45Sf128Crc),
+            max_pkt_size: MAX_PAYLOAD,
+            frequency: self.frequency,
+            rx_listener: self.rx_enabled,
+            tx_power: Some(self.tx_power),
+            sync_word: Some(self.params.sync_word),
+            rx_bad: self.rx_bad,
+            rx_good: self.rx_good,
+            tx_good: self.tx_good,
+            radio_state: Some(self.radio_state),
+            ..Status::new()
+        })
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        Ok(MAX_PAYLOAD)
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.set_radio_params(mode.into())
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        params.validate()?;
+        if params.spreading_factor == 6 && params.header == HeaderMode::Explicit {
+            return Err(ModemError::InvalidArgument(
+                "spreading factor 6 requires implicit headers".into(),
+            ));
+        }
+        self.idle()?;
+        self.params = params;
+        self.apply_params()
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        let config1 = self.read_register(REG_MODEM_CONFIG_1)?;
+        let config2 = self.read_register(REG_MODEM_CONFIG_2)?;
+        let msb = self.read_register(REG_PREAMBLE_MSB)? as u16;
+        let lsb = self.read_register(REG_PREAMBLE_MSB + 1)? as u16;
+        // the payload length register is reused for transmitting, keep the configured one
+        let header = if config1 & 0x01 != 0 {
+            self.params.header
+        } else {
+            HeaderMode::Explicit
+        };
+        Ok(RadioParams {
+            bandwidth: *BANDWIDTHS
+                .get((config1 >> 4) as usize)
+                .ok_or_else(|| ModemError::Parse(format!("unknown bandwidth {}", config1 >> 4)))?,
+            spreading_factor: config2 >> 4,
+            coding_rate: CodingRate::from_denominator((config1 >> 1 & 0x07) + 4).ok_or_else(
+                || ModemError::Parse(format!("unknown coding rate {}", config1 >> 1 & 0x07)),
+            )?,
+            preamble_len: msb << 8 | lsb,
+            crc: config2 & 0x04 != 0,
+            header,
+            sync_word: self.read_register(REG_SYNC_WORD)?,
+        })
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        validate_tx_power(dbm)?;
+        self.tx_power = dbm;
+        self.apply_tx_power()
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        Ok(self.tx_power)
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        Ok(Capabilities::RADIO_PARAMS
+            | Capabilities::TX_POWER
+            | Capabilities::CAD
+            | Capabilities::POWER_MODES
+            | Capabilities::TELEMETRY
+            | Capabilities::IMPLICIT_HEADER)
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        // the temperature sensor only runs in FSK mode, which is entered from sleep
+        self.listening = false;
+        self.set_op_mode(MODE_SLEEP)?;
+        self.write_register(REG_OP_MODE, MODE_SLEEP)?;
+        let image_cal = self.read_register(REG_IMAGE_CAL)?;
+        self.write_register(REG_IMAGE_CAL, image_cal & !TEMP_MONITOR_OFF)?;
+        self.write_register(REG_OP_MODE, MODE_FSK_FS_RX)?;
+        self.delay.delay_ms(1);
+        self.write_register(REG_IMAGE_CAL, image_cal | TEMP_MONITOR_OFF)?;
+        self.write_register(REG_OP_MODE, MODE_SLEEP)?;
+        let raw = self.read_register(REG_TEMP)?;
+        self.enter_lora()?;
+        if self.radio_state != RadioState::Sleep {
+            self.set_op_mode(MODE_STDBY)?;
+        }
+        // two's complement falling by one per degree, uncalibrated and typically
+        // off by a few degrees
+        let temp = if raw & 0x80 != 0 {
+            255 - raw as i16
+        } else {
+            -(raw as i16)
+        };
+        Ok(ModemTelemetry {
+            vcc_mv: None,
+            temp_c: Some(temp as i8),
+        })
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.radio_state = RadioState::Active;
+        self.idle()?;
+        self.write_register(REG_IRQ_FLAGS, 0xff)?;
+        self.write_register(REG_DIO_MAPPING_1, DIO0_CAD_DONE)?;
+        self.set_op_mode(MODE_CAD)?;
+        self.wait_dio0()?;
+        let flags = self.read_register(REG_IRQ_FLAGS)?;
+        self.write_register(REG_IRQ_FLAGS, 0xff)?;
+        Ok(flags & IRQ_CAD_DONE != 0 && flags & IRQ_CAD_DETECTED != 0)
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.rx_enabled = true;
+        Ok(())
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.rx_enabled = false;
+        self.idle()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.listening = false;
+        self.set_op_mode(MODE_SLEEP)?;
+        self.radio_state = RadioState::Sleep;
+        Ok(())
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.listening = false;
+        self.set_op_mode(MODE_STDBY)?;
+        self.radio_state = RadioState::Standby;
+        Ok(())
+    }
+    fn wake(&mut self) -> Result<()> {
+        if self.radio_state == RadioState::Sleep {
+            self.set_op_mode(MODE_STDBY)?;
+        }
+        // reception is restarted by the next `read_packet`
+        self.radio_state = RadioState::Active;
+        Ok(())
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        check_payload(data.len(), MAX_PAYLOAD)?;
+        if let Some(len) = self.params.header.implicit_len() {
+            if data.len() != len {
+                return Err(ModemError::InvalidArgument(format!(
+                    "implicit header mode sends {} byte payloads, got {}",
+                    len,
+                    data.len()
+                )));
+            }
+        }
+        self.radio_state = RadioState::Active;
+        self.idle()?;
+        self.write_register(REG_FIFO_ADDR_PTR, 0)?;
+        let mut words = vec![REG_FIFO | 0x80];
+        words.extend_from_slice(&data);
+        self.spi
+            .transfer_in_place(&mut words)
+            .map_err(transport_error)?;
+        self.write_register(REG_PAYLOAD_LENGTH, data.len() as u8)?;
+        self.write_register(REG_IRQ_FLAGS, 0xff)?;
+        self.write_register(REG_DIO_MAPPING_1, DIO0_TX_DONE)?;
+        self.set_op_mode(MODE_TX)?;
+        self.wait_dio0()?;
+        let flags = self.read_register(REG_IRQ_FLAGS)?;
+        self.write_register(REG_IRQ_FLAGS, 0xff)?;
+        if flags & IRQ_TX_DONE == 0 {
+            return Err(ModemError::ModemReported(
+                "transmission not completed".into(),
+            ));
+        }
+        self.tx_good += 1;
+        Ok(TxReport::new(data.len(), Some(self.params)))
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        if !self.rx_enabled || self.radio_state != RadioState::Active {
+            return Err(ModemError::RxDisabled);
+        }
+        if !self.listening {
+            self.write_register(REG_IRQ_FLAGS, 0xff)?;
+            self.start_listening()?;
+        }
+        self.wait_dio0()?;
+        let flags = self.read_register(REG_IRQ_FLAGS)?;
+        self.write_register(REG_IRQ_FLAGS, 0xff)?;
+        if flags & IRQ_RX_DONE == 0 {
+            return Err(ModemError::Timeout);
+        }
+        if flags & IRQ_PAYLOAD_CRC_ERROR != 0 {
+            self.rx_bad += 1;
+            return Err(ModemError::Parse("received packet with CRC error".into()));
+        }
+        let len = self.read_register(REG_RX_NB_BYTES)? as usize;
+        let start = self.read_register(REG_FIFO_RX_CURRENT_ADDR)?;
+        self.write_register(REG_FIFO_ADDR_PTR, start)?;
+        let mut words = vec![0u8; len + 1];
+        words[0] = REG_FIFO & 0x7f;
+        self.spi
+            .transfer_in_place(&mut words)
+            .map_err(transport_error)?;
+        // in quarter dB
+        let snr = self.read_register(REG_PKT_SNR_VALUE)? as i8 as f32 / 4.0;
+        // the RSSI offset differs between the high and low frequency ports
+        let offset = if self.frequency > Frequency::from_mhz(525) {
+            -157
+        } else {
+            -164
+        };
+        let rssi = offset + self.read_register(REG_PKT_RSSI_VALUE)? as i16;
+        self.rx_good += 1;
+        Ok(RxPacket {
+            rssi,
+            snr,
+            data: words.split_off(1),
+            #[cfg(feature = "std")]
+            received_at: SystemTime::now(),
+            freq_error: None,
+            modem_timestamp: None,
+        })
+    }
+    fn read_line(&mut self) -> Result<String> {
+        Err(ModemError::UnsupportedCommand("read_line".into()))
+    }
+}
@@ -0,0 +1,149 @@
+use crate::rf95::Rf95Modem;
+pub use crate::transport::ReconnectPolicy;
+use crate::transport::Transport;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+/// rf95modem whose serial console is exposed over TCP, e.g. by ser2net.
+pub type TcpModem = Rf95Modem<TcpTransport>;
+
+impl TcpModem {
+    /// Create a modem for the bridge at `addr` (`host:port`), connected by `open()`.
+    pub fn new(addr: &str) -> Self {
+        let mut modem = Rf95Modem::from_transport(TcpTransport::new(addr));
+        modem.set_timeout(Some(Duration::from_secs(5)));
+        modem
+    }
+}
+
+/// TCP connection to a serial bridge.
+pub struct TcpTransport {
+    addr: String,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    reconnect: ReconnectPolicy,
+    reconnected: bool,
+    stream: Option<TcpStream>,
+}
+
+impl TcpTransport {
+    pub fn new(addr: &str) -> Self {
+        TcpTransport {
+            addr: addr.to_string(),
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_millis(100),
+            reconnect: ReconnectPolicy::default(),
+            reconnected: false,
+            stream: None,
+        }
+    }
+    /// Address of the bridge.
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+    /// Maximum time to establish a connection.
+    pub fn set_connect_timeout(&mut self, timeout: Duration) {
+        self.connect_timeout = timeout;
+    }
+    /// How long a single read waits for data, applied on next connect.
+    pub fn set_read_timeout(&mut self, timeout: Duration) {
+        self.read_timeout = timeout;
+    }
+    /// Set the policy for recovering from dropped connections.
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect = policy;
+    }
+
+    fn connect(&mut self) -> io::Result<()> {
+        self.stream = None;
+        let mut last_err = io::Error::new(io::ErrorKind::InvalidInput, "no address resolved");
+        for addr in self.addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.connect_timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(self.read_timeout))?;
+                    stream.set_nodelay(true)?;
+                    self.stream = Some(stream);
+                    return Ok(());
+                }
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+
+    // Try to reestablish a lost connection according to the reconnect policy.
+    fn reconnect(&mut self, cause: io::Error) -> io::Result<()> {
+        self.stream = None;
+        let mut result = Err(cause);
+        for _ in 0..self.reconnect.max_attempts {
+            thread::sleep(self.reconnect.delay);
+            result = self.connect();
+            if result.is_ok() {
+                self.reconnected = true;
+                break;
+            }
+        }
+        result
+    }
+
+    fn stream(&mut self) -> io::Result<&mut TcpStream> {
+        self.stream
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "not connected"))
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::TimedOut || e.kind() == io::ErrorKind::WouldBlock
+}
+
+impl Transport for TcpTransport {
+    fn open(&mut self) -> io::Result<()> {
+        self.connect()
+    }
+    fn is_open(&self) -> bool {
+        self.stream.is_some()
+    }
+    fn set_open_timeout(&mut self, timeout: Option<Duration>) {
+        if let Some(timeout) = timeout {
+            self.connect_timeout = timeout;
+        }
+    }
+    fn take_reconnected(&mut self) -> bool {
+        std::mem::take(&mut self.reconnected)
+    }
+}
+
+impl Read for TcpTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = match self.stream()?.read(buf) {
+            Ok(0) if !buf.is_empty() => Err(io::ErrorKind::ConnectionAborted.into()),
+            result => result,
+        };
+        match result {
+            Err(e) if !is_timeout(&e) && e.kind() != io::ErrorKind::Interrupted => {
+                // nothing was read from the new connection yet
+                self.reconnect(e)?;
+                Err(io::ErrorKind::TimedOut.into())
+            }
+            result => result,
+        }
+    }
+}
+
+impl Write for TcpTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.stream()?.write(buf) {
+            Err(e) if !is_timeout(&e) && e.kind() != io::ErrorKind::Interrupted => {
+                self.reconnect(e)?;
+                self.stream()?.write(buf)
+            }
+            result => result,
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream()?.flush()
+    }
+}
@@ -0,0 +1,40 @@
+use crate::{info_fields, leading_number, round, Result};
+use alloc::string::String;
+
+/// Supply voltage and chip temperature reported by a modem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ModemTelemetry {
+    /// Supply voltage in millivolts, if the device can measure it
+    pub vcc_mv: Option<u16>,
+    /// Chip temperature in degrees Celsius, if the device can measure it
+    pub temp_c: Option<i8>,
+}
+
+impl ModemTelemetry {
+    /// Parse the `AT+TELEMETRY` response, e.g. `+VCC: 3312 mV` and `+TEMP: 24 C`.
+    ///
+    /// Voltages given with a decimal point are taken as volts. Unknown lines are
+    /// ignored and values missing from the output are `None`.
+    pub fn parse(lines: &[String]) -> Result<ModemTelemetry> {
+        let mut telemetry = ModemTelemetry::default();
+        for (key, value) in info_fields(lines) {
+            match key.as_str() {
+                "vcc" | "voltage" | "supply" => {
+                    let number = leading_number(value);
+                    let mv = if number.contains('.') {
+                        number.parse::<f64>()? * 1000.0
+                    } else {
+                        number.parse::<f64>()?
+                    };
+                    telemetry.vcc_mv = Some(round(mv).clamp(0.0, u16::MAX as f64) as u16);
+                }
+                "temp" | "temperature" => {
+                    let celsius: f64 = leading_number(value).parse()?;
+                    telemetry.temp_c = Some(round(celsius).clamp(-128.0, 127.0) as i8);
+                }
+                _ => {}
+            }
+        }
+        Ok(telemetry)
+    }
+}
@@ -0,0 +1,313 @@
+use crate::addressing::{AddressedModem, AddressedPacket, HEADER_LEN};
+use crate::radio::{airtime, RadioParams};
+use crate::stats::LinkStats;
+use crate::telemetry::ModemTelemetry;
+use crate::{
+    Capabilities, Frequency, GpsFix, LoraModemDevice, ModemConfig, ModemError, ModemEvent, Result,
+    RxPacket, Status, TxReport,
+};
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Header flag marking a time synchronization frame.
+pub const FLAG_TIME: u8 = 0x08;
+
+const KIND_REQUEST: u8 = 0x01;
+const KIND_RESPONSE: u8 = 0x02;
+const RESPONSE_LEN: usize = 18;
+/// Exchanges per synchronization, the one with the shortest round trip wins.
+pub const SYNC_ROUNDS: usize = 4;
+
+// Microseconds since the UNIX epoch, negative before it.
+pub(crate) fn micros(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_micros() as i64,
+        Err(e) => -(e.duration().as_micros() as i64),
+    }
+}
+
+fn shift(time: SystemTime, us: i64) -> SystemTime {
+    if us >= 0 {
+        time + Duration::from_micros(us as u64)
+    } else {
+        time - Duration::from_micros(us.unsigned_abs())
+    }
+}
+
+/// Offset of the clock of a peer against the local clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockOffset {
+    /// Time of the peer minus local time, in microseconds
+    pub offset_us: i64,
+    /// Round trip of the exchange it was measured with, airtime and the
+    /// processing time of the peer excluded. Half of it bounds the error.
+    pub delay: Duration,
+}
+
+impl ClockOffset {
+    /// Local `time` on the clock of the peer.
+    pub fn to_peer(&self, time: SystemTime) -> SystemTime {
+        shift(time, self.offset_us)
+    }
+    /// `time` of the peer on the local clock.
+    pub fn to_local(&self, time: SystemTime) -> SystemTime {
+        shift(time, -self.offset_us)
+    }
+}
+
+/// Clock synchronization with another node, for deployments without GPS.
+///
+/// `sync_time_with` measures the offset to the clock of a peer in a request and
+/// response exchange: the peer reports when the request arrived and when it
+/// sent the response, both corrected for the time on air of the frames, which
+/// cancels the propagation and leaves the serial latency as error. Requests of
+/// other nodes are answered by `receive`, so every node running a `SyncedModem`
+/// can serve as reference.
+///
+/// Once synchronized, received packets are timestamped on the clock of the
+/// reference, so packets from nodes synchronized against the same reference
+/// can be ordered across the deployment.
+pub struct SyncedModem<T: LoraModemDevice> {
+    link: AddressedModem<T>,
+    offset: Option<ClockOffset>,
+    timeout: Duration,
+    seq: u8,
+    inbox: VecDeque<AddressedPacket>,
+}
+
+impl<T: LoraModemDevice> SyncedModem<T> {
+    pub fn new(link: AddressedModem<T>) -> Self {
+        SyncedModem {
+            link,
+            offset: None,
+            timeout: Duration::from_secs(3),
+            seq: 0,
+            inbox: VecDeque::new(),
+        }
+    }
+    /// The addressing layer below.
+    pub fn link(&mut self) -> &mut AddressedModem<T> {
+        &mut self.link
+    }
+    /// Unwrap the addressing layer.
+    pub fn into_inner(self) -> AddressedModem<T> {
+        self.link
+    }
+    /// Time to wait for each response of the peer, 3 s by default.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+    /// Offset to the reference clock measured last.
+    pub fn offset(&self) -> Option<ClockOffset> {
+        self.offset
+    }
+    /// Use the clock of the peer `offset` was measured against as reference,
+    /// `None` timestamps packets on the local clock again.
+    pub fn set_offset(&mut self, offset: Option<ClockOffset>) {
+        self.offset = offset;
+    }
+    /// Current time on the reference clock.
+    pub fn now(&self) -> SystemTime {
+        match self.offset {
+            Some(offset) => offset.to_peer(SystemTime::now()),
+            None => SystemTime::now(),
+        }
+    }
+
+    // Time on air of a frame carrying `payload_len` bytes.
+    fn airtime_us(params: &Option<RadioParams>, payload_len: usize) -> i64 {
+        params
+            .and_then(|params| airtime(HEADER_LEN + payload_len, &params).ok())
+            .map_or(0, |toa| toa.as_micros() as i64)
+    }
+
+    /// Measure the offset to the clock of `peer` and use it as reference.
+    pub fn sync_time_with(&mut self, peer: u8) -> Result<ClockOffset> {
+        let params = self.link.get_radio_params().ok();
+        let mut best: Option<ClockOffset> = None;
+        for _ in 0..SYNC_ROUNDS {
+            self.seq = self.seq.wrapping_add(1);
+            let seq = self.seq;
+            let t1 = micros(SystemTime::now());
+            self.link
+                .send_with_flags(peer, FLAG_TIME, &[KIND_REQUEST, seq])?;
+            let response = match self.wait_for_response(peer, seq)? {
+                Some(response) => response,
+                None => continue,
+            };
+            let data = &response.packet.data;
+            let t2 = i64::from_be_bytes(data[2..10].try_into().unwrap_or_default());
+            let t3 = i64::from_be_bytes(data[10..18].try_into().unwrap_or_default());
+            let t4 = micros(response.packet.received_at) - Self::airtime_us(&params, RESPONSE_LEN);
+            let offset = ClockOffset {
+                offset_us: ((t2 - t1) + (t3 - t4)) / 2,
+                delay: Duration::from_micros(((t4 - t1) - (t3 - t2)).max(0) as u64),
+            };
+            if best.is_none_or(|best| offset.delay < best.delay) {
+                best = Some(offset);
+            }
+        }
+        let best = best.ok_or(ModemError::Timeout)?;
+        self.offset = Some(best);
+        Ok(best)
+    }
+
+    // Process incoming frames until the response to `seq` from `peer` arrives or the timeout passed.
+    fn wait_for_response(&mut self, peer: u8, seq: u8) -> Result<Option<AddressedPacket>> {
+        let deadline = Instant::now() + self.timeout;
+        while Instant::now() < deadline {
+            let frame = match self.link.read_packet_for_me() {
+                Ok(frame) => frame,
+                Err(ModemError::Timeout) => continue,
+                Err(e) => return Err(e),
+            };
+            let data = &frame.packet.data;
+            if frame.header.flags & FLAG_TIME == 0 {
+                self.inbox.push_back(frame);
+            } else if data.len() == RESPONSE_LEN && data[0] == KIND_RESPONSE {
+                if frame.header.src == peer && data[1] == seq {
+                    return Ok(Some(frame));
+                }
+            } else {
+                self.answer(&frame)?;
+            }
+        }
+        Ok(None)
+    }
+
+    // Respond to a time request addressed to this node, on the reference clock if synchronized.
+    fn answer(&mut self, request: &AddressedPacket) -> Result<()> {
+        let data = &request.packet.data;
+        if data.len() != 2 || data[0] != KIND_REQUEST || request.header.dst != self.link.node_id() {
+            return Ok(());
+        }
+        let params = self.link.get_radio_params().ok();
+        let received_at = match self.offset {
+            Some(offset) => offset.to_peer(request.packet.received_at),
+            None => request.packet.received_at,
+        };
+        let t2 = micros(received_at) - Self::airtime_us(&params, data.len());
+        let mut response = Vec::with_capacity(RESPONSE_LEN);
+        response.extend_from_slice(&[KIND_RESPONSE, data[1]]);
+        response.extend_from_slice(&t2.to_be_bytes());
+        let t3 = micros(self.now());
+        response.extend_from_slice(&t3.to_be_bytes());
+        self.link
+            .send_with_flags(request.header.src, FLAG_TIME, &response)?;
+        Ok(())
+    }
+
+    /// Receive the next frame for this node, answering time requests.
+    ///
+    /// Once synchronized, the packet is timestamped on the reference clock.
+    pub fn receive(&mut self) -> Result<AddressedPacket> {
+        let mut frame = match self.inbox.pop_front() {
+            Some(frame) => frame,
+            None => loop {
+                let frame = self.link.read_packet_for_me()?;
+                if frame.header.flags & FLAG_TIME == 0 {
+                    break frame;
+                }
+                self.answer(&frame)?;
+            },
+        };
+        if let Some(offset) = self.offset {
+            frame.packet.received_at = offset.to_peer(frame.packet.received_at);
+        }
+        Ok(frame)
+    }
+}
+
+impl<T: LoraModemDevice> LoraModemDevice for SyncedModem<T> {
+    fn open(&mut self) -> Result<()> {
+        self.link.open()
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.link.set_frequency(freq)
+    }
+    fn set_frequency_offset(&mut self, hz: i32) -> Result<()> {
+        self.link.set_frequency_offset(hz)
+    }
+    fn config(&mut self) -> Result<Status> {
+        self.link.config()
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.link.set_mode(mode)
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        self.link.set_radio_params(params)
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        self.link.get_radio_params()
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        self.link.set_tx_power(dbm)
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        self.link.tx_power()
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        self.link.capabilities()
+    }
+    fn set_ble(&mut self, enabled: bool) -> Result<()> {
+        self.link.set_ble(enabled)
+    }
+    fn ble_enabled(&mut self) -> Result<bool> {
+        self.link.ble_enabled()
+    }
+    fn gps_fix(&mut self) -> Result<Option<GpsFix>> {
+        self.link.gps_fix()
+    }
+    fn telemetry(&mut self) -> Result<ModemTelemetry> {
+        self.link.telemetry()
+    }
+    fn at_command(&mut self, cmd: &str) -> Result<Vec<String>> {
+        self.link.at_command(cmd)
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        self.link.max_payload()
+    }
+    fn channel_busy(&mut self) -> Result<bool> {
+        self.link.channel_busy()
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.link.enable_rx()
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.link.disable_rx()
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.link.sleep()
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.link.standby()
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.link.wake()
+    }
+    fn stats(&mut self) -> Result<LinkStats> {
+        self.link.stats()
+    }
+    fn reset_stats(&mut self) -> Result<()> {
+        self.link.reset_stats()
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        self.link.send_data(data)
+    }
+    fn send_slice(&mut self, data: &[u8]) -> Result<TxReport> {
+        self.link.send_slice(data)
+    }
+    fn send_batch(&mut self, frames: &[&[u8]]) -> Vec<Result<TxReport>> {
+        self.link.send_batch(frames)
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        self.receive().map(|frame| frame.packet)
+    }
+    fn read_line(&mut self) -> Result<String> {
+        self.link.read_line()
+    }
+    fn next_event(&mut self) -> Result<ModemEvent> {
+        self.link.next_event()
+    }
+}
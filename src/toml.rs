@@ -0,0 +1,244 @@
+// Minimal TOML for configuration files, no external dependency.
+//
+// Covers what profiles need: `[table]` headers and `key = value` pairs with
+// strings, integers, floats and booleans, plus comments. Arrays, inline tables,
+// dotted keys and dates are rejected.
+
+use crate::{ModemError, Result};
+use std::fmt::{self, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    String(String),
+    // integers and floats keep their text, so decimals are taken exactly
+    Number(String),
+    Bool(bool),
+}
+
+impl Value {
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    pub(crate) fn as_number(&self) -> Option<&str> {
+        match self {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+    pub(crate) fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => {
+                f.write_char('"')?;
+                for c in s.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\r' => f.write_str("\\r")?,
+                        '\t' => f.write_str("\\t")?,
+                        c if c.is_control() => write!(f, "\\u{:04X}", c as u32)?,
+                        c => f.write_char(c)?,
+                    }
+                }
+                f.write_char('"')
+            }
+            Value::Number(n) => f.write_str(n),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+macro_rules! toml_number {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Value {
+            fn from(n: $t) -> Self {
+                Value::Number(n.to_string())
+            }
+        })*
+    };
+}
+
+toml_number!(i8, u8, u16, u32);
+
+// Key and value defined in a table, `table` is empty for the top level.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Entry {
+    pub table: String,
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+pub(crate) fn error(line: usize, msg: &str) -> ModemError {
+    ModemError::InvalidArgument(format!("TOML line {}: {}", line, msg))
+}
+
+fn is_bare(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+// Whitespace and an optional comment are all that may follow a value.
+fn expect_end(rest: &str, line: usize) -> Result<()> {
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(error(line, "unexpected text after value"))
+    }
+}
+
+// A basic string after its opening quote, returns the string and what follows it.
+fn basic_string(s: &str, line: usize) -> Result<(String, &str)> {
+    let mut out = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((out, &s[i + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                    let c = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .filter(|_| hex.len() == 4)
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| error(line, "invalid unicode escape"))?;
+                    out.push(c);
+                }
+                _ => return Err(error(line, "invalid escape sequence")),
+            },
+            c => out.push(c),
+        }
+    }
+    Err(error(line, "unterminated string"))
+}
+
+fn value(s: &str, line: usize) -> Result<Value> {
+    if let Some(rest) = s.strip_prefix('"') {
+        let (string, rest) = basic_string(rest, line)?;
+        expect_end(rest, line)?;
+        return Ok(Value::String(string));
+    }
+    if let Some(rest) = s.strip_prefix('\'') {
+        let end = rest
+            .find('\'')
+            .ok_or_else(|| error(line, "unterminated string"))?;
+        expect_end(&rest[end + 1..], line)?;
+        return Ok(Value::String(rest[..end].to_string()));
+    }
+    let end = s.find(|c: char| c.is_whitespace() || c == '#');
+    let (token, rest) = s.split_at(end.unwrap_or(s.len()));
+    expect_end(rest, line)?;
+    match token {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => {
+            let number = token.replace('_', "");
+            let numeric = number.bytes().any(|b| b.is_ascii_digit())
+                && number
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b));
+            if numeric && number.parse::<f64>().is_ok() {
+                Ok(Value::Number(number))
+            } else {
+                Err(error(line, "unsupported value"))
+            }
+        }
+    }
+}
+
+/// All key/value pairs of `text` in document order.
+pub(crate) fn parse(text: &str) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut table = String::new();
+    let mut tables = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let line = i + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = trimmed.strip_prefix('[') {
+            let end = header
+                .find(']')
+                .ok_or_else(|| error(line, "unterminated table header"))?;
+            expect_end(&header[end + 1..], line)?;
+            let name = header[..end].trim();
+            if !is_bare(name) {
+                return Err(error(line, "unsupported table name"));
+            }
+            if tables.iter().any(|t| t == name) {
+                return Err(error(line, "table defined twice"));
+            }
+            tables.push(name.to_string());
+            table = name.to_string();
+            continue;
+        }
+        let eq = trimmed
+            .find('=')
+            .ok_or_else(|| error(line, "expected `key = value`"))?;
+        let key = trimmed[..eq].trim();
+        if !is_bare(key) {
+            return Err(error(line, "unsupported key"));
+        }
+        if entries.iter().any(|e| e.table == table && e.key == key) {
+            return Err(error(line, "key defined twice"));
+        }
+        entries.push(Entry {
+            table: table.clone(),
+            key: key.to_string(),
+            value: value(trimmed[eq + 1..].trim_start(), line)?,
+            line,
+        });
+    }
+    Ok(entries)
+}
+
+/// Document with the top level keys first, then one section per table.
+pub(crate) fn write(top: &[(&str, Value)], tables: &[(&str, Vec<(&str, Value)>)]) -> String {
+    let mut out = String::new();
+    for (key, value) in top {
+        let _ = writeln!(out, "{} = {}", key, value);
+    }
+    for (name, entries) in tables {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        let _ = writeln!(out, "[{}]", name);
+        for (key, value) in entries {
+            let _ = writeln!(out, "{} = {}", key, value);
+        }
+    }
+    out
+}
@@ -0,0 +1,116 @@
+// Diagnostics through the `tracing` crate, compiled to nothing without the `tracing` feature.
+//
+// Commands run inside a `command` span, raw lines and frames are debug events
+// and input that fails to parse is reported as a warning.
+
+use crate::Result;
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+// Span covering one command and its response.
+pub(crate) struct Span {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+// Guard returned by `Span::enter`, leaving the span when dropped.
+pub(crate) struct Entered<'a> {
+    #[cfg(feature = "tracing")]
+    _entered: tracing::span::Entered<'a>,
+    _span: PhantomData<&'a Span>,
+}
+
+impl Span {
+    pub(crate) fn enter(&self) -> Entered<'_> {
+        Entered {
+            #[cfg(feature = "tracing")]
+            _entered: self.span.enter(),
+            _span: PhantomData,
+        }
+    }
+}
+
+pub(crate) fn command(cmd: &str) -> Span {
+    #[cfg(not(feature = "tracing"))]
+    let _ = cmd;
+    Span {
+        #[cfg(feature = "tracing")]
+        span: tracing::debug_span!("command", cmd),
+    }
+}
+
+// Report the outcome of the command started at `started`.
+#[cfg(feature = "std")]
+pub(crate) fn finished<T>(started: Instant, result: &Result<T>) {
+    #[cfg(feature = "tracing")]
+    {
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        match result {
+            Ok(_) => tracing::debug!(elapsed_ms, "command done"),
+            Err(e) => tracing::debug!(elapsed_ms, error = %e, "command failed"),
+        }
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (started, result);
+}
+
+pub(crate) fn line_out(line: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(line, "write");
+    #[cfg(not(feature = "tracing"))]
+    let _ = line;
+}
+
+pub(crate) fn line_in(line: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(line, "read");
+    #[cfg(not(feature = "tracing"))]
+    let _ = line;
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn bytes_out(bytes: &[u8]) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(bytes = %crate::hex::encode(bytes), "write");
+    #[cfg(not(feature = "tracing"))]
+    let _ = bytes;
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn bytes_in(bytes: &[u8]) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(bytes = %crate::hex::encode(bytes), "read");
+    #[cfg(not(feature = "tracing"))]
+    let _ = bytes;
+}
+
+#[cfg(feature = "sx127x")]
+pub(crate) fn register(reg: u8, value: u8, write: bool) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(reg, value, write, "register");
+    #[cfg(not(feature = "tracing"))]
+    let _ = (reg, value, write);
+}
+
+// Pass the result of parsing `input` on, warning if it failed.
+pub(crate) fn parsed<T>(input: &str, result: Result<T>) -> Result<T> {
+    #[cfg(feature = "tracing")]
+    if let Err(e) = &result {
+        tracing::warn!(input, error = %e, "unparsable modem output");
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = input;
+    result
+}
+
+// Like `parsed`, for responses of several lines.
+pub(crate) fn parsed_lines<T>(lines: &[alloc::string::String], result: Result<T>) -> Result<T> {
+    #[cfg(feature = "tracing")]
+    if let Err(e) = &result {
+        tracing::warn!(lines = ?lines, error = %e, "unparsable modem output");
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = lines;
+    result
+}
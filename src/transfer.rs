@@ -0,0 +1,333 @@
+//! File transfer between two nodes on top of the ARQ layer.
+//!
+//! The sender offers the file with its size, CRC-32 and chunk size, the
+//! receiver answers with the chunk to start at, which is past the chunks of an
+//! earlier interrupted transfer of the same file. Chunks carry their own CRC-32,
+//! a chunk failing it is dropped with all that follow. After the last chunk the
+//! receiver either confirms the file or asks to resume at the first missing one.
+//!
+//! | frame    | layout                                                 |
+//! |----------|--------------------------------------------------------|
+//! | offer    | `0x01`, size u32, CRC-32 u32, chunk size u16, file name |
+//! | resume   | `0x02`, chunk index u32                                |
+//! | chunk    | `0x03`, chunk index u32, CRC-32 u32, data              |
+//! | done     | `0x04`                                                 |
+//! | complete | `0x05`                                                 |
+//!
+//! Integers are big endian. Partial files are kept next to their destination
+//! as `<name>.<crc>.part` until complete.
+
+use crate::addressing::AddressedPacket;
+use crate::reliable::ReliableModem;
+use crate::{LoraModemDevice, ModemError, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const KIND_OFFER: u8 = 0x01;
+const KIND_RESUME: u8 = 0x02;
+const KIND_CHUNK: u8 = 0x03;
+const KIND_DONE: u8 = 0x04;
+const KIND_COMPLETE: u8 = 0x05;
+
+// Bytes of a chunk frame before its data.
+const CHUNK_HEADER_LEN: usize = 9;
+
+// How long to wait for the answer of the peer.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Rounds of resuming without any progress before a transfer is given up.
+const MAX_STALLED: usize = 3;
+
+/// CRC-32 (IEEE 802.3) of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn be32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn malformed(kind: &str) -> ModemError {
+    ModemError::Parse(format!("malformed file transfer {} frame!", kind))
+}
+
+// Next frame of `peer`, or of anyone if `None`, failing with a timeout after `timeout`.
+fn next_frame<T: LoraModemDevice>(
+    modem: &mut ReliableModem<T>,
+    peer: Option<u8>,
+    timeout: Option<Duration>,
+) -> Result<AddressedPacket> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    loop {
+        match modem.receive() {
+            Ok(frame)
+                if !frame.packet.data.is_empty() && peer.is_none_or(|p| p == frame.header.src) =>
+            {
+                return Ok(frame)
+            }
+            Ok(_) | Err(ModemError::Timeout) => {}
+            Err(e) => return Err(e),
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(ModemError::Timeout);
+        }
+    }
+}
+
+// Data bytes of a chunk fitting into one frame of the ARQ layer.
+fn chunk_size<T: LoraModemDevice>(modem: &mut ReliableModem<T>) -> Result<usize> {
+    let mtu = modem.max_payload()?;
+    // sequence number of the ARQ layer
+    let size = mtu.saturating_sub(1 + CHUNK_HEADER_LEN);
+    if size == 0 {
+        return Err(ModemError::InvalidArgument(format!(
+            "packets of {} bytes leave no room for file data",
+            mtu
+        )));
+    }
+    Ok(size.min(u16::MAX as usize))
+}
+
+/// Send the file at `path` to node `dst`.
+///
+/// `progress` is called with the bytes the receiver has and the file size,
+/// once the receiver answered and after every chunk.
+pub fn send_file<T, F>(
+    modem: &mut ReliableModem<T>,
+    dst: u8,
+    path: &Path,
+    mut progress: F,
+) -> Result<()>
+where
+    T: LoraModemDevice,
+    F: FnMut(u64, u64),
+{
+    let data = fs::read(path)?;
+    if data.len() > u32::MAX as usize {
+        return Err(ModemError::BufferOverflow);
+    }
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| ModemError::InvalidArgument("path without a file name".into()))?;
+    let chunk = chunk_size(modem)?;
+    let count = data.len().div_ceil(chunk);
+    let total = data.len() as u64;
+
+    let mut offer = vec![KIND_OFFER];
+    offer.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    offer.extend_from_slice(&crc32(&data).to_be_bytes());
+    offer.extend_from_slice(&(chunk as u16).to_be_bytes());
+    offer.extend_from_slice(name.as_bytes());
+    modem.send_reliable(dst, &offer)?;
+
+    let mut last_resume: Option<usize> = None;
+    let mut stalled = 0;
+    loop {
+        let reply = next_frame(modem, Some(dst), Some(REPLY_TIMEOUT))?;
+        let reply = &reply.packet.data;
+        let resume = match reply[0] {
+            KIND_COMPLETE => {
+                progress(total, total);
+                return Ok(());
+            }
+            KIND_RESUME if reply.len() >= 5 => be32(&reply[1..]) as usize,
+            KIND_RESUME => return Err(malformed("resume")),
+            _ => continue,
+        };
+        if resume > count {
+            return Err(malformed("resume"));
+        }
+        if last_resume.is_some_and(|last| resume <= last) {
+            stalled += 1;
+            if stalled >= MAX_STALLED {
+                return Err(ModemError::NotAcknowledged { attempts: stalled });
+            }
+        } else {
+            stalled = 0;
+        }
+        last_resume = Some(resume);
+        for index in resume..count {
+            progress((index * chunk) as u64, total);
+            let part = &data[index * chunk..((index + 1) * chunk).min(data.len())];
+            let mut frame = Vec::with_capacity(CHUNK_HEADER_LEN + part.len());
+            frame.push(KIND_CHUNK);
+            frame.extend_from_slice(&(index as u32).to_be_bytes());
+            frame.extend_from_slice(&crc32(part).to_be_bytes());
+            frame.extend_from_slice(part);
+            modem.send_reliable(dst, &frame)?;
+        }
+        modem.send_reliable(dst, &[KIND_DONE])?;
+    }
+}
+
+// Transfer in progress on the receiving side.
+struct Incoming {
+    peer: u8,
+    size: u64,
+    crc: u32,
+    chunk: usize,
+    target: PathBuf,
+    part: PathBuf,
+    file: File,
+    next: usize,
+}
+
+impl Incoming {
+    // Accept an offer, continuing a partial file of the same content.
+    fn start(dir: &Path, peer: u8, offer: &[u8]) -> Result<Self> {
+        if offer.len() < 12 {
+            return Err(malformed("offer"));
+        }
+        let size = be32(&offer[1..]) as u64;
+        let crc = be32(&offer[5..]);
+        let chunk = u16::from_be_bytes([offer[9], offer[10]]) as usize;
+        let name = String::from_utf8_lossy(&offer[11..]).to_string();
+        // never leave `dir`, only the last component of the offered name is used
+        let name = Path::new(&name)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .filter(|n| *n != "..")
+            .ok_or_else(|| malformed("offer"))?
+            .to_string();
+        if chunk == 0 {
+            return Err(malformed("offer"));
+        }
+        let target = dir.join(&name);
+        let part = dir.join(format!("{}.{:08x}.part", name, crc));
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&part)?;
+        let have = file.metadata()?.len().min(size);
+        let next = (have / chunk as u64) as usize;
+        file.set_len((next * chunk) as u64)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(Incoming {
+            peer,
+            size,
+            crc,
+            chunk,
+            target,
+            part,
+            file,
+            next,
+        })
+    }
+
+    fn have(&self) -> u64 {
+        ((self.next * self.chunk) as u64).min(self.size)
+    }
+
+    fn count(&self) -> usize {
+        (self.size as usize).div_ceil(self.chunk)
+    }
+
+    // Append a chunk if it is the next one and intact.
+    fn push(&mut self, frame: &[u8]) -> Result<()> {
+        if frame.len() < CHUNK_HEADER_LEN {
+            return Err(malformed("chunk"));
+        }
+        let index = be32(&frame[1..]) as usize;
+        let data = &frame[CHUNK_HEADER_LEN..];
+        if index == self.next && crc32(data) == be32(&frame[5..]) {
+            self.file.write_all(data)?;
+            self.next += 1;
+        }
+        Ok(())
+    }
+
+    // Move the file into place if all of it arrived intact.
+    fn finish(&mut self) -> Result<bool> {
+        if self.next < self.count() {
+            return Ok(false);
+        }
+        self.file.flush()?;
+        let mut data = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut data)?;
+        if data.len() as u64 != self.size || crc32(&data) != self.crc {
+            // chunks of another file under the same name, start over
+            self.file.set_len(0)?;
+            self.file.seek(SeekFrom::Start(0))?;
+            self.next = 0;
+            return Ok(false);
+        }
+        fs::rename(&self.part, &self.target)?;
+        Ok(true)
+    }
+}
+
+/// Receive one file into the directory `dir`, returns its path.
+///
+/// Waits for an offer indefinitely, then fails with `ModemError::Timeout` if
+/// the sender stays silent for too long. The partial file is kept, so a later
+/// transfer of the same file resumes where this one stopped. `progress` is
+/// called with the bytes received so far and the file size.
+pub fn receive_file<T, F>(
+    modem: &mut ReliableModem<T>,
+    dir: &Path,
+    mut progress: F,
+) -> Result<PathBuf>
+where
+    T: LoraModemDevice,
+    F: FnMut(u64, u64),
+{
+    let mut incoming: Option<Incoming> = None;
+    loop {
+        let peer = incoming.as_ref().map(|t| t.peer);
+        let frame = next_frame(modem, peer, peer.map(|_| REPLY_TIMEOUT))?;
+        let data = &frame.packet.data;
+        match data[0] {
+            KIND_OFFER => {
+                let transfer = match Incoming::start(dir, frame.header.src, data) {
+                    Err(ModemError::Parse(_)) => continue,
+                    result => result?,
+                };
+                progress(transfer.have(), transfer.size);
+                let mut resume = vec![KIND_RESUME];
+                resume.extend_from_slice(&(transfer.next as u32).to_be_bytes());
+                modem.send_reliable(transfer.peer, &resume)?;
+                incoming = Some(transfer);
+            }
+            KIND_CHUNK => {
+                if let Some(transfer) = incoming.as_mut() {
+                    match transfer.push(data) {
+                        Ok(()) | Err(ModemError::Parse(_)) => {}
+                        Err(e) => return Err(e),
+                    }
+                    progress(transfer.have(), transfer.size);
+                }
+            }
+            KIND_DONE => {
+                let transfer = match incoming.as_mut() {
+                    Some(transfer) => transfer,
+                    None => continue,
+                };
+                if transfer.finish()? {
+                    modem.send_reliable(transfer.peer, &[KIND_COMPLETE])?;
+                    return Ok(transfer.target.clone());
+                }
+                let mut resume = vec![KIND_RESUME];
+                resume.extend_from_slice(&(transfer.next as u32).to_be_bytes());
+                modem.send_reliable(transfer.peer, &resume)?;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+/// How a transport recovers from a dropped connection.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Number of attempts before giving up, 0 disables reconnecting
+    pub max_attempts: usize,
+    /// Pause between two attempts
+    pub delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            max_attempts: 3,
+            delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Byte stream over which a modem speaking the rf95modem AT protocol can be reached.
+///
+/// Reads are expected to return after a short poll interval if no data is available,
+/// signalled by an error of kind `TimedOut` or `WouldBlock`, so callers can enforce
+/// their own deadlines.
+pub trait Transport: Read + Write {
+    /// Open (or reopen) the underlying connection.
+    fn open(&mut self) -> io::Result<()>;
+    /// Whether the transport is currently open.
+    fn is_open(&self) -> bool;
+    /// Bound the time `open` may take, ignored by transports that cannot.
+    fn set_open_timeout(&mut self, timeout: Option<Duration>) {
+        let _ = timeout;
+    }
+    /// Whether the connection was reestablished since the last call.
+    ///
+    /// The device behind a new connection may have lost its configuration.
+    fn take_reconnected(&mut self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,311 @@
+//! IP over LoRa through a Linux TUN interface.
+//!
+//! Each LoRa frame starts with a kind byte. Data frames carry a `fragment` of
+//! an IP packet, whose IPv4 header is elided down to the fields that cannot be
+//! derived (ToS, TTL, protocol, addresses) if header compression is enabled.
+//! Announce frames carry the largest frame size of the sender, the tunnel
+//! fragments to the smaller one of both ends.
+
+use crate::fragment::{fragment, Reassembler, FRAGMENT_HEADER_LEN};
+use crate::rng::Rng;
+use crate::{LoraModemDevice, ModemError, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::raw::{c_int, c_ulong};
+use std::os::unix::io::AsRawFd;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+const KIND_DATA: u8 = 0x01;
+const KIND_ANNOUNCE: u8 = 0x02;
+const KIND_ANNOUNCE_REPLY: u8 = 0x03;
+
+// Payload encodings inside a data frame.
+const IP_RAW: u8 = 0x00;
+const IP_V4_ELIDED: u8 = 0x01;
+const ELIDED_LEN: usize = 11;
+
+// Full frames a packet of the interface MTU is split into.
+const FRAMES_PER_PACKET: usize = 6;
+
+const TUNSETIFF: c_ulong = 0x4004_54ca;
+const IFF_TUN: u16 = 0x0001;
+const IFF_NO_PI: u16 = 0x1000;
+
+extern "C" {
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+/// A TUN interface passing raw IP packets
+pub struct TunDevice {
+    file: File,
+    name: String,
+}
+
+impl TunDevice {
+    /// Create or attach to the TUN interface `name`, needs `CAP_NET_ADMIN`.
+    pub fn open(name: &str) -> io::Result<Self> {
+        if name.len() >= 16 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "interface name longer than 15 bytes",
+            ));
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/net/tun")?;
+        // struct ifreq: name followed by the flags
+        let mut ifreq = [0u8; 40];
+        ifreq[..name.len()].copy_from_slice(name.as_bytes());
+        ifreq[16..18].copy_from_slice(&(IFF_TUN | IFF_NO_PI).to_ne_bytes());
+        // SAFETY: TUNSETIFF reads and writes a struct ifreq, which fits into the buffer
+        if unsafe { ioctl(file.as_raw_fd(), TUNSETIFF, ifreq.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(TunDevice {
+            file,
+            name: name.to_string(),
+        })
+    }
+    /// Name of the interface.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Set the MTU and bring the interface up, addresses are left to the system.
+    pub fn set_mtu(&self, mtu: usize) -> io::Result<()> {
+        let output = Command::new("ip")
+            .args(["link", "set", "dev", &self.name, "mtu"])
+            .arg(mtu.to_string())
+            .arg("up")
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(())
+    }
+    /// Read one IP packet.
+    pub fn read_packet(&mut self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0u8; 65536];
+        let n = self.file.read(&mut buf)?;
+        buf.truncate(n);
+        Ok(buf)
+    }
+    /// Write one IP packet.
+    pub fn write_packet(&mut self, packet: &[u8]) -> io::Result<()> {
+        self.file.write_all(packet)
+    }
+    fn try_clone(&self) -> io::Result<TunDevice> {
+        Ok(TunDevice {
+            file: self.file.try_clone()?,
+            name: self.name.clone(),
+        })
+    }
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|w| u16::from_be_bytes([w[0], *w.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Elide the derivable fields of an IPv4 header without options or fragmentation.
+///
+/// Other packets are passed unchanged behind a marker byte.
+pub fn compress_header(packet: &[u8]) -> Vec<u8> {
+    let elidable = packet.len() >= 20
+        && packet[0] == 0x45
+        && u16::from_be_bytes([packet[2], packet[3]]) as usize == packet.len()
+        // no more fragments and offset zero, DF may be set
+        && packet[6] & 0xbf == 0
+        && packet[7] == 0;
+    let mut out = Vec::with_capacity(packet.len() + 1);
+    if elidable {
+        out.push(IP_V4_ELIDED);
+        out.extend_from_slice(&[packet[1], packet[8], packet[9]]);
+        out.extend_from_slice(&packet[12..20]);
+        out.extend_from_slice(&packet[20..]);
+    } else {
+        out.push(IP_RAW);
+        out.extend_from_slice(packet);
+    }
+    out
+}
+
+/// Restore a packet produced by `compress_header`.
+pub fn decompress_header(data: &[u8]) -> Result<Vec<u8>> {
+    match data.split_first() {
+        Some((&IP_RAW, packet)) => Ok(packet.to_vec()),
+        Some((&IP_V4_ELIDED, rest)) if rest.len() >= ELIDED_LEN => {
+            let total = 20 + rest.len() - ELIDED_LEN;
+            if total > u16::MAX as usize {
+                return Err(ModemError::Parse("IP packet too large!".into()));
+            }
+            let mut packet = Vec::with_capacity(total);
+            packet.extend_from_slice(&[0x45, rest[0]]);
+            packet.extend_from_slice(&(total as u16).to_be_bytes());
+            // identification zero with DF set
+            packet.extend_from_slice(&[0, 0, 0x40, 0, rest[1], rest[2], 0, 0]);
+            packet.extend_from_slice(&rest[3..ELIDED_LEN]);
+            let checksum = ipv4_checksum(&packet[..20]);
+            packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+            packet.extend_from_slice(&rest[ELIDED_LEN..]);
+            Ok(packet)
+        }
+        _ => Err(ModemError::Parse("malformed tunnel packet!".into())),
+    }
+}
+
+/// Tunnels IP packets between a TUN interface and a LoRa device.
+///
+/// Packets from the interface are read by a background thread and sent from
+/// `step`, which also writes reassembled packets to the interface. Reading
+/// from the device is bounded by its timeout. Payloads can additionally be
+/// compressed by tunneling over a `CompressedModem`.
+pub struct IpTunnel<T: LoraModemDevice> {
+    device: T,
+    tun: TunDevice,
+    from_tun: Receiver<io::Result<Vec<u8>>>,
+    reassembler: Reassembler,
+    compression: bool,
+    next_id: u16,
+    local_frame: usize,
+    peer_frame: Option<usize>,
+}
+
+impl<T: LoraModemDevice> IpTunnel<T> {
+    /// Tunnel between `tun` and the opened `device`, announcing the frame size to peers.
+    pub fn new(mut device: T, tun: TunDevice) -> Result<Self> {
+        let local_frame = device.max_payload()?;
+        let mut reader = tun.try_clone()?;
+        let (sender, from_tun) = mpsc::channel();
+        thread::spawn(move || loop {
+            let packet = reader.read_packet();
+            let failed = packet.is_err();
+            if sender.send(packet).is_err() || failed {
+                break;
+            }
+        });
+        let mut tunnel = IpTunnel {
+            device,
+            tun,
+            from_tun,
+            reassembler: Reassembler::new(Duration::from_secs(30), 4),
+            compression: true,
+            next_id: Rng::from_time().next_u64() as u16,
+            local_frame,
+            peer_frame: None,
+        };
+        tunnel.announce(KIND_ANNOUNCE)?;
+        tunnel.tun.set_mtu(tunnel.ip_mtu())?;
+        Ok(tunnel)
+    }
+    /// Elide IPv4 headers, enabled by default.
+    pub fn set_header_compression(&mut self, enabled: bool) {
+        self.compression = enabled;
+    }
+    /// Frame size used, the smaller one of both ends once the peer announced itself.
+    pub fn frame_size(&self) -> usize {
+        self.peer_frame
+            .map_or(self.local_frame, |peer| peer.min(self.local_frame))
+    }
+    /// MTU of the interface, packets of this size take a few full frames.
+    pub fn ip_mtu(&self) -> usize {
+        let chunk = self.frame_size().saturating_sub(1 + FRAGMENT_HEADER_LEN);
+        (chunk * FRAMES_PER_PACKET).clamp(576, 1500)
+    }
+    /// Access the TUN interface.
+    pub fn tun(&self) -> &TunDevice {
+        &self.tun
+    }
+    /// Unwrap the device.
+    pub fn into_inner(self) -> T {
+        self.device
+    }
+
+    /// Send packets read from the interface, then handle one frame from the device.
+    pub fn step(&mut self) -> Result<()> {
+        loop {
+            match self.from_tun.try_recv() {
+                Ok(packet) => self.send(&packet?)?,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(ModemError::Disconnected),
+            }
+        }
+        let packet = match self.device.read_packet() {
+            Ok(packet) => packet,
+            Err(ModemError::Timeout) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        match packet.data.split_first() {
+            Some((&KIND_DATA, frame)) => {
+                let data = match self.reassembler.push(frame) {
+                    Ok(Some(data)) => data,
+                    Ok(None) | Err(ModemError::Parse(_)) => return Ok(()),
+                    Err(e) => return Err(e),
+                };
+                if let Ok(ip) = decompress_header(&data) {
+                    self.tun.write_packet(&ip)?;
+                }
+            }
+            Some((&kind, size))
+                if (kind == KIND_ANNOUNCE || kind == KIND_ANNOUNCE_REPLY) && size.len() >= 2 =>
+            {
+                let mtu = self.ip_mtu();
+                self.peer_frame = Some(u16::from_be_bytes([size[0], size[1]]) as usize);
+                if kind == KIND_ANNOUNCE {
+                    self.announce(KIND_ANNOUNCE_REPLY)?;
+                }
+                if self.ip_mtu() != mtu {
+                    self.tun.set_mtu(self.ip_mtu())?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Run the tunnel until an error occurs.
+    pub fn run(&mut self) -> Result<()> {
+        loop {
+            self.step()?;
+        }
+    }
+
+    fn send(&mut self, packet: &[u8]) -> Result<()> {
+        let data = if self.compression {
+            compress_header(packet)
+        } else {
+            let mut data = Vec::with_capacity(packet.len() + 1);
+            data.push(IP_RAW);
+            data.extend_from_slice(packet);
+            data
+        };
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        for part in fragment(id, &data, self.frame_size() - 1)? {
+            let mut frame = Vec::with_capacity(1 + part.len());
+            frame.push(KIND_DATA);
+            frame.extend_from_slice(&part);
+            self.device.send_data(frame)?;
+        }
+        Ok(())
+    }
+
+    fn announce(&mut self, kind: u8) -> Result<()> {
+        let size = self.local_frame.min(u16::MAX as usize) as u16;
+        let mut frame = vec![kind];
+        frame.extend_from_slice(&size.to_be_bytes());
+        self.device.send_data(frame)?;
+        Ok(())
+    }
+}
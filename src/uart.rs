@@ -0,0 +1,153 @@
+use crate::embedded::{EmbeddedModem, ErrorType, Read, ReadReady, Write};
+use core::fmt::Debug;
+
+/// Outcome of a non-blocking operation, mirroring `nb::Error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NbError<E> {
+    /// The operation would have to wait, try again later
+    WouldBlock,
+    /// The operation failed
+    Other(E),
+}
+
+/// Result of a non-blocking operation, mirroring `nb::Result`.
+pub type NbResult<T, E> = core::result::Result<T, NbError<E>>;
+
+/// Receiving half of a UART, mirroring `embedded_hal::serial::Read<u8>`.
+pub trait SerialRead {
+    type Error: Debug;
+    /// Read a received byte, `WouldBlock` if there is none.
+    fn read(&mut self) -> NbResult<u8, Self::Error>;
+}
+
+/// Transmitting half of a UART, mirroring `embedded_hal::serial::Write<u8>`.
+pub trait SerialWrite {
+    type Error: Debug;
+    /// Queue a byte for transmission, `WouldBlock` while the transmitter is busy.
+    fn write(&mut self, word: u8) -> NbResult<(), Self::Error>;
+    /// Wait until all queued bytes have been sent, `WouldBlock` while they are not.
+    fn flush(&mut self) -> NbResult<(), Self::Error>;
+}
+
+/// Error of either half of a `Uart`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartError<TE, RE> {
+    Tx(TE),
+    Rx(RE),
+}
+
+/// Blocking byte stream over the two halves of a non-blocking UART.
+pub struct Uart<TX, RX> {
+    tx: TX,
+    rx: RX,
+    // a byte taken from the receiver to answer `read_ready`
+    peeked: Option<u8>,
+}
+
+impl<TX: SerialWrite, RX: SerialRead> Uart<TX, RX> {
+    pub fn new(tx: TX, rx: RX) -> Self {
+        Uart {
+            tx,
+            rx,
+            peeked: None,
+        }
+    }
+    /// Return the transmitting and receiving halves.
+    ///
+    /// A byte already taken from the receiver by `read_ready` is lost.
+    pub fn split(self) -> (TX, RX) {
+        (self.tx, self.rx)
+    }
+
+    fn try_read(&mut self) -> NbResult<u8, UartError<TX::Error, RX::Error>> {
+        match self.peeked.take() {
+            Some(byte) => Ok(byte),
+            None => self.rx.read().map_err(|e| match e {
+                NbError::WouldBlock => NbError::WouldBlock,
+                NbError::Other(e) => NbError::Other(UartError::Rx(e)),
+            }),
+        }
+    }
+}
+
+impl<TX: SerialWrite, RX: SerialRead> ErrorType for Uart<TX, RX> {
+    type Error = UartError<TX::Error, RX::Error>;
+}
+
+impl<TX: SerialWrite, RX: SerialRead> Read for Uart<TX, RX> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // wait for the first byte, then take whatever else arrived already
+        buf[0] = loop {
+            match self.try_read() {
+                Ok(byte) => break byte,
+                Err(NbError::WouldBlock) => {}
+                Err(NbError::Other(e)) => return Err(e),
+            }
+        };
+        let mut n = 1;
+        while n < buf.len() {
+            match self.try_read() {
+                Ok(byte) => {
+                    buf[n] = byte;
+                    n += 1;
+                }
+                Err(NbError::WouldBlock) => break,
+                Err(NbError::Other(e)) => return Err(e),
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<TX: SerialWrite, RX: SerialRead> ReadReady for Uart<TX, RX> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        if self.peeked.is_some() {
+            return Ok(true);
+        }
+        match self.rx.read() {
+            Ok(byte) => {
+                self.peeked = Some(byte);
+                Ok(true)
+            }
+            Err(NbError::WouldBlock) => Ok(false),
+            Err(NbError::Other(e)) => Err(UartError::Rx(e)),
+        }
+    }
+}
+
+impl<TX: SerialWrite, RX: SerialRead> Write for Uart<TX, RX> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        for &byte in buf {
+            loop {
+                match self.tx.write(byte) {
+                    Ok(()) => break,
+                    Err(NbError::WouldBlock) => {}
+                    Err(NbError::Other(e)) => return Err(UartError::Tx(e)),
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        loop {
+            match self.tx.flush() {
+                Ok(()) => return Ok(()),
+                Err(NbError::WouldBlock) => {}
+                Err(NbError::Other(e)) => return Err(UartError::Tx(e)),
+            }
+        }
+    }
+}
+
+/// Modem attached to a microcontroller UART given as separate transmit and receive halves.
+pub type UartModem<TX, RX> = EmbeddedModem<Uart<TX, RX>>;
+
+impl<TX: SerialWrite, RX: SerialRead> EmbeddedModem<Uart<TX, RX>> {
+    /// Drive the modem over the halves of a UART configured for 115200 baud 8N1.
+    pub fn from_uart(tx: TX, rx: RX) -> Self {
+        EmbeddedModem::new(Uart::new(tx, rx))
+    }
+}
@@ -0,0 +1,276 @@
+use crate::radio::{validate_tx_power, RadioParams};
+use crate::rng::Rng;
+use crate::{
+    check_payload, Capabilities, Frequency, LoraModemDevice, ModemConfig, ModemError, RadioState,
+    Result, RxPacket, Status, TxReport,
+};
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+// Largest payload of a LoRa frame.
+const MAX_PAYLOAD: usize = 255;
+
+/// Channel conditions applied to the transmissions of a `VirtualModem`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkModel {
+    /// Probability between 0 and 1 that a transmission is lost
+    pub loss: f64,
+    /// Delay until a transmission is received on the other end
+    pub latency: Duration,
+    /// Mean signal strength in dBm reported by the receiver
+    pub rssi: i16,
+    /// Mean signal to noise ratio in dB reported by the receiver
+    pub snr: f32,
+    /// Largest deviation in dB added to RSSI and SNR of each packet
+    pub jitter: i16,
+}
+
+impl Default for LinkModel {
+    fn default() -> Self {
+        LinkModel {
+            loss: 0.0,
+            latency: Duration::from_millis(0),
+            rssi: -60,
+            snr: 9.0,
+            jitter: 0,
+        }
+    }
+}
+
+// Radio state of one end, as seen by the other.
+struct Node {
+    inbox: VecDeque<(Instant, RxPacket)>,
+    frequency: Frequency,
+    params: RadioParams,
+    rx_enabled: bool,
+    state: RadioState,
+}
+
+struct Medium {
+    nodes: Mutex<[Node; 2]>,
+    arrived: Condvar,
+}
+
+/// One end of an in-memory LoRa link, created in pairs by `VirtualModem::pair()`.
+///
+/// Transmissions are received by the other end if it listens on the same
+/// frequency with the same radio settings, subject to the `LinkModel` of the
+/// sender. Random decisions come from a seeded generator, so runs with the
+/// same seed and the same sequence of calls are reproducible.
+pub struct VirtualModem {
+    medium: Arc<Medium>,
+    index: usize,
+    model: LinkModel,
+    rng: Rng,
+    timeout: Option<Duration>,
+    tx_power: i8,
+    rx_good: usize,
+    tx_good: usize,
+    lost: usize,
+}
+
+impl VirtualModem {
+    /// Two connected modems on 868.1 MHz with a perfect link.
+    pub fn pair() -> (VirtualModem, VirtualModem) {
+        VirtualModem::pair_with(LinkModel::default(), 0)
+    }
+    /// Two connected modems applying `model` in both directions, randomized by `seed`.
+    pub fn pair_with(model: LinkModel, seed: u64) -> (VirtualModem, VirtualModem) {
+        let node = || Node {
+            inbox: VecDeque::new(),
+            frequency: Frequency::from_khz(868_100),
+            params: ModemConfig::MediumBw125Cr45Sf128Crc.into(),
+            rx_enabled: true,
+            state: RadioState::Active,
+        };
+        let medium = Arc::new(Medium {
+            nodes: Mutex::new([node(), node()]),
+            arrived: Condvar::new(),
+        });
+        let end = |index: usize| VirtualModem {
+            medium: medium.clone(),
+            index,
+            model: model.clone(),
+            rng: Rng::new(seed.wrapping_add(index as u64)),
+            timeout: Some(Duration::from_secs(1)),
+            tx_power: 14,
+            rx_good: 0,
+            tx_good: 0,
+            lost: 0,
+        };
+        (end(0), end(1))
+    }
+    /// Change the conditions applied to transmissions of this end.
+    pub fn set_model(&mut self, model: LinkModel) {
+        self.model = model;
+    }
+    /// Bound reads by `timeout`, `None` blocks until a packet arrives.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+    /// Transmissions of this end dropped by the link model.
+    pub fn lost(&self) -> usize {
+        self.lost
+    }
+
+    fn with_node<R>(&self, f: impl FnOnce(&mut Node) -> R) -> R {
+        let mut nodes = self.medium.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut nodes[self.index])
+    }
+
+    fn jitter(&mut self) -> i16 {
+        let span = self.model.jitter.max(0) as u64;
+        (self.rng.below(2 * span + 1) as i64 - span as i64) as i16
+    }
+}
+
+impl LoraModemDevice for VirtualModem {
+    fn open(&mut self) -> Result<()> {
+        Ok(())
+    }
+    fn set_frequency(&mut self, freq: Frequency) -> Result<()> {
+        self.with_node(|node| node.frequency = freq);
+        Ok(())
+    }
+    fn config(&mut self) -> Result<Status> {
+        let (frequency, params, rx_enabled, state) =
+            self.with_node(|node| (node.frequency, node.params, node.rx_enabled, node.state));
+        Ok(Status {
+            version: "virtual".to_string(),
+            config: params
+                .preset()
+                .unwrap_or(ModemConfig::MediumBw125Cr45Sf128Crc),
+            max_pkt_size: MAX_PAYLOAD,
+            frequency,
+            rx_listener: rx_enabled,
+            tx_power: Some(self.tx_power),
+            sync_word: Some(params.sync_word),
+            rx_good: self.rx_good,
+            tx_good: self.tx_good,
+            radio_state: Some(state),
+            ..Status::new()
+        })
+    }
+    fn max_payload(&mut self) -> Result<usize> {
+        Ok(MAX_PAYLOAD)
+    }
+    fn set_mode(&mut self, mode: ModemConfig) -> Result<()> {
+        self.set_radio_params(mode.into())
+    }
+    fn set_radio_params(&mut self, params: RadioParams) -> Result<()> {
+        params.validate()?;
+        self.with_node(|node| node.params = params);
+        Ok(())
+    }
+    fn get_radio_params(&mut self) -> Result<RadioParams> {
+        Ok(self.with_node(|node| node.params))
+    }
+    fn set_tx_power(&mut self, dbm: i8) -> Result<()> {
+        validate_tx_power(dbm)?;
+        self.tx_power = dbm;
+        Ok(())
+    }
+    fn tx_power(&mut self) -> Result<i8> {
+        Ok(self.tx_power)
+    }
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        Ok(Capabilities::RADIO_PARAMS | Capabilities::TX_POWER | Capabilities::POWER_MODES)
+    }
+    fn enable_rx(&mut self) -> Result<()> {
+        self.with_node(|node| node.rx_enabled = true);
+        Ok(())
+    }
+    fn disable_rx(&mut self) -> Result<()> {
+        self.with_node(|node| node.rx_enabled = false);
+        Ok(())
+    }
+    fn sleep(&mut self) -> Result<()> {
+        self.with_node(|node| node.state = RadioState::Sleep);
+        Ok(())
+    }
+    fn standby(&mut self) -> Result<()> {
+        self.with_node(|node| node.state = RadioState::Standby);
+        Ok(())
+    }
+    fn wake(&mut self) -> Result<()> {
+        self.with_node(|node| node.state = RadioState::Active);
+        Ok(())
+    }
+    fn send_data(&mut self, data: Vec<u8>) -> Result<TxReport> {
+        check_payload(data.len(), MAX_PAYLOAD)?;
+        self.tx_good += 1;
+        let params = self.with_node(|node| {
+            node.state = RadioState::Active;
+            node.params
+        });
+        let lost = (self.rng.next_u64() as f64 / u64::MAX as f64) < self.model.loss;
+        if lost {
+            self.lost += 1;
+            return Ok(TxReport::new(data.len(), Some(params)));
+        }
+        let packet = RxPacket {
+            rssi: self.model.rssi + self.jitter(),
+            snr: self.model.snr + self.jitter() as f32,
+            data,
+            received_at: SystemTime::now(),
+            freq_error: None,
+            modem_timestamp: None,
+        };
+        let len = packet.data.len();
+        let arrival = Instant::now() + self.model.latency;
+        let mut nodes = self.medium.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        let (sender, receiver) = (&nodes[self.index], &nodes[1 - self.index]);
+        if receiver.rx_enabled
+            && receiver.state == RadioState::Active
+            && receiver.frequency == sender.frequency
+            && receiver.params == sender.params
+        {
+            nodes[1 - self.index].inbox.push_back((arrival, packet));
+            self.medium.arrived.notify_all();
+        }
+        Ok(TxReport::new(len, Some(params)))
+    }
+    fn read_packet(&mut self) -> Result<RxPacket> {
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let mut nodes = self.medium.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let node = &mut nodes[self.index];
+            if !node.rx_enabled || node.state != RadioState::Active {
+                return Err(ModemError::RxDisabled);
+            }
+            let now = Instant::now();
+            let next = node.inbox.front().map(|(arrival, _)| *arrival);
+            if next.is_some_and(|arrival| arrival <= now) {
+                let (_, mut packet) = node.inbox.pop_front().expect("inbox not empty");
+                packet.received_at = SystemTime::now();
+                self.rx_good += 1;
+                return Ok(packet);
+            }
+            let wake = match (next, deadline) {
+                (Some(a), Some(d)) => Some(a.min(d)),
+                (a, d) => a.or(d),
+            };
+            if deadline.is_some_and(|d| now >= d) {
+                return Err(ModemError::Timeout);
+            }
+            nodes = match wake {
+                Some(wake) => {
+                    self.medium
+                        .arrived
+                        .wait_timeout(nodes, wake.saturating_duration_since(now))
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self
+                    .medium
+                    .arrived
+                    .wait(nodes)
+                    .unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+    fn read_line(&mut self) -> Result<String> {
+        Ok(self.read_packet()?.to_modem_line())
+    }
+}
@@ -0,0 +1,158 @@
+use crate::replay::{LineSplitter, RECEIVED, SENT};
+use crate::transport::Transport;
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type Sink = Box<dyn Write + Send>;
+
+/// Switchable log of the raw lines exchanged with a modem.
+///
+/// Every line is written as `<unix seconds>.<millis> > line` when sent to the
+/// modem and with `<` when received, which `ReplayTransport` plays back. Clones
+/// share the sink, so a log can be attached and detached at runtime from any
+/// thread while a `WireLogTransport` carries the traffic. A sink failing to
+/// write is detached, the modem keeps working.
+#[derive(Clone, Default)]
+pub struct WireLog {
+    sink: Arc<Mutex<Option<Sink>>>,
+}
+
+impl WireLog {
+    /// Log without a sink, nothing is recorded until `attach`.
+    pub fn new() -> Self {
+        WireLog::default()
+    }
+    /// Record into `sink` from now on, replacing the previous one.
+    pub fn attach<W: Write + Send + 'static>(&self, sink: W) {
+        self.replace(Some(Box::new(sink)));
+    }
+    /// Append to the file at `path`, created if missing.
+    pub fn attach_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.attach(BufWriter::new(file));
+        Ok(())
+    }
+    /// Stop recording, the sink is flushed and dropped.
+    pub fn detach(&self) {
+        self.replace(None);
+    }
+    /// Whether lines are being recorded.
+    pub fn is_attached(&self) -> bool {
+        self.sink.lock().is_ok_and(|sink| sink.is_some())
+    }
+
+    fn replace(&self, sink: Option<Sink>) {
+        if let Ok(mut current) = self.sink.lock() {
+            if let Some(mut old) = std::mem::replace(&mut *current, sink) {
+                let _ = old.flush();
+            }
+        }
+    }
+    fn record(&self, prefix: &str, lines: &[String]) {
+        let mut current = match self.sink.lock() {
+            Ok(current) => current,
+            Err(_) => return,
+        };
+        let sink = match current.as_mut() {
+            Some(sink) => sink,
+            None => return,
+        };
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let written = lines
+            .iter()
+            .try_for_each(|line| {
+                writeln!(
+                    sink,
+                    "{}.{:03} {}{}",
+                    time.as_secs(),
+                    time.subsec_millis(),
+                    prefix,
+                    line
+                )
+            })
+            .and_then(|_| sink.flush());
+        if written.is_err() {
+            *current = None;
+        }
+    }
+}
+
+/// Transport recording its traffic into a `WireLog`, for any backend.
+///
+/// Unlike a `RecordingTransport` it stays in place for the lifetime of the
+/// modem and costs next to nothing while no sink is attached.
+pub struct WireLogTransport<T: Transport> {
+    inner: T,
+    log: WireLog,
+    sent: LineSplitter,
+    received: LineSplitter,
+}
+
+impl<T: Transport> WireLogTransport<T> {
+    pub fn new(inner: T, log: WireLog) -> Self {
+        WireLogTransport {
+            inner,
+            log,
+            sent: LineSplitter::default(),
+            received: LineSplitter::default(),
+        }
+    }
+    /// Handle attaching and detaching the sink.
+    pub fn log(&self) -> &WireLog {
+        &self.log
+    }
+    /// Access the wrapped transport.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Transport> Transport for WireLogTransport<T> {
+    fn open(&mut self) -> io::Result<()> {
+        self.inner.open()
+    }
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+    fn set_open_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_open_timeout(timeout)
+    }
+    fn take_reconnected(&mut self) -> bool {
+        self.inner.take_reconnected()
+    }
+}
+
+impl<T: Transport> Read for WireLogTransport<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let mut lines = Vec::new();
+        self.received.push(&buf[..n], |line| lines.push(line));
+        if !lines.is_empty() {
+            self.log.record(RECEIVED, &lines);
+        }
+        Ok(n)
+    }
+}
+
+impl<T: Transport> Write for WireLogTransport<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        let mut lines = Vec::new();
+        self.sent.push(&buf[..n], |line| lines.push(line));
+        if !lines.is_empty() {
+            self.log.record(SENT, &lines);
+        }
+        Ok(n)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,552 @@
+use crate::error::tx_rejected;
+use crate::filter::{FilterStats, RxFilter};
+use crate::hex;
+use crate::line::{parse_sent, LineKind};
+use crate::queue::{Completions, PacketQueue, Priority, QueueLimits, TxHandle, TxQueue};
+use crate::rf95::Rf95Modem;
+use crate::transport::Transport;
+use crate::{
+    Frequency, LoraModemDevice, ModemConfig, ModemError, ModemEvent, Result, RxPacket, RxPacketRef,
+    Status, StatusChange, TxReport,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+// How long the worker waits for modem output before checking its queues again.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// How long the modem may take to answer a single command.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Configuration change requested from a `ModemWorker`.
+#[derive(Debug)]
+pub enum Command {
+    /// Set frequency
+    SetFrequency(Frequency),
+    /// Switch to one of the predefined modem configs
+    SetMode(ModemConfig),
+    /// Query the current modem status
+    Config,
+}
+
+/// Outcome of a transmission or command handled by a `ModemWorker`.
+#[derive(Debug)]
+pub enum Reply {
+    /// A frame was transmitted
+    Sent(TxReport),
+    /// A command completed successfully
+    Ok,
+    /// Answer to `Command::Config`
+    Status(Status),
+    /// A transmission or command failed
+    Error(ModemError),
+}
+
+enum Op {
+    Tx(Option<TxHandle>),
+    Cmd(Command, Vec<String>),
+    // status poll of the worker itself
+    Poll(Vec<String>),
+}
+
+/// Owns a modem on a background thread, exchanging packets and commands over channels.
+///
+/// While a transmission or command is outstanding, received packets keep flowing
+/// to the packet channel instead of being swallowed by the command response.
+///
+/// Frames wait in a bounded queue and are transmitted by priority, frames sent
+/// through the `frames()` channel count as `Priority::Data`. The outcome of a
+/// frame queued with `submit` is collected through its `TxHandle`, so several
+/// transmissions can be pipelined, all other outcomes arrive on `replies()`.
+///
+/// With `set_status_interval` the worker polls `AT+INFO` in between and sends
+/// `ModemEvent::StatusChanged` to `events()` when the modem was reconfigured by
+/// someone else, its firmware restarted, or it transmitted frames the worker did
+/// not send.
+///
+/// Received packets wait in a `PacketQueue` as large as the receive buffer of
+/// the modem, see `Rf95Modem::set_rx_buffer`, and overflow by the same policy.
+/// With `OverflowPolicy::Block` the worker stops reading while the queue is full.
+/// Dropped packets are counted in `Status::rx_dropped`.
+///
+/// Packets not passing the `RxFilter` set with `set_filter` are dropped on the
+/// worker thread and only counted, so a busy shared channel does not flood the
+/// packet channel. `+RX` lines failing to parse are reported as `ModemEvent::Error`.
+///
+/// `close` shuts the worker down after the queued frames went out, dropping the
+/// worker stops it right away. Either way the thread is joined and the modem
+/// with its port released before returning.
+pub struct ModemWorker<T: Transport + Send + 'static> {
+    packets: Arc<PacketQueue>,
+    events: Receiver<ModemEvent>,
+    status_interval: Arc<Mutex<Option<Duration>>>,
+    filter: Arc<Mutex<(Option<RxFilter>, FilterStats)>>,
+    frames: Sender<Vec<u8>>,
+    queue: Arc<Mutex<TxQueue<Option<TxHandle>>>>,
+    completions: Arc<Completions>,
+    commands: Sender<Command>,
+    replies: Receiver<Reply>,
+    stop: Arc<AtomicBool>,
+    closing: Arc<Mutex<Option<Instant>>>,
+    handle: Option<JoinHandle<Rf95Modem<T>>>,
+}
+
+impl<T: Transport + Send + 'static> ModemWorker<T> {
+    /// Open `modem` if necessary and start the background thread.
+    pub fn spawn(modem: Rf95Modem<T>) -> Result<Self> {
+        Self::spawn_with_limits(modem, QueueLimits::default())
+    }
+    /// Like `spawn`, bounding the transmit queue by `limits`.
+    pub fn spawn_with_limits(mut modem: Rf95Modem<T>, limits: QueueLimits) -> Result<Self> {
+        if !modem.transport().is_open() {
+            modem.open()?;
+        }
+        let (capacity, policy) = modem.rx_buffer();
+        let packets = Arc::new(PacketQueue::new(capacity, policy));
+        let (frames, frame_rx) = mpsc::channel();
+        let (commands, command_rx) = mpsc::channel();
+        let (reply_tx, replies) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        let status_interval = Arc::new(Mutex::new(None));
+        let filter = Arc::new(Mutex::new((None, FilterStats::default())));
+        let stop = Arc::new(AtomicBool::new(false));
+        let closing = Arc::new(Mutex::new(None));
+        let queue = Arc::new(Mutex::new(TxQueue::new(limits)));
+        let completions = Arc::new(Completions::default());
+        let router = Router {
+            modem,
+            packets: packets.clone(),
+            held: None,
+            frames: frame_rx,
+            queue: queue.clone(),
+            completions: completions.clone(),
+            commands: command_rx,
+            replies: reply_tx,
+            events: event_tx,
+            status_interval: status_interval.clone(),
+            filter: filter.clone(),
+            stop: stop.clone(),
+            closing: closing.clone(),
+            inflight: None,
+            watch: StatusWatch::default(),
+        };
+        let handle = thread::spawn(move || router.run());
+        Ok(ModemWorker {
+            packets,
+            events,
+            status_interval,
+            filter,
+            frames,
+            queue,
+            completions,
+            commands,
+            replies,
+            stop,
+            closing,
+            handle: Some(handle),
+        })
+    }
+    /// Queue of all packets received by the modem.
+    pub fn packets(&self) -> &PacketQueue {
+        &self.packets
+    }
+    /// Sender for frames to transmit.
+    pub fn frames(&self) -> Sender<Vec<u8>> {
+        self.frames.clone()
+    }
+    /// Queue `frame` for transmission, failing with `ModemError::QueueFull` instead of blocking.
+    pub fn send(&self, frame: Vec<u8>, priority: Priority) -> Result<()> {
+        self.queue.lock().unwrap().push(priority, frame, None)
+    }
+    /// Queue `frame` like `send`, its outcome is collected with `poll_tx` or `wait_tx`.
+    pub fn submit(&self, frame: Vec<u8>, priority: Priority) -> Result<TxHandle> {
+        let handle = self.completions.handle();
+        self.queue
+            .lock()
+            .unwrap()
+            .push(priority, frame, Some(handle))?;
+        Ok(handle)
+    }
+    /// Outcome of a submitted frame, `None` while it waits or is being transmitted.
+    ///
+    /// The outcome is handed out once, later calls return `None` again.
+    pub fn poll_tx(&self, handle: TxHandle) -> Option<Result<TxReport>> {
+        self.completions.take(handle)
+    }
+    /// Block until the submitted frame was transmitted or failed, at most for `timeout`.
+    pub fn wait_tx(&self, handle: TxHandle, timeout: Option<Duration>) -> Result<TxReport> {
+        self.completions.wait(handle, timeout)
+    }
+    /// Number of frames waiting for transmission.
+    pub fn queued(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+    /// Sender for configuration commands.
+    pub fn commands(&self) -> Sender<Command> {
+        self.commands.clone()
+    }
+    /// Receiver for the outcome of every command and every frame not submitted
+    /// with `submit`, in completion order.
+    pub fn replies(&self) -> &Receiver<Reply> {
+        &self.replies
+    }
+    /// Receiver for status changes detected by the periodic polls.
+    pub fn events(&self) -> &Receiver<ModemEvent> {
+        &self.events
+    }
+    /// Poll the modem status every `interval`, `None` stops polling.
+    ///
+    /// The first poll records the status the following ones are compared with.
+    pub fn set_status_interval(&self, interval: Option<Duration>) {
+        *self.status_interval.lock().unwrap() = interval;
+    }
+    /// Drop received packets not passing `filter`, `None` passes all of them.
+    pub fn set_filter(&self, filter: Option<RxFilter>) {
+        self.filter.lock().unwrap().0 = filter;
+    }
+    /// Packets passed and dropped by the filter so far.
+    pub fn filter_stats(&self) -> FilterStats {
+        self.filter.lock().unwrap().1
+    }
+    /// Start counting filtered packets from zero.
+    pub fn reset_filter_stats(&self) {
+        self.filter.lock().unwrap().1 = FilterStats::default();
+    }
+    /// Stop the background thread and hand back the modem.
+    pub fn stop(mut self) -> Rf95Modem<T> {
+        self.stop.store(true, Ordering::SeqCst);
+        self.join()
+    }
+    /// Transmit the queued frames for at most `timeout`, then stop the thread,
+    /// disable receiving and close the modem.
+    ///
+    /// A serial port gets its previous terminal settings back. Fails with
+    /// `ModemError::Timeout` if frames were left in the queue, their handles
+    /// complete with `ModemError::Disconnected`.
+    pub fn close(mut self, timeout: Duration) -> Result<()> {
+        *self.closing.lock().unwrap() = Some(Instant::now() + timeout);
+        let mut modem = self.join();
+        let disabled = modem.disable_rx();
+        drop(modem);
+        if self.queued() > 0 {
+            return Err(ModemError::Timeout);
+        }
+        disabled
+    }
+
+    fn join(&mut self) -> Rf95Modem<T> {
+        let handle = self.handle.take().expect("worker thread joined twice");
+        match handle.join() {
+            Ok(modem) => modem,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl<T: Transport + Send + 'static> Drop for ModemWorker<T> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            // the modem closes its port as it is dropped along with the result
+            let _ = handle.join();
+        }
+    }
+}
+
+struct Router<T: Transport> {
+    modem: Rf95Modem<T>,
+    packets: Arc<PacketQueue>,
+    // packet waiting for room in the full queue, reading pauses meanwhile
+    held: Option<RxPacket>,
+    frames: Receiver<Vec<u8>>,
+    queue: Arc<Mutex<TxQueue<Option<TxHandle>>>>,
+    completions: Arc<Completions>,
+    commands: Receiver<Command>,
+    replies: Sender<Reply>,
+    events: Sender<ModemEvent>,
+    status_interval: Arc<Mutex<Option<Duration>>>,
+    filter: Arc<Mutex<(Option<RxFilter>, FilterStats)>>,
+    stop: Arc<AtomicBool>,
+    // deadline for the remaining frames once `close` was called
+    closing: Arc<Mutex<Option<Instant>>>,
+    inflight: Option<(Op, Instant)>,
+    watch: StatusWatch,
+}
+
+// Status of the last poll and the traffic of the worker since then.
+#[derive(Default)]
+struct StatusWatch {
+    status: Option<Status>,
+    polled_at: Option<Instant>,
+    sent: usize,
+}
+
+impl StatusWatch {
+    fn due(&self, interval: Option<Duration>) -> bool {
+        match (interval, self.polled_at) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(interval), Some(at)) => at.elapsed() >= interval,
+        }
+    }
+
+    // Changes since the last poll not caused by the worker, making `status` the new baseline.
+    fn update(&mut self, status: Status) -> Vec<StatusChange> {
+        let sent = std::mem::take(&mut self.sent);
+        let old = match self.status.replace(status) {
+            Some(old) => old,
+            None => return Vec::new(),
+        };
+        let new = self.status.as_ref().unwrap();
+        Status::diff(&old, new)
+            .into_iter()
+            .filter(|change| match *change {
+                // every transmission passes through the worker
+                StatusChange::Counter {
+                    name: "tx_good",
+                    old,
+                    new,
+                } => new != old + sent,
+                StatusChange::Counter { old, new, .. } => new < old,
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+impl<T: Transport> Router<T> {
+    fn run(mut self) -> Rf95Modem<T> {
+        // packets read before the worker took over, e.g. while opening
+        for (line, at) in self.modem.take_pending() {
+            self.receive(&line, at);
+        }
+        while !self.stop.load(Ordering::SeqCst) {
+            if self.closed() {
+                break;
+            }
+            if let Some(packet) = self.held.take() {
+                self.held = self.packets.push(packet, POLL_INTERVAL);
+                if self.held.is_some() {
+                    self.expire();
+                    continue;
+                }
+            }
+            if self.inflight.is_none() && !self.issue() {
+                break;
+            }
+            match self.modem.poll_line(Some(Instant::now() + POLL_INTERVAL)) {
+                Ok(Some(line)) => self.route(line),
+                Ok(None) => self.expire(),
+                Err(e) => {
+                    let _ = self.replies.send(Reply::Error(e));
+                    break;
+                }
+            }
+        }
+        self.completions.close();
+        self.packets.close();
+        self.modem
+    }
+
+    // Whether `close` was called and the queue is drained or its deadline passed.
+    fn closed(&mut self) -> bool {
+        let deadline = match *self.closing.lock().unwrap() {
+            Some(deadline) => deadline,
+            None => return false,
+        };
+        if self.inflight.is_none() {
+            self.enqueue_frames();
+            if self.queue.lock().unwrap().len() == 0 {
+                return true;
+            }
+        }
+        Instant::now() >= deadline
+    }
+
+    // Report the outcome of a transmission through its handle or as a reply.
+    fn finish_tx(&self, handle: Option<TxHandle>, result: Result<TxReport>) {
+        match handle {
+            Some(handle) => self.completions.complete(handle, result),
+            None => {
+                let reply = match result {
+                    Ok(report) => Reply::Sent(report),
+                    Err(e) => Reply::Error(e),
+                };
+                let _ = self.replies.send(reply);
+            }
+        }
+    }
+
+    // Start the next command, transmission or status poll, in this order of precedence.
+    // Returns false once all handles feeding the worker are gone.
+    fn issue(&mut self) -> bool {
+        let (op, line) = match self.commands.try_recv() {
+            Ok(cmd) => {
+                let line = match cmd {
+                    Command::SetFrequency(freq) => format!("AT+FREQ={:.6}", freq),
+                    Command::SetMode(mode) => format!("AT+MODE={}", mode as usize),
+                    Command::Config => "AT+INFO".to_string(),
+                };
+                (Op::Cmd(cmd, Vec::new()), line)
+            }
+            Err(cmd_err) => {
+                let frames_open = self.enqueue_frames();
+                match self.queue.lock().unwrap().pop() {
+                    Some((frame, handle)) => {
+                        (Op::Tx(handle), format!("AT+TX={}", hex::encode(&frame)))
+                    }
+                    None if frames_open
+                        && self.watch.due(*self.status_interval.lock().unwrap()) =>
+                    {
+                        self.watch.polled_at = Some(Instant::now());
+                        (Op::Poll(Vec::new()), "AT+INFO".to_string())
+                    }
+                    None if frames_open => return true,
+                    None => return cmd_err == TryRecvError::Empty,
+                }
+            }
+        };
+        match (self.modem.write_line(&line), op) {
+            (Ok(()), op) => self.inflight = Some((op, Instant::now())),
+            (Err(e), Op::Tx(handle)) => self.finish_tx(handle, Err(e)),
+            (Err(e), Op::Cmd(..)) => {
+                let _ = self.replies.send(Reply::Error(e));
+            }
+            (Err(e), Op::Poll(_)) => {
+                let _ = self.events.send(ModemEvent::Error(e.to_string()));
+            }
+        }
+        true
+    }
+
+    // Move frames from the channel into the queue, returns false once the channel is closed.
+    fn enqueue_frames(&mut self) -> bool {
+        loop {
+            match self.frames.try_recv() {
+                Ok(frame) => {
+                    let pushed = self.queue.lock().unwrap().push(Priority::Data, frame, None);
+                    if let Err(e) = pushed {
+                        let _ = self.replies.send(Reply::Error(e));
+                    }
+                }
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+
+    // Deliver a `+RX` line read at `at` unless the filter drops it.
+    fn receive(&mut self, line: &str, at: SystemTime) {
+        let mut packet = match RxPacketRef::parse(line, self.modem.header()) {
+            Ok(packet) => packet.to_packet(),
+            Err(e) => {
+                let _ = self.events.send(ModemEvent::Error(e.to_string()));
+                return;
+            }
+        };
+        packet.received_at = at;
+        let passed = {
+            let (filter, stats) = &mut *self.filter.lock().unwrap();
+            let outcome = filter.as_ref().and_then(|f| f.check(&packet));
+            stats.record(outcome);
+            outcome.is_none()
+        };
+        if passed {
+            self.held = self.packets.push(packet, Duration::ZERO);
+        }
+    }
+
+    fn route(&mut self, line: String) {
+        match LineKind::of(&line) {
+            LineKind::Rx => self.receive(&line, SystemTime::now()),
+            LineKind::Sent => {
+                if let Some((Op::Tx(handle), _)) = self.inflight {
+                    self.inflight = None;
+                    let result = parse_sent(&line).map(|n| TxReport::new(n, None));
+                    if result.is_ok() {
+                        self.watch.sent += 1;
+                    }
+                    self.finish_tx(handle, result);
+                }
+            }
+            LineKind::Ok => match self.inflight.take() {
+                Some((Op::Cmd(cmd, lines), _)) => {
+                    let reply = match cmd {
+                        Command::Config => match Status::parse(&lines) {
+                            Ok(mut status) => {
+                                status.rx_dropped =
+                                    self.modem.rx_dropped() + self.packets.dropped();
+                                Reply::Status(status)
+                            }
+                            Err(e) => Reply::Error(e),
+                        },
+                        _ => Reply::Ok,
+                    };
+                    // our own changes are no drift
+                    if let Some(status) = &mut self.watch.status {
+                        match cmd {
+                            Command::SetFrequency(freq) => status.frequency = freq,
+                            Command::SetMode(mode) => status.config = mode,
+                            Command::Config => {}
+                        }
+                    }
+                    let _ = self.replies.send(reply);
+                }
+                Some((Op::Poll(lines), _)) => match Status::parse(&lines) {
+                    Ok(status) => {
+                        let changes = self.watch.update(status);
+                        if !changes.is_empty() {
+                            let _ = self.events.send(ModemEvent::StatusChanged(changes));
+                        }
+                    }
+                    Err(e) => {
+                        let _ = self.events.send(ModemEvent::Error(e.to_string()));
+                    }
+                },
+                inflight => self.inflight = inflight,
+            },
+            LineKind::Error => match self.inflight.take() {
+                Some((Op::Tx(handle), _)) => {
+                    self.finish_tx(handle, Err(tx_rejected(ModemError::ModemReported(line))))
+                }
+                Some((Op::Cmd(..), _)) => {
+                    let _ = self
+                        .replies
+                        .send(Reply::Error(ModemError::ModemReported(line)));
+                }
+                Some((Op::Poll(_), _)) => {
+                    let _ = self.events.send(ModemEvent::Error(line));
+                }
+                None => {}
+            },
+            LineKind::Other => match &mut self.inflight {
+                Some((Op::Cmd(_, lines), _)) | Some((Op::Poll(lines), _)) => lines.push(line),
+                _ => {}
+            },
+        }
+    }
+
+    // Give up on an operation the modem never answered.
+    fn expire(&mut self) {
+        let expired = self
+            .inflight
+            .as_ref()
+            .is_some_and(|(_, started)| started.elapsed() > RESPONSE_TIMEOUT);
+        if !expired {
+            return;
+        }
+        match self.inflight.take() {
+            Some((Op::Tx(handle), _)) => self.finish_tx(handle, Err(ModemError::Timeout)),
+            Some((Op::Poll(_), _)) => {
+                let _ = self
+                    .events
+                    .send(ModemEvent::Error(ModemError::Timeout.to_string()));
+            }
+            _ => {
+                let _ = self.replies.send(Reply::Error(ModemError::Timeout));
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use lora_modem_hal::{
+    AddressedModem, AdrConfig, AdrModem, ArqConfig, Bandwidth, LinkModel, ModemError,
+    ReliableModem, VirtualModem,
+};
+use std::thread;
+use std::time::Duration;
+
+fn node(mut modem: VirtualModem, id: u8) -> AdrModem<VirtualModem> {
+    modem.set_timeout(Some(Duration::from_millis(20)));
+    let arq = ArqConfig {
+        ack_timeout: Duration::from_millis(200),
+        ..ArqConfig::default()
+    };
+    let reliable = ReliableModem::new(AddressedModem::new(modem, id), arq);
+    AdrModem::new(reliable, AdrConfig::default()).unwrap()
+}
+
+// Send `count` frames from node 1 to node 2, returns the rate node 1 ended at.
+fn exchange(snr: f32, count: usize) -> (u8, Bandwidth) {
+    let model = LinkModel {
+        snr,
+        jitter: 0,
+        ..LinkModel::default()
+    };
+    let (a, b) = VirtualModem::pair_with(model, 1);
+    let mut sender = node(a, 1);
+    let mut receiver = node(b, 2);
+    let listener = thread::spawn(move || {
+        let mut received = Vec::new();
+        while received.len() < count {
+            match receiver.receive() {
+                Ok(frame) => received.push(frame.packet.data[0] as usize),
+                Err(ModemError::Timeout) => {}
+                Err(e) => panic!("{}", e),
+            }
+        }
+        (received, receiver.rate(1))
+    });
+    // both ends start at the most robust rate
+    assert_eq!(sender.rate(2), (12, Bandwidth::Bw125kHz));
+    for i in 0..count {
+        sender.send(2, &[i as u8]).unwrap();
+    }
+    let (received, followed) = listener.join().unwrap();
+    assert_eq!(received, (0..count).collect::<Vec<_>>());
+    assert_eq!(followed, sender.rate(2));
+    sender.rate(2)
+}
+
+#[test]
+fn speeds_up_on_a_clean_link() {
+    assert_eq!(exchange(9.0, 30), (7, Bandwidth::Bw500kHz));
+}
+
+#[test]
+fn keeps_margin_on_a_noisy_link() {
+    // 9.5 dB margin at SF11, SF10 would leave only 7 dB
+    assert_eq!(exchange(-8.0, 30), (11, Bandwidth::Bw125kHz));
+}
@@ -0,0 +1,186 @@
+use lora_modem_hal::{
+    Checksum, ChecksumModem, DutyCycleModem, DutyCyclePolicy, DutyCycleTracker, Frequency,
+    LoraModemDevice, MockModem, ModemConfig, ModemError, Redundancy, RedundantModem, StatsModem,
+    SubBand, VirtualModem,
+};
+use std::time::Duration;
+
+#[test]
+fn reserves_duty_cycle_once_per_batch() {
+    let (mut a, mut b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let mode = ModemConfig::MediumBw125Cr45Sf128Crc;
+    let frames: [&[u8]; 3] = [b"t=21.5", b"h=40", b"p=1013"];
+    let batch_toa: Duration = frames.iter().map(|f| mode.airtime(f.len())).sum();
+
+    let freq = Frequency::from_hz(868_100_000);
+    let mut tracker = DutyCycleTracker::new();
+    tracker.set_window(Duration::from_secs(60));
+    // room for one batch but not for two
+    let duty_cycle = (batch_toa.as_secs_f32() * 1.5) / 60.0;
+    tracker.add_band(SubBand::new(
+        Frequency::from_hz(868_000_000),
+        Frequency::from_hz(868_600_000),
+        duty_cycle,
+        DutyCyclePolicy::Reject,
+    ));
+    let mut modem = DutyCycleModem::new(a, tracker);
+    modem.set_frequency(freq).unwrap();
+    modem.set_mode(mode).unwrap();
+
+    let results = modem.send_batch(&frames);
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(Result::is_ok));
+    for frame in &frames {
+        assert_eq!(&b.read_packet().unwrap().data[..], *frame);
+    }
+    assert_eq!(modem.tracker().used(freq), batch_toa);
+
+    // the second batch does not fit as a whole, not even its first frame is sent
+    let results = modem.send_batch(&frames);
+    assert_eq!(results.len(), 3);
+    assert!(results
+        .iter()
+        .all(|r| matches!(r, Err(ModemError::DutyCycleExceeded { .. }))));
+    assert!(matches!(b.read_packet(), Err(ModemError::Timeout)));
+}
+
+#[test]
+fn refuses_frames_exceeding_the_whole_budget() {
+    let (mut a, mut b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let mode = ModemConfig::MediumBw125Cr45Sf128Crc;
+    let freq = Frequency::from_hz(868_100_000);
+    let mut tracker = DutyCycleTracker::new();
+    tracker.set_window(Duration::from_secs(1));
+    // room for a short frame, never for a long one
+    let duty_cycle = mode.airtime(16).as_secs_f32();
+    tracker.add_band(SubBand::new(
+        Frequency::from_hz(868_000_000),
+        Frequency::from_hz(868_600_000),
+        duty_cycle,
+        DutyCyclePolicy::Queue,
+    ));
+    let mut modem = DutyCycleModem::new(a, tracker);
+    modem.set_frequency(freq).unwrap();
+    modem.set_mode(mode).unwrap();
+
+    let refused = modem.send_data(vec![0; 200]);
+    assert!(
+        matches!(refused, Err(ModemError::PayloadTooLarge { max }) if max < 200),
+        "{:?}",
+        refused
+    );
+    assert_eq!(modem.queued(), 0);
+    // the queue is not held up by the refused frame
+    assert_eq!(modem.send_data(vec![1; 8]).unwrap().bytes, 8);
+    assert_eq!(b.read_packet().unwrap().data, [1; 8]);
+
+    let long = [0u8; 200];
+    let frames: [&[u8]; 2] = [&long, b"ok"];
+    let results = modem.send_batch(&frames);
+    assert!(matches!(
+        results[0],
+        Err(ModemError::PayloadTooLarge { .. })
+    ));
+    assert_eq!(results.len(), 2);
+    assert_eq!(modem.queued(), 1);
+}
+
+#[test]
+fn wrappers_pass_batches_down() {
+    let (a, mut b) = VirtualModem::pair();
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let mode = ModemConfig::MediumBw125Cr45Sf128Crc;
+    let frames: [&[u8]; 3] = [b"t=21.5", b"h=40", b"p=1013"];
+    // the CRC-16 is part of every frame on air
+    let batch_toa: Duration = frames.iter().map(|f| mode.airtime(f.len() + 2)).sum();
+
+    let freq = Frequency::from_hz(868_100_000);
+    let mut tracker = DutyCycleTracker::new();
+    tracker.set_window(Duration::from_secs(60));
+    let duty_cycle = (batch_toa.as_secs_f32() * 1.5) / 60.0;
+    tracker.add_band(SubBand::new(
+        Frequency::from_hz(868_000_000),
+        Frequency::from_hz(868_600_000),
+        duty_cycle,
+        DutyCyclePolicy::Reject,
+    ));
+    let limited = DutyCycleModem::new(a, tracker);
+    let mut modem = StatsModem::new(ChecksumModem::new(limited, Checksum::Crc16));
+    modem.set_frequency(freq).unwrap();
+    modem.set_mode(mode).unwrap();
+
+    let results = modem.send_batch(&frames);
+    assert_eq!(results.len(), 3);
+    for (result, frame) in results.iter().zip(&frames) {
+        assert_eq!(result.as_ref().unwrap().bytes, frame.len());
+        assert_eq!(&b.read_packet().unwrap().data[..frame.len()], *frame);
+    }
+    let stats = modem.stats().unwrap();
+    assert_eq!(stats.packets_out, 3);
+    assert_eq!(stats.bytes_out, 16);
+
+    // held back as a whole, frame by frame the first one would still fit
+    let results = modem.send_batch(&frames);
+    assert!(results
+        .iter()
+        .all(|r| matches!(r, Err(ModemError::DutyCycleExceeded { .. }))));
+    assert!(matches!(b.read_packet(), Err(ModemError::Timeout)));
+    let mut limited = modem.into_inner().into_inner();
+    assert_eq!(limited.tracker().used(freq), batch_toa);
+}
+
+#[test]
+fn reports_every_frame_once_for_all_its_copies() {
+    let (a, mut b) = VirtualModem::pair();
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let mut modem = RedundantModem::new(a, Redundancy::default());
+    let frames: [&[u8]; 2] = [b"t=21.5", b"h=40"];
+
+    let results = modem.send_batch(&frames);
+    assert_eq!(results.len(), 2);
+    for (result, frame) in results.iter().zip(&frames) {
+        assert_eq!(result.as_ref().unwrap().bytes, frame.len());
+    }
+    for frame in &frames {
+        for _ in 0..2 {
+            assert_eq!(&b.read_packet().unwrap().data[4..], *frame);
+        }
+    }
+}
+
+#[test]
+fn keeps_queued_frames_failing_to_send() {
+    let mut mock = MockModem::new();
+    mock.open().unwrap();
+    let mode = ModemConfig::MediumBw125Cr45Sf128Crc;
+    let freq = Frequency::from_hz(868_100_000);
+    let window = Duration::from_millis(200);
+    let mut tracker = DutyCycleTracker::new();
+    tracker.set_window(window);
+    // room for one frame per window
+    let duty_cycle = mode.airtime(16).as_secs_f32() / window.as_secs_f32();
+    tracker.add_band(SubBand::new(
+        Frequency::from_hz(868_000_000),
+        Frequency::from_hz(868_600_000),
+        duty_cycle,
+        DutyCyclePolicy::Queue,
+    ));
+    let mut modem = DutyCycleModem::new(mock, tracker);
+    modem.set_frequency(freq).unwrap();
+    modem.set_mode(mode).unwrap();
+    modem.send_data(vec![1; 16]).unwrap();
+    modem.send_data(vec![2; 16]).unwrap();
+    assert_eq!(modem.queued(), 1);
+
+    std::thread::sleep(window);
+    modem.inner_mut().status_mut().max_pkt_size = 8;
+    assert!(modem.flush_queue().is_err());
+    assert_eq!(modem.queued(), 1);
+    modem.inner_mut().status_mut().max_pkt_size = 251;
+    assert_eq!(modem.flush_queue().unwrap(), 1);
+    assert_eq!(modem.inner_mut().sent().last().unwrap(), &[2; 16]);
+}
@@ -0,0 +1,80 @@
+use lora_modem_hal::{
+    AddressedModem, DownlinkScheduler, ListenSchedule, LoraModemDevice, QueueLimits, SyncedModem,
+    VirtualModem,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+// Windows of `window` every hour, the current one opened `ago` before now.
+fn schedule(ago: Duration, window: Duration) -> ListenSchedule {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let offset = (now.as_secs() + 3600 - ago.as_secs()) % 3600;
+    ListenSchedule {
+        period: HOUR,
+        offset: Duration::from_secs(offset),
+        window,
+    }
+}
+
+#[test]
+fn computes_windows() {
+    let schedule = ListenSchedule {
+        period: Duration::from_secs(60),
+        offset: Duration::from_secs(10),
+        window: Duration::from_secs(5),
+    };
+    let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+    assert_eq!(schedule.open_until(at(612)), Some(at(615)));
+    assert_eq!(schedule.next_window(at(612)), at(612));
+    assert_eq!(schedule.open_until(at(615)), None);
+    assert_eq!(schedule.next_window(at(615)), at(670));
+    assert_eq!(schedule.next_window(at(5)), at(10));
+}
+
+#[test]
+fn holds_frames_until_the_peer_listens() {
+    let (a, b) = VirtualModem::pair();
+    let mut scheduler = DownlinkScheduler::new(
+        SyncedModem::new(AddressedModem::new(a, 1)),
+        QueueLimits::default(),
+    );
+    let mut peer = AddressedModem::new(b, 2);
+
+    // window closed for another half hour
+    scheduler
+        .set_schedule(2, Some(schedule(HOUR / 2, Duration::from_secs(10))))
+        .unwrap();
+    assert!(!scheduler.send(2, b"first").unwrap());
+    assert!(!scheduler.send(2, b"second").unwrap());
+    assert_eq!(scheduler.queued(), 2);
+    let queued: Vec<&[u8]> = scheduler.queue(2).map(|f| &f.data[..]).collect();
+    assert_eq!(queued, [&b"first"[..], b"second"]);
+    let release = scheduler.next_release(2).unwrap();
+    assert!(release > SystemTime::now() + HOUR / 2 - Duration::from_secs(5));
+    assert_eq!(scheduler.poll().unwrap(), 0);
+
+    // open since a minute
+    scheduler
+        .set_schedule(2, Some(schedule(Duration::from_secs(60), HOUR / 2)))
+        .unwrap();
+    assert_eq!(scheduler.poll().unwrap(), 2);
+    assert_eq!(scheduler.queued(), 0);
+    assert_eq!(peer.read_packet().unwrap().data, b"first");
+    assert_eq!(peer.read_packet().unwrap().data, b"second");
+    assert!(scheduler.send(2, b"now").unwrap());
+    assert_eq!(peer.read_packet().unwrap().data, b"now");
+
+    // peers without schedule are always listening
+    assert!(scheduler.send(3, b"unscheduled").unwrap());
+    assert!(scheduler
+        .set_schedule(
+            2,
+            Some(ListenSchedule {
+                period: Duration::from_secs(1),
+                offset: Duration::ZERO,
+                window: Duration::from_secs(2),
+            })
+        )
+        .is_err());
+}
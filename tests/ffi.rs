@@ -0,0 +1,131 @@
+#![cfg(feature = "ffi")]
+
+use lora_modem_hal::ffi::*;
+use lora_modem_hal::{LoraModemDevice, MockModem, ModemError, VirtualModem};
+use std::ffi::CStr;
+use std::os::raw::c_void;
+use std::sync::mpsc::{self, Sender};
+use std::time::{Duration, Instant};
+use std::{ptr, slice};
+
+#[test]
+fn sends_and_reads_through_the_c_interface() {
+    let (mut a, mut b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let modem = into_raw(a);
+    unsafe {
+        assert_eq!(lora_modem_send(modem, b"ping".as_ptr(), 4), LORA_OK);
+        assert_eq!(b.read_packet().unwrap().data, b"ping");
+
+        b.send_data(b"pong".to_vec()).unwrap();
+        let mut buf = [0u8; 16];
+        let mut packet = LoraRxPacket {
+            data: ptr::null(),
+            len: 0,
+            rssi: 0,
+            snr: 0.0,
+        };
+        let read = lora_modem_read_packet(modem, buf.as_mut_ptr(), buf.len(), &mut packet, 500);
+        assert_eq!(read, LORA_OK);
+        assert_eq!(slice::from_raw_parts(packet.data, packet.len), b"pong");
+
+        b.send_data(vec![0; 32]).unwrap();
+        let read = lora_modem_read_packet(modem, buf.as_mut_ptr(), buf.len(), &mut packet, 500);
+        assert_eq!(read, LORA_ERR_BUFFER);
+        let read = lora_modem_read_packet(modem, buf.as_mut_ptr(), buf.len(), &mut packet, 0);
+        assert_eq!(read, LORA_ERR_TIMEOUT);
+        assert_eq!(
+            CStr::from_ptr(lora_modem_last_error()).to_str().unwrap(),
+            ModemError::Timeout.to_string()
+        );
+        assert_eq!(
+            lora_modem_send(ptr::null_mut(), ptr::null(), 0),
+            LORA_ERR_INVALID
+        );
+        lora_modem_close(modem);
+    }
+}
+
+unsafe extern "C" fn forward(packet: *const LoraRxPacket, user_data: *mut c_void) {
+    let packet = &*packet;
+    let received = &*(user_data as *const Sender<Vec<u8>>);
+    let _ = received.send(slice::from_raw_parts(packet.data, packet.len).to_vec());
+}
+
+#[test]
+fn delivers_packets_to_the_rx_callback() {
+    let (mut a, mut b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let modem = into_raw(a);
+    let (tx, rx) = mpsc::channel();
+    let tx = Box::new(tx);
+    unsafe {
+        let user_data = &*tx as *const Sender<Vec<u8>> as *mut c_void;
+        assert_eq!(
+            lora_modem_set_rx_callback(modem, Some(forward), user_data),
+            LORA_OK
+        );
+        b.send_data(b"one".to_vec()).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), b"one");
+        // sending works while the callback is listening
+        assert_eq!(lora_modem_send(modem, b"two".as_ptr(), 3), LORA_OK);
+        assert_eq!(b.read_packet().unwrap().data, b"two");
+        let mut packet = LoraRxPacket {
+            data: ptr::null(),
+            len: 0,
+            rssi: 0,
+            snr: 0.0,
+        };
+        let read = lora_modem_read_packet(modem, ptr::null_mut(), 0, &mut packet, 0);
+        assert_eq!(read, LORA_ERR_BUSY);
+        assert_eq!(
+            lora_modem_set_rx_callback(modem, None, ptr::null_mut()),
+            LORA_OK
+        );
+        b.send_data(b"three".to_vec()).unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        lora_modem_close(modem);
+    }
+}
+
+#[test]
+fn rx_callback_skips_corrupted_lines_and_reports_disconnects() {
+    let mut mock = MockModem::new();
+    mock.open().unwrap();
+    mock.push_line("+RX 9,zz,-80,7");
+    mock.push_rx(b"one", -80, 7.0);
+    // nothing queued afterwards, the mock reports as disconnected
+    let modem = into_raw(mock);
+    let (tx, rx) = mpsc::channel();
+    let tx = Box::new(tx);
+    unsafe {
+        let user_data = &*tx as *const Sender<Vec<u8>> as *mut c_void;
+        assert_eq!(
+            lora_modem_set_rx_callback(modem, Some(forward), user_data),
+            LORA_OK
+        );
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), b"one");
+        let mut packet = LoraRxPacket {
+            data: ptr::null(),
+            len: 0,
+            rssi: 0,
+            snr: 0.0,
+        };
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let read = loop {
+            let read = lora_modem_read_packet(modem, ptr::null_mut(), 0, &mut packet, 0);
+            if read != LORA_ERR_BUSY || Instant::now() > deadline {
+                break read;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        };
+        assert_eq!(read, LORA_ERR);
+        assert_eq!(
+            CStr::from_ptr(lora_modem_last_error()).to_str().unwrap(),
+            ModemError::Disconnected.to_string()
+        );
+        lora_modem_close(modem);
+    }
+}
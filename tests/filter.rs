@@ -0,0 +1,97 @@
+use lora_modem_hal::addressing::HEADER_LEN;
+use lora_modem_hal::{
+    FilterReason, FilterStats, LoraModemDevice, ModemWorker, Priority, ReplayTransport, Rf95Modem,
+    RxFilter, RxPacket,
+};
+use std::convert::TryFrom;
+use std::time::Duration;
+
+fn packet(data: &[u8], rssi: i16) -> RxPacket {
+    let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+    RxPacket::try_from(format!("+RX {},{},{},7", data.len(), hex, rssi).as_str()).unwrap()
+}
+
+#[test]
+fn checks_every_criterion() {
+    let filter = RxFilter::new()
+        .sources(&[2, 3])
+        .block(3)
+        .min_rssi(-110)
+        .ports(HEADER_LEN, &[7]);
+    assert_eq!(filter.check(&packet(&[1, 2, 0, 7], -100)), None);
+    assert_eq!(
+        filter.check(&packet(&[1, 3, 0, 7], -100)),
+        Some(FilterReason::Source)
+    );
+    assert_eq!(
+        filter.check(&packet(&[1, 4, 0, 7], -100)),
+        Some(FilterReason::Source)
+    );
+    assert_eq!(
+        filter.check(&packet(&[1], -100)),
+        Some(FilterReason::Source)
+    );
+    assert_eq!(
+        filter.check(&packet(&[1, 2, 0, 7], -120)),
+        Some(FilterReason::Rssi)
+    );
+    assert_eq!(
+        filter.check(&packet(&[1, 2, 0, 8], -100)),
+        Some(FilterReason::Port)
+    );
+    assert_eq!(
+        filter.check(&packet(&[1, 2, 0], -100)),
+        Some(FilterReason::Port)
+    );
+
+    let prefixed = RxFilter::new().prefix(b"ab").prefix(b"xyz");
+    assert!(prefixed.accepts(&packet(b"abc", -80)));
+    assert!(prefixed.accepts(&packet(b"xyz", -80)));
+    assert_eq!(
+        prefixed.check(&packet(b"xy", -80)),
+        Some(FilterReason::Prefix)
+    );
+    assert!(RxFilter::default().accepts(&packet(&[], -130)));
+}
+
+#[test]
+fn worker_drops_filtered_packets() {
+    let trace = "> AT+TX=00\n< +SENT 1 bytes\n\
+                 < +RX 4,01020007,-90,7\n< +RX 4,01030007,-90,7\n\
+                 < +RX 4,01020007,-120,7\n< +RX 4,01020009,-80,7\n";
+    let mut modem = Rf95Modem::from_transport(ReplayTransport::from_trace(trace));
+    modem.open().unwrap();
+    let worker = ModemWorker::spawn(modem).unwrap();
+    worker.set_filter(Some(
+        RxFilter::new()
+            .block(3)
+            .min_rssi(-110)
+            .ports(HEADER_LEN, &[7]),
+    ));
+    worker.send(vec![0], Priority::Data).unwrap();
+
+    let received = worker
+        .packets()
+        .recv_timeout(Duration::from_secs(2))
+        .unwrap();
+    assert_eq!(received.data, [1, 2, 0, 7]);
+    assert!(worker
+        .packets()
+        .recv_timeout(Duration::from_millis(200))
+        .is_err());
+    let stats = worker.filter_stats();
+    assert_eq!(
+        stats,
+        FilterStats {
+            passed: 1,
+            source: 1,
+            prefix: 0,
+            rssi: 1,
+            port: 1,
+        }
+    );
+    assert_eq!(stats.dropped(), 3);
+    worker.reset_filter_stats();
+    assert_eq!(worker.filter_stats(), FilterStats::default());
+    worker.stop();
+}
@@ -0,0 +1,34 @@
+use lora_modem_hal::{Frequency, LoRaChannels};
+
+#[test]
+fn formats_without_float_rounding() {
+    let freq = Frequency::from_khz(868_100);
+    assert_eq!(freq.to_string(), "868.1");
+    assert_eq!(format!("{:.6}", freq), "868.100000");
+    assert_eq!(format!("{:.2}", Frequency::from_hz(868_125_000)), "868.13");
+    assert_eq!(format!("{:.0}", Frequency::from_hz(868_600_000)), "869");
+    assert_eq!(format!("{:.8}", Frequency::from_mhz(915)), "915.00000000");
+    assert_eq!(Frequency::from_mhz(915).to_string(), "915");
+}
+
+#[test]
+fn parses_decimal_megahertz() {
+    let parse = |s: &str| s.parse::<Frequency>().unwrap();
+    assert_eq!(parse("868.1"), Frequency::from_khz(868_100));
+    assert_eq!(parse("433.7750"), Frequency::from_khz(433_775));
+    assert_eq!(parse("915 MHz"), Frequency::from_mhz(915));
+    assert_eq!(parse("868.10000049"), Frequency::from_hz(868_100_000));
+    assert_eq!(parse("868.1000005"), Frequency::from_hz(868_100_001));
+    for invalid in ["", ".", "MHz", "-868.1", "868,1", "5000", "868.1.2"] {
+        assert!(invalid.parse::<Frequency>().is_err(), "{:?}", invalid);
+    }
+}
+
+#[test]
+fn converts_from_channels() {
+    let freq: Frequency = LoRaChannels::Ch10_868.into();
+    assert_eq!(freq, LoRaChannels::Ch10_868.frequency());
+    assert_eq!(freq.hz(), LoRaChannels::Ch10_868.frequency_hz());
+    assert!(Frequency::from(LoRaChannels::Ch00_900) > freq);
+    assert_eq!(freq.offset(-1_000).offset(1_000), freq);
+}
@@ -0,0 +1,44 @@
+use lora_modem_hal::{LoraModemDevice, ModemHandle, VirtualModem};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn shares_one_modem_between_threads() {
+    let (mut a, mut b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let handle = ModemHandle::new(a);
+
+    let senders: Vec<_> = (0..4u8)
+        .map(|i| {
+            let mut handle = handle.clone();
+            thread::spawn(move || {
+                for j in 0..5u8 {
+                    handle.send_data(vec![i, j]).unwrap();
+                }
+            })
+        })
+        .collect();
+    for sender in senders {
+        sender.join().unwrap();
+    }
+    assert_eq!(handle.handles(), 1);
+
+    let mut received = Vec::new();
+    while let Ok(packet) = b.read_packet() {
+        received.push(packet.data);
+    }
+    received.sort();
+    let expected: Vec<Vec<u8>> = (0..4u8)
+        .flat_map(|i| (0..5u8).map(move |j| vec![i, j]))
+        .collect();
+    assert_eq!(received, expected);
+
+    // a type erased handle reads what the peer sends
+    let mut shared = handle.into_dyn();
+    let mut reader = shared.clone();
+    b.send_data(b"stats".to_vec()).unwrap();
+    let read = thread::spawn(move || reader.read_packet().map(|packet| packet.data));
+    assert_eq!(read.join().unwrap().unwrap(), b"stats");
+    assert!(shared.config().is_ok());
+}
@@ -0,0 +1,60 @@
+use lora_modem_hal::{
+    HeaderMode, LoraModemDevice, ModemError, ModemEvent, RadioParams, ReplayTransport, Rf95Modem,
+    RxPacketRef,
+};
+use std::time::Duration;
+
+#[test]
+fn receives_packets_without_length_in_implicit_mode() {
+    let trace = "> AT+HELP\n< AT+BW AT+SF AT+IMPLICIT\n< +OK\n\
+                 > AT+BW=125000\n< +OK\n> AT+SF=6\n< +OK\n> AT+CR=5\n< +OK\n\
+                 > AT+PREAMBLE=8\n< +OK\n> AT+CRC=1\n< +OK\n> AT+IMPLICIT=4\n< +OK\n\
+                 < +RX ,01020304,-80,7\n< +RX 0,05060708,-81,6\n";
+    let mut modem = Rf95Modem::from_transport(ReplayTransport::from_trace(trace));
+    modem.open().unwrap();
+    modem.set_timeout(Some(Duration::from_millis(50)));
+    let params = RadioParams {
+        spreading_factor: 6,
+        header: HeaderMode::Implicit { len: 4 },
+        ..RadioParams::default()
+    };
+    modem.set_radio_params(params).unwrap();
+    assert_eq!(modem.read_packet().unwrap().data, [1, 2, 3, 4]);
+    // events are parsed for the header mode as well
+    match modem.next_event().unwrap() {
+        ModemEvent::PacketReceived(packet) => assert_eq!(packet.rssi, -81),
+        event => panic!("{:?}", event),
+    }
+}
+
+#[test]
+fn implicit_lines_carry_the_configured_length() {
+    let implicit = HeaderMode::Implicit { len: 2 };
+    let packet = RxPacketRef::parse("+RX ,abcd,-80,7", implicit).unwrap();
+    assert_eq!(packet.len(), 2);
+    // a length reported anyway has to match
+    assert!(RxPacketRef::parse("+RX 2,abcd,-80,7", implicit).is_ok());
+    assert!(matches!(
+        RxPacketRef::parse("+RX ,abcdef,-80,7", implicit),
+        Err(ModemError::Parse(_))
+    ));
+    assert!(RxPacketRef::parse("+RX ,abcd,-80,7", HeaderMode::Explicit).is_err());
+}
+
+#[test]
+fn implicit_headers_shorten_the_airtime() {
+    let explicit = RadioParams::default();
+    let implicit = RadioParams {
+        header: HeaderMode::Implicit { len: 16 },
+        ..explicit
+    };
+    assert!(implicit.airtime(16).unwrap() < explicit.airtime(16).unwrap());
+    let empty = RadioParams {
+        header: HeaderMode::Implicit { len: 0 },
+        ..explicit
+    };
+    assert!(matches!(
+        empty.validate(),
+        Err(ModemError::InvalidArgument(_))
+    ));
+}
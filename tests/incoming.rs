@@ -0,0 +1,46 @@
+use lora_modem_hal::{
+    Checksum, ChecksumModem, LoraModemDevice, ModemError, ModemEvent, ReplayTransport, Rf95Modem,
+    StatsModem, VirtualModem,
+};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn iterates_packets_as_read_by_wrappers() {
+    let (a, mut b) = VirtualModem::pair();
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let mut sender = ChecksumModem::new(a, Checksum::Crc16);
+    sender.send_data(b"t=21.5".to_vec()).unwrap();
+    // a frame without a valid checksum does not end the iteration
+    let mut a = sender.into_inner();
+    a.send_data(b"h=40".to_vec()).unwrap();
+    let mut sender = ChecksumModem::new(a, Checksum::Crc16);
+    sender.send_data(b"p=1013".to_vec()).unwrap();
+
+    let mut receiver = ChecksumModem::new(b, Checksum::Crc16);
+    let mut incoming = receiver.incoming();
+    assert_eq!(incoming.next().unwrap().unwrap().data, b"t=21.5");
+    assert!(matches!(
+        incoming.next(),
+        Some(Err(ModemError::ChecksumMismatch))
+    ));
+    assert_eq!(incoming.next().unwrap().unwrap().data, b"p=1013");
+}
+
+#[test]
+fn wrappers_pass_events_through() {
+    let trace = "< +RX 2,0102,-80,7\n> AT+RX=0\n< +OK\n";
+    let mut modem = Rf95Modem::from_transport(ReplayTransport::from_trace(trace));
+    modem.open().unwrap();
+    modem.set_timeout(Some(Duration::from_millis(50)));
+    modem.buffer_rx().unwrap();
+    let buffered = SystemTime::now();
+    thread::sleep(Duration::from_millis(20));
+
+    // the packet keeps the time it was read, not the time it was handed out
+    let mut wrapped = StatsModem::new(ChecksumModem::new(modem, Checksum::Crc16));
+    match wrapped.next_event().unwrap() {
+        ModemEvent::PacketReceived(packet) => assert!(packet.received_at <= buffered),
+        event => panic!("{:?}", event),
+    }
+}
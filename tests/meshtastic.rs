@@ -0,0 +1,50 @@
+use lora_modem_hal::decode::meshtastic::{self, Header, BROADCAST, DEFAULT_KEY, HEADER_LEN};
+
+// Packet of node !a1b2c3d4 on LongFast carrying `data` as plaintext `Data` message.
+fn packet(to: u32, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::new();
+    frame.extend_from_slice(&to.to_le_bytes());
+    frame.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+    frame.extend_from_slice(&0x1234_5678u32.to_le_bytes());
+    // hop start 3, hop limit 2
+    frame.extend_from_slice(&[3 << 5 | 2, 0x08, 0, 0xd4]);
+    frame.extend_from_slice(data);
+    meshtastic::crypt(&mut frame, &DEFAULT_KEY);
+    frame
+}
+
+#[test]
+fn recognizes_meshtastic_packets() {
+    assert_eq!(meshtastic::channel_hash("LongFast", &DEFAULT_KEY), 0x08);
+
+    let frame = packet(BROADCAST, b"\x08\x01\x12\x05hello");
+    assert_ne!(&frame[HEADER_LEN..HEADER_LEN + 2], b"\x08\x01");
+    let decoded = meshtastic::decode(&frame).unwrap();
+    assert_eq!(decoded.header.from, 0xa1b2_c3d4);
+    assert_eq!(decoded.header.hops_taken(), Some(1));
+    assert_eq!(decoded.header.relay_node, 0xd4);
+    assert_eq!(decoded.portnum, Some(1));
+    assert_eq!(decoded.payload_len, 9);
+    assert_eq!(
+        decoded.to_string(),
+        "meshtastic !a1b2c3d4 -> ^all id 12345678 channel 08 TEXT_MESSAGE_APP"
+    );
+
+    // decrypting twice gives back the plaintext
+    let mut plain = frame.clone();
+    meshtastic::crypt(&mut plain, &DEFAULT_KEY);
+    assert_eq!(&plain[HEADER_LEN..], b"\x08\x01\x12\x05hello");
+
+    // a private channel only yields the header
+    let mut key = DEFAULT_KEY;
+    key[15] ^= 0x5a;
+    assert!(meshtastic::decode_with_key(&frame, &key).is_none());
+    let frame = packet(0x0badcafe, b"\x08\x43\x12\x02\x08\x01");
+    let decoded = meshtastic::decode(&frame).unwrap();
+    assert_eq!(decoded.portnum, Some(67));
+    assert!(decoded.to_string().contains("-> !0badcafe"));
+
+    // rf95modem frames and noise are not taken for Meshtastic
+    assert!(Header::parse(&[1, 2, 3, 4, 5]).is_none());
+    assert!(Header::parse(&[0; 24]).is_none());
+}
@@ -0,0 +1,104 @@
+use lora_modem_hal::{
+    AddressedModem, ArqConfig, Beacon, LoraModemDevice, ModemError, Outbox, ReliableModem,
+    VirtualModem,
+};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+const DAY: Duration = Duration::from_secs(86_400);
+
+fn log_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("outbox-{}-{}.log", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn keeps_messages_across_restarts() {
+    let path = log_path("restart");
+    {
+        let mut outbox = Outbox::open(&path, 3, DAY).unwrap();
+        let first = outbox.push(2, b"first").unwrap();
+        outbox.push(2, b"second").unwrap();
+        outbox.push(3, b"other").unwrap();
+        assert!(matches!(
+            outbox.push(2, b"full"),
+            Err(ModemError::QueueFull)
+        ));
+        assert!(outbox.remove(first).unwrap());
+        assert!(!outbox.remove(first).unwrap());
+        outbox
+            .push_with_ttl(3, b"stale", Duration::from_secs(0))
+            .unwrap();
+    }
+    // a write cut short by a crash
+    OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap()
+        .write_all(&[b'A', 0, 0])
+        .unwrap();
+
+    let mut outbox = Outbox::open(&path, 3, DAY).unwrap();
+    let data: Vec<&[u8]> = outbox.messages().iter().map(|m| &m.data[..]).collect();
+    assert_eq!(data, [&b"second"[..], b"other"]);
+    assert_eq!(outbox.pending(3).count(), 1);
+    // new messages get fresh ids and survive a compaction
+    let id = outbox.push(3, b"third").unwrap();
+    assert!(outbox.messages().iter().all(|m| m.id <= id));
+    outbox.compact().unwrap();
+    drop(outbox);
+    let outbox = Outbox::open(&path, 3, DAY).unwrap();
+    assert_eq!(outbox.len(), 3);
+    assert_eq!(outbox.pending(3).last().unwrap().data, b"third");
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn delivers_when_the_peer_beacons() {
+    let path = log_path("beacon");
+    let (mut a, mut b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let arq = ArqConfig {
+        ack_timeout: Duration::from_millis(200),
+        ..ArqConfig::default()
+    };
+    let mut node = ReliableModem::new(AddressedModem::new(a, 1), arq.clone());
+    let mut peer = ReliableModem::new(AddressedModem::new(b, 2), arq);
+
+    let mut outbox = Outbox::open(&path, 8, DAY).unwrap();
+    outbox.push(2, b"one").unwrap();
+    outbox.push(2, b"two").unwrap();
+    outbox.push(3, b"away").unwrap();
+
+    let listener = thread::spawn(move || {
+        let mut beacon = Beacon::new(2, Duration::from_secs(60));
+        beacon.set_gps(false);
+        assert!(beacon.poll(peer.link().inner_mut()).unwrap());
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            match peer.receive() {
+                Ok(frame) => received.push(frame.packet.data),
+                Err(ModemError::Timeout) => {}
+                Err(e) => panic!("{}", e),
+            }
+        }
+        received
+    });
+    let packet = loop {
+        match node.link().inner_mut().read_packet() {
+            Ok(packet) => break packet,
+            Err(ModemError::Timeout) => {}
+            Err(e) => panic!("{}", e),
+        }
+    };
+    assert_eq!(outbox.on_beacon(&mut node, &packet).unwrap(), 2);
+    assert_eq!(listener.join().unwrap(), [b"one".to_vec(), b"two".to_vec()]);
+    assert_eq!(outbox.len(), 1);
+    assert_eq!(outbox.pending(3).count(), 1);
+    fs::remove_file(&path).unwrap();
+}
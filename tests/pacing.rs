@@ -0,0 +1,26 @@
+use lora_modem_hal::{
+    LoraModemDevice, ModemError, ReplayTransport, Rf95Modem, TxRejection, WritePacing,
+};
+use std::time::Duration;
+
+#[test]
+fn repeats_transmissions_the_firmware_lost() {
+    let trace = "> AT+TX=0102\n< +ERROR\n> AT+TX=0102\n< +FAIL: rx buffer full\n\
+                 > AT+TX=0102\n< +SENT 2 bytes\n> AT+TX=03\n< +ERROR\n";
+    let mut modem = Rf95Modem::from_transport(ReplayTransport::from_trace(trace));
+    modem.open().unwrap();
+    modem.set_timeout(Some(Duration::from_millis(50)));
+    modem.set_write_pacing(
+        WritePacing::chunked(4, Duration::ZERO).with_retries(2, Duration::from_millis(1)),
+    );
+    assert_eq!(modem.send_data(vec![1, 2]).unwrap().bytes, 2);
+    modem.set_write_pacing(WritePacing::chunked(4, Duration::ZERO));
+    assert!(matches!(
+        modem.send_data(vec![3]),
+        Err(ModemError::TxRejected(_))
+    ));
+    assert_eq!(
+        TxRejection::from_message("+FAIL: buffer full"),
+        TxRejection::BufferFull
+    );
+}
@@ -0,0 +1,189 @@
+mod common;
+
+use common::Gen;
+use lora_modem_hal::hex::{self, HexError, HexFormat};
+use lora_modem_hal::{
+    GpsFix, LoraModemDevice, MockModem, ModemError, RadioParams, ReplayTransport, Rf95Modem, Status,
+};
+use std::time::Duration;
+
+fn lines(output: &str) -> Vec<String> {
+    output.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn payloads_round_trip_through_hex() {
+    let mut gen = Gen(0x4e5);
+    let mut modem = MockModem::new();
+    modem.open().unwrap();
+    for _ in 0..500 {
+        let data = gen.bytes(255);
+        modem.push_rx(&data, -90, 3.0);
+        assert_eq!(modem.read_packet().unwrap().data, data);
+    }
+}
+
+#[test]
+fn random_status_output_never_panics() {
+    let mut gen = Gen(0x57a7);
+    let keys = [
+        "firmware",
+        "modem config",
+        "max pkt size",
+        "frequency",
+        "rx listener",
+        "tx power",
+        "BLE",
+        "rx bad",
+        "bandwidth",
+    ];
+    for _ in 0..5_000 {
+        let mut output = String::new();
+        for _ in 0..gen.below(6) {
+            let key = keys[gen.below(keys.len())];
+            let value = gen.text(b"0123456789.-+ :|abcdBm\xc3", 12);
+            output.push_str(&format!("{}: {}\n", key, value));
+        }
+        let _ = Status::parse(&lines(&output));
+        let garbage = gen.bytes(64);
+        let _ = Status::parse(&lines(&String::from_utf8_lossy(&garbage)));
+    }
+}
+
+#[test]
+fn random_gps_output_never_panics() {
+    let mut gen = Gen(0x6b5);
+    for _ in 0..5_000 {
+        let line = format!("+GPS {}", gen.text(b"0123456789.,-e NOFIX", 60));
+        let _ = GpsFix::parse(&line);
+        let _ = GpsFix::decode(&gen.bytes(16));
+    }
+}
+
+#[test]
+fn gps_time_out_of_range_is_an_error() {
+    assert!(GpsFix::parse("+GPS 1,2,3,4,5,18446744073709551615").is_err());
+}
+
+#[test]
+fn gps_fixes_round_trip_through_encoding() {
+    let mut gen = Gen(0x9f1);
+    for _ in 0..1_000 {
+        let fix = GpsFix::parse(&format!(
+            "+GPS {:.7},{:.7},{},{},{:.1}",
+            gen.below(180_0000000) as f64 / 1e7 - 90.0,
+            gen.below(360_0000000) as f64 / 1e7 - 180.0,
+            gen.below(9000) as i32 - 400,
+            gen.below(30),
+            gen.below(250) as f32 / 10.0
+        ))
+        .unwrap()
+        .unwrap();
+        let decoded = GpsFix::decode(&fix.encode()).unwrap();
+        assert!((decoded.lat - fix.lat).abs() < 1e-6);
+        assert!((decoded.lon - fix.lon).abs() < 1e-6);
+        assert_eq!(decoded.alt, fix.alt);
+        assert_eq!(decoded.sats, fix.sats);
+        assert!((decoded.hdop - fix.hdop).abs() < 0.051);
+    }
+}
+
+#[test]
+fn random_lines_never_panic_a_device() {
+    let mut gen = Gen(0x11e);
+    let mut modem = MockModem::new();
+    modem.open().unwrap();
+    for _ in 0..2_000 {
+        let line = gen.text(b"+RXSENTOK 0123456789abcdef,-=ts", 40);
+        modem.push_line(&line);
+        let _ = modem.read_packet();
+    }
+}
+
+#[test]
+fn hex_formats_round_trip() {
+    let mut gen = Gen(0x4ec5);
+    let formats = [
+        HexFormat::default(),
+        HexFormat {
+            uppercase: true,
+            separator: Some(' '),
+            bytes_per_line: None,
+        },
+        HexFormat {
+            uppercase: false,
+            separator: Some(':'),
+            bytes_per_line: Some(16),
+        },
+    ];
+    for _ in 0..500 {
+        let data = gen.bytes(300);
+        for format in &formats {
+            assert_eq!(format.decode(&format.encode(&data)).unwrap(), data);
+        }
+        assert_eq!(hex::decode(&hex::encode(&data)).unwrap(), data);
+        // the batched modem format matches the digit by digit one
+        let spaced = formats[1].encode(&data);
+        assert_eq!(hex::encode(&data), spaced.replace(' ', "").to_lowercase());
+    }
+    let format = formats[2];
+    assert_eq!(format.encode(&[1, 2, 3]), "01:02:03");
+    assert_eq!(formats[1].encode(&[0xab, 0xcd]), "AB CD");
+}
+
+#[test]
+fn hex_errors_are_typed() {
+    assert_eq!(hex::decode("abc"), Err(HexError::OddLength { digits: 3 }));
+    assert_eq!(
+        hex::decode("0g"),
+        Err(HexError::InvalidDigit { position: 1 })
+    );
+    assert_eq!(
+        hex::decode("+f"),
+        Err(HexError::InvalidDigit { position: 0 })
+    );
+    let spaced = HexFormat {
+        separator: Some(' '),
+        ..HexFormat::default()
+    };
+    assert_eq!(
+        spaced.decode("a b"),
+        Err(HexError::InvalidDigit { position: 1 })
+    );
+}
+
+#[test]
+fn rejects_radio_settings_out_of_range() {
+    for sf in ["70", "2"] {
+        let trace = format!(
+            "> AT+INFO\n< +STATUS:\n< firmware: 0.7.3\n< modem config: 0\n\
+             < frequency: 868.1000\n< bandwidth: 125000 Hz\n< spreading factor: {}\n\
+             < coding rate: 4/5\n< preamble: 8\n< crc: 1\n< +OK\n",
+            sf
+        );
+        let mut modem = Rf95Modem::from_transport(ReplayTransport::from_trace(&trace));
+        modem.open().unwrap();
+        modem.set_timeout(Some(Duration::from_millis(50)));
+        let params = modem.get_radio_params();
+        assert!(
+            matches!(params, Err(ModemError::InvalidArgument(_))),
+            "{:?}",
+            params
+        );
+    }
+}
+
+#[test]
+fn refuses_airtime_for_spreading_factors_out_of_range() {
+    for spreading_factor in [0, 2, 13, 70, 255] {
+        let params = RadioParams {
+            spreading_factor,
+            ..RadioParams::default()
+        };
+        assert!(matches!(
+            params.airtime(16),
+            Err(ModemError::InvalidArgument(_))
+        ));
+    }
+    assert!(RadioParams::default().airtime(16).is_ok());
+}
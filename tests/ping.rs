@@ -0,0 +1,65 @@
+use lora_modem_hal::addressing::FLAG_ECHO;
+use lora_modem_hal::{AddressedModem, LinkModel, LoraModemDevice, ModemError, VirtualModem};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn pings_peer_answering_while_reading() {
+    let model = LinkModel {
+        rssi: -92,
+        snr: 6.25,
+        jitter: 0,
+        ..LinkModel::default()
+    };
+    let (mut a, mut b) = VirtualModem::pair_with(model, 7);
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let mut node = AddressedModem::new(a, 1);
+    node.set_ping_timeout(Duration::from_millis(500));
+    let mut peer = AddressedModem::new(b, 2);
+    let responder = thread::spawn(move || {
+        // the echo request is answered, only the data frame is returned
+        loop {
+            match peer.read_packet() {
+                Ok(packet) => return packet.data,
+                Err(ModemError::Timeout) => {}
+                Err(e) => panic!("{}", e),
+            }
+        }
+    });
+    let report = node.ping(2).unwrap();
+    assert_eq!(report.rssi_fwd, -92);
+    assert_eq!(report.rssi_back, -92);
+    assert_eq!(report.snr_fwd, 6.25);
+    assert_eq!(report.snr_back, 6.25);
+    assert!(report.rtt < Duration::from_millis(500));
+    node.send_to(2, b"done").unwrap();
+    assert_eq!(responder.join().unwrap(), b"done");
+
+    // nobody answers anymore
+    assert!(matches!(node.ping(2), Err(ModemError::Timeout)));
+}
+
+#[test]
+fn overhears_echo_frames_in_promiscuous_mode() {
+    let (mut a, mut b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let mut node = AddressedModem::new(a, 1);
+    node.set_ping_timeout(Duration::from_millis(50));
+    let mut sniffer = AddressedModem::new(b, 3);
+    sniffer.set_promiscuous(true);
+
+    assert!(matches!(node.ping(2), Err(ModemError::Timeout)));
+    let frame = sniffer.read_packet_for_me().unwrap();
+    assert_eq!(frame.header.dst, 2);
+    assert_ne!(frame.header.flags & FLAG_ECHO, 0);
+
+    // without promiscuous mode echo frames stay internal
+    sniffer.set_promiscuous(false);
+    assert!(matches!(node.ping(3), Err(ModemError::Timeout)));
+    assert!(matches!(
+        sniffer.read_packet_for_me(),
+        Err(ModemError::Timeout)
+    ));
+}
@@ -0,0 +1,68 @@
+use lora_modem_hal::{Frequency, LoraModemDevice, MockModem, ModemError, ModemPool, TxStrategy};
+
+fn modem(khz: u32) -> MockModem {
+    let mut modem = MockModem::new();
+    modem.open().unwrap();
+    modem.set_frequency(Frequency::from_khz(khz)).unwrap();
+    modem.enable_rx().unwrap();
+    modem
+}
+
+#[test]
+fn tags_packets_with_their_modem() {
+    let mut first = modem(868_100);
+    first.push_timeout();
+    first.push_rx(b"one", -80, 7.5);
+    first.push_timeout();
+    let mut second = modem(868_300);
+    second.push_rx(b"two", -95, -2.0);
+    second.push_timeout();
+    second.push_timeout();
+    let mut pool = ModemPool::new(TxStrategy::RoundRobin);
+    pool.add(first);
+    pool.add(second);
+
+    let packet = pool.read_packet().unwrap();
+    assert_eq!(packet.modem, 1);
+    assert_eq!(packet.frequency, Some(Frequency::from_khz(868_300)));
+    assert_eq!(packet.packet.data, b"two");
+    let packet = pool.read_packet().unwrap();
+    assert_eq!(packet.modem, 0);
+    assert_eq!(packet.packet.data, b"one");
+    assert!(matches!(pool.read_packet(), Err(ModemError::Timeout)));
+
+    assert_eq!(pool.stats(0).unwrap().packets_in, 1);
+    assert_eq!(pool.total_stats().packets_in, 2);
+    assert_eq!(pool.total_stats().bytes_in, 6);
+}
+
+#[test]
+fn balances_and_pins_transmissions() {
+    let mut pool = ModemPool::new(TxStrategy::RoundRobin);
+    pool.add(modem(868_100));
+    pool.add(modem(868_300));
+    assert_eq!(pool.send(vec![1; 10]).unwrap().0, 0);
+    assert_eq!(pool.send(vec![2; 10]).unwrap().0, 1);
+    assert_eq!(pool.send(vec![3; 10]).unwrap().0, 0);
+
+    let (index, _) = pool.send_on(Frequency::from_khz(868_300), vec![4]).unwrap();
+    assert_eq!(index, 1);
+    assert!(pool.send_on(Frequency::from_khz(869_525), vec![5]).is_err());
+    pool.set_frequency(0, Frequency::from_khz(869_525)).unwrap();
+    assert_eq!(
+        pool.send_on(Frequency::from_khz(869_525), vec![5])
+            .unwrap()
+            .0,
+        0
+    );
+
+    assert_eq!(pool.modem(1).unwrap().config().unwrap().tx_good, 2);
+    assert_eq!(pool.total_stats().packets_out, 5);
+
+    let mut pool = ModemPool::new(TxStrategy::LeastAirtime);
+    pool.add(modem(868_100));
+    pool.add(modem(868_300));
+    assert_eq!(pool.send(vec![0; 200]).unwrap().0, 0);
+    assert_eq!(pool.send(vec![0; 10]).unwrap().0, 1);
+    assert_eq!(pool.send(vec![0; 10]).unwrap().0, 1);
+}
@@ -0,0 +1,60 @@
+use lora_modem_hal::{LoraModemDevice, ModemError, PortMux, VirtualModem};
+use std::time::Duration;
+
+#[test]
+fn separates_traffic_by_port() {
+    let (mut a, mut b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let local = PortMux::new(a);
+    let remote = PortMux::new(b);
+    let telemetry = local.open_port(1).unwrap();
+    let chat = local.open_port(2).unwrap();
+    assert!(matches!(
+        local.open_port(2),
+        Err(ModemError::InvalidArgument(_))
+    ));
+    let remote_telemetry = remote.open_port(1).unwrap();
+    let remote_chat = remote.open_port(2).unwrap();
+
+    telemetry.send(b"t1").unwrap();
+    chat.send(b"hello").unwrap();
+    telemetry.send(b"t2").unwrap();
+    local
+        .with_modem(|modem| modem.send_data(vec![9, 9]))
+        .unwrap();
+
+    // reading chat queues the telemetry frame in front of it
+    assert_eq!(remote_chat.receive().unwrap().data, b"hello");
+    assert_eq!(remote_telemetry.queued(), 1);
+    assert_eq!(remote_telemetry.try_receive().unwrap().data, b"t1");
+    assert_eq!(remote_telemetry.receive().unwrap().data, b"t2");
+    // the frame for port 9 is dropped on the way
+    assert!(matches!(remote_chat.receive(), Err(ModemError::Timeout)));
+    assert_eq!(remote.dropped(), 1);
+
+    // a closed port can be opened again, frames for it are dropped meanwhile
+    drop(remote_chat);
+    assert_eq!(remote.ports(), [1]);
+    chat.send(b"gone").unwrap();
+    assert_eq!(remote.poll().unwrap(), None);
+    let remote_chat = remote.open_port(2).unwrap();
+    chat.send(b"back").unwrap();
+    assert_eq!(remote_chat.receive().unwrap().data, b"back");
+}
+
+#[test]
+fn survives_a_panicking_holder() {
+    let (mut a, mut b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let mux = std::sync::Arc::new(PortMux::new(a));
+    let port = mux.open_port(1).unwrap();
+    let holder = mux.clone();
+    let panicked = std::thread::spawn(move || holder.with_modem(|_| panic!("holder fails")));
+    assert!(panicked.join().is_err());
+
+    // the report counts payload bytes, not the port byte
+    assert_eq!(port.send(b"ok").unwrap().bytes, 2);
+    assert_eq!(b.read_packet().unwrap().data, [1, b'o', b'k']);
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "serial")]
+
+use lora_modem_hal::probe::identify;
+use lora_modem_hal::{Firmware, ReplayTransport, Transport};
+use std::time::Duration;
+
+fn identify_trace(trace: &str) -> Option<(Firmware, String)> {
+    let mut transport = ReplayTransport::from_trace(trace);
+    transport.open().unwrap();
+    identify(&mut transport, Duration::from_millis(200)).unwrap()
+}
+
+#[test]
+fn identifies_firmware_by_its_answers() {
+    let rf95 = "> AT+INFO\n< +STATUS:\n< \n< firmware:      0.7.3\n< modem config:  0\n\
+                < max pkt size:  251\n< frequency:     868.1000\n< rx listener:   1\n< +OK\n";
+    assert_eq!(
+        identify_trace(rf95),
+        Some((Firmware::Rf95Modem, "0.7.3".to_string()))
+    );
+
+    let rak = "> AT+INFO\n< AT_COMMAND_NOT_FOUND\n> AT+VER=?\n< AT+VER=RUI_4.0.6_RAK3172-E\n< OK\n";
+    assert_eq!(
+        identify_trace(rak),
+        Some((Firmware::Rak, "RUI_4.0.6_RAK3172-E".to_string()))
+    );
+
+    let rn = "> AT+INFO\n< invalid_param\n> AT+VER=?\n< invalid_param\n\
+              > sys get ver\n< RN2483 1.0.5 Oct 31 2018 15:06:52\n";
+    assert_eq!(
+        identify_trace(rn),
+        Some((
+            Firmware::Rn2xx3,
+            "RN2483 1.0.5 Oct 31 2018 15:06:52".to_string()
+        ))
+    );
+
+    // garbage at the wrong baud rate
+    let mut transport = ReplayTransport::from_trace("< \u{fffd}~x\n");
+    transport.set_strict(false);
+    transport.open().unwrap();
+    assert_eq!(
+        identify(&mut transport, Duration::from_millis(50)).unwrap(),
+        None
+    );
+}
@@ -0,0 +1,99 @@
+use lora_modem_hal::{
+    Bandwidth, CodingRate, Frequency, LoraModemDevice, MockModem, ModemProfile, RadioParams,
+    Region, SYNC_WORD_LORAWAN, SYNC_WORD_PRIVATE,
+};
+
+const GATEWAY: &str = r#"
+# rooftop gateway
+port = "/dev/ttyACM0"
+baud = 115_200
+frequency = 869.525    # MHz
+tx_power = 14
+node_id = 7
+region = "eu868"
+
+[radio]
+bandwidth = 250000
+spreading_factor = 9
+coding_rate = "4/7"
+"#;
+
+#[test]
+fn parses_profile() {
+    let profile = ModemProfile::from_toml(GATEWAY).unwrap();
+    assert_eq!(profile.port.as_deref(), Some("/dev/ttyACM0"));
+    assert_eq!(profile.baud, Some(115_200));
+    assert_eq!(profile.frequency, Some(Frequency::from_khz(869_525)));
+    assert_eq!(profile.tx_power, Some(14));
+    assert_eq!(profile.node_id, Some(7));
+    assert_eq!(profile.region, Some(Region::EU868));
+    let radio = profile.radio.unwrap();
+    assert_eq!(radio.bandwidth, Bandwidth::Bw250kHz);
+    assert_eq!(radio.spreading_factor, 9);
+    assert_eq!(radio.coding_rate, CodingRate::Cr4_7);
+    assert_eq!(radio.preamble_len, RadioParams::default().preamble_len);
+}
+
+#[test]
+fn round_trips_through_toml() {
+    let profile = ModemProfile::from_toml(GATEWAY).unwrap();
+    assert_eq!(
+        ModemProfile::from_toml(&profile.to_toml()).unwrap(),
+        profile
+    );
+    let empty = ModemProfile::default();
+    assert_eq!(empty.to_toml(), "");
+    assert_eq!(ModemProfile::from_toml("").unwrap(), empty);
+}
+
+#[test]
+fn rejects_invalid_profiles() {
+    for invalid in [
+        "frequncy = 868.1",
+        "frequency = \"868.1\"",
+        "port = /dev/ttyUSB0",
+        "baud = 115200 extra",
+        "region = \"XX123\"",
+        "[radio]\nspreading_factor = 13",
+        "[radio]\ncoding_rate = \"4/9\"",
+        "[gps]\nenabled = true",
+        "node_id = 1\nnode_id = 2",
+        "port = \"unterminated",
+    ] {
+        assert!(ModemProfile::from_toml(invalid).is_err(), "{:?}", invalid);
+    }
+}
+
+#[test]
+fn applies_settings_within_region() {
+    let mut modem = MockModem::new();
+    modem.open().unwrap();
+    let mut profile = ModemProfile::from_toml(GATEWAY).unwrap();
+    profile.apply(&mut modem).unwrap();
+    let status = modem.config().unwrap();
+    assert_eq!(status.frequency, Frequency::from_khz(869_525));
+    assert_eq!(status.tx_power, Some(14));
+    assert_eq!(modem.get_radio_params().unwrap(), profile.radio.unwrap());
+
+    profile.frequency = Some(Frequency::from_mhz(915));
+    assert!(profile.apply(&mut modem).is_err());
+    assert_eq!(
+        modem.config().unwrap().frequency,
+        Frequency::from_khz(869_525)
+    );
+}
+
+#[test]
+fn keeps_the_sync_word() {
+    let profile = ModemProfile::from_toml("[radio]\nsync_word = \"0x34\"").unwrap();
+    let radio = profile.radio.unwrap();
+    assert_eq!(radio.sync_word, SYNC_WORD_LORAWAN);
+    assert!(profile.to_toml().contains("sync_word = \"0x34\""));
+    assert_eq!(
+        ModemProfile::from_toml(&profile.to_toml()).unwrap(),
+        profile
+    );
+    let decimal = ModemProfile::from_toml("[radio]\nsync_word = 18").unwrap();
+    assert_eq!(decimal.radio.unwrap().sync_word, SYNC_WORD_PRIVATE);
+    assert!(ModemProfile::from_toml("[radio]\nsync_word = \"0x1ff\"").is_err());
+}
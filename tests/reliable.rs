@@ -0,0 +1,55 @@
+use lora_modem_hal::reliable::FLAG_ACK;
+use lora_modem_hal::{
+    AddressedModem, ArqConfig, Checksum, ChecksumModem, LoraModemDevice, ModemError, ReliableModem,
+    VirtualModem,
+};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn keeps_waiting_for_the_ack_past_corrupted_frames() {
+    let (mut a, mut b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let arq = ArqConfig {
+        ack_timeout: Duration::from_millis(500),
+        max_retries: 0,
+        ..ArqConfig::default()
+    };
+    let link = AddressedModem::new(ChecksumModem::new(a, Checksum::Crc16), 1);
+    let mut node = ReliableModem::new(link, arq);
+    let peer = thread::spawn(move || {
+        loop {
+            match b.read_packet() {
+                Ok(_) => break,
+                Err(ModemError::Timeout) => {}
+                Err(e) => panic!("{}", e),
+            }
+        }
+        // a frame failing its checksum, then the ack for sequence number 0
+        b.send_data(b"noise".to_vec()).unwrap();
+        let mut peer = AddressedModem::new(ChecksumModem::new(b, Checksum::Crc16), 2);
+        peer.send_with_flags(1, FLAG_ACK, &[0, 0]).unwrap();
+    });
+    let delivery = node.deliver(2, 0, b"hello").unwrap();
+    assert_eq!(delivery.attempts, 1);
+    peer.join().unwrap();
+}
+
+#[test]
+fn caps_the_growing_ack_timeout() {
+    let (mut a, _b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(5)));
+    // without the cap the second wait would take more than a year
+    let arq = ArqConfig {
+        ack_timeout: Duration::from_millis(10),
+        max_retries: 2,
+        backoff: u32::MAX,
+        max_ack_timeout: Duration::from_millis(20),
+    };
+    let mut node = ReliableModem::new(AddressedModem::new(a, 1), arq);
+    assert!(matches!(
+        node.deliver(2, 0, b"hello"),
+        Err(ModemError::NotAcknowledged { attempts: 3 })
+    ));
+}
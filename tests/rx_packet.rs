@@ -0,0 +1,194 @@
+mod common;
+
+use common::Gen;
+use lora_modem_hal::{LinkQuality, ModemError, RxPacket, RxPacketRef};
+use std::convert::TryFrom;
+use std::time::SystemTime;
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_error(line: &str) -> String {
+    match RxPacket::try_from(line) {
+        Err(ModemError::Parse(msg)) => msg,
+        other => panic!("expected a parse error for {:?}, got {:?}", line, other),
+    }
+}
+
+#[test]
+fn parses_packet() {
+    let packet = RxPacket::try_from("+RX 3,0102ff,-80,7").unwrap();
+    assert_eq!(packet.data, vec![1, 2, 255]);
+    assert_eq!((packet.rssi, packet.snr), (-80, 7.0));
+    assert_eq!(packet.freq_error, None);
+}
+
+#[test]
+fn parses_optional_fields() {
+    let packet = RxPacket::try_from("+RX 1,aa,-80,7,-1200,ts=123456").unwrap();
+    assert_eq!(packet.freq_error, Some(-1200));
+    assert_eq!(packet.modem_timestamp, Some(123456));
+}
+
+#[test]
+fn parses_fractional_snr() {
+    assert_eq!(RxPacket::try_from("+RX 1,aa,-80,9.75").unwrap().snr, 9.75);
+    assert_eq!(RxPacket::try_from("+RX 1,aa,-80,-7.25").unwrap().snr, -7.25);
+}
+
+#[test]
+fn accepts_uppercase_hex() {
+    let packet = RxPacket::try_from("+RX 2,ABcd,-1,2").unwrap();
+    assert_eq!(packet.data, vec![0xab, 0xcd]);
+}
+
+#[test]
+fn short_input_is_an_error() {
+    for line in &["", "+", "+R", "+RX", "+RX ", "é", "+RXé", "1,2"] {
+        assert!(RxPacket::try_from(*line).is_err(), "{:?}", line);
+    }
+}
+
+#[test]
+fn errors_name_the_field() {
+    assert!(parse_error("+RX x,00,-1,2").contains("length"));
+    assert!(parse_error("+RX 1,00,loud,2").contains("rssi"));
+    assert!(parse_error("+RX 1,00,-1,").contains("snr"));
+    assert!(parse_error("+RX 1,00,-1,2,ts=soon").contains("timestamp"));
+    assert!(parse_error("+RX 1,00,-1,2,far").contains("frequency error"));
+    assert!(parse_error("+RX 2,00,-1,2").contains("payload length"));
+}
+
+#[test]
+fn rejects_malformed_hex() {
+    assert!(parse_error("+RX 1,abc,-1,2").contains("odd number"));
+    assert!(parse_error("+RX 1,zz,-1,2").contains("hex digit"));
+    assert!(parse_error("+RX 1,+f,-1,2").contains("hex digit"));
+    assert!(RxPacket::try_from("+RX 1,é,-1,2").is_err());
+}
+
+#[test]
+fn random_packets_round_trip() {
+    let mut gen = Gen(0x5eed);
+    for _ in 0..1000 {
+        let data = gen.bytes(255);
+        let rssi = -(gen.below(150) as i16);
+        // quarter dB steps as reported by SX127x radios
+        let snr = gen.below(160) as f32 / 4.0 - 20.0;
+        let line = format!("+RX {},{},{},{}", data.len(), hex(&data), rssi, snr);
+        let packet = RxPacket::try_from(line.as_str()).unwrap();
+        assert_eq!((packet.data, packet.rssi, packet.snr), (data, rssi, snr));
+    }
+}
+
+#[test]
+fn displays_wire_format() {
+    for line in &[
+        "+RX 3,0102ff,-80,7",
+        "+RX 0,,-120,-12.5",
+        "+RX 1,aa,-80,9.75,-1200",
+        "+RX 1,aa,-80,7,-1200,ts=123456",
+        "+RX 1,aa,-80,7,ts=42",
+    ] {
+        let packet = RxPacket::try_from(*line).unwrap();
+        assert_eq!(packet.to_string(), *line);
+        assert_eq!(packet.to_modem_line(), *line);
+    }
+}
+
+#[test]
+fn displays_lowercase_hex() {
+    let packet = RxPacket::try_from("+RX 2,ABcd,-1,2").unwrap();
+    assert_eq!(packet.to_modem_line(), "+RX 2,abcd,-1,2");
+}
+
+#[test]
+fn random_packets_round_trip_through_display() {
+    let mut gen = Gen(0xd15_91a7);
+    for _ in 0..1000 {
+        let packet = RxPacket {
+            rssi: -(gen.below(150) as i16),
+            snr: gen.below(160) as f32 / 4.0 - 20.0,
+            data: gen.bytes(255),
+            received_at: SystemTime::now(),
+            freq_error: match gen.below(2) {
+                0 => None,
+                _ => Some(gen.below(20_000) as i32 - 10_000),
+            },
+            modem_timestamp: match gen.below(2) {
+                0 => None,
+                _ => Some(gen.next()),
+            },
+        };
+        let parsed = RxPacket::try_from(packet.to_modem_line().as_str()).unwrap();
+        assert_eq!(
+            (parsed.data, parsed.rssi, parsed.snr),
+            (packet.data, packet.rssi, packet.snr)
+        );
+        assert_eq!(parsed.freq_error, packet.freq_error);
+        assert_eq!(parsed.modem_timestamp, packet.modem_timestamp);
+    }
+}
+
+#[test]
+fn garbage_never_panics() {
+    let mut gen = Gen(0xbad_5eed);
+    let alphabet = b"+RX 0123456789abcdefABCDEF,-=tsxz\r\n";
+    for _ in 0..10_000 {
+        let line = gen.text(alphabet, 40);
+        let _ = RxPacket::try_from(line.as_str());
+        // arbitrary bytes from a flaky serial line, as decoded by the readers
+        let bytes = gen.bytes(40);
+        let _ = RxPacket::try_from(String::from_utf8_lossy(&bytes).as_ref());
+    }
+}
+
+#[test]
+fn truncated_packets_never_panic() {
+    let line = "+RX 4,deadbeef,-97,-3,150,ts=42";
+    for end in 0..line.len() {
+        let _ = RxPacket::try_from(&line[..end]);
+    }
+}
+
+#[test]
+fn classifies_signal_quality() {
+    let packet = |rssi: i16, snr: f32| {
+        RxPacket::try_from(format!("+RX 1,00,{},{}", rssi, snr).as_str()).unwrap()
+    };
+    assert_eq!(packet(-60, 9.5).quality(), LinkQuality::Excellent);
+    assert_eq!(packet(-60, -3.0).quality(), LinkQuality::Fair);
+    assert_eq!(packet(-100, 7.0).quality(), LinkQuality::Good);
+    assert_eq!(packet(-120, 7.0).quality(), LinkQuality::Poor);
+    assert_eq!(LinkQuality::Poor.bars(), 1);
+    assert_eq!(LinkQuality::Excellent.bars(), 4);
+
+    assert_eq!(packet(-110, -5.0).link_margin(7), 2.5);
+    assert_eq!(packet(-110, -5.0).link_margin(12), 15.0);
+    assert!(packet(-125, -9.0).link_margin(7) < 0.0);
+}
+
+#[test]
+fn decodes_borrowed_packets_into_a_buffer() {
+    let mut gen = Gen(0x95);
+    let mut buf = [0u8; 255];
+    for _ in 0..200 {
+        let data = gen.bytes(255);
+        let line = format!("+RX {},{},-97,6.5,ts=42", data.len(), hex(&data));
+        let packet = RxPacketRef::try_from(line.as_str()).unwrap();
+        assert_eq!(packet.len(), data.len());
+        let len = packet.decode_into(&mut buf).unwrap();
+        assert_eq!(&buf[..len], &data[..]);
+        let owned = packet.to_packet();
+        assert_eq!(owned.data, data);
+        assert_eq!((owned.rssi, owned.modem_timestamp), (-97, Some(42)));
+    }
+    let packet = RxPacketRef::try_from("+RX 3,0102FF,-80,7").unwrap();
+    assert_eq!(packet.hex(), "0102FF");
+    assert!(matches!(
+        packet.decode_into(&mut buf[..2]),
+        Err(ModemError::BufferOverflow)
+    ));
+    assert!(RxPacketRef::try_from("+RX 2,01zz,-80,7").is_err());
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "serial")]
+
+use lora_modem_hal::{SerialPort, SerialPortInfo};
+
+#[test]
+fn picks_candidate_ports() {
+    let port = |usb_id| SerialPortInfo {
+        path: "/dev/ttyUSB0".to_string(),
+        usb_id,
+        product: None,
+    };
+    assert!(port(Some((0x10c4, 0xea60))).is_candidate());
+    assert!(port(Some((0x1a86, 0x7523))).is_candidate());
+    assert!(!port(Some((0x046d, 0xc52b))).is_candidate());
+    // only Linux reports the ids of every USB port
+    assert_eq!(port(None).is_candidate(), !cfg!(target_os = "linux"));
+}
+
+#[test]
+fn opens_com_ports_in_the_device_namespace() {
+    let com = SerialPort::new("COM12", 115_200);
+    assert_eq!(com.path(), "COM12");
+    if cfg!(windows) {
+        assert_eq!(com.device_path(), r"\\.\COM12");
+    } else {
+        assert_eq!(com.device_path(), "COM12");
+    }
+    assert_eq!(SerialPort::new("COMx", 115_200).device_path(), "COMx");
+    assert_eq!(
+        SerialPort::new("/dev/ttyACM0", 115_200).device_path(),
+        "/dev/ttyACM0"
+    );
+}
+
+#[test]
+fn keeps_modem_errors_carried_by_io_errors() {
+    use lora_modem_hal::ModemError;
+    use std::io;
+
+    let missing = ModemError::Transport("`stty` not found".into());
+    let err = ModemError::from(io::Error::other(missing));
+    assert!(matches!(err, ModemError::Transport(msg) if msg.contains("stty")));
+    let err = ModemError::from(io::Error::from(io::ErrorKind::TimedOut));
+    assert!(matches!(err, ModemError::Timeout));
+}
@@ -0,0 +1,130 @@
+use lora_modem_hal::{Frequency, ModemConfig, Status, StatusChange};
+
+fn lines(output: &str) -> Vec<String> {
+    output.lines().map(str::to_string).collect()
+}
+
+const RF95MODEM_0_6: &str = "+STATUS:
+
+firmware:      0.6.1
+modem config:  0
+max pkt size:  251
+frequency:     868.1000
+rx listener:   1
+
+rx bad:        0
+rx good:       12
+tx good:       3
++OK";
+
+const RF95MODEM_0_7: &str = "+STATUS:
+
+firmware:      0.7.3
+features:      BLE GPS
+modem config:  3 | slow+long range
+max pkt size:  251
+frequency:     433.7750
+rx listener:   0
+tx power:      14 dBm
+BLE:           1
+BLE connected: 0
+
+rx bad:        2
+rx good:       140
+tx good:       77
++OK";
+
+const ESP32_PORT: &str = "+INFO
+Frequency: 915.0000 MHz
+Firmware: 0.7.3-esp32
+TX Power: 20 dBm
+Modem Config: 1 (fast+short range)
+Max Pkt Size: 251
+chip id: 24:0a:c4:00:01:10
+RX Listener: on
+TX Good: 5
+RX Good: 9
+RX Bad: 1
++OK";
+
+#[test]
+fn parses_rf95modem_0_6() {
+    let status = Status::parse(&lines(RF95MODEM_0_6)).unwrap();
+    assert_eq!(status.version, "0.6.1");
+    assert_eq!(status.config, ModemConfig::MediumBw125Cr45Sf128Crc);
+    assert_eq!(status.max_pkt_size, 251);
+    assert_eq!(status.frequency, Frequency::from_khz(868_100));
+    assert!(status.rx_listener);
+    assert_eq!(status.tx_power, None);
+    assert_eq!(status.ble_enabled, None);
+    assert_eq!((status.rx_bad, status.rx_good, status.tx_good), (0, 12, 3));
+}
+
+#[test]
+fn parses_rf95modem_0_7() {
+    let status = Status::parse(&lines(RF95MODEM_0_7)).unwrap();
+    assert_eq!(status.version, "0.7.3");
+    assert_eq!(status.config, ModemConfig::SlowLongBw125Cr48Sf4096Crc);
+    assert_eq!(status.frequency, Frequency::from_khz(433_775));
+    assert!(!status.rx_listener);
+    assert_eq!(status.tx_power, Some(14));
+    assert_eq!(status.ble_enabled, Some(true));
+    assert_eq!(status.ble_connected, Some(false));
+    assert_eq!(
+        (status.rx_bad, status.rx_good, status.tx_good),
+        (2, 140, 77)
+    );
+}
+
+#[test]
+fn parses_esp32_port() {
+    let status = Status::parse(&lines(ESP32_PORT)).unwrap();
+    assert_eq!(status.version, "0.7.3-esp32");
+    assert_eq!(status.config, ModemConfig::FastShortBw500Cr45Sf128Crc);
+    assert_eq!(status.frequency, Frequency::from_mhz(915));
+    assert!(status.rx_listener);
+    assert_eq!(status.tx_power, Some(20));
+    assert_eq!((status.rx_bad, status.rx_good, status.tx_good), (1, 9, 5));
+}
+
+#[test]
+fn missing_fields_keep_defaults() {
+    let status = Status::parse(&lines("firmware: 0.5\nsomething new: 42")).unwrap();
+    assert_eq!(status.version, "0.5");
+    assert_eq!(status.max_pkt_size, 0);
+    assert_eq!(status.config, ModemConfig::MediumBw125Cr45Sf128Crc);
+}
+
+#[test]
+fn rejects_malformed_known_field() {
+    assert!(Status::parse(&lines("max pkt size: lots")).is_err());
+    assert!(Status::parse(&lines("modem config: 9")).is_err());
+}
+
+#[test]
+fn diff_lists_changed_fields() {
+    let old = Status::parse(&lines(RF95MODEM_0_6)).unwrap();
+    assert!(Status::diff(&old, &old.clone()).is_empty());
+    let mut new = old.clone();
+    new.frequency = Frequency::from_khz(869_525);
+    new.tx_power = Some(20);
+    new.rx_good = 0;
+    assert_eq!(
+        Status::diff(&old, &new),
+        vec![
+            StatusChange::Frequency {
+                old: Frequency::from_khz(868_100),
+                new: Frequency::from_khz(869_525),
+            },
+            StatusChange::TxPower {
+                old: None,
+                new: Some(20),
+            },
+            StatusChange::Counter {
+                name: "rx_good",
+                old: 12,
+                new: 0,
+            },
+        ]
+    );
+}
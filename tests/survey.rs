@@ -0,0 +1,69 @@
+use lora_modem_hal::{GpsFix, RxPacket, SampleSource, SiteSurvey, SurveySample};
+use std::convert::TryFrom;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn fix(lat: f64, lon: f64) -> GpsFix {
+    GpsFix {
+        lat,
+        lon,
+        alt: 112.5,
+        sats: 9,
+        hdop: 0.9,
+        time: Some(UNIX_EPOCH + Duration::from_secs(1_714_564_800)),
+    }
+}
+
+fn survey() -> SiteSurvey {
+    let mut survey = SiteSurvey::new();
+    survey.record(SurveySample {
+        time: UNIX_EPOCH + Duration::from_secs(1_714_564_799),
+        fix: fix(50.8101, 8.7702),
+        source: SampleSource::Ping,
+        node: Some(2),
+        rssi: Some(-97),
+        snr: Some(4.25),
+        rssi_fwd: Some(-99),
+        snr_fwd: Some(3.5),
+    });
+    survey.record(SurveySample {
+        rssi: None,
+        snr: None,
+        rssi_fwd: None,
+        snr_fwd: None,
+        ..survey.samples()[0].clone()
+    });
+    let packet = RxPacket::try_from("+RX 2,abcd,-120,-9.5").unwrap();
+    survey.record_packet(fix(50.8123, 8.7756), &packet);
+    survey
+}
+
+#[test]
+fn exports_csv() {
+    let mut csv = Vec::new();
+    survey().write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "time,lat,lon,alt,sats,hdop,source,node,rssi,snr,rssi_fwd,snr_fwd",
+            "2024-05-01T11:59:59Z,50.8101,8.7702,112.5,9,0.9,ping,2,-97,4.25,-99,3.5",
+            "2024-05-01T11:59:59Z,50.8101,8.7702,112.5,9,0.9,ping,2,,,,",
+            "2024-05-01T12:00:00Z,50.8123,8.7756,112.5,9,0.9,packet,,-120,-9.5,,",
+        ]
+    );
+}
+
+#[test]
+fn exports_gpx_track() {
+    let mut gpx = Vec::new();
+    survey().write_gpx(&mut gpx).unwrap();
+    let gpx = String::from_utf8(gpx).unwrap();
+    assert!(gpx.starts_with("<?xml"));
+    assert_eq!(gpx.matches("<trkpt ").count(), 3);
+    assert!(gpx.contains(r#"<trkpt lat="50.8123" lon="8.7756">"#));
+    assert!(gpx.contains("<desc>ping node 2: RSSI -97 dBm, SNR 4.25 dB</desc>"));
+    assert!(gpx.contains("<desc>ping node 2: no reply</desc>"));
+    assert!(gpx.contains("<time>2024-05-01T12:00:00Z</time>"));
+    assert!(gpx.trim_end().ends_with("</gpx>"));
+}
@@ -0,0 +1,51 @@
+use lora_modem_hal::{
+    LoraModemDevice, ReplayTransport, Rf95Modem, Status, StatusChange, SYNC_WORD_LORAWAN,
+    SYNC_WORD_PRIVATE,
+};
+use std::time::Duration;
+
+const INFO: &str = "> AT+INFO\n< +STATUS:\n< firmware: 0.7.3\n< modem config: 0\n\
+                    < max pkt size: 251\n< frequency: 868.1000\n< rx listener: 1\n\
+                    < bandwidth: 125000 Hz\n< spreading factor: 7\n< coding rate: 4/5\n\
+                    < preamble: 8\n< crc: 1\n< sync word: 0x12\n< +OK\n";
+
+#[test]
+fn sets_the_sync_word_with_the_other_settings() {
+    let trace = format!(
+        "{}> AT+HELP\n< AT+BW AT+SF AT+SYNCWORD\n< +OK\n\
+         > AT+BW=125000\n< +OK\n> AT+SF=7\n< +OK\n> AT+CR=5\n< +OK\n\
+         > AT+PREAMBLE=8\n< +OK\n> AT+CRC=1\n< +OK\n> AT+SYNCWORD=0x34\n< +OK\n{}",
+        INFO,
+        INFO.replace("0x12", "0x34")
+    );
+    let mut modem = Rf95Modem::from_transport(ReplayTransport::from_trace(&trace));
+    modem.open().unwrap();
+    modem.set_timeout(Some(Duration::from_millis(50)));
+    modem.set_sync_word(SYNC_WORD_LORAWAN).unwrap();
+    assert_eq!(modem.sync_word().unwrap(), SYNC_WORD_LORAWAN);
+}
+
+#[test]
+fn reports_sync_word_changes() {
+    let lines = |info: &str| -> Vec<String> {
+        info.lines()
+            .filter_map(|line| line.strip_prefix("< "))
+            .map(str::to_string)
+            .collect()
+    };
+    let old = Status::parse(&lines(INFO)).unwrap();
+    assert_eq!(old.sync_word, Some(SYNC_WORD_PRIVATE));
+    let new = Status::parse(&lines(&INFO.replace("0x12", "52"))).unwrap();
+    let changes = Status::diff(&old, &new);
+    assert_eq!(
+        changes,
+        [StatusChange::SyncWord {
+            old: Some(0x12),
+            new: Some(0x34)
+        }]
+    );
+    assert_eq!(
+        changes[0].to_string(),
+        "sync word changed from 0x12 to 0x34"
+    );
+}
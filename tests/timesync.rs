@@ -0,0 +1,53 @@
+use lora_modem_hal::{AddressedModem, ClockOffset, ModemError, SyncedModem, VirtualModem};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// Synchronize node 1 against node 2, which answers from a thread until done.
+fn sync(reference: Option<ClockOffset>) -> ClockOffset {
+    let (mut a, mut b) = VirtualModem::pair();
+    a.set_timeout(Some(Duration::from_millis(20)));
+    b.set_timeout(Some(Duration::from_millis(20)));
+    let mut node = SyncedModem::new(AddressedModem::new(a, 1));
+    let mut peer = SyncedModem::new(AddressedModem::new(b, 2));
+    peer.set_offset(reference);
+    let done = Arc::new(AtomicBool::new(false));
+    let stop = done.clone();
+    let server = thread::spawn(move || {
+        while !stop.load(Ordering::Relaxed) {
+            match peer.receive() {
+                Ok(frame) => panic!("unexpected frame {:?}", frame),
+                Err(ModemError::Timeout) => {}
+                Err(e) => panic!("{}", e),
+            }
+        }
+    });
+    let offset = node.sync_time_with(2).unwrap();
+    done.store(true, Ordering::Relaxed);
+    server.join().unwrap();
+    assert_eq!(node.offset(), Some(offset));
+    offset
+}
+
+#[test]
+fn measures_offset_to_peer_clock() {
+    // both ends share the clock of the test process
+    let offset = sync(None);
+    assert!(offset.offset_us.abs() < 20_000, "{:?}", offset);
+    assert!(offset.delay < Duration::from_millis(40), "{:?}", offset);
+}
+
+#[test]
+fn follows_the_reference_of_the_peer() {
+    let reference = ClockOffset {
+        offset_us: -5_000_000,
+        delay: Duration::from_millis(1),
+    };
+    let offset = sync(Some(reference));
+    assert!(
+        (offset.offset_us + 5_000_000).abs() < 20_000,
+        "{:?}",
+        offset
+    );
+}
@@ -0,0 +1,53 @@
+use lora_modem_hal::{
+    LoraModemDevice, ReplayModem, ReplayTransport, Rf95Modem, WireLog, WireLogTransport,
+};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn records_a_replayable_log_while_attached() {
+    let trace = "> AT+TX=01\n< +SENT 1 bytes\n> AT+TX=02\n< +SENT 1 bytes\n\
+                 < +RX 1,03,-80,7\n> AT+TX=04\n< +SENT 1 bytes\n";
+    let log = WireLog::new();
+    let transport = WireLogTransport::new(ReplayTransport::from_trace(trace), log.clone());
+    let mut modem = Rf95Modem::from_transport(transport);
+    modem.set_timeout(Some(Duration::from_millis(50)));
+    modem.open().unwrap();
+    modem.send_data(vec![1]).unwrap();
+
+    let sink = Shared::default();
+    log.attach(sink.clone());
+    assert!(log.is_attached());
+    modem.send_data(vec![2]).unwrap();
+    assert_eq!(modem.read_packet().unwrap().data, [3]);
+    log.detach();
+    modem.send_data(vec![4]).unwrap();
+
+    let recorded = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = recorded.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", recorded);
+    let (time, line) = lines[0].split_once(' ').unwrap();
+    assert!(time.contains('.') && time.bytes().all(|b| b != b'-'));
+    assert_eq!(line, "> AT+TX=02");
+    assert!(lines[2].ends_with("< +RX 1,03,-80,7"));
+
+    let mut replay = ReplayModem::from_trace(&recorded);
+    replay.set_timeout(Some(Duration::from_millis(50)));
+    replay.open().unwrap();
+    assert_eq!(replay.send_data(vec![2]).unwrap().bytes, 1);
+    assert_eq!(replay.read_packet().unwrap().data, [3]);
+}
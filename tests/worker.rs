@@ -0,0 +1,109 @@
+use lora_modem_hal::{
+    HeaderMode, LoraModemDevice, ModemError, ModemEvent, ModemWorker, OverflowPolicy, Priority,
+    RadioParams, ReplayTransport, Rf95Modem,
+};
+use std::time::{Duration, Instant};
+
+fn replay_worker(trace: &str) -> ModemWorker<ReplayTransport> {
+    let mut modem = Rf95Modem::from_transport(ReplayTransport::from_trace(trace));
+    modem.set_timeout(Some(Duration::from_millis(200)));
+    ModemWorker::spawn(modem).unwrap()
+}
+
+#[test]
+fn close_flushes_the_queue_before_stopping() {
+    let worker = replay_worker(
+        "> AT+TX=01\n< +SENT 1 bytes\n> AT+TX=02\n< +SENT 1 bytes\n> AT+RX=0\n< +OK\n",
+    );
+    worker.send(vec![1], Priority::Data).unwrap();
+    worker.send(vec![2], Priority::Data).unwrap();
+    // both frames went out before receiving was disabled, as the strict replay checks
+    worker.close(Duration::from_secs(2)).unwrap();
+
+    // the firmware never confirms, the deadline ends the transmission
+    let worker = replay_worker("> AT+TX=03\n> AT+TX=04\n> AT+RX=0\n< +OK\n");
+    worker.send(vec![3], Priority::Data).unwrap();
+    worker.send(vec![4], Priority::Data).unwrap();
+    let started = Instant::now();
+    let closed = worker.close(Duration::from_millis(100));
+    assert!(matches!(closed, Err(ModemError::Timeout)), "{:?}", closed);
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn delivers_packets_buffered_before_spawning() {
+    // the pending command keeps reads timing out once the packet was read
+    let trace = "< +RX 2,0102,-80,7\n> AT+RX=0\n< +OK\n";
+    let mut modem = Rf95Modem::from_transport(ReplayTransport::from_trace(trace));
+    modem.open().unwrap();
+    modem.set_timeout(Some(Duration::from_millis(200)));
+    assert_eq!(modem.buffer_rx().unwrap(), 1);
+    let worker = ModemWorker::spawn(modem).unwrap();
+    let packet = worker
+        .packets()
+        .recv_timeout(Duration::from_secs(2))
+        .unwrap();
+    assert_eq!(packet.data, [1, 2]);
+}
+
+#[test]
+fn bounds_the_packet_queue_like_the_modem_buffer() {
+    let trace = "< +RX 1,01,-80,7\n< +RX 1,02,-80,7\n< +RX 1,03,-80,7\n> AT+RX=0\n< +OK\n";
+    let mut modem = Rf95Modem::from_transport(ReplayTransport::from_trace(trace));
+    modem.set_timeout(Some(Duration::from_millis(200)));
+    modem.set_rx_buffer(2, OverflowPolicy::DropOldest);
+    let worker = ModemWorker::spawn(modem).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while worker.packets().dropped() == 0 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(worker.packets().dropped(), 1);
+    assert_eq!(worker.packets().len(), 2);
+    let packet = worker.packets().try_recv().unwrap();
+    assert_eq!(packet.data, [2]);
+}
+
+#[test]
+fn blocking_buffer_stays_within_capacity_during_commands() {
+    let trace = "> AT+RX=1\n< +RX 1,01,-80,7\n< +RX 1,02,-80,7\n< +OK\n> AT+RX=0\n< +OK\n";
+    let mut modem = Rf95Modem::from_transport(ReplayTransport::from_trace(trace));
+    modem.open().unwrap();
+    modem.set_timeout(Some(Duration::from_millis(200)));
+    modem.set_rx_buffer(1, OverflowPolicy::Block);
+    modem.enable_rx().unwrap();
+    assert_eq!(modem.rx_dropped(), 1);
+    assert!(matches!(
+        modem.disable_rx(),
+        Err(ModemError::BufferOverflow)
+    ));
+    assert_eq!(modem.read_packet().unwrap().data, [1]);
+    modem.disable_rx().unwrap();
+}
+
+#[test]
+fn parses_packets_for_implicit_headers() {
+    let trace = "> AT+HELP\n< AT+BW AT+SF AT+IMPLICIT\n< +OK\n\
+                 > AT+BW=125000\n< +OK\n> AT+SF=7\n< +OK\n> AT+CR=5\n< +OK\n\
+                 > AT+PREAMBLE=8\n< +OK\n> AT+CRC=1\n< +OK\n> AT+IMPLICIT=2\n< +OK\n\
+                 < +RX ,0102,-80,7\n< +RX ,010203,-80,7\n";
+    let mut modem = Rf95Modem::from_transport(ReplayTransport::from_trace(trace));
+    modem.open().unwrap();
+    modem.set_timeout(Some(Duration::from_millis(200)));
+    let params = RadioParams {
+        header: HeaderMode::Implicit { len: 2 },
+        ..RadioParams::default()
+    };
+    modem.set_radio_params(params).unwrap();
+    let worker = ModemWorker::spawn(modem).unwrap();
+    let packet = worker
+        .packets()
+        .recv_timeout(Duration::from_secs(2))
+        .unwrap();
+    assert_eq!(packet.data, [1, 2]);
+    // a packet of the wrong length is reported rather than dropped silently
+    let event = worker
+        .events()
+        .recv_timeout(Duration::from_secs(2))
+        .unwrap();
+    assert!(matches!(event, ModemEvent::Error(_)), "{:?}", event);
+}